@@ -63,21 +63,12 @@ pub struct BlobMetadata {
 }
 
 /// 64-byte signature (split into two 32-byte arrays for serde compatibility)
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, SchemaRead, SchemaWrite)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize, SchemaRead, SchemaWrite)]
 pub struct Signature {
     pub part1: [u8; 32],
     pub part2: [u8; 32],
 }
 
-impl Default for Signature {
-    fn default() -> Self {
-        Signature {
-            part1: [0u8; 32],
-            part2: [0u8; 32],
-        }
-    }
-}
-
 /// A transaction in the network
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize, SchemaRead, SchemaWrite)]
 pub struct Transaction {
@@ -0,0 +1,121 @@
+//! Project defaults read from a `tcs.toml` file
+//!
+//! The file is a flat list of `key = value` lines. Keys are the options of a
+//! schema's `options { ... }` block (e.g. `no_std = true`), plus `output_dir`
+//! for the default directory of generated files. Values are `true`, `false`
+//! or a double-quoted string; `#` starts a comment. Tables, arrays and other
+//! TOML value types are not supported.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use tcs_compiler::error::codes;
+use tcs_compiler::utils::verification_error;
+use tcs_compiler::{GenOptions, TcsError};
+use tcs_schema::{OptionValue, SchemaOption};
+
+/// Name of the project file looked up in the current directory
+pub const CONFIG_FILE: &str = "tcs.toml";
+
+/// Settings from a project file
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Generation options, applied before the schema's own `options` block
+    pub options: Vec<SchemaOption>,
+    /// Where `gen-rust` writes `<schema name>.rs` when no `--output` is given,
+    /// relative to the file's directory
+    pub output_dir: Option<PathBuf>,
+}
+
+/// Read the project file at `path`, or `tcs.toml` in the current directory if
+/// there is one. Without either, every setting is left at its default.
+pub fn load(path: Option<&Path>) -> Result<Config, TcsError> {
+    let path = match path {
+        Some(path) => path,
+        None if Path::new(CONFIG_FILE).is_file() => Path::new(CONFIG_FILE),
+        None => return Ok(Config::default()),
+    };
+    let source = fs::read_to_string(path)?;
+    let mut config = parse(&source).map_err(|(line, msg)| {
+        verification_error(
+            codes::INVALID_OPTION,
+            &format!("{}:{}: {}", path.display(), line, msg),
+        )
+    })?;
+    if let Some(dir) = config.output_dir.take() {
+        let base = path.parent().unwrap_or(Path::new(""));
+        config.output_dir = Some(base.join(dir));
+    }
+    Ok(config)
+}
+
+/// Parse the contents of a project file. Errors carry the (1-indexed) line.
+fn parse(source: &str) -> Result<Config, (usize, String)> {
+    let mut config = Config::default();
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+        let (key, value) = text
+            .split_once('=')
+            .ok_or_else(|| (line, format!("Expected `key = value`, found \"{}\"", text)))?;
+        let key = key.trim();
+        let value = parse_value(value.trim()).map_err(|msg| (line, msg))?;
+
+        if key == "output_dir" {
+            match value {
+                OptionValue::String(dir) => config.output_dir = Some(PathBuf::from(dir)),
+                OptionValue::Bool(_) => return Err((line, "output_dir expects a string".into())),
+            }
+            continue;
+        }
+        let option = SchemaOption {
+            name: key.to_string(),
+            line,
+            column: 1,
+            value,
+        };
+        // Reject unknown options and wrong value types up front, with the line
+        GenOptions::default()
+            .apply_options(std::slice::from_ref(&option))
+            .map_err(|e| (line, e.message()))?;
+        config.options.push(option);
+    }
+    Ok(config)
+}
+
+/// Parse `true`, `false` or a basic string, followed by an optional comment
+fn parse_value(text: &str) -> Result<OptionValue, String> {
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        loop {
+            match chars.next() {
+                Some('"') => break,
+                Some('\\') => match chars.next() {
+                    Some('"') => value.push('"'),
+                    Some('\\') => value.push('\\'),
+                    Some(c) => return Err(format!("Unsupported escape \"\\{}\"", c)),
+                    None => return Err("Unterminated string".to_string()),
+                },
+                Some(c) => value.push(c),
+                None => return Err("Unterminated string".to_string()),
+            }
+        }
+        return match chars.as_str().trim() {
+            rest if rest.is_empty() || rest.starts_with('#') => Ok(OptionValue::String(value)),
+            rest => Err(format!("Unexpected \"{}\" after the value", rest)),
+        };
+    }
+
+    let value = text.split('#').next().unwrap_or_default().trim();
+    match value {
+        "true" => Ok(OptionValue::Bool(true)),
+        "false" => Ok(OptionValue::Bool(false)),
+        _ => Err(format!(
+            "Expected true, false or a string but found \"{}\"",
+            value
+        )),
+    }
+}
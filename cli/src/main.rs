@@ -4,11 +4,23 @@
 //! - gen-rust: Generate Rust code from a .tcs schema
 //! - validate: Validate a .tcs schema
 //! - format: Format a .tcs schema file
+//! - explain: Explain an error code
 
-use clap::{Parser, Subcommand};
+mod config;
+
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use std::collections::HashSet;
 use std::fs;
-use std::path::PathBuf;
-use tcs_compiler::{compile, TcsError};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+use tcs_compiler::error::codes;
+use tcs_compiler::{FormatOptions, GenOptions, Severity, TcsError, VerifyOptions, Warning};
+use tcs_schema::Schema;
+
+/// Marker comments delimiting the generated region of a hand-maintained file
+const BEGIN_MARKER: &str = "// TCS-GENERATED-BEGIN";
+const END_MARKER: &str = "// TCS-GENERATED-END";
 
 #[derive(Parser)]
 #[command(name = "tcs")]
@@ -16,17 +28,239 @@ use tcs_compiler::{compile, TcsError};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Only print errors; warnings are hidden unless they fail the command
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Also print how long each pipeline stage takes
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Character encoding of input schema files, which are transcoded to UTF-8
+    #[arg(long, global = true, value_enum, default_value_t = Encoding::Utf8)]
+    encoding: Encoding,
+}
+
+/// Character encodings accepted for input schema files
+#[derive(Clone, Copy, ValueEnum)]
+enum Encoding {
+    Utf8,
+    /// Latin-1, decoded as its Windows-1252 superset
+    #[value(alias = "windows-1252")]
+    Latin1,
+}
+
+/// How much informational output to print to stderr
+#[derive(Clone, Copy, PartialEq)]
+enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+impl Verbosity {
+    /// Print an informational message, unless `--quiet` is set
+    fn info(self, msg: std::fmt::Arguments) {
+        if self != Verbosity::Quiet {
+            eprintln!("{}", msg);
+        }
+    }
+
+    /// Run one pipeline stage, printing its duration with `--verbose`
+    fn time<T>(self, stage: &str, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        if self == Verbosity::Verbose {
+            eprintln!("  {}: {:.2?}", stage, start.elapsed());
+        }
+        result
+    }
+}
+
+/// Code generation flags. Each overrides the same-named entry of the
+/// schema's `options` block, which in turn overrides the project file.
+#[derive(Args)]
+struct GenFlags {
+    /// Project file with default options (defaults to ./tcs.toml, if present)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Emit definitions in dependency/alphabetical order instead of source order
+    #[arg(long)]
+    sort: bool,
+
+    /// Generate `visit` methods that walk nested structs and messages
+    #[arg(long)]
+    visitors: bool,
+
+    /// Generate zero-copy `View` readers for structs whose fields all have a fixed size
+    #[arg(long)]
+    views: bool,
+
+    /// Escape keyword field names as raw identifiers (`r#type`) instead of `type_`
+    #[arg(long)]
+    raw_identifiers: bool,
+
+    /// Derive `arbitrary::Arbitrary` behind an `arbitrary` feature, for fuzzing
+    #[arg(long)]
+    arbitrary: bool,
+
+    /// Derive serde traits behind a `serde` feature, with byte arrays as hex strings
+    #[arg(long)]
+    serde: bool,
+
+    /// With --serde, keep the schema's enum variant names (`VALIDATOR`) in serde output
+    #[arg(long)]
+    serde_enum_names: bool,
+
+    /// Print byte-array fields as hex strings in `Debug` output
+    #[arg(long)]
+    hex_debug: bool,
+
+    /// Give undocumented items placeholder docs, for crates that deny `missing_docs`
+    #[arg(long)]
+    doc_stubs: bool,
+
+    /// Don't derive `Clone` on structs and messages
+    #[arg(long)]
+    no_clone: bool,
+
+    /// Leave enum values implicit where they count up from 0 in declaration order
+    #[arg(long)]
+    implicit_discriminants: bool,
+
+    /// Implement `Default` by hand on messages instead of deriving it
+    #[arg(long)]
+    explicit_default: bool,
+
+    /// Leave deprecated fields out of `PartialEq`
+    #[arg(long)]
+    eq_ignore_deprecated: bool,
+
+    /// Generate single-field structs as transparent newtypes with `Deref` and `From`
+    #[arg(long)]
+    newtypes: bool,
+
+    /// Generate `set_<field>_checked` methods that range-check wide integers
+    #[arg(long)]
+    checked_setters: bool,
+
+    /// Generate `to_array` for fixed-size structs, serializing without allocating
+    #[arg(long)]
+    to_array: bool,
+
+    /// Path the generated code uses for the wincode crate (default `wincode`); any other
+    /// path gets hand-written impls instead of wincode's derives
+    #[arg(long)]
+    wincode_path: Option<String>,
+
+    /// Group output into type, impl and constant sections instead of per definition
+    #[arg(long)]
+    sectioned: bool,
+
+    /// Generate code for a `no_std` crate that uses `alloc`
+    #[arg(long)]
+    no_std: bool,
+
+    /// Wrap the generated code in a module of this name
+    #[arg(long)]
+    rust_module: Option<String>,
+
+    /// Embed the schema source in the output as `SCHEMA_SOURCE`
+    #[arg(long)]
+    embed_source: bool,
+}
+
+impl GenFlags {
+    /// The project file's options, then the schema's, with any flags given on
+    /// the command line applied. `source` is the schema's source text, if it
+    /// came from a single file.
+    fn options_for(&self, schema: &Schema, source: Option<&str>) -> Result<GenOptions, TcsError> {
+        let mut options = GenOptions::default();
+        options.apply_options(&config::load(self.config.as_deref())?.options)?;
+        options.apply_options(&schema.options)?;
+        options.sort_definitions |= self.sort;
+        options.generate_visitors |= self.visitors;
+        options.generate_views |= self.views;
+        options.raw_identifiers |= self.raw_identifiers;
+        options.derive_arbitrary |= self.arbitrary;
+        options.derive_serde |= self.serde;
+        options.serde_enum_names |= self.serde_enum_names;
+        options.hex_debug |= self.hex_debug;
+        options.doc_stubs |= self.doc_stubs;
+        options.no_clone |= self.no_clone;
+        options.explicit_discriminants &= !self.implicit_discriminants;
+        options.explicit_default |= self.explicit_default;
+        options.eq_ignore_deprecated |= self.eq_ignore_deprecated;
+        options.newtypes |= self.newtypes;
+        options.checked_setters |= self.checked_setters;
+        options.to_array |= self.to_array;
+        options.sectioned_output |= self.sectioned;
+        options.no_std |= self.no_std;
+        if let Some(ref path) = self.wincode_path {
+            options.wincode_path = path.clone();
+        }
+        if let Some(ref module) = self.rust_module {
+            options.rust_module = Some(module.clone());
+        }
+        if self.embed_source {
+            options.embed_source = source.map(str::to_string);
+        }
+        Ok(options)
+    }
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Generate Rust code from a .tcs schema file
     GenRust {
+        /// Input .tcs schema file
+        #[arg(short, long, required_unless_present = "input_dir")]
+        input: Option<PathBuf>,
+
+        /// Directory of .tcs files to merge into one schema (instead of --input)
+        #[arg(long, conflicts_with_all = ["input", "embed_source"])]
+        input_dir: Option<PathBuf>,
+
+        /// Output .rs file (defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[command(flatten)]
+        flags: GenFlags,
+
+        /// Print whether the output file would be created or overwritten, without writing it
+        #[arg(long, requires = "output")]
+        dry_run: bool,
+    },
+
+    /// Generate several outputs from a .tcs schema file, parsing and verifying it once
+    #[command(group(ArgGroup::new("outputs").required(true).multiple(true)))]
+    Gen {
         /// Input .tcs schema file
         #[arg(short, long)]
         input: PathBuf,
 
-        /// Output .rs file (defaults to stdout)
+        /// Output .rs file for Rust code
+        #[arg(long, group = "outputs")]
+        rust: Option<PathBuf>,
+
+        /// Output .json file for a JSON Schema document
+        #[arg(long, group = "outputs")]
+        json_schema: Option<PathBuf>,
+
+        #[command(flatten)]
+        flags: GenFlags,
+    },
+
+    /// Generate a JSON Schema document from a .tcs schema file
+    GenJsonSchema {
+        /// Input .tcs schema file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output .json file (defaults to stdout)
         #[arg(short, long)]
         output: Option<PathBuf>,
     },
@@ -36,6 +270,36 @@ enum Commands {
         /// Input .tcs schema file
         #[arg(short, long)]
         input: PathBuf,
+
+        /// Exit with an error if any warnings are reported
+        #[arg(long, alias = "warnings-as-errors")]
+        deny_warnings: bool,
+
+        /// Treat unrecognized @name annotations as errors
+        #[arg(long)]
+        strict_annotations: bool,
+    },
+
+    /// Report every warning and style lint for a .tcs schema file
+    Lint {
+        /// Input .tcs schema file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Exit with an error if anything is reported
+        #[arg(long)]
+        deny_warnings: bool,
+    },
+
+    /// Report a .tcs schema file's statistics, hash and definitions
+    SchemaInfo {
+        /// Input .tcs schema file
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
     },
 
     /// Format a .tcs schema file
@@ -48,90 +312,678 @@ enum Commands {
         #[arg(short, long)]
         output: Option<PathBuf>,
 
-        /// Check if file is formatted (exit 1 if not)
+        /// Check if file is formatted (print a diff and exit 1 if not)
         #[arg(long)]
         check: bool,
+
+        /// Put small enums and structs on a single line
+        #[arg(long)]
+        compact: bool,
+
+        /// Sort message fields by ID and enum variants by value, keeping their comments
+        #[arg(long)]
+        sort: bool,
+
+        /// With --sort, also sort struct fields by name (this changes their encoding)
+        #[arg(long, requires = "sort")]
+        sort_structs: bool,
     },
+
+    /// Explain an error code (e.g. TCS0002)
+    Explain {
+        /// Error code shown in brackets in error messages
+        code: String,
+    },
+}
+
+/// Why a command failed
+enum CliError {
+    /// An error for `main` to report
+    Tcs(TcsError),
+    /// The command already printed why it failed, e.g. `format --check`
+    Failed,
+}
+
+impl From<TcsError> for CliError {
+    fn from(e: TcsError) -> Self {
+        CliError::Tcs(e)
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(e: std::io::Error) -> Self {
+        CliError::Tcs(e.into())
+    }
 }
 
-fn main() {
+fn main() -> ExitCode {
     let cli = Cli::parse();
+    let verbosity = if cli.quiet {
+        Verbosity::Quiet
+    } else if cli.verbose {
+        Verbosity::Verbose
+    } else {
+        Verbosity::Normal
+    };
 
-    let result = match cli.command {
-        Commands::GenRust { input, output } => gen_rust(input, output),
-        Commands::Validate { input } => validate(input),
-        Commands::Format { input, output, check } => do_format(input, output, check),
+    let encoding = cli.encoding;
+
+    let (input, result) = match cli.command {
+        Commands::GenRust {
+            input,
+            input_dir,
+            output,
+            flags,
+            dry_run,
+        } => match (input, input_dir) {
+            (Some(input), _) => (
+                Some(input.clone()),
+                gen_rust(input, output, &flags, dry_run, encoding, verbosity),
+            ),
+            (None, Some(dir)) => match read_schema_dir(&dir, encoding) {
+                Ok(dir) => {
+                    let result =
+                        gen_rust_schema(&dir.schema, None, output, &flags, dry_run, verbosity);
+                    let origin = match result {
+                        Err(CliError::Tcs(ref e)) => dir.origin(e).map(Path::to_path_buf),
+                        _ => None,
+                    };
+                    (origin, result)
+                }
+                Err((path, e)) => (path, Err(e.into())),
+            },
+            (None, None) => unreachable!("clap requires --input or --input-dir"),
+        },
+        Commands::Gen {
+            input,
+            rust,
+            json_schema,
+            flags,
+        } => (
+            Some(input.clone()),
+            gen_all(input, rust, json_schema, &flags, encoding, verbosity),
+        ),
+        Commands::GenJsonSchema { input, output } => (
+            Some(input.clone()),
+            gen_json_schema(input, output, encoding, verbosity),
+        ),
+        Commands::Validate {
+            input,
+            deny_warnings,
+            strict_annotations,
+        } => (
+            Some(input.clone()),
+            validate(
+                input,
+                deny_warnings,
+                strict_annotations,
+                encoding,
+                verbosity,
+            ),
+        ),
+        Commands::Lint {
+            input,
+            deny_warnings,
+        } => (
+            Some(input.clone()),
+            lint(input, deny_warnings, encoding, verbosity),
+        ),
+        Commands::SchemaInfo { input, json } => (
+            Some(input.clone()),
+            schema_info(input, json, encoding, verbosity),
+        ),
+        Commands::Format {
+            input,
+            output,
+            check,
+            compact,
+            sort,
+            sort_structs,
+        } => {
+            let options = FormatOptions {
+                compact_small_defs: compact,
+                sort_fields: sort,
+                sort_struct_fields: sort_structs,
+                ..FormatOptions::default()
+            };
+            (
+                Some(input.clone()),
+                do_format(input, output, check, &options, encoding, verbosity),
+            )
+        }
+        Commands::Explain { code } => (None, explain(&code)),
     };
 
-    if let Err(e) = result {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(CliError::Tcs(e)) => {
+            report_error(&e, input.as_deref(), encoding);
+            ExitCode::FAILURE
+        }
+        Err(CliError::Failed) => ExitCode::FAILURE,
+    }
+}
+
+/// Print an error, followed by the offending source line for parse errors
+fn report_error(e: &TcsError, input: Option<&Path>, encoding: Encoding) {
+    eprintln!("Error: {}", e);
+
+    if let (Some((line, column)), Some(input)) = (e.location(), input) {
+        let snippet = read_source(input, encoding)
+            .ok()
+            .and_then(|source| tcs_compiler::utils::source_snippet(&source, line, column));
+        if let Some(snippet) = snippet {
+            eprintln!(" --> {}:{}:{}", input.display(), line, column);
+            eprintln!("{}", snippet);
+        }
+    } else if let Some(input) = input {
+        eprintln!(" --> {}", input.display());
+    }
+
+    eprintln!("For more information, try `tcs explain {}`", e.code());
+}
+
+/// Read a schema file in the given encoding. Invalid UTF-8 is reported with the
+/// file name and byte offset.
+fn read_source(path: &Path, encoding: Encoding) -> Result<String, TcsError> {
+    let bytes = fs::read(path)?;
+    if let Encoding::Latin1 = encoding {
+        // Every byte is a character in Windows-1252, so decoding can't fail
+        let (source, _) = encoding_rs::WINDOWS_1252.decode_without_bom_handling(&bytes);
+        return Ok(source.into_owned());
+    }
+    String::from_utf8(bytes).map_err(|e| {
+        TcsError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "{} is not valid UTF-8 (invalid byte at offset {})",
+                path.display(),
+                e.utf8_error().valid_up_to()
+            ),
+        ))
+    })
+}
+
+fn gen_rust(
+    input: PathBuf,
+    output: Option<PathBuf>,
+    flags: &GenFlags,
+    dry_run: bool,
+    encoding: Encoding,
+    verbosity: Verbosity,
+) -> Result<(), CliError> {
+    let source = read_source(&input, encoding)?;
+    let tokens = verbosity.time("tokenize", || tcs_compiler::tokenize_schema(&source))?;
+    let schema = verbosity.time("parse", || tcs_compiler::parse_schema(&tokens))?;
+    if schema.definitions.is_empty() {
+        verbosity.info(format_args!(
+            "Warning: {} has no definitions; the generated module is empty",
+            input.display()
+        ));
+    }
+
+    // Without --output, the project file may name a directory for the code
+    let output = match output {
+        Some(output) => Some(output),
+        None => match config::load(flags.config.as_deref())?.output_dir {
+            Some(dir) => {
+                fs::create_dir_all(&dir)?;
+                let name = input.with_extension("rs");
+                Some(dir.join(name.file_name().unwrap_or_default()))
+            }
+            None => None,
+        },
+    };
+    gen_rust_schema(&schema, Some(&source), output, flags, dry_run, verbosity)
+}
+
+/// The `.tcs` files of a directory, and the schema merged from them
+struct SchemaDir {
+    schema: Schema,
+    /// Each file with the schema parsed from it, in file name order
+    files: Vec<(PathBuf, Schema)>,
+}
+
+impl SchemaDir {
+    /// The file that `err`, from verifying the merged schema, comes from: the
+    /// first one that fails the same way when verified along with the files
+    /// before it. Types from later files count as defined, so references to
+    /// them don't fail in the meantime.
+    fn origin(&self, err: &TcsError) -> Option<&Path> {
+        let mut schema = Schema::new();
+        for (i, (path, parsed)) in self.files.iter().enumerate() {
+            schema.merge(parsed.clone()).ok()?;
+            let packages: Vec<Option<&str>> = schema
+                .definitions
+                .iter()
+                .map(|d| d.package.as_deref())
+                .collect();
+            let later = self.files[i + 1..].iter().flat_map(|(_, s)| &s.definitions);
+            let mut external_types = HashSet::new();
+            for definition in later {
+                external_types.insert(definition.qualified_name());
+                // Plain names only resolve within the same package
+                if packages.contains(&definition.package.as_deref()) {
+                    external_types.insert(definition.name.clone());
+                }
+            }
+            let options = VerifyOptions {
+                external_types,
+                ..VerifyOptions::default()
+            };
+            if let Err(e) = tcs_compiler::verify_schema_with_options(&schema, &options) {
+                return (e.to_string() == err.to_string()).then_some(path.as_path());
+            }
+        }
+        None
+    }
+}
+
+/// Parse every `.tcs` file in `dir` (in file name order) and merge them into
+/// one schema. On error, also returns the file it came from, if any.
+fn read_schema_dir(
+    dir: &Path,
+    encoding: Encoding,
+) -> Result<SchemaDir, (Option<PathBuf>, TcsError)> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .and_then(|entries| entries.map(|e| e.map(|e| e.path())).collect())
+        .map_err(|e| (None, TcsError::from(e)))?;
+    paths.retain(|p| p.extension().is_some_and(|ext| ext == "tcs"));
+    paths.sort();
+
+    let mut schema = Schema::new();
+    let mut files: Vec<(PathBuf, Schema)> = Vec::new();
+    for path in paths {
+        let parsed = read_source(&path, encoding)
+            .and_then(|source| tcs_compiler::parse_schema(&tcs_compiler::tokenize_schema(&source)?))
+            .map_err(|e| (Some(path.clone()), e))?;
+
+        schema.merge(parsed.clone()).map_err(|dup| {
+            // Which file the definition was already taken by
+            let first = files
+                .iter()
+                .find(|(_, s)| s.definitions.iter().any(|d| d.qualified_name() == dup.name))
+                .map(|(p, _)| p.display().to_string())
+                .unwrap_or_default();
+            let msg = format!(
+                "The type \"{}\" is defined in both {} and {}",
+                dup.name,
+                first,
+                path.display()
+            );
+            (
+                None,
+                tcs_compiler::utils::verification_error(codes::DUPLICATE_TYPE, &msg),
+            )
+        })?;
+        files.push((path, parsed));
+    }
+
+    Ok(SchemaDir { schema, files })
+}
+
+fn gen_rust_schema(
+    schema: &Schema,
+    source: Option<&str>,
+    output: Option<PathBuf>,
+    flags: &GenFlags,
+    dry_run: bool,
+    verbosity: Verbosity,
+) -> Result<(), CliError> {
+    verbosity.time("verify", || tcs_compiler::verify_schema(schema))?;
+    let rust_code = rust_code(schema, source, flags, output.as_deref(), verbosity)?;
+    Ok(write_rust(&rust_code, output, dry_run, verbosity)?)
+}
+
+/// Generate Rust code for an already verified schema, to be written to
+/// `output`. Code spliced between markers goes without the file header.
+fn rust_code(
+    schema: &Schema,
+    source: Option<&str>,
+    flags: &GenFlags,
+    output: Option<&Path>,
+    verbosity: Verbosity,
+) -> Result<String, TcsError> {
+    let mut options = flags.options_for(schema, source)?;
+    options.file_header = !output.is_some_and(has_markers);
+    tcs_compiler::verify_gen_options(schema, &options)?;
+    verbosity.time("codegen", || {
+        tcs_compiler::try_compile_schema_to_rust_with_options(schema, &options)
+    })
+}
+
+/// Generate each requested output from one parse and verification of `input`
+fn gen_all(
+    input: PathBuf,
+    rust: Option<PathBuf>,
+    json_schema: Option<PathBuf>,
+    flags: &GenFlags,
+    encoding: Encoding,
+    verbosity: Verbosity,
+) -> Result<(), CliError> {
+    let source = read_source(&input, encoding)?;
+    let tokens = verbosity.time("tokenize", || tcs_compiler::tokenize_schema(&source))?;
+    let schema = verbosity.time("parse", || tcs_compiler::parse_schema(&tokens))?;
+    verbosity.time("verify", || tcs_compiler::verify_schema(&schema))?;
+
+    if let Some(path) = rust {
+        let rust_code = rust_code(&schema, Some(&source), flags, Some(&path), verbosity)?;
+        write_rust(&rust_code, Some(path), false, verbosity)?;
+    }
+    if let Some(path) = json_schema {
+        let json = verbosity.time("codegen", || {
+            tcs_compiler::compile_schema_to_json_schema(&schema)
+        });
+        write_output(json.trim_end(), Some(path), verbosity)?;
+    }
+    Ok(())
+}
+
+/// Write generated Rust code. If the output file already contains the marker
+/// comments, only the region between them is replaced. With `dry_run`, only
+/// print what would happen to the file.
+fn write_rust(
+    rust_code: &str,
+    output: Option<PathBuf>,
+    dry_run: bool,
+    verbosity: Verbosity,
+) -> Result<(), TcsError> {
+    if let Some(ref path) = output {
+        let spliced = match fs::read_to_string(path) {
+            Ok(existing) => splice_generated(&existing, rust_code, path)?,
+            Err(_) => None,
+        };
+        if dry_run {
+            let action = if spliced.is_some() {
+                "update (between markers)"
+            } else if path.exists() {
+                "overwrite"
+            } else {
+                "create"
+            };
+            println!("Would {} {}", action, path.display());
+            return Ok(());
+        }
+        if let Some(spliced) = spliced {
+            fs::write(path, spliced)?;
+            verbosity.info(format_args!(
+                "Generated: {} (between markers)",
+                path.display()
+            ));
+            return Ok(());
+        }
+    }
+    write_output(rust_code, output, verbosity)
+}
+
+/// Whether the file at `path` exists and has a generated region to splice into
+fn has_markers(path: &Path) -> bool {
+    fs::read_to_string(path).is_ok_and(|existing| existing.contains(BEGIN_MARKER))
+}
+
+/// Replace the lines between the begin and end markers in `existing` with
+/// `code`, or return `None` if there are no markers
+fn splice_generated(existing: &str, code: &str, path: &Path) -> Result<Option<String>, TcsError> {
+    let (begin, end) = match (existing.find(BEGIN_MARKER), existing.find(END_MARKER)) {
+        (None, None) => return Ok(None),
+        (Some(begin), Some(end)) if begin < end => (begin, end),
+        _ => {
+            return Err(TcsError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "{} must contain both {} and {}, in that order",
+                    path.display(),
+                    BEGIN_MARKER,
+                    END_MARKER
+                ),
+            )))
+        }
+    };
+
+    // Keep both marker lines, including any indentation before the end marker
+    let region_start = existing[begin..]
+        .find('\n')
+        .map_or(existing.len(), |i| begin + i + 1)
+        .min(end);
+    let region_end = existing[..end]
+        .rfind('\n')
+        .map_or(0, |i| i + 1)
+        .max(region_start);
+
+    let mut spliced = existing[..region_start].to_string();
+    if !spliced.ends_with('\n') {
+        spliced.push('\n');
     }
+    spliced.push_str(code.trim_end());
+    spliced.push('\n');
+    spliced.push_str(&existing[region_end..]);
+    Ok(Some(spliced))
 }
 
-fn gen_rust(input: PathBuf, output: Option<PathBuf>) -> Result<(), TcsError> {
-    let source = fs::read_to_string(&input)?;
-    let rust_code = compile(&source)?;
+fn gen_json_schema(
+    input: PathBuf,
+    output: Option<PathBuf>,
+    encoding: Encoding,
+    verbosity: Verbosity,
+) -> Result<(), CliError> {
+    let source = read_source(&input, encoding)?;
+    let tokens = verbosity.time("tokenize", || tcs_compiler::tokenize_schema(&source))?;
+    let schema = verbosity.time("parse", || tcs_compiler::parse_schema(&tokens))?;
+    verbosity.time("verify", || tcs_compiler::verify_schema(&schema))?;
+    let json = verbosity.time("codegen", || {
+        tcs_compiler::compile_schema_to_json_schema(&schema)
+    });
+    Ok(write_output(json.trim_end(), output, verbosity)?)
+}
 
+/// Write generated code to `output`, or print it if there's no output file
+fn write_output(code: &str, output: Option<PathBuf>, verbosity: Verbosity) -> Result<(), TcsError> {
     match output {
         Some(path) => {
-            fs::write(&path, &rust_code)?;
-            eprintln!("Generated: {}", path.display());
+            fs::write(&path, code)?;
+            verbosity.info(format_args!("Generated: {}", path.display()));
         }
         None => {
-            println!("{}", rust_code);
+            println!("{}", code);
         }
     }
 
     Ok(())
 }
 
-fn validate(input: PathBuf) -> Result<(), TcsError> {
-    let source = fs::read_to_string(&input)?;
+fn validate(
+    input: PathBuf,
+    deny_warnings: bool,
+    strict_annotations: bool,
+    encoding: Encoding,
+    verbosity: Verbosity,
+) -> Result<(), CliError> {
+    let source = read_source(&input, encoding)?;
 
-    let tokens = tcs_compiler::tokenize_schema(&source)?;
-    let schema = tcs_compiler::parse_schema(&tokens)?;
-    tcs_compiler::verify_schema(&schema)?;
+    let tokens = verbosity.time("tokenize", || tcs_compiler::tokenize_schema(&source))?;
+    let schema = verbosity.time("parse", || tcs_compiler::parse_schema(&tokens))?;
+    let options = VerifyOptions {
+        strict_annotations,
+        ..VerifyOptions::default()
+    };
+    let warnings = verbosity.time("verify", || {
+        tcs_compiler::verify_schema_with_warnings(&schema, &options)
+    })?;
 
-    eprintln!("Schema is valid: {}", input.display());
-    eprintln!(
-        "  {} definition(s)",
-        schema.definitions.len()
-    );
+    // With --deny-warnings they are errors, which --quiet doesn't hide
+    for warning in &warnings {
+        if deny_warnings {
+            eprintln!("{}", warning);
+        } else {
+            verbosity.info(format_args!("{}", warning));
+        }
+    }
+    if deny_warnings && !warnings.is_empty() {
+        eprintln!(
+            "Schema has {} warning(s) and --deny-warnings is set: {}",
+            warnings.len(),
+            input.display()
+        );
+        return Err(CliError::Failed);
+    }
+
+    verbosity.info(format_args!("Schema is valid: {}", input.display()));
+    let stats = schema.stats();
+    verbosity.info(format_args!(
+        "  {} definition(s): {} enum(s), {} struct(s), {} message(s)",
+        schema.definitions.len(),
+        stats.enums,
+        stats.structs,
+        stats.messages
+    ));
+    verbosity.info(format_args!(
+        "  {} field(s), {} deprecated",
+        stats.fields, stats.deprecated_fields
+    ));
+    if schema.definitions.is_empty() {
+        verbosity.info(format_args!(
+            "  note: the schema is empty (it only contains comments or whitespace)"
+        ));
+    }
 
     for def in &schema.definitions {
-        eprintln!(
+        verbosity.info(format_args!(
             "    - {} ({:?}, {} field(s))",
             def.name,
             def.kind,
             def.fields.len()
-        );
+        ));
     }
 
     Ok(())
 }
 
-fn do_format(input: PathBuf, output: Option<PathBuf>, check: bool) -> Result<(), TcsError> {
-    let source = fs::read_to_string(&input)?;
+fn schema_info(
+    input: PathBuf,
+    json: bool,
+    encoding: Encoding,
+    verbosity: Verbosity,
+) -> Result<(), CliError> {
+    let source = read_source(&input, encoding)?;
+
+    let tokens = verbosity.time("tokenize", || tcs_compiler::tokenize_schema(&source))?;
+    let schema = verbosity.time("parse", || tcs_compiler::parse_schema(&tokens))?;
+    verbosity.time("verify", || tcs_compiler::verify_schema(&schema))?;
 
-    let tokens = tcs_compiler::tokenize_schema(&source)?;
-    let schema = tcs_compiler::parse_schema(&tokens)?;
-    tcs_compiler::verify_schema(&schema)?;
+    if json {
+        print!("{}", tcs_compiler::schema_info_json(&schema));
+        return Ok(());
+    }
 
-    let formatted = tcs_compiler::format_schema(&schema);
+    let stats = schema.stats();
+    println!("package: {}", schema.package.as_deref().unwrap_or("(none)"));
+    println!("version: {}", env!("CARGO_PKG_VERSION"));
+    println!("schema hash: {}", tcs_compiler::schema_hash(&schema));
+    println!(
+        "{} definition(s): {} enum(s), {} struct(s), {} message(s)",
+        schema.definitions.len(),
+        stats.enums,
+        stats.structs,
+        stats.messages
+    );
+    println!(
+        "{} field(s), {} deprecated",
+        stats.fields, stats.deprecated_fields
+    );
+    for (name, max_id) in &stats.max_field_ids {
+        match max_id {
+            Some(id) => println!("  {}: highest field ID {}", name, id),
+            None => println!("  {}: no fields", name),
+        }
+    }
+
+    Ok(())
+}
+
+fn lint(
+    input: PathBuf,
+    deny_warnings: bool,
+    encoding: Encoding,
+    verbosity: Verbosity,
+) -> Result<(), CliError> {
+    let source = read_source(&input, encoding)?;
+
+    let tokens = verbosity.time("tokenize", || tcs_compiler::tokenize_schema(&source))?;
+    let schema = verbosity.time("parse", || tcs_compiler::parse_schema(&tokens))?;
+    let warnings = verbosity.time("lint", || {
+        tcs_compiler::lint_schema(&schema, &VerifyOptions::default())
+    })?;
+
+    for (severity, heading) in [(Severity::Warning, "Warnings"), (Severity::Style, "Style")] {
+        let group: Vec<&Warning> = warnings
+            .iter()
+            .filter(|w| w.severity() == severity)
+            .collect();
+        if !group.is_empty() {
+            println!("{}:", heading);
+            for warning in group {
+                println!("  {}", warning);
+            }
+        }
+    }
+
+    if warnings.is_empty() {
+        verbosity.info(format_args!("No lints: {}", input.display()));
+    } else if deny_warnings {
+        eprintln!(
+            "Schema has {} lint(s) and --deny-warnings is set: {}",
+            warnings.len(),
+            input.display()
+        );
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn do_format(
+    input: PathBuf,
+    output: Option<PathBuf>,
+    check: bool,
+    options: &FormatOptions,
+    encoding: Encoding,
+    verbosity: Verbosity,
+) -> Result<(), CliError> {
+    let source = read_source(&input, encoding)?;
+
+    let tokens = verbosity.time("tokenize", || tcs_compiler::tokenize_schema(&source))?;
+    let schema = verbosity.time("parse", || tcs_compiler::parse_schema(&tokens))?;
+    verbosity.time("verify", || tcs_compiler::verify_schema(&schema))?;
+
+    let formatted = verbosity.time("format", || {
+        tcs_compiler::format_schema_with_options(&schema, options)
+    });
 
     if check {
         if source == formatted {
-            eprintln!("File is formatted: {}", input.display());
+            verbosity.info(format_args!("File is formatted: {}", input.display()));
             Ok(())
         } else {
             eprintln!("File needs formatting: {}", input.display());
+            print!("{}", tcs_compiler::diff_lines(&source, &formatted));
             std::process::exit(1);
         }
     } else {
         let target = output.unwrap_or(input);
         fs::write(&target, &formatted)?;
-        eprintln!("Formatted: {}", target.display());
+        verbosity.info(format_args!("Formatted: {}", target.display()));
         Ok(())
     }
 }
+
+fn explain(code: &str) -> Result<(), CliError> {
+    match tcs_compiler::error::explain(code) {
+        Some(text) => {
+            println!("{}: {}", code.to_ascii_uppercase(), text);
+            Ok(())
+        }
+        None => {
+            eprintln!("Unknown error code: {}", code);
+            Err(CliError::Failed)
+        }
+    }
+}
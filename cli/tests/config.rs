@@ -0,0 +1,114 @@
+//! Integration tests for `tcs.toml` project files
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const NO_STD_IMPORT: &str = "use alloc::{string::String, vec::Vec};";
+
+/// Create an empty, unique temp directory holding `schema.tcs`
+fn project(name: &str, schema: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tcs-cli-config-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("schema.tcs"), schema).unwrap();
+    dir
+}
+
+/// Run `tcs gen-rust --input schema.tcs` from `dir`
+fn gen_rust(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .current_dir(dir)
+        .args(["gen-rust", "--input", "schema.tcs"])
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_config_in_current_dir() {
+    let dir = project("cwd", "struct Point { int x; }\n");
+    fs::write(dir.join("tcs.toml"), "# Project defaults\nno_std = true\n").unwrap();
+
+    let output = gen_rust(&dir, &[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(String::from_utf8_lossy(&output.stdout).contains(NO_STD_IMPORT));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_config_precedence() {
+    let dir = project(
+        "precedence",
+        "options { rust_module = \"from_schema\"; }\nstruct Point { int x; }\n",
+    );
+    fs::write(
+        dir.join("project.toml"),
+        "rust_module = \"from_config\"  # overridden by the schema\nserde = true\n",
+    )
+    .unwrap();
+
+    // The schema's options block overrides the file, and flags override both
+    let output = gen_rust(&dir, &["--config", "project.toml"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("pub mod from_schema {"));
+    assert!(stdout.contains("serde::Serialize"));
+    assert!(!stdout.contains(NO_STD_IMPORT));
+
+    let output = gen_rust(&dir, &["--config", "project.toml", "--rust-module", "flag"]);
+    assert!(String::from_utf8_lossy(&output.stdout).contains("pub mod flag {"));
+
+    // No tcs.toml in the directory, so nothing is applied without --config
+    let output = gen_rust(&dir, &[]);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("serde::Serialize"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_config_output_dir() {
+    let dir = project("output", "struct Point { int x; }\n");
+    fs::write(dir.join("tcs.toml"), "output_dir = \"src/generated\"\n").unwrap();
+
+    let output = gen_rust(&dir, &[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(output.stdout.is_empty());
+    let code = fs::read_to_string(dir.join("src/generated/schema.rs")).unwrap();
+    assert!(code.contains("pub struct Point"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_invalid_config() {
+    let dir = project("invalid", "struct Point { int x; }\n");
+    for (config, msg) in [
+        (
+            "no_std = true\nno_sdt = true\n",
+            "tcs.toml:2: Unknown option \"no_sdt\"",
+        ),
+        (
+            "no_std = \"yes\"\n",
+            "tcs.toml:1: The option \"no_std\" expects true or false",
+        ),
+        ("[gen]\n", "tcs.toml:1: Expected `key = value`"),
+        ("rust_module = \"open\n", "tcs.toml:1: Unterminated string"),
+    ] {
+        fs::write(dir.join("tcs.toml"), config).unwrap();
+        let output = gen_rust(&dir, &[]);
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(msg), "{}", stderr);
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,27 @@
+//! Integration tests for `tcs explain`
+
+use std::process::Command;
+
+#[test]
+fn test_explain() {
+    let explain = |code: &str| {
+        Command::new(env!("CARGO_BIN_EXE_tcs"))
+            .args(["explain", code])
+            .output()
+            .unwrap()
+    };
+
+    let output = explain("tcs0001");
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .starts_with("TCS0001: "));
+
+    // An unknown code fails with just the one message
+    let output = explain("TCS9999");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Unknown error code: TCS9999\n"
+    );
+}
@@ -0,0 +1,78 @@
+//! Integration tests for `tcs format`
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_check_prints_diff() {
+    let path = std::env::temp_dir().join(format!("tcs-cli-format-{}.tcs", std::process::id()));
+    fs::write(&path, "struct Point {\n  int   x;\n  int y;\n}\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .arg("format")
+        .arg("--input")
+        .arg(&path)
+        .arg("--check")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("-  int   x;"));
+    assert!(stdout.contains("+  int x;"));
+
+    // Check mode never rewrites the file
+    assert_eq!(fs::read_to_string(&path).unwrap(), "struct Point {\n  int   x;\n  int y;\n}\n");
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_check_reports_line_endings() {
+    let path = std::env::temp_dir().join(format!("tcs-cli-format-crlf-{}.tcs", std::process::id()));
+    fs::write(&path, "struct Point {\r\n  int x;\r\n}").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .arg("format")
+        .arg("--input")
+        .arg(&path)
+        .arg("--check")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(
+        stdout,
+        "\\ line endings differ: CRLF instead of LF\n\\ missing trailing newline\n"
+    );
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_sort() {
+    let path = std::env::temp_dir().join(format!("tcs-cli-format-sort-{}.tcs", std::process::id()));
+    fs::write(
+        &path,
+        "message Transfer {\n  // Who pays\n  byte[32] from = 3;\n  uint64 amount = 1;\n  byte[32] to = 2;\n}\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["format", "--sort", "--input"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "message Transfer {\n  uint64 amount = 1;\n  byte[32] to = 2;\n  // Who pays\n  byte[32] from = 3;\n}\n"
+    );
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,72 @@
+//! Integration tests for `tcs gen`
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_gen_multiple_outputs() {
+    let dir = std::env::temp_dir().join(format!("tcs-cli-gen-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("schema.tcs");
+    fs::write(
+        &input,
+        "options { no_clone = true; }\nmessage Transaction {\n  uint64 nonce = 1;\n}\n",
+    )
+    .unwrap();
+    let rust = dir.join("generated.rs");
+    let json = dir.join("schema.json");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen", "--input"])
+        .arg(&input)
+        .arg("--rust")
+        .arg(&rust)
+        .arg("--json-schema")
+        .arg(&json)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Each output matches what its own command generates
+    let rust_code = fs::read_to_string(&rust).unwrap();
+    assert!(rust_code.contains("pub struct Transaction {"));
+    assert!(rust_code.contains("#[derive(Debug, PartialEq, Default, SchemaRead, SchemaWrite)]"));
+    let expected = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen-json-schema", "--input"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(&json).unwrap() + "\n",
+        String::from_utf8(expected.stdout).unwrap()
+    );
+
+    // At least one output is required
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen", "--input"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    // Nothing is written if the schema doesn't verify
+    fs::remove_file(&rust).unwrap();
+    fs::write(&input, "message Transaction { Unknown field = 1; }\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen", "--input"])
+        .arg(&input)
+        .arg("--rust")
+        .arg(&rust)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("TCS0001"));
+    assert!(!rust.exists());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,44 @@
+//! Integration tests for `tcs gen-json-schema`
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_gen_json_schema() {
+    let path = std::env::temp_dir().join(format!("tcs-cli-json-{}.tcs", std::process::id()));
+    fs::write(
+        &path,
+        "message Transaction {\n  uint64 nonce = 1;\n  byte[32] hash = 2;\n}\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .arg("gen-json-schema")
+        .arg("--input")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("{\n  \"$schema\""));
+    assert!(stdout.contains("\"Transaction\": {"));
+    assert!(stdout.contains("\"nonce\": {\n          \"type\": \"integer\","));
+    assert!(stdout.ends_with("}\n"));
+
+    // The schema is verified first
+    fs::write(&path, "message Transaction { Unknown field = 1; }\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .arg("gen-json-schema")
+        .arg("--input")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("TCS0001"));
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,437 @@
+//! Integration tests for `tcs gen-rust`
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Create an empty, unique temp directory
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("tcs-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn gen_rust_dir(dir: &PathBuf) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .arg("gen-rust")
+        .arg("--input-dir")
+        .arg(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_input_dir() {
+    let dir = temp_dir("dir");
+    fs::write(
+        dir.join("a.tcs"),
+        "package chain;\nstruct Header { uint64 height; }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("b.tcs"),
+        "package chain;\nmessage Block { Header header = 1; }\n",
+    )
+    .unwrap();
+    fs::write(dir.join("notes.txt"), "not a schema").unwrap();
+
+    let output = gen_rust_dir(&dir);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("pub mod chain {").count(), 1);
+    assert!(stdout.contains("pub struct Header"));
+    assert!(stdout.contains("pub struct Block"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_input_dir_duplicate() {
+    let dir = temp_dir("dup");
+    fs::write(dir.join("a.tcs"), "struct Header { uint64 height; }\n").unwrap();
+    fs::write(dir.join("b.tcs"), "struct Header { uint32 height; }\n").unwrap();
+
+    let output = gen_rust_dir(&dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("TCS0003"));
+    assert!(stderr.contains("a.tcs") && stderr.contains("b.tcs"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_input_dir_duplicate_in_package() {
+    // Only the two `b.Point`s collide, not `a.Point`
+    let dir = temp_dir("dup-package");
+    fs::write(dir.join("1.tcs"), "package a;\nstruct Point { int x; }\n").unwrap();
+    fs::write(dir.join("2.tcs"), "package b;\nstruct Point { int x; }\n").unwrap();
+    fs::write(dir.join("3.tcs"), "package b;\nstruct Point { int y; }\n").unwrap();
+
+    let output = gen_rust_dir(&dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let expected = format!(
+        "The type \"b.Point\" is defined in both {} and {}",
+        dir.join("2.tcs").display(),
+        dir.join("3.tcs").display()
+    );
+    assert!(stderr.contains(&expected), "{}", stderr);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_input_dir_error_file() {
+    // Errors in the merged schema name the file they come from, even when
+    // it refers to types from later files
+    let dir = temp_dir("dir-error");
+    fs::write(
+        dir.join("a.tcs"),
+        "package chain;\nstruct Header { uint64 height; Hash hash; }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("b.tcs"),
+        "package chain;\nstruct Hash { byte[32] bytes; }\nmessage Block { Header header = 1; Hash hash = 1; }\n",
+    )
+    .unwrap();
+
+    let output = gen_rust_dir(&dir);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("TCS0002"), "{}", stderr);
+    assert!(
+        stderr.contains(&format!(" --> {}", dir.join("b.tcs").display())),
+        "{}",
+        stderr
+    );
+
+    fs::write(
+        dir.join("a.tcs"),
+        "package chain;\nstruct Header { uint64 height; Missing hash; }\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("b.tcs"),
+        "package chain;\nmessage Block { Header header = 1; }\n",
+    )
+    .unwrap();
+    let output = gen_rust_dir(&dir);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("TCS0001"), "{}", stderr);
+    assert!(
+        stderr.contains(&format!(" --> {}", dir.join("a.tcs").display())),
+        "{}",
+        stderr
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_comments_only() {
+    let dir = temp_dir("comments");
+    let path = dir.join("empty.tcs");
+    fs::write(&path, "// Nothing here yet\n   \n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .arg("gen-rust")
+        .arg("--input")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("has no definitions"));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("// This file was generated by the TCS compiler."));
+    assert!(!stdout.contains("use wincode"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_quiet() {
+    let dir = temp_dir("quiet");
+    let input = dir.join("point.tcs");
+    let output_path = dir.join("point.rs");
+    fs::write(&input, "struct Point { int x; }\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen-rust", "--quiet", "--input"])
+        .arg(&input)
+        .arg("--output")
+        .arg(&output_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert!(fs::read_to_string(&output_path)
+        .unwrap()
+        .contains("pub struct Point"));
+
+    // Including the warning for a schema without definitions
+    fs::write(&input, "// Nothing yet\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen-rust", "--quiet", "--input"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_flags_override_schema_options() {
+    let dir = temp_dir("options");
+    let input = dir.join("schema.tcs");
+    fs::write(
+        &input,
+        "options {\n  no_std = true;\n  rust_module = \"protocol\";\n}\n\nmessage Ping { uint64 id = 1; }\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen-rust", "--rust-module", "wire", "--input"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("pub mod wire {"));
+    assert!(!stdout.contains("pub mod protocol {"));
+    assert!(stdout.contains("use alloc::{string::String, vec::Vec};"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_embed_source() {
+    let dir = temp_dir("embed");
+    let input = dir.join("schema.tcs");
+    let source = "package chain;\n\n# Height of the chain\nmessage Tip { uint64 height = 1; }\n";
+    fs::write(&input, source).unwrap();
+    let output_path = dir.join("generated.rs");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen-rust", "--embed-source", "--input"])
+        .arg(&input)
+        .arg("--output")
+        .arg(&output_path)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let generated = fs::read_to_string(&output_path).unwrap();
+    assert!(generated.contains(&format!("pub const SCHEMA_SOURCE: &str = r\"{}\";", source)));
+
+    // A directory has no single source to embed
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen-rust", "--embed-source", "--input-dir"])
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_generate_between_markers() {
+    let dir = temp_dir("markers");
+    let input = dir.join("schema.tcs");
+    fs::write(
+        &input,
+        "package chain;\nmessage Tip { uint64 height = 1; }\n",
+    )
+    .unwrap();
+    let output_path = dir.join("lib.rs");
+    let before = "//! Hand-written crate docs\n\nuse std::fmt;\n\n// TCS-GENERATED-BEGIN\n";
+    let after = "    // TCS-GENERATED-END\n\npub fn helper() -> u32 {\n    42\n}\n";
+    fs::write(&output_path, format!("{}stale code\n{}", before, after)).unwrap();
+
+    let gen_rust = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+            .args(["gen-rust", "--input"])
+            .arg(&input)
+            .arg("--output")
+            .arg(&output_path)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        fs::read_to_string(&output_path).unwrap()
+    };
+
+    // The host file keeps its own header; the generated one is left out
+    let generated = gen_rust();
+    assert!(generated.starts_with(&format!("{}#[allow(dead_code)]\npub mod chain {{", before)));
+    assert!(!generated.contains("// This file was generated"));
+    assert!(generated.ends_with(&format!("}}\n{}", after)));
+    assert!(generated.contains("pub struct Tip"));
+    assert!(!generated.contains("stale code"));
+
+    // Regenerating is stable
+    assert_eq!(gen_rust(), generated);
+
+    // A missing end marker is an error rather than a silent overwrite
+    fs::write(&output_path, before).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen-rust", "--input"])
+        .arg(&input)
+        .arg("--output")
+        .arg(&output_path)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("TCS-GENERATED-END"));
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), before);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_generate_between_markers_without_package() {
+    // The example crate compiles src/signals.rs, whose generated region
+    // comes from a schema without a package
+    let example = Path::new(env!("CARGO_MANIFEST_DIR")).join("../example");
+    let expected = fs::read_to_string(example.join("src/signals.rs")).unwrap();
+    let begin = expected.find("// TCS-GENERATED-BEGIN\n").unwrap() + 23;
+    let end = expected.find("// TCS-GENERATED-END").unwrap();
+
+    let dir = temp_dir("markers-no-package");
+    let output_path = dir.join("signals.rs");
+    let stale = format!("{}stale code\n{}", &expected[..begin], &expected[end..]);
+    fs::write(&output_path, stale).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen-rust", "--input"])
+        .arg(example.join("signals.tcs"))
+        .arg("--output")
+        .arg(&output_path)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let generated = fs::read_to_string(&output_path).unwrap();
+    assert!(!generated.contains("#!["));
+    assert_eq!(generated, expected);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_dry_run() {
+    let dir = temp_dir("dry-run");
+    let input = dir.join("schema.tcs");
+    fs::write(&input, "struct Point { int x; int y; }\n").unwrap();
+
+    let dry_run = |output_path: &PathBuf| {
+        let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+            .args(["gen-rust", "--dry-run", "--input"])
+            .arg(&input)
+            .arg("--output")
+            .arg(output_path)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let new_path = dir.join("new.rs");
+    assert_eq!(
+        dry_run(&new_path),
+        format!("Would create {}\n", new_path.display())
+    );
+    assert!(!new_path.exists());
+
+    let existing_path = dir.join("existing.rs");
+    fs::write(&existing_path, "hand-written\n").unwrap();
+    assert_eq!(
+        dry_run(&existing_path),
+        format!("Would overwrite {}\n", existing_path.display())
+    );
+
+    let marked_path = dir.join("marked.rs");
+    let marked = "// TCS-GENERATED-BEGIN\n// TCS-GENERATED-END\n";
+    fs::write(&marked_path, marked).unwrap();
+    assert_eq!(
+        dry_run(&marked_path),
+        format!("Would update (between markers) {}\n", marked_path.display())
+    );
+
+    // Nothing was written
+    assert_eq!(
+        fs::read_to_string(&existing_path).unwrap(),
+        "hand-written\n"
+    );
+    assert_eq!(fs::read_to_string(&marked_path).unwrap(), marked);
+    let mut names: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["existing.rs", "marked.rs", "schema.tcs"]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_latin1_encoding() {
+    let dir = temp_dir("latin1");
+    let input = dir.join("schema.tcs");
+    // "Größe" in Latin-1, which isn't valid UTF-8
+    fs::write(
+        &input,
+        b"# Gr\xf6\xdfe des Blocks\nstruct Block { uint64 size; }\n",
+    )
+    .unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen-rust", "--input"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is not valid UTF-8"));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .args(["gen-rust", "--encoding", "latin1", "--input"])
+        .arg(&input)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("/// Größe des Blocks\n"));
+    assert!(stdout.contains("pub struct Block {"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
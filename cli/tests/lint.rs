@@ -0,0 +1,52 @@
+//! Integration tests for `tcs lint`
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_lint_groups_by_severity() {
+    let path = std::env::temp_dir().join(format!("tcs-cli-lint-{}.tcs", std::process::id()));
+    fs::write(
+        &path,
+        "// Job states\nenum Status {\n  PENDING = 0;\n  failed = 2;\n}\n\ntype Unused = int;\n\nmessage job {\n  Status status = 1;\n}\n",
+    )
+    .unwrap();
+
+    let lint = |extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_tcs"))
+            .arg("lint")
+            .arg("--input")
+            .arg(&path)
+            .args(extra)
+            .output()
+            .unwrap()
+    };
+
+    let output = lint(&[]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let warnings = stdout.find("Warnings:\n").unwrap();
+    let style = stdout.find("Style:\n").unwrap();
+    assert!(warnings < style);
+    for (code, group) in [
+        ("[TCS1001]", warnings),
+        ("[TCS1002]", warnings),
+        ("[TCS2001]", style),
+        ("[TCS2002]", style),
+    ] {
+        let at = stdout
+            .find(code)
+            .unwrap_or_else(|| panic!("{} missing", code));
+        assert!(at > group);
+        if group == warnings {
+            assert!(at < style, "{} is not a warning", code);
+        }
+    }
+    assert!(stdout.contains("The variant \"failed\" should be UPPER_SNAKE_CASE"));
+
+    let output = lint(&["--deny-warnings"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("5 lint(s)"));
+
+    fs::remove_file(&path).unwrap();
+}
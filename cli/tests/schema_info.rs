@@ -0,0 +1,36 @@
+//! Integration tests for `tcs schema-info`
+
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_schema_info_json() {
+    let path = std::env::temp_dir().join(format!("tcs-cli-info-{}.tcs", std::process::id()));
+    let source = "package demo;\n\nenum Kind { A = 0; B = 1; }\n\nstruct Point { int x; int y; }\n\nmessage Shape {\n  Kind kind = 1;\n  Point[] points = 2;\n}\n";
+    fs::write(&path, source).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .arg("schema-info")
+        .arg("--input")
+        .arg(&path)
+        .arg("--json")
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    let tokens = tcs_compiler::tokenize_schema(source).unwrap();
+    let schema = tcs_compiler::parse_schema(&tokens).unwrap();
+    let hash = tcs_compiler::schema_hash(&schema);
+    assert!(stdout.contains(&format!("\"schema_hash\": \"{}\"", hash)));
+    assert!(stdout.contains("\"package\": \"demo\""));
+    assert!(stdout.contains("\"max_field_ids\": {\n      \"Shape\": 2\n    }"));
+    assert_eq!(stdout.matches("\"kind\": ").count(), 3);
+    assert!(stdout.contains("\"name\": \"Shape\",\n      \"kind\": \"message\""));
+
+    fs::remove_file(&path).unwrap();
+}
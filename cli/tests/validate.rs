@@ -0,0 +1,137 @@
+//! Integration tests for `tcs validate`
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Write a schema to a unique temp file and return its path
+fn write_schema(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("tcs-cli-{}-{}.tcs", name, std::process::id()));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn validate(path: &PathBuf, extra: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_tcs"))
+        .arg("validate")
+        .arg("--input")
+        .arg(path)
+        .args(extra)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_invalid_utf8() {
+    let path = std::env::temp_dir().join(format!("tcs-cli-utf8-{}.tcs", std::process::id()));
+    fs::write(&path, b"struct Foo { int \xff; }").unwrap();
+
+    let output = validate(&path, &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is not valid UTF-8 (invalid byte at offset 17)"));
+    assert!(stderr.contains(&path.display().to_string()));
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_comments_only() {
+    let path = write_schema("comments", "// Nothing here yet\n\n// TODO\n");
+
+    let output = validate(&path, &["--deny-warnings"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Schema is valid"));
+    assert!(stderr.contains("0 definition(s)"));
+    assert!(stderr.contains("note: the schema is empty"));
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_deny_warnings() {
+    let path = write_schema(
+        "gap",
+        "enum Status {\n  PENDING = 0;\n  FAILED = 2;\n}\n\nmessage Job {\n  Status status = 1;\n}\n",
+    );
+
+    let output = validate(&path, &[]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("TCS1001"));
+
+    let output = validate(&path, &["--deny-warnings"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("TCS1001"));
+
+    let output = validate(&path, &["--warnings-as-errors"]);
+    assert!(!output.status.success());
+
+    // --quiet hides warnings, but not the ones --deny-warnings turns into errors
+    let output = validate(&path, &["--quiet"]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+
+    let output = validate(&path, &["--quiet", "--deny-warnings"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("TCS1001"));
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_strict_annotations() {
+    let path = write_schema(
+        "annotations",
+        "message Tx {\n  uint64 nonce = 1 @deprectaed;\n}\n",
+    );
+
+    let output = validate(&path, &[]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("TCS1005"));
+
+    let output = validate(&path, &["--strict-annotations"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unknown annotation \"@deprectaed\""));
+    assert!(stderr.contains("line 2, column 20"));
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_quiet_and_verbose() {
+    let path = write_schema("quiet", "message Point {\n  int x = 1;\n}\n");
+
+    let output = validate(&path, &["--quiet"]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+
+    let output = validate(&path, &["--verbose"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Schema is valid"));
+    for stage in ["tokenize: ", "parse: ", "verify: "] {
+        assert!(stderr.contains(stage), "missing {}", stage);
+    }
+
+    // Errors are still reported
+    let bad = write_schema("quiet-bad", "struct Point {\n  Missing x;\n}\n");
+    let output = validate(&bad, &["--quiet"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error:"));
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&bad).unwrap();
+}
+
+#[test]
+fn test_example_has_no_warnings() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../example");
+    for schema in ["tapedrive.tcs", "signals.tcs"] {
+        let output = validate(&dir.join(schema), &["--deny-warnings"]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{}: {}", schema, stderr);
+        assert!(!stderr.contains("TCS1002"), "{}: {}", schema, stderr);
+    }
+}
@@ -1,22 +1,731 @@
 //! Error types for TCS compiler
 
+use tcs_schema::MAX_NAME_LEN;
 use thiserror::Error;
 
+use crate::utils::source_snippet;
+
+/// Stable error codes, searchable and documented via `tcs explain <code>`
+pub mod codes {
+    pub const UNDEFINED_TYPE: &str = "TCS0001";
+    pub const DUPLICATE_FIELD_ID: &str = "TCS0002";
+    pub const DUPLICATE_TYPE: &str = "TCS0003";
+    pub const RESERVED_NAME: &str = "TCS0004";
+    pub const INVALID_FIXED_ARRAY: &str = "TCS0005";
+    pub const FIELD_ID_OUT_OF_RANGE: &str = "TCS0006";
+    pub const RECURSIVE_STRUCT: &str = "TCS0007";
+    pub const DUPLICATE_ENUM_VARIANT: &str = "TCS0008";
+    pub const UNSUPPORTED_ARRAY_TYPE: &str = "TCS0009";
+    pub const UNQUALIFIED_CROSS_PACKAGE: &str = "TCS0010";
+    pub const FORMAT_ROUNDTRIP: &str = "TCS0011";
+    pub const INVALID_KEY_FIELD: &str = "TCS0012";
+    pub const INVALID_NAME: &str = "TCS0013";
+    pub const INVALID_OPTION: &str = "TCS0014";
+    pub const NO_COMPARABLE_FIELDS: &str = "TCS0015";
+    pub const INVALID_VERSION: &str = "TCS0016";
+    pub const INVALID_ORD: &str = "TCS0017";
+    pub const INCOMPATIBLE_CHANGE: &str = "TCS0018";
+    pub const LIMIT_EXCEEDED: &str = "TCS0019";
+    pub const INVALID_VALIDATION: &str = "TCS0020";
+    pub const FEATURE_GATED_TYPE: &str = "TCS0021";
+    pub const RECURSIVE_ALIAS: &str = "TCS0022";
+    pub const RUST_NAME_COLLISION: &str = "TCS0023";
+    pub const ENUM_VALUE_GAP: &str = "TCS1001";
+    pub const UNUSED_TYPE: &str = "TCS1002";
+    pub const FLAGS_NOT_POWER_OF_TWO: &str = "TCS1003";
+    pub const PACKAGE_NAME_COLLISION: &str = "TCS1004";
+    pub const UNKNOWN_ANNOTATION: &str = "TCS1005";
+    pub const UNDOCUMENTED: &str = "TCS2001";
+    pub const NAMING_CONVENTION: &str = "TCS2002";
+    pub const PARSE: &str = "TCS0100";
+    pub const CODEGEN: &str = "TCS0200";
+    pub const IO: &str = "TCS0300";
+}
+
+/// An error from tokenizing, parsing, verifying or generating code.
+///
+/// The verification and code generation variants may gain fields, so they
+/// can't be built or fully destructured outside this crate. Use
+/// [`TcsError::verification`], [`TcsError::verification_at`],
+/// [`TcsError::code`], [`TcsError::location`] and [`TcsError::message`]
+/// instead; `message` is the text the old `VerificationError(msg)` and
+/// `CodeGenError(msg)` tuple variants held.
 #[derive(Debug, Error)]
 pub enum TcsError {
-    #[error("I/O error: {0}")]
+    #[error("I/O error [{code}]: {0}", code = codes::IO)]
     Io(#[from] std::io::Error),
 
-    #[error("Parse error at line {line}, column {column}: {msg}")]
+    #[error(
+        "Parse error [{code}] at line {line}, column {column}: {msg}",
+        code = codes::PARSE
+    )]
     ParseError {
         msg: String,
         line: usize,
         column: usize,
     },
 
-    #[error("Verification error: {0}")]
-    VerificationError(String),
+    /// `position` is the line and column the error points at, if it has one
+    #[error("Verification error [{code}]{}: {msg}", at_position(.position))]
+    #[non_exhaustive]
+    VerificationError {
+        code: &'static str,
+        msg: String,
+        position: Option<(usize, usize)>,
+    },
+
+    /// `location` is the definition name, or `Definition.field` for a field
+    #[error(
+        "Code generation error [{code}] in {location} at line {line}, column {column}: {msg}",
+        code = codes::CODEGEN
+    )]
+    #[non_exhaustive]
+    CodeGenError {
+        msg: String,
+        location: String,
+        line: usize,
+        column: usize,
+    },
+}
+
+/// A non-fatal diagnostic produced during verification
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Warning [{code}] at line {line}, column {column}: {msg}")]
+pub struct Warning {
+    pub code: &'static str,
+    pub msg: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// How serious a [`Warning`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Likely mistakes (`TCS1xxx`), reported by `tcs validate`
+    Warning,
+    /// Style lints (`TCS2xxx`), only reported by `tcs lint`
+    Style,
+}
+
+impl Warning {
+    /// The severity implied by the warning's code
+    pub fn severity(&self) -> Severity {
+        if self.code.starts_with("TCS2") {
+            Severity::Style
+        } else {
+            Severity::Warning
+        }
+    }
+}
+
+impl From<tcs_schema::InvalidName> for TcsError {
+    fn from(err: tcs_schema::InvalidName) -> Self {
+        TcsError::verification(codes::INVALID_NAME, err.to_string())
+    }
+}
+
+impl From<tcs_schema::UnresolvedType> for TcsError {
+    fn from(err: tcs_schema::UnresolvedType) -> Self {
+        TcsError::verification(codes::UNDEFINED_TYPE, err.to_string())
+    }
+}
+
+impl TcsError {
+    /// A verification error with the given error code and no position
+    pub fn verification(code: &'static str, msg: impl Into<String>) -> Self {
+        TcsError::VerificationError {
+            code,
+            msg: msg.into(),
+            position: None,
+        }
+    }
+
+    /// A verification error pointing at a line and column of the schema
+    pub fn verification_at(
+        code: &'static str,
+        msg: impl Into<String>,
+        line: usize,
+        column: usize,
+    ) -> Self {
+        TcsError::VerificationError {
+            code,
+            msg: msg.into(),
+            position: Some((line, column)),
+        }
+    }
+
+    /// The error's message, without its kind, code or location
+    pub fn message(&self) -> String {
+        match self {
+            TcsError::Io(err) => err.to_string(),
+            TcsError::ParseError { msg, .. }
+            | TcsError::VerificationError { msg, .. }
+            | TcsError::CodeGenError { msg, .. } => msg.clone(),
+        }
+    }
+
+    /// The stable error code for this error (e.g. `TCS0001`)
+    pub fn code(&self) -> &'static str {
+        match self {
+            TcsError::Io(_) => codes::IO,
+            TcsError::ParseError { .. } => codes::PARSE,
+            TcsError::VerificationError { code, .. } => code,
+            TcsError::CodeGenError { .. } => codes::CODEGEN,
+        }
+    }
+
+    /// The line and column (both 1-indexed) the error points at, if any
+    pub fn location(&self) -> Option<(usize, usize)> {
+        match *self {
+            TcsError::ParseError { line, column, .. }
+            | TcsError::CodeGenError { line, column, .. } => Some((line, column)),
+            TcsError::VerificationError { position, .. } => position,
+            TcsError::Io(_) => None,
+        }
+    }
+
+    /// Render the error with the offending line of `source` underneath, in a
+    /// line-number gutter with a `^` under the column, like rustc. Errors
+    /// without a location render as just the message.
+    pub fn render(&self, source: &str) -> String {
+        let snippet = self
+            .location()
+            .and_then(|(line, column)| source_snippet(source, line, column));
+        match snippet {
+            Some(snippet) => format!("{}\n{}", self, snippet),
+            None => self.to_string(),
+        }
+    }
+}
+
+/// ` at line L, column C` for a verification error with a position
+fn at_position(position: &Option<(usize, usize)>) -> String {
+    match position {
+        Some((line, column)) => format!(" at line {}, column {}", line, column),
+        None => String::new(),
+    }
+}
+
+/// Sort collected errors by location (line, then column) so they read top to
+/// bottom. Errors without a location go last, in the order they were found.
+pub fn sort_by_location(errors: &mut [TcsError]) {
+    errors.sort_by_key(|e| {
+        let location = e.location();
+        (location.is_none(), location)
+    });
+}
+
+/// Longer explanation (with an example) for an error code, if it is known
+pub fn explain(code: &str) -> Option<String> {
+    let text = match code.to_ascii_uppercase().as_str() {
+        codes::UNDEFINED_TYPE => {
+            "A field refers to a type that is neither a native type nor defined in the schema.
+
+Erroneous example:
+
+    struct Foo {
+      Bar value;   // `Bar` is never defined
+    }
+
+Define the type in the schema, fix the spelling, or use a native type such as
+`uint64` or `string`.
+
+A definition nested in a struct or message (`message Canvas { struct Layer
+{ ... } }`) is only in scope inside that definition. To use it elsewhere, move
+it to the top level."
+        }
+        codes::DUPLICATE_FIELD_ID => {
+            "Two fields of the same message share an id. Field ids identify fields on the
+wire, so each must be unique within its definition.
+
+Erroneous example:
+
+    message Foo {
+      uint64 a = 1;
+      uint64 b = 1;
+    }
+
+Give every field its own id."
+        }
+        codes::DUPLICATE_TYPE => {
+            "A type name is defined more than once in a package, or shadows a native type.
+
+Erroneous example:
+
+    struct Foo { int x; }
+    struct Foo { int y; }
+
+Rename or remove one of the definitions."
+        }
+        codes::RESERVED_NAME => {
+            "A definition uses a name that is reserved by TCS (such as `ByteBuffer`), or a
+definition or field is named after a keyword of the schema language: `enum`,
+`struct`, `message`, `package`, `options`, or `import`, `const` and `reserved`,
+which are kept for future syntax.
+
+Erroneous examples:
+
+    struct ByteBuffer { int x; }
+    struct message { int const; }
+
+Pick a different name."
+        }
+        codes::INVALID_FIXED_ARRAY => {
+            "A fixed-size array is only supported for the `byte` (or `uint8`) type and
+must have a non-zero size.
+
+Erroneous example:
+
+    struct Foo {
+      int[32] values;
+      byte[0] empty;
+    }
+
+Use `byte[N]` with N > 0, or a variable-length array like `int[]`."
+        }
+        codes::FIELD_ID_OUT_OF_RANGE => {
+            "Message field ids must be positive and no larger than the number of fields,
+and the values of a `[flags]` enum can't be negative.
+
+Erroneous example:
+
+    message Foo {
+      uint64 a = 0;
+      uint64 b = 7;
+    }
+
+    enum Perm [flags] {
+      ALL = -1;
+    }
+
+Number fields 1..=N, and give each flag a single bit (1, 2, 4, ...)."
+        }
+        codes::RECURSIVE_STRUCT => {
+            "A struct contains itself (directly or through other structs) without an
+array in between, which would make it infinitely large.
+
+Erroneous example:
+
+    struct Node {
+      Node next;
+    }
+
+Use an array (`Node[] children;`) or a message to break the cycle."
+        }
+        codes::DUPLICATE_ENUM_VARIANT => {
+            "An enum declares the same variant name twice, or two variants share a value.
+
+Erroneous example:
+
+    enum Status {
+      OK = 0;
+      SUCCESS = 0;
+    }
+
+Give each variant its own value, or declare an explicit alias with
+`SUCCESS = OK;`."
+        }
+        codes::UNSUPPORTED_ARRAY_TYPE => {
+            "The element type can't be used in an array.
+
+Erroneous example:
+
+    struct Foo {
+      bytes[] chunks;
+    }
+
+`bytes` is already a byte sequence; use `byte[]` for a single buffer or wrap
+`bytes` in a struct and make an array of that."
+        }
+        codes::UNQUALIFIED_CROSS_PACKAGE => {
+            "A field refers to a type from another package without qualifying it. When a
+file declares several packages, types from other packages must be written as
+`package.Type`.
+
+Erroneous example:
+
+    package geometry;
+    struct Point { int x; int y; }
+
+    package shapes;
+    struct Circle {
+      Point center;   // should be `geometry.Point`
+    }"
+        }
+        codes::FORMAT_ROUNDTRIP => {
+            "Formatting a schema and parsing the result produced a different schema. The
+formatter should never change what a schema means, so this points to a bug in
+the formatter or parser rather than in your schema; please report it along with
+the schema that triggered it."
+        }
+        codes::INVALID_KEY_FIELD => {
+            "A field marked `[key]` can't be used for equality and hashing. Key fields
+must not be deprecated, and their type must be hashable: any native type except
+`float`, an enum, or a struct/message that itself has key fields.
+
+Erroneous example:
+
+    struct Reading {
+      float value [key];   // floats aren't hashable
+    }
+
+Pick a different key field, or add a `[key]` field to the nested type."
+        }
+        codes::INVALID_NAME => {
+            return Some(format!(
+                "A definition or field name is not a valid identifier. Names may only contain
+ASCII letters, digits and underscores, cannot start with a digit, and can be at
+most {} characters long.
+
+Schemas parsed from `.tcs` files always satisfy the character rules; this error
+usually comes from a schema built or merged in code:
+
+    let mut schema = Schema::new();
+    schema.definitions.push(Definition {{ name: \"2d-point\".into(), .. }});
+
+The same error is reported for a field or enum variant whose name is no longer
+a valid Rust identifier once converted to snake_case or PascalCase:
+
+    enum Kind {{
+      _ = 0;    // PascalCase of `_` is empty
+      _1 = 1;   // becomes `1`
+    }}
+
+Package names follow the same rules, and must be a single identifier:
+`tapedrive.chain` is not supported yet.
+
+Rename the definition, field or package.",
+                MAX_NAME_LEN
+            ));
+        }
+        codes::INVALID_OPTION => {
+            "An entry in the schema's `options` block has an unknown name or a value of
+the wrong type. The names match the `tcs gen-rust` flags:
+
+    options {
+      no_std = true;              // true or false
+      rust_module = \"protocol\";  // a string
+    }
+
+Boolean options: sort, visitors, views, raw_identifiers, arbitrary, serde,
+serde_enum_names, hex_debug, doc_stubs, no_clone, explicit_discriminants,
+explicit_default, eq_ignore_deprecated, newtypes, checked_setters, to_array,
+sectioned, no_std.
+String options: wincode_path, rust_module."
+        }
+        codes::NO_COMPARABLE_FIELDS => {
+            "With the `eq_ignore_deprecated` option, equality skips deprecated fields. A
+message whose fields are all deprecated would have nothing left to compare, so
+any two values would be equal.
+
+Erroneous example:
+
+    options { eq_ignore_deprecated = true; }
+    message Legacy {
+      uint64 id = 1 [deprecated];
+    }
+
+Keep a field that isn't deprecated, or deprecate the whole message instead."
+        }
+        codes::INVALID_VERSION => {
+            "A `@since` annotation records the version a field was added in. The
+version must be numbers separated by dots.
+
+Erroneous example:
+
+    message Block {
+      uint64 epoch = 6 @since(\"v2\");
+    }
+
+Write the version as `@since(\"2\")` or `@since(\"2.0\")`."
+        }
+        codes::INVALID_ORD => {
+            "An `[ord]` struct derives `Eq` and `Ord`, comparing fields in declaration
+order, so every field must be totally ordered. Floats aren't (NaN compares
+unequal to itself), and a nested struct must be `[ord]` itself. `[key]` fields
+aren't allowed either, since equality has to agree with the ordering.
+
+Erroneous example:
+
+    struct Sample [ord] {
+      uint64 at;
+      float value;
+    }
+
+Store the value in an integer type (e.g. fixed-point), or drop `[ord]`."
+        }
+        codes::INCOMPATIBLE_CHANGE => {
+            "Compared with a baseline schema, a message field kept its name but changed
+its id, type or presence (optional or `required`), an id now belongs to a
+differently named field, or a field was removed, reordered or had a new field
+declared before it. Messages are encoded as their fields in declaration order,
+so code generated from the baseline would no longer decode new data as
+intended.
+
+Erroneous example (baseline first):
+
+    message Tx { uint64 nonce = 1; }
+    message Tx { uint64 nonce = 2; }
+
+Keep existing fields as they were, and add new ones at the end. To replace a
+field, deprecate it and add the new field under an unused id."
+        }
+        codes::LIMIT_EXCEEDED => {
+            "The schema is larger than the limits it was verified with: it has too many
+definitions, a definition has too many fields, structs and messages nest too
+deeply, or a fixed-size struct is too many bytes. Limits are only enforced when
+a caller asks for them, e.g. a service compiling schemas from untrusted
+sources. The size of a fixed-size struct must always fit in a `usize`.
+
+Split the schema into smaller definitions, or raise the limits."
+        }
+        codes::INVALID_VALIDATION => {
+            "A `@validate` annotation has bounds that don't fit the field. `min` and `max`
+apply to integer fields and must be within the range of the field's type;
+`len_min` and `len_max` apply to variable-length arrays. The lower bound can't
+be greater than the upper bound, or no value would pass.
+
+Erroneous example:
+
+    message Transfer {
+      uint8 fee = 1 @validate(min = 10, max = 5);
+      uint64 amount = 2 @validate(len_max = 4);
+    }
+
+Swap or fix the bounds, and use `len_min`/`len_max` only on array fields."
+        }
+        codes::FEATURE_GATED_TYPE => {
+            "A field refers to a definition marked `[feature = \"...\"]`, but the field's
+own definition isn't gated on the same feature. The gated type is only
+generated when the feature is enabled, so the generated code would fail to
+compile without it.
+
+Erroneous example:
+
+    message Experimental [feature = \"exp\"] { uint64 id = 1; }
+    message Envelope {
+      Experimental payload = 1;   // Envelope is always generated
+    }
+
+Gate the referring definition on the same feature, or remove the gate."
+        }
+        codes::RECURSIVE_ALIAS => {
+            "A type alias refers to itself, directly or through other aliases, so it
+never resolves to a real type.
+
+Erroneous example:
+
+    type Id = Key;
+    type Key = Id;
+
+Point one of the aliases at a built-in type or a definition."
+        }
+        codes::RUST_NAME_COLLISION => {
+            "Two enum variants, or two fields of a struct or message, have different
+names in the schema that become the same identifier in generated Rust. Plain
+enum variants are converted to PascalCase, and fields, flags and variant
+aliases to snake_case, so the generated code would declare the name twice.
+
+Erroneous example:
+
+    enum Role {
+      NODE_ROLE = 0;
+      nodeRole = 1;   // both become Role::NodeRole
+    }
+
+Rename one of them."
+        }
+        codes::ENUM_VALUE_GAP => {
+            "Warning: an enum skips values between two of its variants. This is often a
+leftover from a removed variant; if so, consider keeping the old variant so the
+value isn't reused by accident.
+
+Example:
+
+    enum Status {
+      PENDING = 0;
+      FAILED = 2;   // nothing uses 1
+    }"
+        }
+        codes::UNUSED_TYPE => {
+            "Warning: a type alias, or a definition nested in another one, is never used by
+any field. Top-level definitions are entry points for the code using the
+generated types and never reported.
+
+Example:
+
+    type Spare = int;   // no field has type `Spare`
+
+    message Job {
+      struct Orphan { int x; }   // no field of `Job` has type `Orphan`
+      uint64 id = 1;
+    }
+
+Remove the type, or reference it from a field."
+        }
+        codes::FLAGS_NOT_POWER_OF_TWO => {
+            "Warning: a variant of a `[flags]` enum has a value that is not a single bit.
+Such a value combines (or overlaps) other flags, which is usually a mistake.
+Zero is allowed for an empty set.
+
+Example:
+
+    enum Perm [flags] {
+      READ = 1;
+      WRITE = 2;
+      EXEC = 3;   // overlaps READ and WRITE; use 4
+    }
+
+If the variant is meant to be a combination, consider leaving it out of the
+schema and combining flags in code with `|`."
+        }
+        codes::PACKAGE_NAME_COLLISION => {
+            "Warning: a definition has the same name as its package, ignoring case. The
+package becomes a snake_case module and the definition a PascalCase type, so
+they don't clash in Rust, but `chain::Chain` is easy to confuse.
+
+Example:
+
+    package chain;
+    struct Chain { uint64 height; }   // generates chain::Chain
+
+Rename the package or the definition."
+        }
+        codes::UNKNOWN_ANNOTATION => {
+            "Warning: a field has an `@name` annotation the compiler doesn't recognize,
+often a typo. The known annotations are `@since(\"...\")` and `@validate(...)`;
+others are kept by the formatter but otherwise ignored. With
+`VerifyOptions::strict_annotations` (`tcs validate --strict-annotations`) this
+is an error instead.
+
+Example:
+
+    message Tx {
+      uint64 nonce = 1 @sinse(\"2.0\");   // meant @since
+    }
+
+Fix the spelling or remove the annotation."
+        }
+        codes::UNDOCUMENTED => {
+            "Style lint: a definition has no comment above it. Comments directly above a
+definition become doc comments on the generated type.
+
+Example:
+
+    struct Point { int x; int y; }
+
+Add a comment, e.g. `// A position on the canvas`, on the line above."
+        }
+        codes::NAMING_CONVENTION => {
+            "Style lint: a name doesn't follow the schema conventions. Types are
+PascalCase, enum variants and flags are UPPER_SNAKE_CASE, and fields start with
+a lowercase letter (camelCase or snake_case).
+
+Example:
+
+    enum status { Pending = 0; }
+
+Rename them, e.g. `enum Status { PENDING = 0; }`."
+        }
+        codes::PARSE => {
+            "The schema source could not be tokenized or parsed. The message names the
+unexpected token and its position.
+
+Erroneous example:
+
+    struct Foo {
+      int x   // missing `;`
+    }
+
+Fix the syntax at the reported line and column."
+        }
+        codes::CODEGEN => {
+            "Code could not be generated for a definition or field, named in the message
+as `Type` or `Type.field` along with its position. Verified schemas don't hit
+this; it usually means code was generated for a schema that skipped
+verification."
+        }
+        codes::IO => "A file could not be read or written. Check the path and permissions.",
+        _ => return None,
+    };
+    Some(text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_schema;
+    use crate::tokenizer::tokenize_schema;
+    use crate::utils::{verification_error, verification_error_at};
+
+    #[test]
+    fn test_render() {
+        let source = "struct Foo {\n  int x\n}\n";
+        let err = parse_schema(&tokenize_schema(source).unwrap()).unwrap_err();
+        assert_eq!(
+            err.render(source),
+            "Parse error [TCS0100] at line 3, column 1: Expected \";\" but found \"}\"\n  |\n3 | }\n  | ^"
+        );
+
+        let err = TcsError::ParseError {
+            msg: "Unexpected token".to_string(),
+            line: 2,
+            column: 7,
+        };
+        assert!(err.render(source).ends_with("\n2 |   int x\n  |       ^"));
+
+        // Without a location, or pointing past the source, only the message is rendered
+        let err = TcsError::verification(codes::UNDEFINED_TYPE, "The type \"Bar\" is not defined");
+        assert_eq!(err.render(source), err.to_string());
+        let err = TcsError::ParseError {
+            msg: "Unexpected end".to_string(),
+            line: 9,
+            column: 1,
+        };
+        assert_eq!(err.render(source), err.to_string());
+    }
+
+    #[test]
+    fn test_constructors_and_accessors() {
+        let err = TcsError::verification_at(codes::INVALID_NAME, "Bad name", 2, 7);
+        assert_eq!(err.code(), codes::INVALID_NAME);
+        assert_eq!(err.location(), Some((2, 7)));
+        assert_eq!(err.message(), "Bad name");
+        assert_eq!(
+            err.to_string(),
+            "Verification error [TCS0013] at line 2, column 7: Bad name"
+        );
+
+        let err = TcsError::verification(codes::UNDEFINED_TYPE, String::from("Missing"));
+        assert_eq!(err.location(), None);
+        assert_eq!(err.message(), "Missing");
+    }
 
-    #[error("Code generation error: {0}")]
-    CodeGenError(String),
+    #[test]
+    fn test_sort_by_location() {
+        let parse_error = |line, column| TcsError::ParseError {
+            msg: "Unexpected token".to_string(),
+            line,
+            column,
+        };
+        let mut errors = vec![
+            verification_error(codes::UNDEFINED_TYPE, "No position"),
+            parse_error(7, 1),
+            verification_error_at(codes::INCOMPATIBLE_CHANGE, "Changed", 3, 5),
+            verification_error(codes::INVALID_NAME, "Also no position"),
+            parse_error(3, 2),
+        ];
+        sort_by_location(&mut errors);
+        let locations: Vec<_> = errors.iter().map(TcsError::location).collect();
+        assert_eq!(
+            locations,
+            [Some((3, 2)), Some((3, 5)), Some((7, 1)), None, None]
+        );
+        // Errors without a location keep the order they were found in
+        assert_eq!(errors[3].code(), codes::UNDEFINED_TYPE);
+        assert_eq!(
+            errors[1].to_string(),
+            "Verification error [TCS0018] at line 3, column 5: Changed"
+        );
+    }
 }
@@ -2,87 +2,490 @@
 //!
 //! Produces consistently formatted output from a parsed Schema AST.
 
-use tcs_schema::{Definition, DefinitionKind, Schema};
+use tcs_schema::{Definition, DefinitionKind, Presence, Schema};
+
+/// Options controlling schema formatting
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Put small enums and structs on a single line, e.g.
+    /// `enum Color { RED = 0; GREEN = 1; }`
+    pub compact_small_defs: bool,
+    /// Definitions with more fields than this stay multi-line
+    pub compact_max_fields: usize,
+    /// Definitions whose single-line form would be wider than this stay multi-line
+    pub compact_max_width: usize,
+    /// Reorder fields: messages by field ID and enums by value (so the first
+    /// variant, which is the generated `Default`, may change). Comments move
+    /// with their fields.
+    pub sort_fields: bool,
+    /// With `sort_fields`, also sort struct fields by name. This changes the
+    /// encoding, since struct fields are encoded in declaration order.
+    pub sort_struct_fields: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            compact_small_defs: false,
+            compact_max_fields: 4,
+            compact_max_width: 80,
+            sort_fields: false,
+            sort_struct_fields: false,
+        }
+    }
+}
 
 /// Format a Schema AST back into a .tcs source string with consistent formatting.
 pub fn format_schema(schema: &Schema) -> String {
+    format_schema_with_options(schema, &FormatOptions::default())
+}
+
+/// Format a Schema AST with the given options
+pub fn format_schema_with_options(schema: &Schema, options: &FormatOptions) -> String {
     let mut output = String::new();
 
+    // File header
+    if let Some(ref header) = schema.header {
+        for line in header.split('\n') {
+            if line.is_empty() {
+                output.push_str("//!\n");
+            } else {
+                output.push_str(&format!("//! {}\n", line));
+            }
+        }
+        output.push('\n');
+    }
+    format_floating_comments(&schema.leading_comments, "", &mut output);
+
     // Package declaration
     if let Some(ref pkg) = schema.package {
         output.push_str(&format!("package {};\n", pkg));
+        if !schema.definitions.is_empty()
+            || !schema.options.is_empty()
+            || !schema.aliases.is_empty()
+        {
+            output.push('\n');
+        }
+    }
+
+    // Options block
+    if !schema.options.is_empty() {
+        output.push_str("options {\n");
+        for option in &schema.options {
+            output.push_str(&format!("  {} = {};\n", option.name, option.value));
+        }
+        output.push_str("}\n");
+        if !schema.definitions.is_empty() || !schema.aliases.is_empty() {
+            output.push('\n');
+        }
+    }
+
+    // Type aliases, which apply to every package, in one group
+    if !schema.aliases.is_empty() {
+        for (i, alias) in schema.aliases.iter().enumerate() {
+            if i > 0 && !alias.floating_comments.is_empty() {
+                output.push('\n');
+            }
+            format_floating_comments(&alias.floating_comments, "", &mut output);
+            format_comments(&alias.comments, "", &mut output);
+            output.push_str(&format!("type {} = {}", alias.name, alias.type_));
+            format_array_suffix(
+                alias.is_array,
+                alias.array_size,
+                &alias.array_size_text,
+                &mut output,
+            );
+            output.push(';');
+            output.push_str(&trailing_comment(&alias.trailing_comment));
+            output.push('\n');
+        }
         if !schema.definitions.is_empty() {
             output.push('\n');
         }
     }
 
-    // Definitions
-    for (i, def) in schema.definitions.iter().enumerate() {
+    // Definitions, with a new package declaration wherever the package changes.
+    // Nested definitions are written inside their parents.
+    let mut current_package = schema.package.as_deref();
+    let top_level = schema.definitions.iter().filter(|d| d.parent.is_none());
+    for (i, def) in top_level.enumerate() {
         if i > 0 {
             output.push('\n');
         }
-        format_definition(def, &mut output);
+        let package = def.package.as_deref().or(current_package);
+        if package != current_package {
+            if let Some(pkg) = package {
+                output.push_str(&format!("package {};\n\n", pkg));
+            }
+            current_package = package;
+        }
+        format_definition(def, schema, "", options, &mut output);
     }
 
+    // Comments at the end of the file
+    if !schema.end_comments.is_empty() && !output.is_empty() && !output.ends_with("\n\n") {
+        output.push('\n');
+    }
+    format_end_comments(&schema.end_comments, "", &mut output);
+
     output
 }
 
-fn format_definition(def: &Definition, output: &mut String) {
+fn format_definition(
+    def: &Definition,
+    schema: &Schema,
+    indent: &str,
+    options: &FormatOptions,
+    output: &mut String,
+) {
     let keyword = match def.kind {
         DefinitionKind::Enum => "enum",
         DefinitionKind::Struct => "struct",
         DefinitionKind::Message => "message",
     };
+    let mut header = format!("{} {}", keyword, def.name);
+    if def.is_flags {
+        header.push_str(" [flags]");
+    }
+    if def.is_ord {
+        header.push_str(" [ord]");
+    }
+    if def.is_deprecated {
+        header.push_str(" [deprecated]");
+    }
+    if let Some(ref feature) = def.feature {
+        header.push_str(&format!(" [feature = \"{}\"]", feature));
+    }
+
+    format_floating_comments(&def.floating_comments, indent, output);
+    format_comments(&def.comments, indent, output);
+    let trailing = trailing_comment(&def.trailing_comment);
+
+    let nested: Vec<&Definition> = schema
+        .definitions
+        .iter()
+        .filter(|d| d.parent.as_deref() == Some(def.name.as_str()) && d.package == def.package)
+        .collect();
+    let fields = ordered_fields(def, options);
+    if nested.is_empty() && def.end_comments.is_empty() {
+        // A signal type without fields is always written as `message Ping {}`
+        if fields.is_empty() {
+            output.push_str(&format!("{}{} {{}}{}\n", indent, header, trailing));
+            return;
+        }
+        if let Some(line) = compact_definition(def, &fields, &header, options) {
+            output.push_str(&format!("{}{}{}\n", indent, line, trailing));
+            return;
+        }
+    }
+
+    output.push_str(&format!("{}{} {{{}\n", indent, header, trailing));
+
+    // Nested definitions come first, each followed by a blank line if more follows
+    let inner = format!("{}  ", indent);
+    for (i, nested_def) in nested.iter().enumerate() {
+        format_definition(nested_def, schema, &inner, options, output);
+        if i + 1 < nested.len() || !fields.is_empty() {
+            output.push('\n');
+        }
+    }
+
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 && !field.floating_comments.is_empty() {
+            output.push('\n');
+        }
+        format_floating_comments(&field.floating_comments, &inner, output);
+        format_comments(&field.comments, &inner, output);
+        output.push_str(&format!(
+            "{}{}{}\n",
+            inner,
+            field_text(field, def.kind),
+            trailing_comment(&field.trailing_comment)
+        ));
+    }
+
+    // Comments after the last field, separated from it by a blank line
+    if !def.end_comments.is_empty() && (!nested.is_empty() || !fields.is_empty()) {
+        output.push('\n');
+    }
+    format_end_comments(&def.end_comments, &inner, output);
 
-    output.push_str(&format!("{} {} {{\n", keyword, def.name));
+    output.push_str(&format!("{}}}\n", indent));
+}
+
+/// A definition's fields in the order to write them: as declared, or sorted
+/// if `options` ask for it. The sort is stable, so enum aliases stay after
+/// the variants they share a value with.
+fn ordered_fields<'a>(def: &'a Definition, options: &FormatOptions) -> Vec<&'a tcs_schema::Field> {
+    let mut fields: Vec<&tcs_schema::Field> = def.fields.iter().collect();
+    if options.sort_fields {
+        match def.kind {
+            DefinitionKind::Enum | DefinitionKind::Message => fields.sort_by_key(|f| f.field_id),
+            DefinitionKind::Struct if options.sort_struct_fields => {
+                fields.sort_by(|a, b| a.name.cmp(&b.name))
+            }
+            DefinitionKind::Struct => {}
+        }
+    }
+    fields
+}
+
+/// Single-line form of a small enum or struct, if `options` allow it.
+/// Fields with comments of any kind always keep the definition multi-line.
+fn compact_definition(
+    def: &Definition,
+    fields: &[&tcs_schema::Field],
+    header: &str,
+    options: &FormatOptions,
+) -> Option<String> {
+    if !options.compact_small_defs
+        || def.kind == DefinitionKind::Message
+        || fields.is_empty()
+        || fields.len() > options.compact_max_fields
+        || fields.iter().any(|f| {
+            !f.comments.is_empty()
+                || !f.floating_comments.is_empty()
+                || f.trailing_comment.is_some()
+        })
+    {
+        return None;
+    }
+
+    let fields: Vec<String> = fields.iter().map(|f| field_text(f, def.kind)).collect();
+    let line = format!("{} {{ {} }}", header, fields.join(" "));
+    (line.chars().count() <= options.compact_max_width).then_some(line)
+}
+
+fn format_comments(comments: &[String], indent: &str, output: &mut String) {
+    for line in comments {
+        if line.is_empty() {
+            output.push_str(&format!("{}//\n", indent));
+        } else {
+            output.push_str(&format!("{}// {}\n", indent, line));
+        }
+    }
+}
+
+/// Comment blocks that aren't attached to what follows them, each followed by
+/// a blank line
+fn format_floating_comments(blocks: &[Vec<String>], indent: &str, output: &mut String) {
+    for block in blocks {
+        format_comments(block, indent, output);
+        output.push('\n');
+    }
+}
+
+/// Comment blocks at the end of a definition or file, separated by blank lines
+fn format_end_comments(blocks: &[Vec<String>], indent: &str, output: &mut String) {
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 {
+            output.push('\n');
+        }
+        format_comments(block, indent, output);
+    }
+}
 
-    for field in &def.fields {
-        format_field(field, def.kind, output);
+/// A comment to write after code on the same line, with its leading space
+fn trailing_comment(comment: &Option<String>) -> String {
+    match comment.as_deref() {
+        Some("") => " //".to_string(),
+        Some(text) => format!(" // {}", text),
+        None => String::new(),
     }
+}
 
-    output.push_str("}\n");
+/// A number as written in the source (keeping `_` separators), or else its value
+fn spelled(text: &Option<String>, value: impl std::fmt::Display) -> String {
+    text.clone().unwrap_or_else(|| value.to_string())
 }
 
-fn format_field(field: &tcs_schema::Field, kind: DefinitionKind, output: &mut String) {
-    output.push_str("  ");
+/// A single field, without indentation or a trailing newline
+fn field_text(field: &tcs_schema::Field, kind: DefinitionKind) -> String {
+    let mut output = String::new();
 
     match kind {
         DefinitionKind::Enum => {
-            // Enum variant: NAME = value;
-            output.push_str(&format!("{} = {};\n", field.name, field.field_id));
+            // Enum variant: NAME = value; or alias: NAME = OTHER;
+            match field.alias_of {
+                Some(ref target) => output.push_str(&format!("{} = {}", field.name, target)),
+                None => output.push_str(&format!(
+                    "{} = {}",
+                    field.name,
+                    spelled(&field.field_id_text, field.field_id)
+                )),
+            }
+            format_named_annotations(field, &mut output);
+            output.push(';');
         }
         DefinitionKind::Struct => {
-            // Struct field: type name;
-            format_typed_field(field, output);
-            output.push_str(";\n");
+            // Struct field: type name [key];
+            format_typed_field(field, &mut output);
+            if field.is_key {
+                output.push_str(" [key]");
+            }
+            format_named_annotations(field, &mut output);
+            output.push(';');
         }
         DefinitionKind::Message => {
-            // Message field: type name = id [deprecated];
-            format_typed_field(field, output);
-            output.push_str(&format!(" = {}", field.field_id));
+            // Message field: type name = id [key] [deprecated];
+            format_typed_field(field, &mut output);
+            output.push_str(&format!(
+                " = {}",
+                spelled(&field.field_id_text, field.field_id)
+            ));
+            if field.is_key {
+                output.push_str(" [key]");
+            }
             if field.is_deprecated {
                 output.push_str(" [deprecated]");
             }
-            output.push_str(";\n");
+            format_named_annotations(field, &mut output);
+            output.push(';');
         }
     }
+
+    output
+}
+
+/// Append a field's `@since` and `@validate` annotations, in that order, then
+/// any unrecognized ones as written
+fn format_named_annotations(field: &tcs_schema::Field, output: &mut String) {
+    if let Some(ref since) = field.since {
+        output.push_str(&format!(" @since(\"{}\")", since));
+    }
+    if let Some(ref validate) = field.validate {
+        let bounds: Vec<String> = [
+            ("min", validate.min, &validate.min_text),
+            ("max", validate.max, &validate.max_text),
+            (
+                "len_min",
+                validate.len_min.map(|n| n as i128),
+                &validate.len_min_text,
+            ),
+            (
+                "len_max",
+                validate.len_max.map(|n| n as i128),
+                &validate.len_max_text,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(key, value, text)| Some(format!("{} = {}", key, spelled(text, value?))))
+        .collect();
+        output.push_str(&format!(" @validate({})", bounds.join(", ")));
+    }
+    for annotation in &field.unknown_annotations {
+        output.push(' ');
+        output.push_str(&annotation.text);
+    }
 }
 
 fn format_typed_field(field: &tcs_schema::Field, output: &mut String) {
     if let Some(ref type_name) = field.type_ {
+        match field.presence {
+            Presence::Implicit => {}
+            Presence::Optional => output.push_str("optional "),
+            Presence::Required => output.push_str("required "),
+        }
         output.push_str(type_name);
+        format_array_suffix(
+            field.is_array,
+            field.array_size,
+            &field.array_size_text,
+            output,
+        );
+        output.push(' ');
+        output.push_str(&field.name);
+    }
+}
 
-        if field.is_array {
-            if let Some(size) = field.array_size {
-                output.push_str(&format!("[{}]", size));
+fn format_array_suffix(
+    is_array: bool,
+    array_size: Option<usize>,
+    array_size_text: &Option<String>,
+    output: &mut String,
+) {
+    if is_array {
+        if let Some(size) = array_size {
+            output.push_str(&format!("[{}]", spelled(array_size_text, size)));
+        } else {
+            output.push_str("[]");
+        }
+    }
+}
+
+/// Line-by-line diff between `old` and `new`, empty if they are identical.
+///
+/// Each run of changed lines is introduced by an `@@ line N @@` header (N is the
+/// line in `old`), followed by the removed lines prefixed with `-` and the added
+/// lines prefixed with `+`. Differences the lines don't show, a missing
+/// trailing newline or CRLF instead of LF line endings, are reported on their
+/// own `\` lines at the end.
+pub fn diff_lines(old: &str, new: &str) -> String {
+    let mut output = diff_line_contents(old, new);
+
+    if old.contains("\r\n") != new.contains("\r\n") {
+        let (found, expected) = if old.contains("\r\n") {
+            ("CRLF", "LF")
+        } else {
+            ("LF", "CRLF")
+        };
+        output.push_str(&format!(
+            "\\ line endings differ: {} instead of {}\n",
+            found, expected
+        ));
+    }
+    if !old.is_empty() && old.ends_with('\n') != new.ends_with('\n') {
+        if new.ends_with('\n') {
+            output.push_str("\\ missing trailing newline\n");
+        } else {
+            output.push_str("\\ unexpected trailing newline\n");
+        }
+    }
+
+    output
+}
+
+/// Line-by-line part of [`diff_lines`], ignoring line endings
+fn diff_line_contents(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence lengths of the suffixes old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
             } else {
-                output.push_str("[]");
-            }
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
         }
+    }
 
-        output.push(' ');
-        output.push_str(&field.name);
+    let mut output = String::new();
+    let (mut i, mut j) = (0, 0);
+    let mut in_hunk = false;
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+            in_hunk = false;
+            continue;
+        }
+        if !in_hunk {
+            output.push_str(&format!("@@ line {} @@\n", i + 1));
+            in_hunk = true;
+        }
+        if j == new.len() || (i < old.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            output.push_str(&format!("-{}\n", old[i]));
+            i += 1;
+        } else {
+            output.push_str(&format!("+{}\n", new[j]));
+            j += 1;
+        }
     }
+
+    output
 }
 
 #[cfg(test)]
@@ -112,6 +515,152 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_format_enum_alias() {
+        let input = "enum Status { OK = 0; SUCCESS=OK; }";
+        let formatted = parse_and_format(input);
+        assert_eq!(formatted, "enum Status {\n  OK = 0;\n  SUCCESS = OK;\n}\n");
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let input = r#"
+            // A point
+            struct Point {
+                //   x coordinate
+                int x;
+                int y;    // trailing comments are kept
+            }
+        "#;
+        let formatted = parse_and_format(input);
+        assert_eq!(
+            formatted,
+            "// A point\nstruct Point {\n  //   x coordinate\n  int x;\n  int y; // trailing comments are kept\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_comments_round_trip() {
+        let formatted = "\
+// Chain types
+
+package chain;
+
+options {
+  no_std = true;
+}
+
+// About the hashes
+
+// A block hash
+type Hash = byte[32]; // sha256
+
+// Shapes
+
+// A point
+struct Point { // 2D
+  // Horizontal
+  int x; // left to right
+
+  // Floating between fields
+
+  int y;
+
+  // More to come
+}
+
+enum Kind { // compact
+  A = 0;
+  B = 1;
+}
+
+message Ping {
+  // Nothing yet
+}
+
+// The end
+//
+// Really
+";
+        assert_eq!(parse_and_format(formatted), formatted);
+
+        // Comments inside a field or an options block move to whatever comes
+        // next instead of being dropped
+        let input = "options {\n  // Smaller\n  no_std = true;\n}\nstruct Point {\n  int // across\n  x;\n}\n";
+        assert_eq!(
+            parse_and_format(input),
+            "options {\n  no_std = true;\n}\n\n// Smaller\n\nstruct Point {\n  int x;\n\n  // across\n}\n"
+        );
+
+        // A comment after the closing brace stays with the definition
+        assert_eq!(
+            parse_and_format("enum Kind { A = 0; } // compact"),
+            "enum Kind { // compact\n  A = 0;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_header() {
+        let input =
+            "//!  SPDX-License-Identifier: MIT\n//!\npackage chain;\nstruct Point { int x; }";
+        let formatted = parse_and_format(input);
+        assert_eq!(
+            formatted,
+            "//!  SPDX-License-Identifier: MIT\n//!\n\npackage chain;\n\nstruct Point {\n  int x;\n}\n"
+        );
+        assert_eq!(parse_and_format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_format_flags_enum() {
+        let input = "enum Perm  [flags]{ READ = 1; WRITE = 2; }";
+        let formatted = parse_and_format(input);
+        assert_eq!(
+            formatted,
+            "enum Perm [flags] {\n  READ = 1;\n  WRITE = 2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_options() {
+        let input = "package chain;\noptions{no_std=true;rust_module=\"protocol\";}\nstruct Point { int x; }";
+        let formatted = parse_and_format(input);
+        assert_eq!(
+            formatted,
+            "package chain;\n\noptions {\n  no_std = true;\n  rust_module = \"protocol\";\n}\n\nstruct Point {\n  int x;\n}\n"
+        );
+        assert_eq!(parse_and_format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_format_aliases() {
+        let input = "package chain;\nstruct Block { Hash prev; }\n// A block hash\ntype Hash=byte[32];\ntype Hashes = Hash[];";
+        let formatted = parse_and_format(input);
+        assert_eq!(
+            formatted,
+            "package chain;\n\n// A block hash\ntype Hash = byte[32];\ntype Hashes = Hash[];\n\nstruct Block {\n  Hash prev;\n}\n"
+        );
+        assert_eq!(parse_and_format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_format_ord_struct() {
+        let formatted = parse_and_format("struct P [deprecated][ord] { int x; }");
+        assert_eq!(formatted, "struct P [ord] [deprecated] {\n  int x;\n}\n");
+        // Only structs can be ordered
+        assert!(parse_schema(&tokenize_schema("enum E [ord] { A; }").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_format_deprecated_definition() {
+        let input = "message Old [deprecated]{ uint64 id = 1; }\nenum Perm [deprecated] [flags] { READ = 1; }";
+        let formatted = parse_and_format(input);
+        assert_eq!(
+            formatted,
+            "message Old [deprecated] {\n  uint64 id = 1;\n}\n\nenum Perm [flags] [deprecated] {\n  READ = 1;\n}\n"
+        );
+    }
+
     #[test]
     fn test_format_struct() {
         let input = r#"
@@ -144,6 +693,126 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_format_empty_definitions() {
+        let input = "// A keep-alive\nmessage Ping {\n}\nstruct Empty {}";
+        let formatted = parse_and_format(input);
+        assert_eq!(
+            formatted,
+            "// A keep-alive\nmessage Ping {}\n\nstruct Empty {}\n"
+        );
+        assert_eq!(parse_and_format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_format_since() {
+        let input = "message M { uint64 epoch = 6 @since( \"2.0\"); }";
+        assert!(tokenize_schema(input).is_err());
+
+        let input = "message M { uint64 epoch = 6  @since(\"2.0\") [deprecated]; }\nenum E { A = 0 @since(\"1\"); }\nstruct S { int x @since(\"3\"); }";
+        let formatted = parse_and_format(input);
+        assert_eq!(
+            formatted,
+            "message M {\n  uint64 epoch = 6 [deprecated] @since(\"2.0\");\n}\n\nenum E {\n  A = 0 @since(\"1\");\n}\n\nstruct S {\n  int x @since(\"3\");\n}\n"
+        );
+
+        // Unrecognized annotations are kept as written, after the known ones
+        let input = "struct S { int x @deprectaed @since(\"3\") @todo(a = 1); }";
+        assert_eq!(
+            parse_and_format(input),
+            "struct S {\n  int x @since(\"3\") @deprectaed @todo(a = 1);\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_sorted() {
+        let input = r#"
+            # Transfers
+            message Transfer {
+                # Who pays
+                byte[32] from = 3;
+                uint64 amount = 1 [deprecated];
+                // Who receives
+                // (an account)
+                byte[32] to = 2;
+            }
+            enum Role { ARCHIVER = 2; OTHER = ARCHIVER; VALIDATOR = 1; }
+            struct Point { int y; int x; }
+        "#;
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let options = FormatOptions {
+            sort_fields: true,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            format_schema_with_options(&schema, &options),
+            "// Transfers\nmessage Transfer {\n  uint64 amount = 1 [deprecated];\n  \
+             // Who receives\n  // (an account)\n  byte[32] to = 2;\n  \
+             // Who pays\n  byte[32] from = 3;\n}\n\n\
+             enum Role {\n  VALIDATOR = 1;\n  ARCHIVER = 2;\n  OTHER = ARCHIVER;\n}\n\n\
+             struct Point {\n  int y;\n  int x;\n}\n"
+        );
+
+        // Struct fields are only sorted when asked, since that changes their encoding
+        let options = FormatOptions {
+            sort_struct_fields: true,
+            ..options
+        };
+        assert!(format_schema_with_options(&schema, &options)
+            .ends_with("struct Point {\n  int x;\n  int y;\n}\n"));
+    }
+
+    #[test]
+    fn test_format_nested_definitions() {
+        let input = "message Canvas { Layer[] layers = 1;\n // One layer\n struct Layer { enum Mode { FLAT = 0; } Mode mode; } }";
+        let formatted = parse_and_format(input);
+        assert_eq!(
+            formatted,
+            "message Canvas {\n  // One layer\n  struct Layer {\n    enum Mode {\n      FLAT = 0;\n    }\n\n    \
+             Mode mode;\n  }\n\n  Layer[] layers = 1;\n}\n"
+        );
+        assert_eq!(parse_and_format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_format_validate() {
+        let input = "message M { uint64 amount = 1 @validate(max=1_000,min=1); int[] items = 2 @validate( len_max = 8 ) [deprecated]; }";
+        let formatted = parse_and_format(input);
+        assert_eq!(
+            formatted,
+            "message M {\n  uint64 amount = 1 @validate(min = 1, max = 1_000);\n  int[] items = 2 [deprecated] @validate(len_max = 8);\n}\n"
+        );
+        assert_eq!(parse_and_format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_format_digit_separators() {
+        let input = "type Page = byte[4_096];\n\nenum Big {\n  A = 1_000;\n  B = 2_000;\n}\n\nstruct S {\n  byte[1_024] data;\n}\n\nmessage M {\n  uint64 amount = 1_0 @validate(max = 10_000);\n}\n";
+        assert_eq!(parse_and_format(input), input);
+    }
+
+    #[test]
+    fn test_format_deprecated_keyword() {
+        // The keyword prefix is normalized to the trailing tag
+        let keyword = parse_and_format("message M { deprecated required uint64 old = 1; }");
+        let tag = parse_and_format("message M { required uint64 old = 1 [deprecated]; }");
+        assert_eq!(keyword, tag);
+        assert_eq!(
+            keyword,
+            "message M {\n  required uint64 old = 1 [deprecated];\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_format_presence_keywords() {
+        let input = "message M { required  uint64 a = 1; optional string b=2; }";
+        let formatted = parse_and_format(input);
+        assert_eq!(
+            formatted,
+            "message M {\n  required uint64 a = 1;\n  optional string b = 2;\n}\n"
+        );
+    }
+
     #[test]
     fn test_format_with_package() {
         let input = r#"
@@ -159,6 +828,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_format_multiple_packages() {
+        let input = r#"
+            package a;
+            struct Foo { int x; }
+            struct Bar { int y; }
+            package b;
+            struct Baz { a.Foo foo; }
+        "#;
+        let formatted = parse_and_format(input);
+        assert_eq!(
+            formatted,
+            "package a;\n\nstruct Foo {\n  int x;\n}\n\nstruct Bar {\n  int y;\n}\n\npackage b;\n\nstruct Baz {\n  a.Foo foo;\n}\n"
+        );
+    }
+
     #[test]
     fn test_format_multiple_definitions() {
         let input = r#"
@@ -172,6 +857,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_format_compact_small_defs() {
+        let input = r#"
+            enum Color { RED = 0; GREEN = 1; }
+            enum Big { A = 0; B = 1; C = 2; D = 3; E = 4; }
+            struct Point { int x; int y; }
+            message Ping { uint64 id = 1; }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let options = FormatOptions {
+            compact_small_defs: true,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            format_schema_with_options(&schema, &options),
+            "enum Color { RED = 0; GREEN = 1; }\n\n\
+             enum Big {\n  A = 0;\n  B = 1;\n  C = 2;\n  D = 3;\n  E = 4;\n}\n\n\
+             struct Point { int x; int y; }\n\n\
+             message Ping {\n  uint64 id = 1;\n}\n"
+        );
+
+        // Over the width budget
+        let options = FormatOptions {
+            compact_max_width: 20,
+            ..options
+        };
+        assert!(format_schema_with_options(&schema, &options).starts_with("enum Color {\n"));
+    }
+
     #[test]
     fn test_format_variable_array() {
         let input = r#"
@@ -185,5 +900,35 @@ mod tests {
             formatted,
             "struct Data {\n  int32[] values;\n  string[] names;\n}\n"
         );
+
+        // `repeated T` is written in the canonical `T[]` form
+        let formatted = parse_and_format("message Block { repeated Transaction txs = 1; }");
+        assert_eq!(formatted, "message Block {\n  Transaction[] txs = 1;\n}\n");
+    }
+
+    #[test]
+    fn test_diff_lines() {
+        let source = "struct Point {\n  int   x;\n  int y;\n}\n";
+        let formatted = parse_and_format(source);
+        assert_eq!(
+            diff_lines(source, &formatted),
+            "@@ line 2 @@\n-  int   x;\n+  int x;\n"
+        );
+        assert_eq!(diff_lines(&formatted, &formatted), "");
+
+        // Only the line endings differ
+        let point = parse_and_format("struct Point { int x; }");
+        assert_eq!(
+            diff_lines("struct Point {\n  int x;\n}", &point),
+            "\\ missing trailing newline\n"
+        );
+        assert_eq!(
+            diff_lines("struct Point {\r\n  int x;\r\n}\r\n", &point),
+            "\\ line endings differ: CRLF instead of LF\n"
+        );
+        assert_eq!(
+            diff_lines("struct Point {\r\n  int x;\r\n}", &point),
+            "\\ line endings differ: CRLF instead of LF\n\\ missing trailing newline\n"
+        );
     }
 }
@@ -0,0 +1,281 @@
+//! JSON Schema generation for TCS schemas
+//!
+//! Describes the JSON form of each definition in a JSON Schema (draft 2020-12)
+//! document, for APIs that exchange the generated types as JSON.
+
+use tcs_schema::{Definition, DefinitionKind, Field, Presence, Schema};
+
+use crate::json::Json;
+use crate::utils::{escape_rust_keyword, split_qualified, to_pascal_case, to_snake_case};
+
+/// Generate a JSON Schema document with a `$defs` entry per definition
+///
+/// Entries are named like the generated Rust types, prefixed with the package
+/// (`other.Point`) for definitions outside the schema's own package.
+///
+/// Structs require every field, while messages only require fields marked
+/// `required`. Fixed-size byte arrays are hex strings (matching the `serde`
+/// generation option) and other byte sequences are base64 strings.
+pub fn compile_schema_to_json_schema(schema: &Schema) -> String {
+    let schema = &schema.resolve_aliases();
+    let defs = schema
+        .definitions
+        .iter()
+        .map(|definition| {
+            (
+                def_key(definition, schema),
+                definition_schema(definition, schema),
+            )
+        })
+        .collect();
+
+    let document = Json::object(vec![
+        (
+            "$schema",
+            Json::str("https://json-schema.org/draft/2020-12/schema"),
+        ),
+        ("$defs", Json::Object(defs)),
+    ]);
+
+    let mut out = String::new();
+    document.write(0, &mut out);
+    out.push('\n');
+    out
+}
+
+/// The `$defs` key of a definition
+fn def_key(definition: &Definition, schema: &Schema) -> String {
+    let name = to_pascal_case(&definition.name);
+    match definition.package {
+        Some(ref package) if definition.package != schema.package => {
+            format!("{}.{}", package, name)
+        }
+        _ => name,
+    }
+}
+
+/// The `$defs` entry for one definition
+fn definition_schema(definition: &Definition, schema: &Schema) -> Json {
+    let mut entries = Vec::new();
+    if !definition.comments.is_empty() {
+        entries.push(("description", description(&definition.comments)));
+    }
+    if definition.is_deprecated {
+        entries.push(("deprecated", Json::Bool(true)));
+    }
+
+    match definition.kind {
+        DefinitionKind::Enum if definition.is_flags => {
+            entries.extend(integer("uint32", Some(0)));
+        }
+        DefinitionKind::Enum => {
+            let variants = definition
+                .fields
+                .iter()
+                .filter(|f| f.alias_of.is_none())
+                .map(|f| Json::String(to_pascal_case(&f.name)))
+                .collect();
+            entries.push(("type", Json::str("string")));
+            entries.push(("enum", Json::Array(variants)));
+        }
+        DefinitionKind::Struct | DefinitionKind::Message => {
+            let is_message = definition.kind == DefinitionKind::Message;
+            let mut properties = Vec::new();
+            let mut required = Vec::new();
+            for field in &definition.fields {
+                let name = escape_rust_keyword(&to_snake_case(&field.name));
+                if !is_message || field.presence == Presence::Required {
+                    required.push(Json::String(name.clone()));
+                }
+                properties.push((name, field_schema(field, definition, schema)));
+            }
+            entries.push(("type", Json::str("object")));
+            entries.push(("properties", Json::Object(properties)));
+            entries.push(("required", Json::Array(required)));
+            entries.push(("additionalProperties", Json::Bool(false)));
+        }
+    }
+
+    Json::object(entries)
+}
+
+/// The schema for one field of a struct or message
+fn field_schema(field: &Field, definition: &Definition, schema: &Schema) -> Json {
+    let type_name = field.type_.as_deref().unwrap_or_default();
+    let mut entries = Vec::new();
+    if !field.comments.is_empty() {
+        entries.push(("description", description(&field.comments)));
+    }
+    if field.is_deprecated {
+        entries.push(("deprecated", Json::Bool(true)));
+    }
+
+    if let Some(size) = field.array_size.filter(|_| field.is_fixed_byte_array()) {
+        entries.push(("type", Json::str("string")));
+        entries.push((
+            "pattern",
+            Json::String(format!("^[0-9a-fA-F]{{{}}}$", size * 2)),
+        ));
+    } else if field.is_array && matches!(type_name, "byte" | "uint8") {
+        entries.extend(base64());
+    } else if field.is_array {
+        entries.push(("type", Json::str("array")));
+        entries.push((
+            "items",
+            Json::object(type_schema(type_name, definition, schema)),
+        ));
+    } else {
+        entries.extend(type_schema(type_name, definition, schema));
+    }
+
+    Json::object(entries)
+}
+
+/// The schema entries for a single value of a native or user-defined type,
+/// used by a field of `definition`
+fn type_schema(
+    type_name: &str,
+    definition: &Definition,
+    schema: &Schema,
+) -> Vec<(&'static str, Json)> {
+    match type_name {
+        "bool" => vec![("type", Json::str("boolean"))],
+        "byte" | "uint8" => {
+            let mut entries = integer("uint8", Some(0));
+            entries.push(("maximum", Json::Number(255)));
+            entries
+        }
+        "int" => integer("int32", None),
+        "uint" => integer("uint32", Some(0)),
+        "int64" => integer("int64", None),
+        "uint64" | "timestamp" => integer("uint64", Some(0)),
+        "int128" => integer("int128", None),
+        "uint128" => integer("uint128", Some(0)),
+        // 32 little-endian bytes, as serialized by serde
+        "uint256" => vec![
+            ("type", Json::str("array")),
+            (
+                "items",
+                Json::object(type_schema("uint8", definition, schema)),
+            ),
+            ("minItems", Json::Number(32)),
+            ("maxItems", Json::Number(32)),
+        ],
+        "float" => vec![
+            ("type", Json::str("number")),
+            ("format", Json::str("float")),
+        ],
+        "string" => vec![("type", Json::str("string"))],
+        "bytes" => base64(),
+        other => {
+            // Verified schemas only refer to types that exist
+            let name = match schema.resolve_type(other, definition) {
+                Some(target) => def_key(target, schema),
+                None => to_pascal_case(split_qualified(other).1),
+            };
+            vec![("$ref", Json::String(format!("#/$defs/{}", name)))]
+        }
+    }
+}
+
+/// An integer with a format hint and an optional lower bound
+fn integer(format: &str, minimum: Option<i64>) -> Vec<(&'static str, Json)> {
+    let mut entries = vec![
+        ("type", Json::str("integer")),
+        ("format", Json::str(format)),
+    ];
+    if let Some(minimum) = minimum {
+        entries.push(("minimum", Json::Number(minimum)));
+    }
+    entries
+}
+
+/// A base64-encoded byte string
+fn base64() -> Vec<(&'static str, Json)> {
+    vec![
+        ("type", Json::str("string")),
+        ("contentEncoding", Json::str("base64")),
+    ]
+}
+
+/// Doc comment lines joined into a description
+fn description(comments: &[String]) -> Json {
+    let lines: Vec<&str> = comments.iter().map(|c| c.trim()).collect();
+    Json::String(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_schema;
+    use crate::tokenizer::tokenize_schema;
+
+    fn generate(input: &str) -> String {
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        compile_schema_to_json_schema(&schema)
+    }
+
+    #[test]
+    fn test_message_properties() {
+        let output = generate(
+            r#"
+            enum Status { PENDING = 0; DONE = 1; }
+            // A signed transfer
+            message Transaction {
+                required uint64 nonce = 1;
+                byte[32] hash = 2;
+                byte[] payload = 3;
+                Status status = 4;
+                string[] tags = 5;
+            }
+            "#,
+        );
+        assert!(output.starts_with(
+            "{\n  \"$schema\": \"https://json-schema.org/draft/2020-12/schema\",\n  \"$defs\": {\n"
+        ));
+        assert!(output.contains(
+            "    \"Transaction\": {\n      \"description\": \"A signed transfer\",\n      \
+             \"type\": \"object\",\n      \"properties\": {\n        \"nonce\": {\n          \
+             \"type\": \"integer\",\n          \"format\": \"uint64\",\n          \"minimum\": 0\n        },"
+        ));
+        assert!(output.contains(
+            "\"hash\": {\n          \"type\": \"string\",\n          \"pattern\": \"^[0-9a-fA-F]{64}$\"\n        }"
+        ));
+        assert!(output.contains("\"contentEncoding\": \"base64\""));
+        assert!(output.contains("\"status\": {\n          \"$ref\": \"#/$defs/Status\"\n        }"));
+        assert!(output.contains(
+            "\"tags\": {\n          \"type\": \"array\",\n          \"items\": {\n            \"type\": \"string\"\n          }\n        }"
+        ));
+        // Only `required` message fields are required
+        assert!(output.contains("\"required\": [\n        \"nonce\"\n      ],"));
+        assert!(output.contains(
+            "    \"Status\": {\n      \"type\": \"string\",\n      \"enum\": [\n        \"Pending\",\n        \"Done\"\n      ]\n    }"
+        ));
+    }
+
+    #[test]
+    fn test_struct_fields_required() {
+        let output = generate("struct Point { int x; int y; }\nstruct Empty {}");
+        assert!(output.contains("\"required\": [\n        \"x\",\n        \"y\"\n      ],"));
+        assert!(output.contains("\"properties\": {},\n      \"required\": [],"));
+        assert!(output.ends_with("}\n"));
+    }
+
+    #[test]
+    fn test_packages_share_names() {
+        let output = generate(
+            r#"
+            package geometry;
+            struct Point { int x; }
+            package shapes;
+            struct Point { int y; }
+            struct Circle { Point center; geometry.Point origin; }
+            "#,
+        );
+        assert!(output.contains("    \"Point\": {"));
+        assert!(output.contains("    \"shapes.Point\": {"));
+        assert!(output.contains("\"center\": {\n          \"$ref\": \"#/$defs/shapes.Point\""));
+        assert!(output.contains("\"origin\": {\n          \"$ref\": \"#/$defs/Point\""));
+    }
+}
@@ -2,25 +2,55 @@
 //!
 //! Generates Rust code with wincode derives for serialization/deserialization.
 
-use tcs_schema::{Definition, DefinitionKind, Field, Schema};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use tcs_schema::{Definition, DefinitionKind, Field, OptionValue, Presence, Schema, SchemaOption};
 
-use crate::utils::{escape_rust_keyword, to_pascal_case, to_snake_case};
+use crate::error::{codes, TcsError};
+use crate::utils::{
+    codegen_error, escape_rust_keyword, fixed_size, fixed_struct_sizes, integer_range, quote,
+    raw_rust_keyword, split_qualified, to_pascal_case, to_snake_case, verification_error,
+};
 use crate::verifier::NATIVE_TYPES;
 
-/// Maps TCS types to Rust types
-fn map_type(field: &Field, is_message: bool) -> String {
+/// Native types that the wincode derive can't serialize directly
+const MANUAL_SCHEMA_TYPES: &[&str] = &["bytes"];
+
+/// Opt-in derive for fuzzing, gated on the consuming crate's `arbitrary` feature
+const ARBITRARY_DERIVE: &str = "#[cfg_attr(feature = \"arbitrary\", derive(arbitrary::Arbitrary))]";
+
+/// Opt-in serde derives, gated on the consuming crate's `serde` feature
+const SERDE_DERIVE: &str =
+    "#[cfg_attr(feature = \"serde\", derive(serde::Serialize, serde::Deserialize))]";
+
+/// Largest array length for which std implements `Default`
+const MAX_DERIVE_ARRAY_LEN: usize = 32;
+
+/// Whether a field is generated wrapped in `Option` (message fields not marked `required`)
+fn is_optional(field: &Field, is_message: bool) -> bool {
+    is_message && field.presence != Presence::Required
+}
+
+/// Maps TCS types to Rust types, as seen from the module `from` is generated in
+fn map_type(field: &Field, is_message: bool, ctx: &GenContext, from: &Definition) -> String {
+    let is_message = is_optional(field, is_message);
     let type_name = field.type_.as_deref().unwrap_or("");
 
     let rust_type = match type_name {
         "bool" => "bool".to_string(),
-        "byte" => "u8".to_string(),
+        "byte" | "uint8" => "u8".to_string(),
         "int" => "i32".to_string(),
         "uint" => "u32".to_string(),
         "float" => "f32".to_string(),
         "string" => "String".to_string(),
         "int64" => "i64".to_string(),
-        "uint64" => "u64".to_string(),
-        other => to_pascal_case(other),
+        // Epoch milliseconds; a logical alias for uint64
+        "uint64" | "timestamp" => "u64".to_string(),
+        "int128" => "i128".to_string(),
+        "uint128" => "u128".to_string(),
+        // No native 256-bit integer; 32 little-endian bytes, matching the wire encoding
+        "uint256" => "[u8; 32]".to_string(),
+        "bytes" => "bytes::Bytes".to_string(),
+        other => ctx.type_path(other, from),
     };
 
     // Handle arrays
@@ -54,95 +84,1359 @@ fn is_native_type(type_name: &str) -> bool {
     NATIVE_TYPES.contains(&type_name)
 }
 
-/// Entry point: generate Rust code from a Schema
+/// Lints allowed on generated code by default: schemas often declare types
+/// the crate including them never uses
+pub const DEFAULT_ALLOWED_LINTS: &[&str] = &["dead_code"];
+
+/// Options controlling Rust code generation
+#[derive(Debug, Clone)]
+pub struct GenOptions {
+    /// Emit definitions in dependency order (alphabetical among independent
+    /// types) instead of source order, so output doesn't depend on how the
+    /// schema file happens to be arranged
+    pub sort_definitions: bool,
+    /// Lints to `#[allow(...)]` on each generated module (or, without a
+    /// package, on each generated item), so output stays clean in crates that
+    /// deny warnings. Item attributes don't reach the impls that derive macros
+    /// expand to, so without a package, the module the code is included in
+    /// must allow `deprecated` itself for schemas with deprecated types.
+    pub allowed_lints: Vec<String>,
+    /// Generate a `visit` method on structs and messages that contain nested
+    /// structs or messages, walking every nested value
+    pub generate_visitors: bool,
+    /// Generate a zero-copy `NameView<'a>` for each struct whose fields all
+    /// have a fixed size, with accessors reading each field from its offset
+    /// in the serialized bytes
+    pub generate_views: bool,
+    /// Whether deprecated fields are included in each type's generated
+    /// `FIELD_COUNT` constant
+    pub count_deprecated_fields: bool,
+    /// Write each enum variant's value (`Storage = 1`). When false, values are
+    /// left implicit on enums whose values already count up from 0 in
+    /// declaration order; other enums keep explicit values, since the wire
+    /// format depends on them.
+    pub explicit_discriminants: bool,
+    /// Implement `Default` by hand on messages instead of deriving it, writing
+    /// out each field's value (`None` for optional fields)
+    pub explicit_default: bool,
+    /// Escape fields and variants named after Rust keywords as raw identifiers
+    /// (`r#type`) instead of with a trailing underscore (`type_`)
+    pub raw_identifiers: bool,
+    /// Derive `arbitrary::Arbitrary` on every generated type when the consuming
+    /// crate enables its `arbitrary` feature
+    pub derive_arbitrary: bool,
+    /// Derive serde's `Serialize` and `Deserialize` on every generated type
+    /// when the consuming crate enables its `serde` feature. Fixed-size byte
+    /// arrays are rendered as hex strings, which needs the `hex` crate.
+    pub derive_serde: bool,
+    /// With `derive_serde`, rename each enum variant to its name in the schema
+    /// (`VALIDATOR` rather than `Validator`), so JSON matches the schema
+    pub serde_enum_names: bool,
+    /// Implement `Debug` by hand on structs and messages with byte-array
+    /// fields (`byte[32]`, `byte[]`), printing those fields as hex strings
+    /// instead of lists of numbers
+    pub hex_debug: bool,
+    /// Give every public item a doc comment, using a placeholder such as
+    /// ``/// The `nonce` field.`` where the schema has no comment, so the
+    /// output compiles under `#![deny(missing_docs)]`
+    pub doc_stubs: bool,
+    /// Leave `Clone` out of the derives on structs and messages, so large
+    /// values can't be copied by accident. Enums stay `Clone + Copy`.
+    pub no_clone: bool,
+    /// Implement `PartialEq` by hand on types with deprecated fields, comparing
+    /// only the fields that aren't deprecated
+    pub eq_ignore_deprecated: bool,
+    /// Treat structs with exactly one field as newtypes: `#[repr(transparent)]`
+    /// plus `Deref` and `From` conversions to and from the inner type
+    pub newtypes: bool,
+    /// Generate a `set_<field>_checked` method for each integer field of a
+    /// struct or message, which narrows any integer to the field's type and
+    /// fails with a `RangeError` if the value doesn't fit. Meant for
+    /// hand-written decoders that read wide integers.
+    pub checked_setters: bool,
+    /// Generate `SIZE` and a `to_array(&self) -> [u8; SIZE]` method for each
+    /// struct whose fields all have a fixed size, serializing into a stack
+    /// array without allocating
+    pub to_array: bool,
+    /// Path the generated code uses to reach the wincode crate, for vendored
+    /// or renamed dependencies (e.g. `crate::vendor::wincode`). The derive
+    /// macros always expand to `::wincode`, so with anything but the default
+    /// every type gets hand-written `SchemaRead`/`SchemaWrite` impls instead,
+    /// with the same encoding, and `wincode_derive` isn't needed.
+    pub wincode_path: String,
+    /// Emit each module as three commented sections (type definitions, then
+    /// impls, then associated constants) instead of grouping everything
+    /// per definition
+    pub sectioned_output: bool,
+    /// Generate code for `no_std` crates: `core::` paths instead of `std::`,
+    /// and `Vec`/`String` imported from `alloc` (the crate root must declare
+    /// `extern crate alloc;`)
+    pub no_std: bool,
+    /// Wrap all generated code (including package modules) in a module of
+    /// this name
+    pub rust_module: Option<String>,
+    /// Start the output with the comments saying it's generated, after the
+    /// schema's own header. Turned off for code spliced into a hand-written
+    /// file, which has a header of its own.
+    pub file_header: bool,
+    /// Schema source to embed verbatim as `pub const SCHEMA_SOURCE: &str`, so
+    /// the generated file records what it was generated from
+    pub embed_source: Option<String>,
+}
+
+/// Schema-wide information needed while generating individual definitions
+struct GenContext<'a> {
+    /// The schema being generated, to look up the types fields refer to
+    schema: &'a Schema,
+    /// Structs and messages that get a generated `visit` method, by
+    /// qualified name
+    visitable: HashSet<String>,
+    /// Enums used by `[ord]` structs, which need `PartialOrd` and `Ord` too
+    ordered_enums: HashSet<String>,
+    /// Serialized sizes of the fixed-size structs, when views or `to_array`
+    /// are generated
+    fixed_sizes: HashMap<String, usize>,
+    /// Generate zero-copy views of fixed-size structs
+    views: bool,
+    /// Include deprecated fields in `FIELD_COUNT`
+    count_deprecated: bool,
+    /// Always write enum variant values
+    explicit_discriminants: bool,
+    /// Write out `Default` impls for messages
+    explicit_default: bool,
+    /// Escape keywords as raw identifiers
+    raw_identifiers: bool,
+    /// Emit feature-gated `Arbitrary` derives
+    arbitrary: bool,
+    /// Emit feature-gated serde derives
+    serde: bool,
+    /// Keep schema variant names in serde output
+    serde_enum_names: bool,
+    /// Print byte arrays as hex in `Debug` output
+    hex_debug: bool,
+    /// Give undocumented items placeholder docs
+    doc_stubs: bool,
+    /// Don't derive `Clone` on structs and messages
+    no_clone: bool,
+    /// Leave deprecated fields out of `PartialEq`
+    eq_ignore_deprecated: bool,
+    /// Generate single-field structs as transparent newtypes
+    newtypes: bool,
+    /// Generate `set_<field>_checked` methods for integer fields
+    checked_setters: bool,
+    /// Generate `to_array` for fixed-size structs
+    to_array: bool,
+    /// Path to the wincode crate
+    wincode: &'a str,
+    /// Keep associated consts in impl blocks of their own
+    sectioned: bool,
+    /// Crate providing `ops`, `hash`, `mem` and `any` (`std` or `core`)
+    core: &'static str,
+}
+
+impl<'a> GenContext<'a> {
+    fn new(schema: &'a Schema, options: &'a GenOptions) -> Self {
+        let visitable = if options.generate_visitors {
+            schema
+                .definitions
+                .iter()
+                .filter(|d| d.kind != DefinitionKind::Enum)
+                .filter(|d| d.fields.iter().any(|f| nested_type(f, d, schema).is_some()))
+                .map(Definition::qualified_name)
+                .collect()
+        } else {
+            HashSet::new()
+        };
+        let ordered_enums = schema
+            .iter_fields()
+            .filter(|(d, _)| d.is_ord)
+            .filter_map(|(d, f)| schema.resolve_type(f.type_.as_deref()?, d))
+            .filter(|target| target.kind == DefinitionKind::Enum)
+            .map(Definition::qualified_name)
+            .collect();
+        let fixed_sizes = if options.generate_views || options.to_array {
+            // A schema whose sizes overflow fails verification; it gets neither
+            fixed_struct_sizes(schema).unwrap_or_default()
+        } else {
+            HashMap::new()
+        };
+        GenContext {
+            schema,
+            visitable,
+            ordered_enums,
+            fixed_sizes,
+            views: options.generate_views,
+            count_deprecated: options.count_deprecated_fields,
+            explicit_discriminants: options.explicit_discriminants,
+            explicit_default: options.explicit_default,
+            raw_identifiers: options.raw_identifiers,
+            arbitrary: options.derive_arbitrary,
+            serde: options.derive_serde,
+            serde_enum_names: options.serde_enum_names,
+            hex_debug: options.hex_debug,
+            doc_stubs: options.doc_stubs,
+            no_clone: options.no_clone,
+            eq_ignore_deprecated: options.eq_ignore_deprecated,
+            newtypes: options.newtypes,
+            checked_setters: options.checked_setters,
+            to_array: options.to_array,
+            wincode: &options.wincode_path,
+            sectioned: options.sectioned_output,
+            core: if options.no_std { "core" } else { "std" },
+        }
+    }
+
+    /// `PartialOrd, Ord, ` for enums used by `[ord]` structs, else nothing
+    fn ord_derives(&self, definition: &Definition) -> &'static str {
+        if self.ordered_enums.contains(&definition.qualified_name()) {
+            "PartialOrd, Ord, "
+        } else {
+            ""
+        }
+    }
+
+    /// With `doc_stubs`, give an item that has no doc lines a placeholder one
+    fn push_doc_stub(&self, docs: &mut Vec<String>, indent: &str, stub: String) {
+        if self.doc_stubs && docs.is_empty() {
+            docs.push(format!("{}/// {}", indent, stub));
+        }
+    }
+
+    /// Rust identifier for a field or variant name that may be a keyword
+    fn ident(&self, name: &str) -> String {
+        if self.raw_identifiers {
+            raw_rust_keyword(name)
+        } else {
+            escape_rust_keyword(name)
+        }
+    }
+
+    /// Rust path of the definition named `ty` (optionally package-qualified),
+    /// from the module `from` is generated in. Definitions nested in `from`
+    /// are in its module; everything else is in scope through the module's
+    /// `use super::*;`, except other packages, which are siblings of the
+    /// outermost module.
+    fn type_path(&self, ty: &str, from: &Definition) -> String {
+        match split_qualified(ty) {
+            (Some(package), name) => format!(
+                "{}{}::{}",
+                "super::".repeat(self.depth(from) + 1),
+                to_snake_case(package),
+                to_pascal_case(name)
+            ),
+            (None, name) if self.is_nested_in(ty, from) => {
+                format!(
+                    "{}::{}",
+                    self.nested_module(&from.name),
+                    to_pascal_case(name)
+                )
+            }
+            (None, name) => to_pascal_case(name),
+        }
+    }
+
+    /// Whether the type `from` refers to as `ty` is nested in `from`
+    fn is_nested_in(&self, ty: &str, from: &Definition) -> bool {
+        self.schema
+            .resolve_type(ty, from)
+            .is_some_and(|target| target.parent.as_deref() == Some(from.name.as_str()))
+    }
+
+    /// Number of definitions `definition` is nested in
+    fn depth(&self, definition: &Definition) -> usize {
+        let mut depth = 0;
+        let mut current = definition;
+        while let Some(parent) = current
+            .parent
+            .as_ref()
+            .and_then(|p| self.schema.resolve_type(p, current))
+        {
+            depth += 1;
+            current = parent;
+        }
+        depth
+    }
+
+    /// Name of the module holding the definitions nested in `parent`
+    fn nested_module(&self, parent: &str) -> String {
+        self.ident(&to_snake_case(parent))
+    }
+
+    /// Value of the generated `FIELD_COUNT` constant for a definition. Enum
+    /// aliases don't count, since they aren't distinct variants.
+    fn field_count(&self, definition: &Definition) -> usize {
+        definition
+            .fields
+            .iter()
+            .filter(|f| f.alias_of.is_none())
+            .filter(|f| self.count_deprecated || !f.is_deprecated)
+            .count()
+    }
+}
+
+/// The struct/message a field of `from` refers to, if any
+fn nested_type<'a>(field: &Field, from: &Definition, schema: &'a Schema) -> Option<&'a Definition> {
+    let target = schema.resolve_type(field.type_.as_deref()?, from)?;
+    (target.kind != DefinitionKind::Enum).then_some(target)
+}
+
+/// Generated code for one definition, kept in pieces so it can be emitted
+/// either per definition or in sections
+struct DefinitionCode {
+    /// The type declaration, with its docs and derives
+    type_def: String,
+    /// Impl blocks holding only associated consts
+    consts: Vec<String>,
+    /// All other impl blocks
+    impls: Vec<String>,
+    /// Module holding the definitions nested in this one
+    module: Option<String>,
+}
+
+impl DefinitionCode {
+    /// Add `attr` to every item generated for the definition
+    fn add_attribute(&mut self, attr: &str) {
+        // An item's own `#[allow(deprecated)]` would be a duplicate
+        if attr.contains("deprecated") {
+            for item in self.consts.iter_mut().chain(self.impls.iter_mut()) {
+                let lines = item.split('\n').filter(|l| *l != "#[allow(deprecated)]");
+                *item = lines.collect::<Vec<_>>().join("\n");
+            }
+        }
+        self.type_def = with_attribute(&self.type_def, attr);
+        for item in self.consts.iter_mut().chain(self.impls.iter_mut()) {
+            *item = with_attribute(item, attr);
+        }
+        // Only on the module itself, not on the definitions inside it
+        if let Some(ref mut module) = self.module {
+            let at = module.find("pub mod ").unwrap_or(0);
+            module.insert_str(at, &format!("{}\n", attr));
+        }
+    }
+
+    /// Everything for this definition, in declaration order
+    fn join(self) -> String {
+        let mut items = vec![self.type_def];
+        items.extend(self.consts);
+        items.extend(self.impls);
+        items.extend(self.module);
+        items.join("\n\n")
+    }
+}
+
+impl Default for GenOptions {
+    fn default() -> Self {
+        GenOptions {
+            sort_definitions: false,
+            generate_visitors: false,
+            generate_views: false,
+            count_deprecated_fields: true,
+            explicit_discriminants: true,
+            explicit_default: false,
+            raw_identifiers: false,
+            derive_arbitrary: false,
+            derive_serde: false,
+            serde_enum_names: false,
+            hex_debug: false,
+            doc_stubs: false,
+            no_clone: false,
+            eq_ignore_deprecated: false,
+            newtypes: false,
+            checked_setters: false,
+            to_array: false,
+            wincode_path: "wincode".to_string(),
+            sectioned_output: false,
+            no_std: false,
+            rust_module: None,
+            file_header: true,
+            embed_source: None,
+            allowed_lints: DEFAULT_ALLOWED_LINTS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+/// Options that can be set in a schema's `options { ... }` block, with the kind
+/// of value each expects
+const SCHEMA_OPTIONS: &[(&str, &str)] = &[
+    ("sort", "true or false"),
+    ("visitors", "true or false"),
+    ("views", "true or false"),
+    ("raw_identifiers", "true or false"),
+    ("arbitrary", "true or false"),
+    ("serde", "true or false"),
+    ("serde_enum_names", "true or false"),
+    ("hex_debug", "true or false"),
+    ("doc_stubs", "true or false"),
+    ("no_clone", "true or false"),
+    ("explicit_discriminants", "true or false"),
+    ("explicit_default", "true or false"),
+    ("eq_ignore_deprecated", "true or false"),
+    ("newtypes", "true or false"),
+    ("checked_setters", "true or false"),
+    ("to_array", "true or false"),
+    ("sectioned", "true or false"),
+    ("no_std", "true or false"),
+    ("wincode_path", "a string"),
+    ("rust_module", "a string"),
+];
+
+impl GenOptions {
+    /// The default options, overridden by the schema's `options { ... }` block
+    pub fn from_schema(schema: &Schema) -> Result<Self, TcsError> {
+        let mut options = GenOptions::default();
+        options.apply_options(&schema.options)?;
+        Ok(options)
+    }
+
+    /// Override these options with entries named like those of a schema's
+    /// `options` block (e.g. from a project file), in order
+    pub fn apply_options(&mut self, options: &[SchemaOption]) -> Result<(), TcsError> {
+        for option in options {
+            self.set(option)?;
+        }
+        Ok(())
+    }
+
+    /// Apply one entry of a schema's `options` block
+    fn set(&mut self, option: &SchemaOption) -> Result<(), TcsError> {
+        match (option.name.as_str(), &option.value) {
+            ("sort", OptionValue::Bool(value)) => self.sort_definitions = *value,
+            ("visitors", OptionValue::Bool(value)) => self.generate_visitors = *value,
+            ("views", OptionValue::Bool(value)) => self.generate_views = *value,
+            ("raw_identifiers", OptionValue::Bool(value)) => self.raw_identifiers = *value,
+            ("arbitrary", OptionValue::Bool(value)) => self.derive_arbitrary = *value,
+            ("serde", OptionValue::Bool(value)) => self.derive_serde = *value,
+            ("serde_enum_names", OptionValue::Bool(value)) => self.serde_enum_names = *value,
+            ("hex_debug", OptionValue::Bool(value)) => self.hex_debug = *value,
+            ("doc_stubs", OptionValue::Bool(value)) => self.doc_stubs = *value,
+            ("no_clone", OptionValue::Bool(value)) => self.no_clone = *value,
+            ("explicit_discriminants", OptionValue::Bool(value)) => {
+                self.explicit_discriminants = *value
+            }
+            ("explicit_default", OptionValue::Bool(value)) => self.explicit_default = *value,
+            ("eq_ignore_deprecated", OptionValue::Bool(value)) => {
+                self.eq_ignore_deprecated = *value
+            }
+            ("newtypes", OptionValue::Bool(value)) => self.newtypes = *value,
+            ("checked_setters", OptionValue::Bool(value)) => self.checked_setters = *value,
+            ("to_array", OptionValue::Bool(value)) => self.to_array = *value,
+            ("sectioned", OptionValue::Bool(value)) => self.sectioned_output = *value,
+            ("no_std", OptionValue::Bool(value)) => self.no_std = *value,
+            ("wincode_path", OptionValue::String(value)) => self.wincode_path = value.clone(),
+            ("rust_module", OptionValue::String(value)) => self.rust_module = Some(value.clone()),
+            (name, value) => {
+                let msg = match SCHEMA_OPTIONS.iter().find(|(known, _)| *known == name) {
+                    Some((_, expected)) => format!(
+                        "The option {} expects {}, not {}",
+                        quote(name),
+                        expected,
+                        value
+                    ),
+                    None => format!("Unknown option {}", quote(name)),
+                };
+                return Err(verification_error(codes::INVALID_OPTION, &msg));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Order definitions so that every type comes after the types it references,
+/// breaking ties (and cycles through arrays/messages) alphabetically. The
+/// definitions are siblings (in the same package and parent), so their names
+/// are distinct.
+fn dependency_order<'a>(definitions: Vec<&'a Definition>, schema: &Schema) -> Vec<&'a Definition> {
+    let by_name: HashMap<&str, &Definition> =
+        definitions.iter().map(|d| (d.name.as_str(), *d)).collect();
+    let deps: HashMap<&str, BTreeSet<&str>> = definitions
+        .iter()
+        .map(|def| {
+            let refs = def
+                .fields
+                .iter()
+                .filter_map(|f| schema.resolve_type(f.type_.as_deref()?, def))
+                .filter(|target| by_name.get(target.name.as_str()) == Some(target))
+                .map(|target| target.name.as_str())
+                .filter(|name| *name != def.name)
+                .collect();
+            (def.name.as_str(), refs)
+        })
+        .collect();
+
+    let mut remaining: BTreeSet<&str> = by_name.keys().copied().collect();
+    let mut ordered = Vec::with_capacity(definitions.len());
+
+    while !remaining.is_empty() {
+        let next = remaining
+            .iter()
+            .copied()
+            .find(|name| deps[name].is_disjoint(&remaining))
+            .or_else(|| remaining.iter().next().copied())
+            .unwrap();
+        remaining.remove(next);
+        ordered.push(by_name[next]);
+    }
+
+    ordered
+}
+
+/// Schema comment text as it should follow `///` (with a separating space)
+fn doc_text(comment: &str) -> String {
+    if comment.is_empty() {
+        String::new()
+    } else {
+        format!(" {}", comment)
+    }
+}
+
+/// Emit schema comments as Rust doc comments
+fn push_doc_comments(comments: &[String], indent: &str, lines: &mut Vec<String>) {
+    for comment in comments {
+        lines.push(format!("{}///{}", indent, doc_text(comment)));
+    }
+}
+
+/// Emit a field's `@since` version as a doc note, separated from any doc
+/// lines already in `lines`
+fn push_since_note(field: &Field, indent: &str, lines: &mut Vec<String>) {
+    if let Some(ref since) = field.since {
+        if !lines.is_empty() {
+            lines.push(format!("{}///", indent));
+        }
+        lines.push(format!("{}/// Since: {}", indent, since));
+    }
+}
+
+/// Entry point: generate Rust code from a Schema, with the options from its
+/// `options` block (invalid ones are ignored here; verification reports them)
 pub fn compile_schema_to_rust(schema: &Schema) -> String {
-    let package = schema.package.clone();
+    let options = GenOptions::from_schema(schema).unwrap_or_default();
+    compile_schema_to_rust_with_options(schema, &options)
+}
+
+/// Generate Rust code from a Schema with the given options, first checking
+/// that every field has a Rust representation. Verified schemas always pass;
+/// this catches unverified ones that would otherwise produce code that
+/// doesn't compile.
+pub fn try_compile_schema_to_rust_with_options(
+    schema: &Schema,
+    options: &GenOptions,
+) -> Result<String, TcsError> {
+    let resolved = schema.resolve_aliases();
+    let schema = &resolved;
+    let data_types = schema
+        .definitions
+        .iter()
+        .filter(|d| d.kind != DefinitionKind::Enum);
+    for definition in data_types {
+        for field in &definition.fields {
+            let msg = match field.type_.as_deref() {
+                None | Some("") => "Field has no type".to_string(),
+                Some(ty) if field.array_size.is_some() && !field.is_fixed_byte_array() => {
+                    format!("Fixed-size arrays of {} are not supported", quote(ty))
+                }
+                Some(_) => continue,
+            };
+            return Err(codegen_error(definition, Some(field), &msg));
+        }
+    }
+    Ok(compile_schema_to_rust_with_options(schema, options))
+}
+
+/// Generate Rust code from a Schema with the given options.
+///
+/// Without a `package`, types are emitted at the top level of the file, which
+/// is itself the module. Each package's types are wrapped in a `pub mod` named
+/// after it instead. Fields declared with a type alias get the aliased type.
+pub fn compile_schema_to_rust_with_options(schema: &Schema, options: &GenOptions) -> String {
+    let resolved = schema.resolve_aliases();
+    let schema = &resolved;
     let mut rust_code: Vec<String> = Vec::new();
 
-    // Header
-    rust_code.push("// This file was generated by the TCS compiler.".to_string());
-    rust_code.push("// Do not edit manually.".to_string());
-    rust_code.push("".to_string());
+    if options.file_header {
+        // The schema's own header (e.g. a license) comes first, verbatim
+        if let Some(ref header) = schema.header {
+            for line in header.split('\n') {
+                rust_code.push(format!("// {}", line).trim_end().to_string());
+            }
+            rust_code.push("".to_string());
+        }
+
+        rust_code.push("// This file was generated by the TCS compiler.".to_string());
+        rust_code.push("// Do not edit manually.".to_string());
+        rust_code.push("".to_string());
+    }
+
+    // Group definitions by package, in order of first appearance. Definitions
+    // built without a package of their own fall back to the schema's.
+    let mut groups: Vec<(Option<&str>, Vec<&Definition>)> = Vec::new();
+    for definition in &schema.definitions {
+        let package = definition.package.as_deref().or(schema.package.as_deref());
+        match groups.iter_mut().find(|(p, _)| *p == package) {
+            Some((_, defs)) => defs.push(definition),
+            None => groups.push((package, vec![definition])),
+        }
+    }
+    if groups.is_empty() {
+        groups.push((schema.package.as_deref(), Vec::new()));
+    }
+    if options.sort_definitions {
+        groups.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+
+    let ctx = GenContext::new(schema, options);
+
+    // Generated code refers to deprecated types itself (in derives, impls and
+    // other types' fields), so only uses outside the module should warn
+    let mut lints = options.allowed_lints.clone();
+    if schema.definitions.iter().any(|d| d.is_deprecated) {
+        lints.push("deprecated".to_string());
+    }
+    let allows = if lints.is_empty() {
+        None
+    } else {
+        Some(format!("allow({})", lints.join(", ")))
+    };
+
+    // Without a package there's no module of our own to put the lints on.
+    // The output may be `include!`d or spliced into another file, where an
+    // inner attribute isn't allowed, so each item gets an outer one instead.
+    let mut item_allows = match groups[0].0 {
+        None => allows.as_ref().map(|allows| format!("#[{}]", allows)),
+        Some(_) => None,
+    };
 
-    // If there's a package, wrap everything in a module
-    if let Some(ref name) = package {
-        rust_code.push(format!("pub mod {} {{", to_snake_case(name)));
+    if let Some(ref module) = options.rust_module {
+        if options.doc_stubs {
+            rust_code.push("/// Types generated from a TCS schema".to_string());
+        }
+        // Unless each package module gets the lints itself
+        if let Some(attr) = item_allows.take() {
+            rust_code.push(attr);
+        }
+        rust_code.push(format!("pub mod {} {{", module));
         rust_code.push("".to_string());
     }
 
-    // Imports
-    rust_code.push("use wincode::io::Writer;".to_string());
-    rust_code.push("use wincode_derive::{SchemaRead, SchemaWrite};".to_string());
-    rust_code.push("".to_string());
+    if let Some(ref source) = options.embed_source {
+        rust_code.push("/// The schema this file was generated from".to_string());
+        // Outside any module of ours
+        if let (Some(ref allows), None) = (&allows, &options.rust_module) {
+            rust_code.push(format!("#[{}]", allows));
+        }
+        rust_code.push(format!(
+            "pub const SCHEMA_SOURCE: &str = {};",
+            raw_string_literal(source)
+        ));
+        rust_code.push("".to_string());
+    }
 
-    // Generate code for each definition
-    for definition in &schema.definitions {
-        match definition.kind {
-            DefinitionKind::Enum => {
-                rust_code.push(generate_enum(definition));
+    for (package, definitions) in groups {
+        // If there's a package, wrap its definitions in a module
+        if let Some(name) = package {
+            if options.doc_stubs {
+                rust_code.push(format!("/// Types of the `{}` package", name));
             }
-            DefinitionKind::Struct => {
-                rust_code.push(generate_struct(definition, false));
+            if let Some(ref allows) = allows {
+                rust_code.push(format!("#[{}]", allows));
+            }
+            rust_code.push(format!("pub mod {} {{", to_snake_case(name)));
+            rust_code.push("".to_string());
+        }
+
+        // Imports (an empty schema gets none, so they aren't reported as unused)
+        if !definitions.is_empty() {
+            let wincode = &options.wincode_path;
+            rust_code.push(format!("use {}::io::Writer;", wincode));
+            // Unless every definition has hand-written impls
+            if definitions.iter().any(|d| uses_schema_derive(d, wincode)) {
+                rust_code.push("use wincode_derive::{SchemaRead, SchemaWrite};".to_string());
             }
-            DefinitionKind::Message => {
-                rust_code.push(generate_struct(definition, true));
+            if options.no_std {
+                rust_code.push("#[allow(unused_imports)]".to_string());
+                rust_code.push("use alloc::{string::String, vec::Vec};".to_string());
             }
+            rust_code.push("".to_string());
+        }
+
+        let with_allows = |code: String| match item_allows {
+            Some(ref attr) => with_attribute(&code, attr),
+            None => code,
+        };
+
+        let has_byte_arrays = definitions
+            .iter()
+            .filter(|d| d.kind != DefinitionKind::Enum)
+            .any(|d| d.fields.iter().any(|f| f.is_fixed_byte_array()));
+        if options.derive_serde && has_byte_arrays {
+            rust_code.push(with_allows(serde_hex_module(options.no_std)));
+            rust_code.push("".to_string());
+        }
+        let has_hex_debug = definitions
+            .iter()
+            .filter(|d| d.kind != DefinitionKind::Enum)
+            .any(|d| d.fields.iter().any(is_byte_array));
+        if options.hex_debug && has_hex_debug {
+            rust_code.push(with_allows(hex_debug_helper(ctx.core)));
+            rust_code.push("".to_string());
+        }
+        let has_validation = definitions
+            .iter()
+            .any(|d| d.fields.iter().any(|f| f.validate.is_some()));
+        if has_validation {
+            rust_code.push(with_allows(validation_error_type(options.no_std)));
+            rust_code.push("".to_string());
+        }
+        let has_integer_fields = definitions
+            .iter()
+            .filter(|d| d.kind != DefinitionKind::Enum)
+            .any(|d| d.fields.iter().any(is_integer_field));
+        if options.checked_setters && has_integer_fields {
+            rust_code.push(with_allows(range_error_type(options.no_std)));
+            rust_code.push("".to_string());
+        }
+
+        // Nested definitions are generated in a module after their parent
+        let mut children: HashMap<&str, Vec<&Definition>> = HashMap::new();
+        for definition in &definitions {
+            if let Some(ref parent) = definition.parent {
+                children.entry(parent).or_default().push(definition);
+            }
+        }
+        let top_level = definitions.into_iter().filter(|d| d.parent.is_none());
+
+        // Generate code for each definition
+        let generated = ordered(top_level.collect(), options, &ctx)
+            .into_iter()
+            .map(|definition| {
+                let allows = item_allows.as_deref();
+                generate_definition(definition, &children, allows, options, &ctx)
+            });
+
+        if options.sectioned_output {
+            let (mut types, mut impls, mut consts) = (Vec::new(), Vec::new(), Vec::new());
+            for code in generated {
+                types.push(code.type_def);
+                types.extend(code.module);
+                impls.extend(code.impls);
+                consts.extend(code.consts);
+            }
+            for (title, items) in [("Types", types), ("Impls", impls), ("Constants", consts)] {
+                if items.is_empty() {
+                    continue;
+                }
+                rust_code.push(format!("// ---- {} ----", title));
+                rust_code.push("".to_string());
+                for item in items {
+                    rust_code.push(item);
+                    rust_code.push("".to_string());
+                }
+            }
+        } else {
+            for code in generated {
+                rust_code.push(code.join());
+                rust_code.push("".to_string());
+            }
+        }
+
+        // Close package module if needed
+        if package.is_some() {
+            rust_code.push("}".to_string());
         }
-        rust_code.push("".to_string());
     }
 
-    // Close package module if needed
-    if package.is_some() {
+    if options.rust_module.is_some() {
         rust_code.push("}".to_string());
     }
 
     rust_code.join("\n")
 }
 
+/// `definitions` in the order to generate them
+fn ordered<'a>(
+    definitions: Vec<&'a Definition>,
+    options: &GenOptions,
+    ctx: &GenContext,
+) -> Vec<&'a Definition> {
+    if options.sort_definitions {
+        dependency_order(definitions, ctx.schema)
+    } else {
+        definitions
+    }
+}
+
+/// Generate one definition, and a module holding the ones nested in it. Each
+/// generated item gets the `allows` attribute, if any.
+fn generate_definition(
+    definition: &Definition,
+    children: &HashMap<&str, Vec<&Definition>>,
+    allows: Option<&str>,
+    options: &GenOptions,
+    ctx: &GenContext,
+) -> DefinitionCode {
+    let mut code = match definition.kind {
+        DefinitionKind::Enum if definition.is_flags => generate_flags(definition, ctx),
+        DefinitionKind::Enum => generate_enum(definition, ctx),
+        DefinitionKind::Struct => generate_struct(definition, false, ctx),
+        DefinitionKind::Message => generate_struct(definition, true, ctx),
+    };
+
+    let cfg = definition
+        .feature
+        .as_ref()
+        .map(|feature| format!("#[cfg(feature = \"{}\")]", feature));
+
+    if let Some(nested) = children.get(definition.name.as_str()) {
+        let mut lines = Vec::new();
+        if ctx.doc_stubs {
+            lines.push(format!("/// Types nested in `{}`", definition.name));
+        }
+        lines.extend(cfg.clone());
+        lines.push(format!(
+            "pub mod {} {{",
+            ctx.nested_module(&definition.name)
+        ));
+        lines.push("use super::*;".to_string());
+        lines.push("".to_string());
+        for nested_def in ordered(nested.clone(), options, ctx) {
+            lines.push(generate_definition(nested_def, children, None, options, ctx).join());
+            lines.push("".to_string());
+        }
+        lines.push("}".to_string());
+        code.module = Some(lines.join("\n"));
+    }
+
+    if let Some(attr) = allows {
+        code.add_attribute(attr);
+    }
+
+    // A gated definition gets the cfg on every item generated for it
+    if let Some(ref cfg) = cfg {
+        code.type_def = with_attribute(&code.type_def, cfg);
+        for item in code.consts.iter_mut().chain(code.impls.iter_mut()) {
+            *item = with_attribute(item, cfg);
+        }
+    }
+
+    code
+}
+
+/// Add `attr` to each top-level item in `code`, after its doc comments. Items
+/// start at column 0 after a blank line (or at the start of `code`).
+fn with_attribute(code: &str, attr: &str) -> String {
+    let mut lines = Vec::new();
+    let mut at_item_start = true;
+    for line in code.split('\n') {
+        if at_item_start && !line.is_empty() && !line.starts_with("///") {
+            // Indented lines after a blank line are inside an item
+            if !line.starts_with(' ') {
+                lines.push(attr.to_string());
+            }
+            at_item_start = false;
+        }
+        lines.push(line.to_string());
+        if line.is_empty() {
+            at_item_start = true;
+        }
+    }
+    lines.join("\n")
+}
+
+/// A raw string literal for `text`, with enough `#`s that nothing in it can
+/// end the literal early
+fn raw_string_literal(text: &str) -> String {
+    let mut hashes = 0;
+    for (i, _) in text.match_indices('"') {
+        let run = text[i + 1..].chars().take_while(|&c| c == '#').count();
+        hashes = hashes.max(run + 1);
+    }
+    let hashes = "#".repeat(hashes);
+    format!("r{hashes}\"{text}\"{hashes}")
+}
+
+/// Module of serde `with` helpers rendering fixed-size byte arrays (plain or
+/// optional) as hex strings
+fn serde_hex_module(no_std: bool) -> String {
+    let lines = [
+        "/// Serde helpers rendering fixed-size byte arrays as hex strings",
+        "#[cfg(feature = \"serde\")]",
+        "mod serde_hex {",
+        "    pub fn serialize<S: serde::Serializer, const N: usize>(",
+        "        bytes: &[u8; N],",
+        "        serializer: S,",
+        "    ) -> Result<S::Ok, S::Error> {",
+        "        serializer.serialize_str(&hex::encode(bytes))",
+        "    }",
+        "",
+        "    pub fn deserialize<'de, D: serde::Deserializer<'de>, const N: usize>(",
+        "        deserializer: D,",
+        "    ) -> Result<[u8; N], D::Error> {",
+        "        let mut bytes = [0u8; N];",
+        "        let hex: String = serde::Deserialize::deserialize(deserializer)?;",
+        "        hex::decode_to_slice(hex, &mut bytes).map_err(serde::de::Error::custom)?;",
+        "        Ok(bytes)",
+        "    }",
+        "",
+        "    pub mod option {",
+        "        pub fn serialize<S: serde::Serializer, const N: usize>(",
+        "            bytes: &Option<[u8; N]>,",
+        "            serializer: S,",
+        "        ) -> Result<S::Ok, S::Error> {",
+        "            match bytes {",
+        "                Some(bytes) => serializer.serialize_some(&hex::encode(bytes)),",
+        "                None => serializer.serialize_none(),",
+        "            }",
+        "        }",
+        "",
+        "        pub fn deserialize<'de, D: serde::Deserializer<'de>, const N: usize>(",
+        "            deserializer: D,",
+        "        ) -> Result<Option<[u8; N]>, D::Error> {",
+        "            let hex: Option<String> = serde::Deserialize::deserialize(deserializer)?;",
+        "            hex.map(|hex| {",
+        "                let mut bytes = [0u8; N];",
+        "                hex::decode_to_slice(hex, &mut bytes).map_err(serde::de::Error::custom)?;",
+        "                Ok(bytes)",
+        "            })",
+        "            .transpose()",
+        "        }",
+        "    }",
+        "}",
+    ];
+    let code = lines.join("\n");
+    // `String` is only in the prelude with std
+    if no_std {
+        code.replace(": String", ": alloc::string::String")
+            .replace("<String>", "<alloc::string::String>")
+    } else {
+        code
+    }
+}
+
+/// Wrapper printing a byte slice as a hex string in `Debug` output, used by
+/// the hand-written `Debug` impls of the `hex_debug` option
+fn hex_debug_helper(core: &str) -> String {
+    [
+        "/// Prints bytes as a hex string in `Debug` output".to_string(),
+        "struct HexDebug<'a>(&'a [u8]);".to_string(),
+        "".to_string(),
+        format!("impl {}::fmt::Debug for HexDebug<'_> {{", core),
+        format!(
+            "    fn fmt(&self, f: &mut {0}::fmt::Formatter<'_>) -> {0}::fmt::Result {{",
+            core
+        ),
+        "        f.write_str(\"0x\")?;".to_string(),
+        "        for byte in self.0 {".to_string(),
+        "            write!(f, \"{:02x}\", byte)?;".to_string(),
+        "        }".to_string(),
+        "        Ok(())".to_string(),
+        "    }".to_string(),
+        "}".to_string(),
+    ]
+    .join("\n")
+}
+
+/// Error type returned by the generated `validate` methods
+fn validation_error_type(no_std: bool) -> String {
+    let core = if no_std { "core" } else { "std" };
+    let mut lines = vec![
+        "/// A field whose value is outside the bounds of its `@validate` annotation".to_string(),
+        "#[derive(Debug, Clone, Copy, PartialEq, Eq)]".to_string(),
+        "pub struct ValidationError {".to_string(),
+        "    /// Name of the field in the schema".to_string(),
+        "    pub field: &'static str,".to_string(),
+        "    /// The bound the value breaks: `min`, `max`, `len_min` or `len_max`".to_string(),
+        "    pub bound: &'static str,".to_string(),
+        "}".to_string(),
+        "".to_string(),
+        format!("impl {}::fmt::Display for ValidationError {{", core),
+        format!(
+            "    fn fmt(&self, f: &mut {0}::fmt::Formatter<'_>) -> {0}::fmt::Result {{",
+            core
+        ),
+        "        write!(f, \"{} is outside its {} bound\", self.field, self.bound)".to_string(),
+        "    }".to_string(),
+        "}".to_string(),
+    ];
+    // `core::error::Error` is too recent to rely on
+    if !no_std {
+        lines.push("".to_string());
+        lines.push("impl std::error::Error for ValidationError {}".to_string());
+    }
+    lines.join("\n")
+}
+
+/// The error type returned by the `checked_setters` methods
+fn range_error_type(no_std: bool) -> String {
+    let core = if no_std { "core" } else { "std" };
+    let mut lines = vec![
+        "/// A value too large or too small for the integer field it was set on".to_string(),
+        "#[derive(Debug, Clone, Copy, PartialEq, Eq)]".to_string(),
+        "pub struct RangeError {".to_string(),
+        "    /// Name of the field in the schema".to_string(),
+        "    pub field: &'static str,".to_string(),
+        "    /// The field's type in the schema, e.g. `uint8`".to_string(),
+        "    pub type_name: &'static str,".to_string(),
+        "}".to_string(),
+        "".to_string(),
+        format!("impl {}::fmt::Display for RangeError {{", core),
+        format!(
+            "    fn fmt(&self, f: &mut {0}::fmt::Formatter<'_>) -> {0}::fmt::Result {{",
+            core
+        ),
+        "        write!(f, \"value out of range for {} ({})\", self.field, self.type_name)"
+            .to_string(),
+        "    }".to_string(),
+        "}".to_string(),
+    ];
+    if !no_std {
+        lines.push("".to_string());
+        lines.push("impl std::error::Error for RangeError {}".to_string());
+    }
+    lines.join("\n")
+}
+
+/// Check if a field is a single value of a native integer type
+fn is_integer_field(field: &Field) -> bool {
+    !field.is_array && field.type_.as_deref().and_then(integer_range).is_some()
+}
+
+/// Check if a field is a fixed or variable-size byte array (`byte[32]`, `byte[]`)
+fn is_byte_array(field: &Field) -> bool {
+    field.is_array && matches!(field.type_.as_deref(), Some("byte" | "uint8"))
+}
+
 /// Generate a Rust enum with wincode derives
-fn generate_enum(definition: &Definition) -> String {
+fn generate_enum(definition: &Definition, ctx: &GenContext) -> DefinitionCode {
     let enum_name = to_pascal_case(&definition.name);
     let mut lines = Vec::new();
 
     // Derives and attributes
-    lines.push(
-        "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, SchemaRead, SchemaWrite)]"
-            .to_string(),
-    );
+    push_doc_comments(&definition.comments, "", &mut lines);
+    ctx.push_doc_stub(&mut lines, "", format!("The `{}` enum.", definition.name));
+    if definition.is_deprecated {
+        lines.push("#[deprecated]".to_string());
+    }
+    let derive_schema = uses_schema_derive(definition, ctx.wincode);
+    lines.push(format!(
+        "#[derive(Debug, Clone, Copy, PartialEq, Eq, {}Hash, Default{})]",
+        ctx.ord_derives(definition),
+        if derive_schema {
+            ", SchemaRead, SchemaWrite"
+        } else {
+            ""
+        }
+    ));
+    if ctx.arbitrary {
+        lines.push(ARBITRARY_DERIVE.to_string());
+    }
+    if ctx.serde {
+        lines.push(SERDE_DERIVE.to_string());
+    }
     lines.push("#[repr(u32)]".to_string());
     lines.push(format!("pub enum {} {{", enum_name));
 
-    // Variants
-    for (i, field) in definition.fields.iter().enumerate() {
-        let var_name = escape_rust_keyword(&to_pascal_case(&field.name));
+    // Variants (aliases become associated consts below). Values can only be
+    // left implicit when they match the ones Rust would assign.
+    let variants = definition.fields.iter().filter(|f| f.alias_of.is_none());
+    let explicit = ctx.explicit_discriminants
+        || variants
+            .clone()
+            .enumerate()
+            .any(|(i, f)| f.field_id != i as i32);
+    for (i, field) in variants.enumerate() {
+        let var_name = ctx.ident(&to_pascal_case(&field.name));
         let value = field.field_id;
 
+        let mut docs = Vec::new();
+        push_doc_comments(&field.comments, "    ", &mut docs);
+        push_since_note(field, "    ", &mut docs);
+        ctx.push_doc_stub(&mut docs, "    ", format!("The `{}` variant.", field.name));
         let mut variant_line = String::new();
+        for doc in docs {
+            variant_line.push_str(&format!("{}\n", doc));
+        }
         if field.is_deprecated {
             variant_line.push_str("    #[deprecated]\n");
         }
+        // Only the serde name changes; the wire format uses the discriminant
+        if ctx.serde && ctx.serde_enum_names {
+            variant_line.push_str(&format!(
+                "    #[cfg_attr(feature = \"serde\", serde(rename = \"{}\"))]\n",
+                field.name
+            ));
+        }
         // First variant is the default
         if i == 0 {
             variant_line.push_str("    #[default]\n");
         }
-        variant_line.push_str(&format!("    {} = {},", var_name, value));
+        if explicit {
+            variant_line.push_str(&format!("    {} = {},", var_name, value));
+        } else {
+            variant_line.push_str(&format!("    {},", var_name));
+        }
         lines.push(variant_line);
     }
 
     lines.push("}".to_string());
-    lines.join("\n")
+
+    let mut consts = Vec::new();
+    consts.push(format!("impl {} {{", enum_name));
+    consts.push("    /// Number of variants in the schema".to_string());
+    consts.push(format!(
+        "    pub const FIELD_COUNT: usize = {};",
+        ctx.field_count(definition)
+    ));
+
+    // Aliases: NAME = OTHER; -> pub const NAME: Self = Self::Other;
+    for field in definition.fields.iter().filter(|f| f.alias_of.is_some()) {
+        let target = field.alias_of.as_deref().unwrap_or_default();
+        let mut docs = Vec::new();
+        push_doc_comments(&field.comments, "    ", &mut docs);
+        ctx.push_doc_stub(&mut docs, "    ", format!("Alias of `{}`.", target));
+        consts.extend(docs);
+        consts.push(format!(
+            "    pub const {}: Self = Self::{};",
+            to_snake_case(&field.name).to_uppercase(),
+            ctx.ident(&to_pascal_case(target))
+        ));
+    }
+
+    let variants: Vec<String> = definition
+        .fields
+        .iter()
+        .filter(|f| f.alias_of.is_none())
+        .map(|f| format!("Self::{}", ctx.ident(&to_pascal_case(&f.name))))
+        .collect();
+    consts.push("".to_string());
+    consts.push("    /// Every variant, in schema order".to_string());
+    consts.push("    pub const fn all() -> &'static [Self] {".to_string());
+    consts.push(format!("        &[{}]", variants.join(", ")));
+    consts.push("    }".to_string());
+    consts.push("}".to_string());
+
+    let mut impls = Vec::new();
+    if !derive_schema {
+        impls.push(generate_enum_schema_impls(definition, &enum_name, ctx));
+    }
+
+    DefinitionCode {
+        type_def: lines.join("\n"),
+        consts: vec![consts.join("\n")],
+        impls,
+        module: None,
+    }
+}
+
+/// Generate a bit set type for a `[flags]` enum: a `u32` newtype with one
+/// associated const per variant and bitwise operators, encoded as its raw bits
+fn generate_flags(definition: &Definition, ctx: &GenContext) -> DefinitionCode {
+    let name = to_pascal_case(&definition.name);
+    let mut lines = Vec::new();
+
+    push_doc_comments(&definition.comments, "", &mut lines);
+    ctx.push_doc_stub(&mut lines, "", format!("The `{}` flags.", definition.name));
+    if definition.is_deprecated {
+        lines.push("#[deprecated]".to_string());
+    }
+    let derive_schema = uses_schema_derive(definition, ctx.wincode);
+    lines.push(format!(
+        "#[derive(Debug, Clone, Copy, PartialEq, Eq, {}Hash, Default{})]",
+        ctx.ord_derives(definition),
+        if derive_schema {
+            ", SchemaRead, SchemaWrite"
+        } else {
+            ""
+        }
+    ));
+    if ctx.arbitrary {
+        lines.push(ARBITRARY_DERIVE.to_string());
+    }
+    if ctx.serde {
+        lines.push(SERDE_DERIVE.to_string());
+    }
+    lines.push("#[repr(transparent)]".to_string());
+    lines.push(format!("pub struct {}(pub u32);", name));
+
+    let mut consts = Vec::new();
+    for field in &definition.fields {
+        let const_name = to_snake_case(&field.name).to_uppercase();
+        let mut docs = Vec::new();
+        push_doc_comments(&field.comments, "    ", &mut docs);
+        push_since_note(field, "    ", &mut docs);
+        ctx.push_doc_stub(&mut docs, "    ", format!("The `{}` flag.", field.name));
+        consts.extend(docs);
+        match field.alias_of {
+            Some(ref target) => consts.push(format!(
+                "    pub const {}: Self = Self::{};",
+                const_name,
+                to_snake_case(target).to_uppercase()
+            )),
+            None => consts.push(format!(
+                "    pub const {}: Self = Self({});",
+                const_name, field.field_id
+            )),
+        }
+    }
+    consts.push("".to_string());
+    consts.push("    /// Number of flags in the schema".to_string());
+    consts.push(format!(
+        "    pub const FIELD_COUNT: usize = {};",
+        ctx.field_count(definition)
+    ));
+
+    let methods = [
+        "    /// The set with no flags",
+        "    pub const fn empty() -> Self {",
+        "        Self(0)",
+        "    }",
+        "",
+        "    /// The raw bits",
+        "    pub const fn bits(self) -> u32 {",
+        "        self.0",
+        "    }",
+        "",
+        "    /// Whether no flags are set",
+        "    pub const fn is_empty(self) -> bool {",
+        "        self.0 == 0",
+        "    }",
+        "",
+        "    /// Whether every flag in `other` is also set in `self`",
+        "    pub const fn contains(self, other: Self) -> bool {",
+        "        self.0 & other.0 == other.0",
+        "    }",
+    ];
+
+    let mut code = DefinitionCode {
+        type_def: lines.join("\n"),
+        consts: Vec::new(),
+        impls: Vec::new(),
+        module: None,
+    };
+    if ctx.sectioned {
+        code.consts
+            .push(format!("impl {} {{\n{}\n}}", name, consts.join("\n")));
+        code.impls
+            .push(format!("impl {} {{\n{}\n}}", name, methods.join("\n")));
+    } else {
+        code.impls.push(format!(
+            "impl {} {{\n{}\n\n{}\n}}",
+            name,
+            consts.join("\n"),
+            methods.join("\n")
+        ));
+    }
+
+    // Bitwise operators
+    for (trait_name, method, op) in [("BitOr", "bitor", "|"), ("BitAnd", "bitand", "&")] {
+        let mut lines = Vec::new();
+        lines.push(format!(
+            "impl {}::ops::{} for {} {{",
+            ctx.core, trait_name, name
+        ));
+        lines.push("    type Output = Self;".to_string());
+        lines.push("".to_string());
+        lines.push(format!("    fn {}(self, rhs: Self) -> Self {{", method));
+        lines.push(format!("        Self(self.0 {} rhs.0)", op));
+        lines.push("    }".to_string());
+        lines.push("}".to_string());
+        code.impls.push(lines.join("\n"));
+
+        let mut lines = Vec::new();
+        lines.push(format!(
+            "impl {}::ops::{}Assign for {} {{",
+            ctx.core, trait_name, name
+        ));
+        lines.push(format!("    fn {}_assign(&mut self, rhs: Self) {{", method));
+        lines.push(format!("        self.0 {}= rhs.0;", op));
+        lines.push("    }".to_string());
+        lines.push("}".to_string());
+        code.impls.push(lines.join("\n"));
+    }
+
+    if !derive_schema {
+        code.impls.push(generate_flags_schema_impls(&name, ctx));
+    }
+
+    code
 }
 
 /// Generate a Rust struct with wincode derives
-fn generate_struct(definition: &Definition, is_message: bool) -> String {
+fn generate_struct(definition: &Definition, is_message: bool, ctx: &GenContext) -> DefinitionCode {
     let struct_name = to_pascal_case(&definition.name);
     let mut lines = Vec::new();
 
+    // std only implements Default for arrays up to 32 elements, so structs
+    // holding larger fixed arrays get a hand-written Default impl instead
+    let manual_default = (is_message && ctx.explicit_default)
+        || definition.fields.iter().any(|f| {
+            !is_optional(f, is_message) && f.array_size.is_some_and(|n| n > MAX_DERIVE_ARRAY_LEN)
+        });
+
+    let manual_schema = !uses_schema_derive(definition, ctx.wincode);
+
+    // With `[key]` fields, equality and hashing only consider those fields
+    let key_fields: Vec<&Field> = definition.fields.iter().filter(|f| f.is_key).collect();
+
     // Derives and attributes
-    lines.push("#[derive(Debug, Clone, PartialEq, Default, SchemaRead, SchemaWrite)]".to_string());
+    push_doc_comments(&definition.comments, "", &mut lines);
+    let kind = if is_message { "message" } else { "struct" };
+    ctx.push_doc_stub(
+        &mut lines,
+        "",
+        format!("The `{}` {}.", definition.name, kind),
+    );
+    if definition.is_deprecated {
+        lines.push("#[deprecated]".to_string());
+    }
+    // Without `[key]` fields, deprecated fields may still be left out of equality
+    let eq_fields: Vec<&Field> = definition
+        .fields
+        .iter()
+        .filter(|f| !f.is_deprecated)
+        .collect();
+    let manual_eq = key_fields.is_empty()
+        && ctx.eq_ignore_deprecated
+        && eq_fields.len() < definition.fields.len();
+
+    // With `hex_debug`, byte arrays are printed as hex by a hand-written impl
+    let manual_debug = ctx.hex_debug && definition.fields.iter().any(is_byte_array);
+
+    let mut derives = Vec::new();
+    if !manual_debug {
+        derives.push("Debug");
+    }
+    if !ctx.no_clone {
+        derives.push("Clone");
+    }
+    if key_fields.is_empty() && !manual_eq {
+        derives.push("PartialEq");
+    }
+    if definition.is_ord {
+        derives.extend(["Eq", "PartialOrd", "Ord"]);
+    }
+    if !manual_default {
+        derives.push("Default");
+    }
+    if !manual_schema {
+        derives.extend(["SchemaRead", "SchemaWrite"]);
+    }
+    lines.push(format!("#[derive({})]", derives.join(", ")));
+    if ctx.arbitrary {
+        lines.push(ARBITRARY_DERIVE.to_string());
+    }
+    if ctx.serde {
+        lines.push(SERDE_DERIVE.to_string());
+    }
 
-    // Use repr(C) for structs to ensure consistent memory layout
-    if !is_message {
+    // Use repr(C) for structs to ensure consistent memory layout; a newtype
+    // has the layout of its only field
+    let newtype = ctx.newtypes && !is_message && definition.fields.len() == 1;
+    if newtype {
+        lines.push("#[repr(transparent)]".to_string());
+    } else if !is_message {
         lines.push("#[repr(C)]".to_string());
     }
 
@@ -150,170 +1444,2612 @@ fn generate_struct(definition: &Definition, is_message: bool) -> String {
 
     // Fields
     for field in &definition.fields {
-        let rust_name = escape_rust_keyword(&to_snake_case(&field.name));
-        let field_type = map_type(field, is_message);
+        let rust_name = ctx.ident(&to_snake_case(&field.name));
+        let field_type = map_type(field, is_message, ctx, definition);
 
         let mut field_lines = Vec::new();
+        push_doc_comments(&field.comments, "    ", &mut field_lines);
+        if is_message && field.presence == Presence::Required {
+            if !field.comments.is_empty() {
+                field_lines.push("    ///".to_string());
+            }
+            field_lines.push("    /// Required: always present on the wire.".to_string());
+        }
+        push_since_note(field, "    ", &mut field_lines);
+        ctx.push_doc_stub(
+            &mut field_lines,
+            "    ",
+            format!("The `{}` field.", field.name),
+        );
 
         if field.is_deprecated {
             field_lines.push("    #[deprecated]".to_string());
         }
 
+        // bytes::Bytes doesn't implement Arbitrary; build it from a Vec<u8>
+        if ctx.arbitrary && field.type_.as_deref() == Some("bytes") {
+            let with = if is_optional(field, is_message) {
+                "u.arbitrary::<Option<Vec<u8>>>().map(|v| v.map(bytes::Bytes::from))"
+            } else {
+                "u.arbitrary::<Vec<u8>>().map(bytes::Bytes::from)"
+            };
+            field_lines.push(format!(
+                "    #[cfg_attr(feature = \"arbitrary\", arbitrary(with = |u: &mut arbitrary::Unstructured| {}))]",
+                with
+            ));
+        }
+
+        // serde only implements arrays up to 32 elements, and numbers are
+        // unreadable anyway, so go through the hex helper module
+        if ctx.serde && field.is_fixed_byte_array() {
+            let with = if is_optional(field, is_message) {
+                "serde_hex::option"
+            } else {
+                "serde_hex"
+            };
+            field_lines.push(format!(
+                "    #[cfg_attr(feature = \"serde\", serde(with = \"{}\"))]",
+                with
+            ));
+        }
+
         field_lines.push(format!("    pub {}: {},", rust_name, field_type));
         lines.push(field_lines.join("\n"));
     }
 
-    lines.push("}".to_string());
+    if definition.fields.is_empty() {
+        if let Some(header) = lines.last_mut() {
+            header.push('}');
+        }
+    } else {
+        lines.push("}".to_string());
+    }
+
+    let mut code = DefinitionCode {
+        type_def: lines.join("\n"),
+        consts: Vec::new(),
+        impls: Vec::new(),
+        module: None,
+    };
+
+    let fixed_size = ctx.fixed_sizes.get(&definition.qualified_name()).copied();
+    let view = fixed_size
+        .filter(|_| ctx.views)
+        .map(|size| generate_view(definition, &struct_name, size, ctx));
+    if let Some((ref view_def, _)) = view {
+        code.type_def.push_str("\n\n");
+        code.type_def.push_str(view_def);
+    }
+
+    if manual_debug {
+        code.impls.push(generate_debug_impl(
+            definition,
+            &struct_name,
+            is_message,
+            ctx,
+        ));
+    }
+
+    if manual_default {
+        code.impls.push(generate_default_impl(
+            definition,
+            &struct_name,
+            is_message,
+            ctx,
+        ));
+    }
+
+    if manual_schema {
+        code.impls.push(generate_schema_impls(
+            definition,
+            &struct_name,
+            is_message,
+            ctx,
+        ));
+    }
+
+    if !key_fields.is_empty() {
+        code.impls
+            .push(generate_key_impls(&key_fields, &struct_name, ctx));
+    }
+
+    if manual_eq {
+        code.impls
+            .push(generate_partial_eq_impl(&eq_fields, &struct_name, ctx));
+    }
+
+    if newtype {
+        code.impls.push(generate_newtype_impls(
+            &definition.fields[0],
+            definition,
+            &struct_name,
+            ctx,
+        ));
+    }
+
+    // Add helper methods for the struct, with FIELD_COUNT in its own impl
+    // block when output is sectioned
+    let field_count = ctx.field_count(definition);
+    let mut impl_block = if ctx.sectioned {
+        code.consts.push(format!(
+            "impl {} {{\n    /// Number of fields in the schema\n    pub const FIELD_COUNT: usize = {};\n}}",
+            struct_name, field_count
+        ));
+        generate_struct_impl(&struct_name, None, ctx.wincode)
+    } else {
+        generate_struct_impl(&struct_name, Some(field_count), ctx.wincode)
+    };
+
+    if ctx.visitable.contains(&definition.qualified_name()) {
+        impl_block.push_str("\n\n");
+        impl_block.push_str(&generate_visit_impl(
+            definition,
+            &struct_name,
+            is_message,
+            ctx,
+        ));
+    }
+
+    if is_message {
+        impl_block.push_str("\n\n");
+        impl_block.push_str(&generate_present_count_impl(definition, &struct_name, ctx));
+        impl_block.push_str("\n\n");
+        impl_block.push_str(&generate_field_name_impl(definition, &struct_name));
+    }
+
+    if definition.fields.iter().any(|f| f.validate.is_some()) {
+        impl_block.push_str("\n\n");
+        impl_block.push_str(&generate_validate_impl(
+            definition,
+            &struct_name,
+            is_message,
+            ctx,
+        ));
+    }
+
+    if ctx.checked_setters && definition.fields.iter().any(is_integer_field) {
+        impl_block.push_str("\n\n");
+        impl_block.push_str(&generate_checked_setters_impl(
+            definition,
+            &struct_name,
+            is_message,
+            ctx,
+        ));
+    }
+
+    impl_block.push_str("\n\n");
+    impl_block.push_str(&generate_canonical_bytes_impl(
+        definition,
+        &struct_name,
+        is_message,
+        ctx,
+    ));
+
+    if let Some(size) = fixed_size.filter(|_| ctx.to_array) {
+        impl_block.push_str("\n\n");
+        impl_block.push_str(&generate_to_array_impl(&struct_name, size, ctx));
+    }
+
+    code.impls.push(impl_block);
+    if ctx.serde {
+        code.impls.push(generate_json_value_impl(&struct_name));
+    }
+    if let Some((_, view_impl)) = view {
+        code.impls.push(view_impl);
+    }
+    code
+}
+
+/// Generate `SIZE` and `to_array` for a fixed-size struct, serializing into
+/// an array on the stack
+fn generate_to_array_impl(struct_name: &str, size: usize, ctx: &GenContext) -> String {
+    let wincode = ctx.wincode;
+    [
+        format!("impl {} {{", struct_name),
+        format!("    /// Serialized size of a `{}`", struct_name),
+        format!("    pub const SIZE: usize = {};", size),
+        "".to_string(),
+        "    /// Serialize this value into a fixed-size array, without allocating".to_string(),
+        "    pub fn to_array(&self) -> [u8; Self::SIZE] {".to_string(),
+        "        let mut out = [0u8; Self::SIZE];".to_string(),
+        "        let mut writer = out.as_mut_slice();".to_string(),
+        format!("        {wincode}::serialize_into(&mut writer, self)"),
+        "            .expect(\"serialization should not fail\");".to_string(),
+        "        out".to_string(),
+        "    }".to_string(),
+        "}".to_string(),
+    ]
+    .join("\n")
+}
+
+/// Generate `to_json_value` and `from_json_value`, converting through the
+/// serde derives. Gated on a `serde_json` feature alongside `serde`, and
+/// fallible both ways since serde_json numbers can't hold every 128-bit value.
+fn generate_json_value_impl(struct_name: &str) -> String {
+    [
+        "#[cfg(all(feature = \"serde\", feature = \"serde_json\"))]".to_string(),
+        format!("impl {} {{", struct_name),
+        "    /// Convert this value to a `serde_json::Value`".to_string(),
+        "    pub fn to_json_value(&self) -> Result<serde_json::Value, serde_json::Error> {"
+            .to_string(),
+        "        serde_json::to_value(self)".to_string(),
+        "    }".to_string(),
+        "".to_string(),
+        "    /// Read a value from a `serde_json::Value`".to_string(),
+        "    pub fn from_json_value(value: &serde_json::Value) -> Result<Self, serde_json::Error> {"
+            .to_string(),
+        "        <Self as serde::Deserialize>::deserialize(value)".to_string(),
+        "    }".to_string(),
+        "}".to_string(),
+    ]
+    .join("\n")
+}
+
+/// Generate a zero-copy view of a fixed-size struct: the view type, and an
+/// impl with one accessor per field reading it from its offset. Fields are
+/// serialized in declaration order with no padding.
+fn generate_view(
+    definition: &Definition,
+    struct_name: &str,
+    size: usize,
+    ctx: &GenContext,
+) -> (String, String) {
+    let view_name = format!("{}View", struct_name);
+    let type_def = [
+        format!(
+            "/// Zero-copy view of a serialized `{}`, reading fields in place",
+            struct_name
+        ),
+        "#[derive(Debug, Clone, Copy)]".to_string(),
+        format!("pub struct {}<'a>(&'a [u8]);", view_name),
+    ]
+    .join("\n");
+
+    let mut lines = Vec::new();
+    lines.push(format!("impl<'a> {}<'a> {{", view_name));
+    lines.push(format!("    /// Serialized size of a `{}`", struct_name));
+    lines.push(format!("    pub const SIZE: usize = {};", size));
+    lines.push("".to_string());
+    lines.push("    /// View the first `SIZE` bytes, or `None` if there are fewer".to_string());
+    lines.push("    pub fn new(bytes: &'a [u8]) -> Option<Self> {".to_string());
+    lines.push("        bytes.get(..Self::SIZE).map(Self)".to_string());
+    lines.push("    }".to_string());
+
+    let mut offset = 0;
+    for field in &definition.fields {
+        let len = fixed_size(field, definition, ctx.schema, &ctx.fixed_sizes).unwrap_or_default();
+        let range = format!("{}..{}", offset, offset + len);
+        let ty = map_type(field, false, ctx, definition);
+        let (ret, body) = match field.type_.as_deref().unwrap_or_default() {
+            // Byte arrays are borrowed rather than copied
+            name if name == "uint256" || field.is_fixed_byte_array() => (
+                format!("&'a {}", ty),
+                format!("self.0[{}].try_into().unwrap()", range),
+            ),
+            "bool" => (ty, format!("self.0[{}] != 0", offset)),
+            "byte" | "uint8" => (ty, format!("self.0[{}]", offset)),
+            _ if field.is_scalar() => {
+                let body = format!(
+                    "{}::from_le_bytes(self.0[{}].try_into().unwrap())",
+                    ty, range
+                );
+                (ty, body)
+            }
+            // Another fixed-size struct
+            _ => (
+                format!("{}View<'a>", ty),
+                format!("{}View::new(&self.0[{}]).unwrap()", ty, range),
+            ),
+        };
+
+        lines.push("".to_string());
+        let mut docs = Vec::new();
+        push_doc_comments(&field.comments, "    ", &mut docs);
+        ctx.push_doc_stub(&mut docs, "    ", format!("The `{}` field.", field.name));
+        lines.extend(docs);
+        lines.push(format!(
+            "    pub fn {}(&self) -> {} {{",
+            ctx.ident(&to_snake_case(&field.name)),
+            ret
+        ));
+        lines.push(format!("        {}", body));
+        lines.push("    }".to_string());
+        offset += len;
+    }
+
+    lines.push("}".to_string());
+    (type_def, lines.join("\n"))
+}
+
+/// Generate a `validate` method checking each field against the bounds of its
+/// `@validate` annotation. Bounds every value of the type meets are left out.
+fn generate_validate_impl(
+    definition: &Definition,
+    struct_name: &str,
+    is_message: bool,
+    ctx: &GenContext,
+) -> String {
+    let mut lines = Vec::new();
+    if definition.fields.iter().any(|f| f.is_deprecated) {
+        lines.push("#[allow(deprecated)]".to_string());
+    }
+    lines.push(format!("impl {} {{", struct_name));
+    lines.push(
+        "    /// Check each field against the bounds of its `@validate` annotation".to_string(),
+    );
+    lines.push("    pub fn validate(&self) -> Result<(), ValidationError> {".to_string());
+    for field in &definition.fields {
+        let Some(ref validate) = field.validate else {
+            continue;
+        };
+        let rust_name = ctx.ident(&to_snake_case(&field.name));
+        let (lowest, highest) = field
+            .type_
+            .as_deref()
+            .and_then(integer_range)
+            .unwrap_or((i128::MIN, i128::MAX));
+        let checks = [
+            ("min", "<", validate.min.filter(|&min| min > lowest), false),
+            ("max", ">", validate.max.filter(|&max| max < highest), false),
+            (
+                "len_min",
+                "<",
+                validate.len_min.filter(|&n| n > 0).map(|n| n as i128),
+                true,
+            ),
+            ("len_max", ">", validate.len_max.map(|n| n as i128), true),
+        ];
+        for (bound, op, value, is_length) in checks {
+            let Some(value) = value else {
+                continue;
+            };
+            let test = |subject: &str| match (is_length, bound) {
+                (false, _) => format!("{} {} {}", subject, op, value),
+                (true, "len_min") if value == 1 => format!("{}.is_empty()", subject),
+                (true, _) => format!("{}.len() {} {}", subject, op, value),
+            };
+            let condition = if !is_optional(field, is_message) {
+                test(&format!("self.{}", rust_name))
+            } else if is_length {
+                format!("self.{}.as_ref().is_some_and(|v| {})", rust_name, test("v"))
+            } else {
+                format!("self.{}.is_some_and(|v| {})", rust_name, test("v"))
+            };
+            lines.push(format!("        if {} {{", condition));
+            lines.push(format!(
+                "            return Err(ValidationError {{ field: \"{}\", bound: \"{}\" }});",
+                field.name, bound
+            ));
+            lines.push("        }".to_string());
+        }
+    }
+    lines.push("        Ok(())".to_string());
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+/// Generate a `set_<field>_checked` method per integer field, converting any
+/// integer with `TryInto` and reporting values that don't fit
+fn generate_checked_setters_impl(
+    definition: &Definition,
+    struct_name: &str,
+    is_message: bool,
+    ctx: &GenContext,
+) -> String {
+    let mut lines = Vec::new();
+    if definition.fields.iter().any(|f| f.is_deprecated) {
+        lines.push("#[allow(deprecated)]".to_string());
+    }
+    lines.push(format!("impl {} {{", struct_name));
+    let integer_fields = definition.fields.iter().filter(|f| is_integer_field(f));
+    for (i, field) in integer_fields.enumerate() {
+        if i > 0 {
+            lines.push("".to_string());
+        }
+        let snake_name = to_snake_case(&field.name);
+        let rust_name = ctx.ident(&snake_name);
+        let type_name = field.type_.as_deref().unwrap_or_default();
+        let ty = map_type(field, false, ctx, definition);
+        let value = if is_optional(field, is_message) {
+            "Some(value)"
+        } else {
+            "value"
+        };
+        lines.push(format!(
+            "    /// Set `{}` from any integer, failing if it doesn't fit in a `{}`",
+            field.name, type_name
+        ));
+        lines.push(format!(
+            "    pub fn set_{}_checked<T: TryInto<{}>>(&mut self, value: T) -> Result<(), RangeError> {{",
+            snake_name, ty
+        ));
+        lines.push(format!(
+            "        let value = value.try_into().map_err(|_| RangeError {{ field: \"{}\", type_name: \"{}\" }})?;",
+            field.name, type_name
+        ));
+        lines.push(format!("        self.{} = {};", rust_name, value));
+        lines.push("        Ok(())".to_string());
+        lines.push("    }".to_string());
+    }
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+/// Generate a `present_field_count` method for a message, for telemetry on
+/// decoded values
+fn generate_present_count_impl(
+    definition: &Definition,
+    struct_name: &str,
+    ctx: &GenContext,
+) -> String {
+    let mut terms: Vec<String> = definition
+        .fields
+        .iter()
+        .filter(|f| is_optional(f, true))
+        .map(|f| {
+            format!(
+                "self.{}.is_some() as usize",
+                ctx.ident(&to_snake_case(&f.name))
+            )
+        })
+        .collect();
+    let required = definition.fields.len() - terms.len();
+    if required > 0 || terms.is_empty() {
+        terms.push(required.to_string());
+    }
+
+    let mut lines = Vec::new();
+    if definition.fields.iter().any(|f| f.is_deprecated) {
+        lines.push("#[allow(deprecated)]".to_string());
+    }
+    lines.push(format!("impl {} {{", struct_name));
+    lines.push(
+        "    /// Number of fields that are set: every optional field that is `Some`,".to_string(),
+    );
+    lines.push("    /// plus the required fields, which are always present".to_string());
+    lines.push("    pub fn present_field_count(&self) -> usize {".to_string());
+    lines.push(format!("        {}", terms.join("\n            + ")));
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+/// Generate `field_name`, mapping a message field ID back to the field's name
+/// as written in the schema
+fn generate_field_name_impl(definition: &Definition, struct_name: &str) -> String {
+    let mut lines = Vec::new();
+    lines.push(format!("impl {} {{", struct_name));
+    lines.push("    /// The schema name of the field with ID `id`, if there is one".to_string());
+    if definition.fields.is_empty() {
+        lines.push("    pub const fn field_name(_id: u32) -> Option<&'static str> {".to_string());
+        lines.push("        None".to_string());
+    } else {
+        lines.push("    pub const fn field_name(id: u32) -> Option<&'static str> {".to_string());
+        lines.push("        match id {".to_string());
+        for field in &definition.fields {
+            lines.push(format!(
+                "            {} => Some({:?}),",
+                field.field_id, field.name
+            ));
+        }
+        lines.push("            _ => None,".to_string());
+        lines.push("        }".to_string());
+    }
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+/// Generate `Deref` to the only field of a newtype struct, and `From`
+/// conversions in both directions
+fn generate_newtype_impls(
+    field: &Field,
+    definition: &Definition,
+    struct_name: &str,
+    ctx: &GenContext,
+) -> String {
+    let rust_name = ctx.ident(&to_snake_case(&field.name));
+    let inner = map_type(field, false, ctx, definition);
+    let core = ctx.core;
+
+    let mut lines = Vec::new();
+    lines.push(format!("impl {core}::ops::Deref for {} {{", struct_name));
+    lines.push(format!("    type Target = {};", inner));
+    lines.push("".to_string());
+    lines.push(format!("    fn deref(&self) -> &{} {{", inner));
+    lines.push(format!("        &self.{}", rust_name));
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+    lines.push("".to_string());
+    lines.push(format!("impl From<{}> for {} {{", inner, struct_name));
+    lines.push(format!("    fn from({}: {}) -> Self {{", rust_name, inner));
+    lines.push(format!("        Self {{ {} }}", rust_name));
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+    lines.push("".to_string());
+    lines.push(format!("impl From<{}> for {} {{", struct_name, inner));
+    lines.push(format!("    fn from(value: {}) -> Self {{", struct_name));
+    lines.push(format!("        value.{}", rust_name));
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+/// Generate a `canonical_bytes` method that writes fields in field-ID order.
+/// The wire format follows declaration order, so when that already matches
+/// (always for structs) this is just `to_bytes`.
+fn generate_canonical_bytes_impl(
+    definition: &Definition,
+    struct_name: &str,
+    is_message: bool,
+    ctx: &GenContext,
+) -> String {
+    let mut by_id: Vec<&Field> = definition.fields.iter().collect();
+    by_id.sort_by_key(|f| f.field_id);
+    let in_order = by_id
+        .iter()
+        .zip(&definition.fields)
+        .all(|(a, b)| a.name == b.name);
+    let wincode = ctx.wincode;
+
+    let mut lines = Vec::new();
+    if !in_order && by_id.iter().any(|f| f.is_deprecated) {
+        lines.push("#[allow(deprecated)]".to_string());
+    }
+    lines.push(format!("impl {} {{", struct_name));
+    lines.push(
+        "    /// Serialize this value with its fields in field-ID order, for hashing".to_string(),
+    );
+    lines
+        .push("    /// and signing. Equal values always give identical bytes, and the".to_string());
+    lines.push(
+        "    /// output stays the same across versions while the schema is unchanged.".to_string(),
+    );
+    lines.push("    pub fn canonical_bytes(&self) -> Vec<u8> {".to_string());
+    if in_order {
+        lines.push("        // Fields are declared in field-ID order".to_string());
+        lines.push("        self.to_bytes()".to_string());
+    } else {
+        let fields: Vec<FieldSchemaCode> = by_id
+            .iter()
+            .map(|field| {
+                let rust_name = ctx.ident(&to_snake_case(&field.name));
+                field_schema_code(field, definition, &rust_name, is_message, ctx)
+            })
+            .collect();
+        lines.push(format!(
+            "        fn write(src: &{}, out: &mut Vec<u8>) -> {wincode}::WriteResult<()> {{",
+            struct_name
+        ));
+        lines.push("            let mut size = 0;".to_string());
+        for code in &fields {
+            lines.push(format!("            size += {};", code.size));
+        }
+        lines.push(
+            "            let mut trusted = unsafe { out.as_trusted_for(size)? };".to_string(),
+        );
+        lines.push("            let writer = &mut trusted;".to_string());
+        for code in &fields {
+            lines.push(format!(
+                "            {}",
+                code.write.replace("\n        ", "\n            ")
+            ));
+        }
+        lines.push("            trusted.finish()?;".to_string());
+        lines.push("            Ok(())".to_string());
+        lines.push("        }".to_string());
+        lines.push("".to_string());
+        lines.push("        let mut out = Vec::new();".to_string());
+        lines.push(
+            "        write(self, &mut out).expect(\"serialization should not fail\");".to_string(),
+        );
+        lines.push("        out".to_string());
+    }
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+/// Generate a `visit` method that calls `f` on the value itself and on every
+/// nested struct/message reachable through its fields
+fn generate_visit_impl(
+    definition: &Definition,
+    struct_name: &str,
+    is_message: bool,
+    ctx: &GenContext,
+) -> String {
+    let mut lines = Vec::new();
+
+    if definition.fields.iter().any(|f| f.is_deprecated) {
+        lines.push("#[allow(deprecated)]".to_string());
+    }
+    lines.push(format!("impl {} {{", struct_name));
+    lines.push(
+        "    /// Call `f` on this value and, recursively, on every nested struct or message"
+            .to_string(),
+    );
+    lines.push(format!(
+        "    pub fn visit(&self, f: &mut impl FnMut(&dyn {}::any::Any)) {{",
+        ctx.core
+    ));
+    lines.push("        f(self);".to_string());
+
+    for field in &definition.fields {
+        let Some(target) = nested_type(field, definition, ctx.schema) else {
+            continue;
+        };
+
+        let rust_name = ctx.ident(&to_snake_case(&field.name));
+        // Types with their own visitor recurse; leaf structs are passed to f directly
+        let call = |value: &str| {
+            if ctx.visitable.contains(&target.qualified_name()) {
+                format!("{}.visit(f);", value)
+            } else {
+                format!("f({});", value)
+            }
+        };
+        let body = if field.is_array {
+            format!("for item in items {{ {} }}", call("item"))
+        } else {
+            call("value")
+        };
+
+        match (is_optional(field, is_message), field.is_array) {
+            (true, true) => lines.push(format!(
+                "        if let Some(ref items) = self.{} {{ {} }}",
+                rust_name, body
+            )),
+            (true, false) => lines.push(format!(
+                "        if let Some(ref value) = self.{} {{ {} }}",
+                rust_name, body
+            )),
+            (false, true) => lines.push(format!(
+                "        let items = &self.{};\n        {}",
+                rust_name, body
+            )),
+            (false, false) => lines.push(format!(
+                "        let value = &self.{};\n        {}",
+                rust_name, body
+            )),
+        }
+    }
+
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+/// Generate a PartialEq impl comparing only the given fields
+fn generate_partial_eq_impl(fields: &[&Field], struct_name: &str, ctx: &GenContext) -> String {
+    let comparisons: Vec<String> = fields
+        .iter()
+        .map(|f| format!("self.{0} == other.{0}", ctx.ident(&to_snake_case(&f.name))))
+        .collect();
+    let mut lines = Vec::new();
+    lines.push(format!("impl PartialEq for {} {{", struct_name));
+    lines.push("    fn eq(&self, other: &Self) -> bool {".to_string());
+    lines.push(format!("        {}", comparisons.join(" && ")));
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+/// Generate PartialEq, Eq and Hash impls that only look at the `[key]` fields
+fn generate_key_impls(key_fields: &[&Field], struct_name: &str, ctx: &GenContext) -> String {
+    let names: Vec<String> = key_fields
+        .iter()
+        .map(|f| ctx.ident(&to_snake_case(&f.name)))
+        .collect();
+    let mut lines = Vec::new();
+
+    lines.push(generate_partial_eq_impl(key_fields, struct_name, ctx));
+    lines.push("".to_string());
+
+    lines.push(format!("impl Eq for {} {{}}", struct_name));
+    lines.push("".to_string());
+
+    lines.push(format!(
+        "impl {}::hash::Hash for {} {{",
+        ctx.core, struct_name
+    ));
+    lines.push(format!(
+        "    fn hash<H: {}::hash::Hasher>(&self, state: &mut H) {{",
+        ctx.core
+    ));
+    for name in &names {
+        lines.push(format!("        self.{}.hash(state);", name));
+    }
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+/// Generate a manual Debug impl for a struct that prints byte-array fields
+/// through `HexDebug` and every other field as `derive(Debug)` would
+fn generate_debug_impl(
+    definition: &Definition,
+    struct_name: &str,
+    is_message: bool,
+    ctx: &GenContext,
+) -> String {
+    let core = ctx.core;
+    let mut lines = Vec::new();
+
+    if definition.fields.iter().any(|f| f.is_deprecated) {
+        lines.push("#[allow(deprecated)]".to_string());
+    }
+    lines.push(format!("impl {}::fmt::Debug for {} {{", core, struct_name));
+    lines.push(format!(
+        "    fn fmt(&self, f: &mut {0}::fmt::Formatter<'_>) -> {0}::fmt::Result {{",
+        core
+    ));
+    lines.push(format!("        f.debug_struct(\"{}\")", struct_name));
+    for field in &definition.fields {
+        let rust_name = ctx.ident(&to_snake_case(&field.name));
+        let value = if !is_byte_array(field) {
+            format!("&self.{}", rust_name)
+        } else if is_optional(field, is_message) {
+            format!("&self.{}.as_ref().map(|bytes| HexDebug(bytes))", rust_name)
+        } else {
+            format!("&HexDebug(&self.{})", rust_name)
+        };
+        lines.push(format!(
+            "            .field(\"{}\", {})",
+            rust_name.trim_start_matches("r#"),
+            value
+        ));
+    }
+    lines.push("            .finish()".to_string());
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+/// Generate a manual Default impl for a struct (used when a field can't derive
+/// it, or for messages with `explicit_default`)
+fn generate_default_impl(
+    definition: &Definition,
+    struct_name: &str,
+    is_message: bool,
+    ctx: &GenContext,
+) -> String {
+    let mut lines = Vec::new();
+
+    // Messages ask for it to be written out, even where a derive would do
+    if is_message {
+        lines.push("#[allow(clippy::derivable_impls)]".to_string());
+    }
+    if definition.fields.iter().any(|f| f.is_deprecated) {
+        lines.push("#[allow(deprecated)]".to_string());
+    }
+    lines.push(format!("impl Default for {} {{", struct_name));
+    lines.push("    fn default() -> Self {".to_string());
+    lines.push("        Self {".to_string());
+    for field in &definition.fields {
+        let rust_name = ctx.ident(&to_snake_case(&field.name));
+        let value = match field.array_size {
+            _ if is_optional(field, is_message) => "None".to_string(),
+            Some(size) => format!("[Default::default(); {}]", size),
+            None => "Default::default()".to_string(),
+        };
+        lines.push(format!("            {}: {},", rust_name, value));
+    }
+    lines.push("        }".to_string());
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+/// Whether a definition derives SchemaRead/SchemaWrite. Structs and messages
+/// with fields the wincode derive can't handle get hand-written impls for the
+/// whole struct instead, as do signal types without any fields, which the
+/// derive rejects. The derive expands to `::wincode`, so with a custom
+/// wincode path nothing uses it.
+fn uses_schema_derive(definition: &Definition, wincode: &str) -> bool {
+    wincode == "wincode"
+        && (definition.kind == DefinitionKind::Enum
+            || !(definition.fields.is_empty()
+                || definition.fields.iter().any(needs_manual_schema_impl)))
+}
+
+/// Check if a field's type can't go through the wincode derive
+fn needs_manual_schema_impl(field: &Field) -> bool {
+    field
+        .type_
+        .as_deref()
+        .is_some_and(|ty| MANUAL_SCHEMA_TYPES.contains(&ty))
+}
+
+/// Expressions used to size, write and read a single field in manual impls
+struct FieldSchemaCode {
+    size: String,
+    write: String,
+    read: String,
+}
+
+/// Build the manual SchemaWrite/SchemaRead code for one field
+fn field_schema_code(
+    field: &Field,
+    definition: &Definition,
+    rust_name: &str,
+    is_message: bool,
+    ctx: &GenContext,
+) -> FieldSchemaCode {
+    let wincode = ctx.wincode;
+    if !needs_manual_schema_impl(field) {
+        // Anything else is delegated to its own wincode impl
+        let ty = map_type(field, is_message, ctx, definition);
+        return FieldSchemaCode {
+            size: format!(
+                "<{} as {wincode}::SchemaWrite>::size_of(&src.{})?",
+                ty, rust_name
+            ),
+            write: format!(
+                "<{} as {wincode}::SchemaWrite>::write(writer, &src.{})?;",
+                ty, rust_name
+            ),
+            read: format!("<{} as {wincode}::SchemaRead<'de>>::get(reader)?", ty),
+        };
+    }
+
+    // bytes: encoded exactly like byte[] (length prefix + raw bytes)
+    if is_optional(field, is_message) {
+        FieldSchemaCode {
+            size: format!(
+                "1 + src.{}.as_deref().map_or(Ok(0), <[u8] as {wincode}::SchemaWrite>::size_of)?",
+                rust_name
+            ),
+            write: format!(
+                "match &src.{} {{\n            Some(value) => {{\n                <u8 as {wincode}::SchemaWrite>::write(writer, &1)?;\n                <[u8] as {wincode}::SchemaWrite>::write(writer, value)?;\n            }}\n            None => <u8 as {wincode}::SchemaWrite>::write(writer, &0)?,\n        }}",
+                rust_name
+            ),
+            read: format!("<Option<Vec<u8>> as {wincode}::SchemaRead<'de>>::get(reader)?.map(bytes::Bytes::from)"),
+        }
+    } else {
+        FieldSchemaCode {
+            size: format!(
+                "<[u8] as {wincode}::SchemaWrite>::size_of(&src.{})?",
+                rust_name
+            ),
+            write: format!(
+                "<[u8] as {wincode}::SchemaWrite>::write(writer, &src.{})?;",
+                rust_name
+            ),
+            read: format!(
+                "bytes::Bytes::from(<Vec<u8> as {wincode}::SchemaRead<'de>>::get(reader)?)"
+            ),
+        }
+    }
+}
+
+/// Generate manual SchemaWrite/SchemaRead impls, field by field in declaration order
+fn generate_schema_impls(
+    definition: &Definition,
+    struct_name: &str,
+    is_message: bool,
+    ctx: &GenContext,
+) -> String {
+    let fields: Vec<(String, FieldSchemaCode)> = definition
+        .fields
+        .iter()
+        .map(|field| {
+            let rust_name = ctx.ident(&to_snake_case(&field.name));
+            let code = field_schema_code(field, definition, &rust_name, is_message, ctx);
+            (rust_name, code)
+        })
+        .collect();
+    let allow_deprecated = definition.fields.iter().any(|f| f.is_deprecated);
+    let wincode = ctx.wincode;
+    // Without fields, the arguments go unused
+    let unused = if fields.is_empty() { "_" } else { "" };
+    let mut lines = Vec::new();
+
+    if allow_deprecated {
+        lines.push("#[allow(deprecated)]".to_string());
+    }
+    lines.push(format!(
+        "impl {wincode}::SchemaWrite for {} {{",
+        struct_name
+    ));
+    lines.push("    type Src = Self;".to_string());
+    lines.push("".to_string());
+    lines.push(format!(
+        "    fn size_of({unused}src: &Self) -> {wincode}::WriteResult<usize> {{"
+    ));
+    if fields.is_empty() {
+        lines.push("        Ok(0)".to_string());
+    } else {
+        lines.push("        let mut size = 0;".to_string());
+        for (_, code) in &fields {
+            lines.push(format!("        size += {};", code.size));
+        }
+        lines.push("        Ok(size)".to_string());
+    }
+    lines.push("    }".to_string());
+    lines.push("".to_string());
+    lines.push(format!(
+        "    fn write({unused}writer: &mut impl Writer, {unused}src: &Self) -> {wincode}::WriteResult<()> {{"
+    ));
+    for (_, code) in &fields {
+        lines.push(format!("        {}", code.write));
+    }
+    lines.push("        Ok(())".to_string());
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+    lines.push("".to_string());
+
+    if allow_deprecated {
+        lines.push("#[allow(deprecated)]".to_string());
+    }
+    lines.push(format!(
+        "impl<'de> {wincode}::SchemaRead<'de> for {} {{",
+        struct_name
+    ));
+    lines.push("    type Dst = Self;".to_string());
+    lines.push("".to_string());
+    lines.push("    fn read(".to_string());
+    lines.push(format!(
+        "        {unused}reader: &mut impl {wincode}::io::Reader<'de>,"
+    ));
+    lines.push(format!(
+        "        dst: &mut {}::mem::MaybeUninit<Self>,",
+        ctx.core
+    ));
+    lines.push(format!("    ) -> {wincode}::ReadResult<()> {{"));
+    lines.push("        dst.write(Self {".to_string());
+    for (rust_name, code) in &fields {
+        lines.push(format!("            {}: {},", rust_name, code.read));
+    }
+    lines.push("        });".to_string());
+    lines.push("        Ok(())".to_string());
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+/// Generate manual SchemaWrite/SchemaRead impls for an enum, encoded like
+/// the derive: the variant's index (not its value) as a `u32` tag
+fn generate_enum_schema_impls(
+    definition: &Definition,
+    enum_name: &str,
+    ctx: &GenContext,
+) -> String {
+    let variants: Vec<String> = definition
+        .fields
+        .iter()
+        .filter(|f| f.alias_of.is_none())
+        .map(|f| ctx.ident(&to_pascal_case(&f.name)))
+        .collect();
+    let allow_deprecated = definition.fields.iter().any(|f| f.is_deprecated);
+    let wincode = ctx.wincode;
+    let mut lines = Vec::new();
+
+    if allow_deprecated {
+        lines.push("#[allow(deprecated)]".to_string());
+    }
+    lines.push(format!("impl {wincode}::SchemaWrite for {} {{", enum_name));
+    lines.push("    type Src = Self;".to_string());
+    lines.push("".to_string());
+    lines.push(format!(
+        "    fn size_of(_src: &Self) -> {wincode}::WriteResult<usize> {{"
+    ));
+    lines.push(format!(
+        "        <u32 as {wincode}::SchemaWrite>::size_of(&0)"
+    ));
+    lines.push("    }".to_string());
+    lines.push("".to_string());
+    lines.push(format!(
+        "    fn write(writer: &mut impl Writer, src: &Self) -> {wincode}::WriteResult<()> {{"
+    ));
+    lines.push("        let tag: u32 = match src {".to_string());
+    for (i, variant) in variants.iter().enumerate() {
+        lines.push(format!("            Self::{} => {},", variant, i));
+    }
+    lines.push("        };".to_string());
+    lines.push(format!(
+        "        <u32 as {wincode}::SchemaWrite>::write(writer, &tag)"
+    ));
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+    lines.push("".to_string());
+
+    if allow_deprecated {
+        lines.push("#[allow(deprecated)]".to_string());
+    }
+    lines.push(format!(
+        "impl<'de> {wincode}::SchemaRead<'de> for {} {{",
+        enum_name
+    ));
+    lines.push("    type Dst = Self;".to_string());
+    lines.push("".to_string());
+    lines.push("    fn read(".to_string());
+    lines.push(format!(
+        "        reader: &mut impl {wincode}::io::Reader<'de>,"
+    ));
+    lines.push(format!(
+        "        dst: &mut {}::mem::MaybeUninit<Self>,",
+        ctx.core
+    ));
+    lines.push(format!("    ) -> {wincode}::ReadResult<()> {{"));
+    lines.push(format!(
+        "        let tag = <u32 as {wincode}::SchemaRead<'de>>::get(reader)?;"
+    ));
+    lines.push("        dst.write(match tag {".to_string());
+    for (i, variant) in variants.iter().enumerate() {
+        lines.push(format!("            {} => Self::{},", i, variant));
+    }
+    lines.push(format!(
+        "            _ => return Err({wincode}::error::invalid_tag_encoding(tag as usize)),"
+    ));
+    lines.push("        });".to_string());
+    lines.push("        Ok(())".to_string());
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+/// Generate manual SchemaWrite/SchemaRead impls for a flags type, encoded
+/// as its raw bits like the derive
+fn generate_flags_schema_impls(name: &str, ctx: &GenContext) -> String {
+    let wincode = ctx.wincode;
+    let mut lines = Vec::new();
+
+    lines.push(format!("impl {wincode}::SchemaWrite for {} {{", name));
+    lines.push("    type Src = Self;".to_string());
+    lines.push("".to_string());
+    lines.push(format!(
+        "    fn size_of(src: &Self) -> {wincode}::WriteResult<usize> {{"
+    ));
+    lines.push(format!(
+        "        <u32 as {wincode}::SchemaWrite>::size_of(&src.0)"
+    ));
+    lines.push("    }".to_string());
+    lines.push("".to_string());
+    lines.push(format!(
+        "    fn write(writer: &mut impl Writer, src: &Self) -> {wincode}::WriteResult<()> {{"
+    ));
+    lines.push(format!(
+        "        <u32 as {wincode}::SchemaWrite>::write(writer, &src.0)"
+    ));
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+    lines.push("".to_string());
+
+    lines.push(format!(
+        "impl<'de> {wincode}::SchemaRead<'de> for {} {{",
+        name
+    ));
+    lines.push("    type Dst = Self;".to_string());
+    lines.push("".to_string());
+    lines.push("    fn read(".to_string());
+    lines.push(format!(
+        "        reader: &mut impl {wincode}::io::Reader<'de>,"
+    ));
+    lines.push(format!(
+        "        dst: &mut {}::mem::MaybeUninit<Self>,",
+        ctx.core
+    ));
+    lines.push(format!("    ) -> {wincode}::ReadResult<()> {{"));
+    lines.push(format!(
+        "        dst.write(Self(<u32 as {wincode}::SchemaRead<'de>>::get(reader)?));"
+    ));
+    lines.push("        Ok(())".to_string());
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+/// Generate impl block with helper methods
+fn generate_struct_impl(struct_name: &str, field_count: Option<usize>, wincode: &str) -> String {
+    let mut lines = Vec::new();
+
+    lines.push(format!("impl {} {{", struct_name));
+
+    if let Some(field_count) = field_count {
+        lines.push("    /// Number of fields in the schema".to_string());
+        lines.push(format!(
+            "    pub const FIELD_COUNT: usize = {};",
+            field_count
+        ));
+        lines.push("".to_string());
+    }
+
+    // Serialize method
+    lines.push("    /// Serialize this value to bytes".to_string());
+    lines.push("    pub fn to_bytes(&self) -> Vec<u8> {".to_string());
+    lines.push("        let mut out = Vec::new();".to_string());
+    lines.push("        self.to_bytes_into(&mut out)".to_string());
+    lines.push("            .expect(\"serialization should not fail\");".to_string());
+    lines.push("        out".to_string());
+    lines.push("    }".to_string());
+    lines.push("".to_string());
+    lines.push("    /// Serialize this value into a buffer, replacing its contents".to_string());
+    lines.push(
+        format!("    pub fn to_bytes_into(&self, out: &mut Vec<u8>) -> Result<(), {wincode}::WriteError> {{"),
+    );
+    lines.push("        out.clear();".to_string());
+    lines.push("        self.write_to(out)".to_string());
+    lines.push("    }".to_string());
+    lines.push("".to_string());
+    lines.push(
+        "    /// Serialize this value, appending to `out` without reallocating if it has room"
+            .to_string(),
+    );
+    lines.push(format!(
+        "    pub fn write_to(&self, out: &mut Vec<u8>) -> Result<(), {wincode}::WriteError> {{"
+    ));
+    lines.push(format!(
+        "        let size = {wincode}::serialized_size(self)? as usize;"
+    ));
+    lines.push("        out.reserve(size);".to_string());
+    lines.push("        let mut writer = unsafe { out.as_trusted_for(size)? };".to_string());
+    lines.push(format!(
+        "        {wincode}::serialize_into(&mut writer, self)?;"
+    ));
+    lines.push("        writer.finish()?;".to_string());
+    lines.push("        Ok(())".to_string());
+    lines.push("    }".to_string());
+    lines.push("".to_string());
+
+    // Deserialize method
+    lines.push("    /// Deserialize from bytes".to_string());
+    lines.push(format!(
+        "    pub fn from_bytes(bytes: &[u8]) -> Result<Self, {wincode}::ReadError> {{"
+    ));
+    lines.push(format!("        {wincode}::deserialize(bytes)"));
+    lines.push("    }".to_string());
+
+    lines.push("}".to_string());
+    lines.push("".to_string());
+
+    // TryFrom<&[u8]> for generic deserialization code
+    lines.push(format!("impl TryFrom<&[u8]> for {} {{", struct_name));
+    lines.push(format!("    type Error = {wincode}::ReadError;"));
+    lines.push("".to_string());
+    lines.push("    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {".to_string());
+    lines.push("        Self::from_bytes(bytes)".to_string());
+    lines.push("    }".to_string());
+    lines.push("}".to_string());
+
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_schema;
+    use crate::tokenizer::tokenize_schema;
+
+    fn generate(input: &str) -> String {
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        compile_schema_to_rust(&schema)
+    }
+
+    #[test]
+    fn test_generate_simple_struct() {
+        let input = r#"
+            struct Point {
+                int x;
+                int y;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("pub struct Point"));
+        assert!(output.contains("pub x: i32"));
+        assert!(output.contains("pub y: i32"));
+        assert!(output.contains("SchemaRead, SchemaWrite"));
+        assert!(output.contains("#[repr(C)]"));
+    }
+
+    #[test]
+    fn test_generate_write_to() {
+        let output = generate("struct Point { int x; int y; }");
+        assert!(output.contains(
+            "pub fn to_bytes_into(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {\n        \
+             out.clear();\n        self.write_to(out)\n    }"
+        ));
+        assert!(output.contains(
+            "pub fn write_to(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {"
+        ));
+    }
+
+    #[test]
+    fn test_generate_try_from_bytes() {
+        let output = generate("struct Point { int x; int y; }");
+        assert!(output.contains("pub fn from_bytes(bytes: &[u8])"));
+        assert!(output.contains(
+            "impl TryFrom<&[u8]> for Point {\n    type Error = wincode::ReadError;\n\n    \
+             fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {\n        \
+             Self::from_bytes(bytes)\n    }\n}"
+        ));
+    }
+
+    #[test]
+    fn test_generate_key_hash() {
+        let input = r#"
+            message Transaction {
+                byte[32] txHash = 1 [key];
+                byte[] payload = 2;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("#[derive(Debug, Clone, Default, SchemaRead, SchemaWrite)]"));
+        assert!(output.contains(
+            "impl PartialEq for Transaction {\n    fn eq(&self, other: &Self) -> bool {\n        \
+             self.tx_hash == other.tx_hash\n    }\n}"
+        ));
+        assert!(output.contains("impl Eq for Transaction {}"));
+        assert!(output.contains(
+            "impl std::hash::Hash for Transaction {\n    \
+             fn hash<H: std::hash::Hasher>(&self, state: &mut H) {\n        \
+             self.tx_hash.hash(state);\n    }\n}"
+        ));
+        assert!(!output.contains("self.payload.hash"));
+    }
+
+    #[test]
+    fn test_generate_fixed_byte_array() {
+        let input = r#"
+            struct BlockHeader {
+                byte[32] prevHash;
+                byte[32] merkleRoot;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("pub prev_hash: [u8; 32]"));
+        assert!(output.contains("pub merkle_root: [u8; 32]"));
+    }
+
+    #[test]
+    fn test_generate_uint8_fixed_array() {
+        let byte_input = "struct Header { byte[32] hash; }";
+        let uint8_input = "struct Header { uint8[32] hash; }";
+        assert_eq!(generate(byte_input), generate(uint8_input));
+
+        for input in [byte_input, uint8_input] {
+            let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+            assert!(schema.definitions[0].fields[0].is_fixed_byte_array());
+        }
+    }
+
+    #[test]
+    fn test_generate_scalar_byte() {
+        let input = r#"
+            struct Header { byte flags; uint8 version; byte[] data; }
+            message Tx { byte flags = 1; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("pub flags: u8,"));
+        assert!(output.contains("pub version: u8,"));
+        assert!(output.contains("pub data: Vec<u8>,"));
+        assert!(output.contains("pub flags: Option<u8>,"));
+
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        assert!(!schema.definitions[0].fields[0].is_fixed_byte_array());
+        assert!(!schema.definitions[0].fields[2].is_fixed_byte_array());
+    }
+
+    #[test]
+    fn test_generate_timestamp() {
+        let input = r#"
+            struct Header { timestamp t; timestamp[] history; }
+            message Tx { timestamp created = 1; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("pub t: u64,"));
+        assert!(output.contains("pub history: Vec<u64>,"));
+        assert!(output.contains("pub created: Option<u64>,"));
+    }
+
+    #[test]
+    fn test_generate_wide_integers() {
+        let input = r#"
+            struct Balance { uint128 x; int128 delta; uint256 y; }
+            message Supply { uint256 total = 1; uint128[] shares = 2; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("pub x: u128,"));
+        assert!(output.contains("pub delta: i128,"));
+        assert!(output.contains("pub y: [u8; 32],"));
+        assert!(output.contains("pub total: Option<[u8; 32]>,"));
+        assert!(output.contains("pub shares: Option<Vec<u128>>,"));
+    }
+
+    #[test]
+    fn test_generate_large_fixed_array() {
+        let input = r#"
+            struct Signed {
+                byte[32] hash;
+                byte[64] sig;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("pub sig: [u8; 64]"));
+        assert!(output.contains("#[derive(Debug, Clone, PartialEq, SchemaRead, SchemaWrite)]"));
+        assert!(output.contains("impl Default for Signed {"));
+        assert!(output.contains("sig: [Default::default(); 64],"));
+    }
+
+    #[test]
+    fn test_generate_doc_comments() {
+        let input = r#"
+            // A 2D point
+            struct Point {
+                // Horizontal position
+                int x;
+                int y;
+            }
+
+            enum Kind {
+                // The first kind
+                A = 0;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("/// A 2D point\n#[allow(dead_code)]\n#[derive("));
+        assert!(output.contains("    /// Horizontal position\n    pub x: i32,"));
+        assert!(output.contains("    /// The first kind\n    #[default]\n    A = 0,"));
+    }
+
+    #[test]
+    fn test_generate_header() {
+        let input =
+            "//! Copyright 2026 Tapedrive\n//!\n//! Licensed under MIT\nstruct Point { int x; }";
+        let output = generate(input);
+        assert!(output.starts_with(
+            "// Copyright 2026 Tapedrive\n//\n// Licensed under MIT\n\n\
+             // This file was generated by the TCS compiler.\n"
+        ));
+        assert!(!output.contains("/// Copyright"));
+
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let options = GenOptions {
+            file_header: false,
+            ..GenOptions::default()
+        };
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+        assert!(output.starts_with("use wincode::io::Writer;\n"));
+    }
+
+    #[test]
+    fn test_generate_manual_schema_impl() {
+        let input = r#"
+            struct Blob {
+                uint64 id;
+                bytes data;
+            }
+
+            message Envelope {
+                bytes payload = 1;
+            }
+
+            struct Plain {
+                uint64 id;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("pub data: bytes::Bytes,"));
+        assert!(output.contains("pub payload: Option<bytes::Bytes>,"));
+        assert!(output
+            .contains("#[derive(Debug, Clone, PartialEq, Default)]\n#[repr(C)]\npub struct Blob"));
+        assert!(output.contains("impl wincode::SchemaWrite for Blob {"));
+        assert!(output.contains("impl<'de> wincode::SchemaRead<'de> for Blob {"));
+        assert!(output.contains("<u64 as wincode::SchemaWrite>::write(writer, &src.id)?;"));
+        assert!(output.contains("impl wincode::SchemaWrite for Envelope {"));
+
+        // Types without such fields keep the derive
+        assert!(!output.contains("wincode::SchemaWrite for Plain"));
+        assert!(output.contains("Default, SchemaRead, SchemaWrite)]\n#[repr(C)]\npub struct Plain"));
+    }
+
+    #[test]
+    fn test_generate_message() {
+        let input = r#"
+            message Transaction {
+                byte[32] txHash = 1;
+                uint64 nonce = 2;
+                byte[] data = 3;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("pub struct Transaction"));
+        assert!(output.contains("pub tx_hash: Option<[u8; 32]>"));
+        assert!(output.contains("pub nonce: Option<u64>"));
+        assert!(output.contains("pub data: Option<Vec<u8>>"));
+        // Messages don't have repr(C)
+        assert!(!output.contains("#[repr(C)]") || output.matches("#[repr(C)]").count() == 0);
+    }
+
+    #[test]
+    fn test_generate_required_message_field() {
+        let input = r#"
+            message Transaction {
+                required uint64 nonce = 1;
+                optional byte[32] hash = 2;
+                required byte[64] sig = 3;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("/// Required: always present on the wire.\n    pub nonce: u64,"));
+        assert!(output.contains("pub hash: Option<[u8; 32]>,"));
+        assert!(output.contains("pub sig: [u8; 64],"));
+        assert!(output.contains("impl Default for Transaction {"));
+        assert!(output.contains("hash: None,"));
+    }
+
+    #[test]
+    fn test_generate_enum() {
+        let input = r#"
+            enum NodeRole {
+                STORAGE = 1;
+                VALIDATOR = 2;
+                LIGHT = 3;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("pub enum NodeRole"));
+        assert!(output.contains("#[repr(u32)]"));
+        assert!(output.contains("Storage = 1"));
+        assert!(output.contains("Validator = 2"));
+        assert!(output.contains("#[default]"));
+    }
+
+    #[test]
+    fn test_generate_enum_all() {
+        let input = r#"
+            enum NodeRole {
+                STORAGE = 1;
+                VALIDATOR = 2;
+                LIGHT = 3;
+                ARCHIVER = STORAGE;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains(
+            "    /// Every variant, in schema order\n    \
+             pub const fn all() -> &'static [Self] {\n        \
+             &[Self::Storage, Self::Validator, Self::Light]\n    }"
+        ));
+
+        let output = generate("enum Status { OK = 0; }");
+        assert!(
+            output.contains("pub const fn all() -> &'static [Self] {\n        &[Self::Ok]\n    }")
+        );
+    }
+
+    #[test]
+    fn test_generate_flags_enum() {
+        let input = r#"
+            enum Perm [flags] {
+                READ = 1;
+                WRITE = 2;
+                EXEC = 4;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("#[repr(transparent)]\npub struct Perm(pub u32);"));
+        assert!(!output.contains("pub enum Perm"));
+        assert!(output.contains("pub const READ: Self = Self(1);"));
+        assert!(output.contains("pub const EXEC: Self = Self(4);"));
+        assert!(output.contains(
+            "impl std::ops::BitOr for Perm {\n    type Output = Self;\n\n    \
+             fn bitor(self, rhs: Self) -> Self {\n        Self(self.0 | rhs.0)\n    }\n}"
+        ));
+        assert!(output.contains("impl std::ops::BitAndAssign for Perm {"));
+    }
+
+    #[test]
+    fn test_generate_enum_alias() {
+        let input = r#"
+            enum Status {
+                OK = 0;
+                SUCCESS = OK;
+            }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        crate::verifier::verify_schema(&schema).unwrap();
+
+        let output = compile_schema_to_rust(&schema);
+        assert!(output.contains("Ok = 0,"));
+        assert!(!output.contains("Success = 0,"));
+        assert!(output.contains("pub const SUCCESS: Self = Self::Ok;"));
+    }
+
+    #[test]
+    fn test_generate_empty_schema() {
+        let output = generate("// Only comments here\n");
+        assert!(!output.contains("use wincode"));
+        assert!(!output.contains("pub struct"));
+
+        let output = generate("package empty;");
+        assert!(output.contains("pub mod empty {\n\n}"));
+        assert!(!output.contains("use wincode"));
+    }
+
+    #[test]
+    fn test_generate_with_package() {
+        let input = r#"
+            package tapedrive;
+
+            struct Blob {
+                byte[] data;
+            }
+            message Upload { Blob blob = 1; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("#[allow(dead_code)]\npub mod tapedrive {\n"));
+        assert!(!output.contains("#!["));
+        // Types are inside the module and refer to each other unqualified
+        let module = output.find("pub mod tapedrive {").unwrap();
+        assert!(output.find("pub struct Blob {").unwrap() > module);
+        assert!(output.contains("    pub blob: Option<Blob>,"));
+        assert!(output.trim_end().ends_with("}"));
+    }
+
+    #[test]
+    fn test_generate_without_package() {
+        let input = r#"
+            struct Blob {
+                byte[] data;
+            }
+            message Upload { Blob blob = 1; }
+        "#;
+        let output = generate(input);
+        // Top-level types, each with its own outer attribute, so the output
+        // can be `include!`d
+        assert!(!output.contains("#!["));
+        assert!(!output.contains("pub mod"));
+        assert!(output.contains("\n#[allow(dead_code)]\n#[derive(Debug, Clone, PartialEq, Default, SchemaRead, SchemaWrite)]\n#[repr(C)]\npub struct Blob {"));
+        assert!(output.contains("\n#[allow(dead_code)]\nimpl Upload {"));
+        assert!(output.contains("\npub struct Upload {"));
+        assert!(output.contains("    pub blob: Option<Blob>,"));
+    }
+
+    #[test]
+    fn test_sorted_output_is_order_independent() {
+        let a = r#"
+            message Wrapper { Point point = 1; Kind kind = 2; }
+            struct Point { int x; int y; }
+            enum Kind { A = 0; B = 1; }
+        "#;
+        let b = r#"
+            enum Kind { A = 0; B = 1; }
+            struct Point { int x; int y; }
+            message Wrapper { Point point = 1; Kind kind = 2; }
+        "#;
+        let options = GenOptions {
+            sort_definitions: true,
+            ..GenOptions::default()
+        };
+        let gen = |input: &str| {
+            let tokens = tokenize_schema(input).unwrap();
+            let schema = parse_schema(&tokens).unwrap();
+            compile_schema_to_rust_with_options(&schema, &options)
+        };
+
+        let output = gen(a);
+        assert_eq!(output, gen(b));
+        assert!(output.find("pub enum Kind").unwrap() < output.find("pub struct Point").unwrap());
+        assert!(
+            output.find("pub struct Point").unwrap() < output.find("pub struct Wrapper").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_generate_multiple_packages() {
+        let input = r#"
+            package geometry;
+            struct Point { int x; int y; }
+
+            package shapes;
+            struct Circle { geometry.Point center; uint radius; }
+        "#;
+        let output = generate(input);
+        let geometry = output.find("pub mod geometry {").unwrap();
+        let shapes = output.find("pub mod shapes {").unwrap();
+        assert!(geometry < output.find("pub struct Point").unwrap());
+        assert!(shapes > output.find("pub struct Point").unwrap());
+        assert!(shapes < output.find("pub struct Circle").unwrap());
+        assert!(output.contains("pub center: super::geometry::Point,"));
+        assert_eq!(output.matches("use wincode::io::Writer;").count(), 2);
+
+        // Packages can reuse a name; views and visitors follow the right one
+        let input = r#"
+            options { views = true; visitors = true; }
+            package geometry;
+            struct Point { int x; int y; }
+
+            package shapes;
+            struct Point { uint64 x; Tag tag; }
+            struct Tag { string label; }
+            struct Line { Point from; geometry.Point to; }
+        "#;
+        let output = generate(input);
+        assert_eq!(output.matches("pub struct Point {").count(), 2);
+        assert_eq!(output.matches("pub struct PointView<'a>").count(), 1);
+        assert!(output.contains("pub const SIZE: usize = 8;"));
+        assert!(output.contains("pub from: Point,\n    pub to: super::geometry::Point,"));
+        assert!(output.contains("let value = &self.from;\n        value.visit(f);"));
+        assert!(output.contains("let value = &self.to;\n        f(value);"));
+    }
+
+    #[test]
+    fn test_nested_definitions() {
+        let input = r#"
+            package geometry;
+            struct Point { int x; int y; }
+
+            package shapes;
+            message Canvas {
+                struct Layer {
+                    enum Mode { FLAT = 0; }
+                    Mode mode;
+                    geometry.Point origin;
+                }
+                Layer[] layers = 1;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("pub layers: Option<Vec<canvas::Layer>>,"));
+        assert!(output.contains("pub mod canvas {\nuse super::*;\n\n"));
+        assert!(output.contains("pub mod layer {\nuse super::*;\n\n"));
+        assert!(output.contains("pub mode: layer::Mode,"));
+        // Other packages are one more module up
+        assert!(output.contains("pub origin: super::super::geometry::Point,"));
+        assert!(output.find("pub struct Canvas").unwrap() < output.find("pub mod canvas").unwrap());
+    }
+
+    #[test]
+    fn test_type_aliases() {
+        let input = r#"
+            type Hash = byte[32];
+            type Root = Hash;
+            struct Header { Hash prev; Root root; }
+            message Block { Header header = 1; Hash hash = 2; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("    pub prev: [u8; 32],\n    pub root: [u8; 32],"));
+        assert!(output.contains("pub hash: Option<[u8; 32]>,"));
+        assert!(!output.contains("Hash"));
+    }
+
+    #[test]
+    fn test_feature_gated_definitions() {
+        let input = r#"
+            options { checked_setters = true; explicit_default = true; }
+            // Not stable yet
+            message Experimental [feature = "exp"] {
+                struct Inner { uint8 x; }
+                Inner[] inners = 1;
+                uint8 level = 2;
+            }
+            enum Mode [feature = "exp"] { A = 0; B = 1; }
+            struct Stable { uint8 level; }
+        "#;
+        let output = generate(input);
+        let cfg = "#[cfg(feature = \"exp\")]";
+        assert!(output.contains(
+            "/// Not stable yet\n#[cfg(feature = \"exp\")]\n#[allow(dead_code)]\n#[derive("
+        ));
+        assert!(output
+            .contains("#[cfg(feature = \"exp\")]\n#[allow(dead_code)]\npub mod experimental {"));
+
+        // Every top-level item for a gated definition carries the cfg, and
+        // no other item does
+        let items = output.split("\n\n").filter(|item| {
+            let code = item.trim_start_matches('\n');
+            !code.starts_with(' ') && (code.contains(" Experimental") || code.contains(" Mode"))
+        });
+        let mut count = 0;
+        for item in items {
+            assert!(item.contains(cfg), "{}", item);
+            count += 1;
+        }
+        assert!(count >= 8, "{}", count);
+        let stable = output.find("pub struct Stable").unwrap();
+        assert!(!output[stable..].contains(cfg));
+        assert_eq!(output.matches(cfg).count(), count + 1);
+        assert!(!output.contains(&format!("    {}", cfg)));
+    }
+
+    #[test]
+    fn test_generate_lint_allows() {
+        let output = generate("package demo; struct Point { int x; }");
+        assert!(output.contains("#[allow(dead_code)]\npub mod demo {"));
+
+        let output = generate("struct Point { int x; }");
+        assert!(!output.contains("#!["));
+        assert!(output.contains("#[allow(dead_code)]\n#[derive("));
+        assert!(output.contains("#[allow(dead_code)]\nimpl Point {"));
+
+        // Without a package, the wrapping module gets them instead
+        let output = generate("options { rust_module = \"proto\"; } struct Point { int x; }");
+        assert!(output.contains("#[allow(dead_code)]\npub mod proto {"));
+        assert_eq!(output.matches("#[allow(").count(), 1);
+
+        let tokens = tokenize_schema("package demo; struct Point { int x; }").unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let options = GenOptions {
+            allowed_lints: vec!["clippy::too_many_arguments".to_string()],
+            ..GenOptions::default()
+        };
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+        assert!(output.contains("#[allow(clippy::too_many_arguments)]\npub mod demo {"));
+    }
+
+    #[test]
+    fn test_generate_visitors() {
+        let input = r#"
+            struct Color { byte r; byte g; byte b; }
+            struct Brush { Color[] colors; }
+            message Canvas { Brush main = 1; Brush[] others = 2; uint width = 3; }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let options = GenOptions {
+            generate_visitors: true,
+            ..GenOptions::default()
+        };
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+
+        assert!(output.contains("impl Canvas {\n    /// Call `f` on this value"));
+        assert!(output.contains("if let Some(ref value) = self.main { value.visit(f); }"));
+        assert!(output.contains(
+            "if let Some(ref items) = self.others { for item in items { item.visit(f); } }"
+        ));
+        assert!(
+            output.contains("let items = &self.colors;\n        for item in items { f(item); }")
+        );
+        // Leaf structs don't get a visitor of their own
+        assert_eq!(output.matches("pub fn visit(").count(), 2);
+
+        // Off by default
+        assert!(!generate(input).contains("pub fn visit("));
+    }
+
+    #[test]
+    fn test_generate_field_count() {
+        let input = r#"
+            enum Status { OK = 0; FAILED = 1; SUCCESS = OK; }
+            message Job { uint64 id = 1; string name = 2 [deprecated]; Status status = 3; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("impl Status {\n    /// Number of variants in the schema\n    pub const FIELD_COUNT: usize = 2;"));
+        assert!(output.contains("impl Job {\n    /// Number of fields in the schema\n    pub const FIELD_COUNT: usize = 3;"));
 
-    // Add helper methods for the struct
-    let impl_block = generate_struct_impl(definition, &struct_name);
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let options = GenOptions {
+            count_deprecated_fields: false,
+            ..GenOptions::default()
+        };
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+        assert!(output.contains("impl Job {\n    /// Number of fields in the schema\n    pub const FIELD_COUNT: usize = 2;"));
+    }
 
-    format!("{}\n\n{}", lines.join("\n"), impl_block)
-}
+    #[test]
+    fn test_rust_keyword_escape() {
+        let input = r#"
+            struct Config {
+                string type;
+                int match;
+            }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("pub type_: String"));
+        assert!(output.contains("pub match_: i32"));
+    }
 
-/// Generate impl block with helper methods
-fn generate_struct_impl(_definition: &Definition, struct_name: &str) -> String {
-    let mut lines = Vec::new();
+    #[test]
+    fn test_raw_identifiers() {
+        let input = r#"
+            struct Config {
+                string type;
+                int self;
+            }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let options = GenOptions {
+            raw_identifiers: true,
+            ..GenOptions::default()
+        };
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+        assert!(output.contains("pub r#type: String"));
+        // `self` can't be a raw identifier
+        assert!(output.contains("pub self_: i32"));
+    }
 
-    lines.push(format!("impl {} {{", struct_name));
+    #[test]
+    fn test_derive_arbitrary() {
+        let input = r#"
+            enum Kind { A = 0; }
+            struct Signature { byte[32] r; byte[32] s; }
+            message Tx { Signature sig = 1; bytes data = 2; }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let options = GenOptions {
+            derive_arbitrary: true,
+            ..GenOptions::default()
+        };
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+        let derive = "#[cfg_attr(feature = \"arbitrary\", derive(arbitrary::Arbitrary))]";
+        assert_eq!(output.matches(derive).count(), 3);
+        assert!(output.contains(&format!("{}\n#[repr(u32)]\npub enum Kind", derive)));
+        assert!(output.contains("arbitrary(with = |u: &mut arbitrary::Unstructured|"));
 
-    // Serialize method
-    lines.push("    /// Serialize this value to bytes".to_string());
-    lines.push("    pub fn to_bytes(&self) -> Vec<u8> {".to_string());
-    lines.push("        let mut out = Vec::new();".to_string());
-    lines.push("        self.to_bytes_into(&mut out)".to_string());
-    lines.push("            .expect(\"serialization should not fail\");".to_string());
-    lines.push("        out".to_string());
-    lines.push("    }".to_string());
-    lines.push("".to_string());
-    lines.push("    /// Serialize this value into a buffer".to_string());
-    lines.push(
-        "    pub fn to_bytes_into(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {"
-            .to_string(),
-    );
-    lines.push("        out.clear();".to_string());
-    lines.push("        let size = wincode::serialized_size(self)? as usize;".to_string());
-    lines.push("        out.reserve(size);".to_string());
-    lines.push("        let mut writer = unsafe { out.as_trusted_for(size)? };".to_string());
-    lines.push("        wincode::serialize_into(&mut writer, self)?;".to_string());
-    lines.push("        writer.finish()?;".to_string());
-    lines.push("        Ok(())".to_string());
-    lines.push("    }".to_string());
-    lines.push("".to_string());
+        // Opt-in only
+        assert!(!generate(input).contains("arbitrary"));
+    }
 
-    // Deserialize method
-    lines.push("    /// Deserialize from bytes".to_string());
-    lines.push(format!(
-        "    pub fn from_bytes(bytes: &[u8]) -> Result<Self, wincode::ReadError> {{"
-    ));
-    lines.push("        wincode::deserialize(bytes)".to_string());
-    lines.push("    }".to_string());
+    #[test]
+    fn test_present_field_count() {
+        let input = r#"
+            message Tx {
+                uint64 nonce = 1;
+                required byte[32] hash = 2;
+                string memo = 3 [deprecated];
+            }
+            message Empty {}
+            struct Point { int x; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains(
+            "#[allow(deprecated)]\nimpl Tx {\n    \
+             /// Number of fields that are set: every optional field that is `Some`,\n    \
+             /// plus the required fields, which are always present\n    \
+             pub fn present_field_count(&self) -> usize {\n        \
+             self.nonce.is_some() as usize\n            + self.memo.is_some() as usize\n            + 1\n    }\n}"
+        ));
+        assert!(output.contains("pub fn present_field_count(&self) -> usize {\n        0\n    }"));
+        // Struct fields are always present
+        assert_eq!(output.matches("present_field_count").count(), 2);
+    }
 
-    lines.push("}".to_string());
+    #[test]
+    fn test_field_name() {
+        let input = r#"
+            message Transaction {
+                byte[32] txHash = 1;
+                uint64 nonce = 2;
+                string memo = 5 [deprecated];
+            }
+            message Empty {}
+            struct Point { int x; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains(
+            "impl Transaction {\n    \
+             /// The schema name of the field with ID `id`, if there is one\n    \
+             pub const fn field_name(id: u32) -> Option<&'static str> {\n        \
+             match id {\n            1 => Some(\"txHash\"),\n            2 => Some(\"nonce\"),\n            \
+             5 => Some(\"memo\"),\n            _ => None,\n        }\n    }\n}"
+        ));
+        assert!(output.contains(
+            "pub const fn field_name(_id: u32) -> Option<&'static str> {\n        None\n    }"
+        ));
+        assert_eq!(output.matches("fn field_name").count(), 2);
+    }
 
-    lines.join("\n")
-}
+    #[test]
+    fn test_no_clone() {
+        let input = r#"
+            enum Kind { A = 0; }
+            struct Point { int x; int y; }
+            message Blob { Point at = 1; bytes data = 2; }
+        "#;
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let options = GenOptions {
+            no_clone: true,
+            ..GenOptions::default()
+        };
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+        assert!(output.contains("#[derive(Debug, PartialEq, Default, SchemaRead, SchemaWrite)]\n#[repr(C)]\npub struct Point"));
+        assert!(output.contains("#[derive(Debug, PartialEq, Default)]\npub struct Blob"));
+        // Enums are Copy, which needs Clone
+        assert!(output.contains("#[derive(Debug, Clone, Copy, PartialEq"));
+        assert_eq!(output.matches("Clone").count(), 1);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::parser::parse_schema;
-    use crate::tokenizer::tokenize_schema;
+        assert!(generate(input).contains("#[derive(Debug, Clone, PartialEq, Default, SchemaRead, SchemaWrite)]\n#[repr(C)]\npub struct Point"));
+    }
 
-    fn generate(input: &str) -> String {
+    #[test]
+    fn test_eq_ignore_deprecated() {
+        let input = r#"
+            options { eq_ignore_deprecated = true; }
+            message Account { uint64 id = 1; string name = 2 [deprecated]; uint64 balance = 3; }
+            struct Point { int x; int y; }
+        "#;
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let options = GenOptions::from_schema(&schema).unwrap();
+        assert!(options.eq_ignore_deprecated);
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+        assert!(output.contains(
+            "#[derive(Debug, Clone, Default, SchemaRead, SchemaWrite)]\npub struct Account"
+        ));
+        assert!(output.contains(
+            "impl PartialEq for Account {\n    fn eq(&self, other: &Self) -> bool {\n        \
+             self.id == other.id && self.balance == other.balance\n    }\n}"
+        ));
+        // Types without deprecated fields keep the derive
+        assert!(output.contains("#[derive(Debug, Clone, PartialEq, Default, SchemaRead, SchemaWrite)]\n#[repr(C)]\npub struct Point"));
+
+        let default = generate(
+            input
+                .replace("options { eq_ignore_deprecated = true; }", "")
+                .as_str(),
+        );
+        assert!(default.contains("#[derive(Debug, Clone, PartialEq, Default, SchemaRead, SchemaWrite)]\npub struct Account"));
+        assert!(!default.contains("impl PartialEq for Account"));
+    }
+
+    #[test]
+    fn test_codegen_error_location() {
+        // Parsed but not verified, so the unsupported array reaches codegen
+        let input = "struct Point { int x; }\nstruct Names {\n    string[32] names;\n}";
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let err =
+            try_compile_schema_to_rust_with_options(&schema, &GenOptions::default()).unwrap_err();
+        assert_eq!(err.code(), codes::CODEGEN);
+        assert!(matches!(
+            &err,
+            TcsError::CodeGenError { location, line: 3, column: 16, .. } if location == "Names.names"
+        ));
+        assert!(err.to_string().contains(
+            "in Names.names at line 3, column 16: Fixed-size arrays of \"string\" are not supported"
+        ));
+
+        let schema = parse_schema(&tokenize_schema("struct Point { int x; }").unwrap()).unwrap();
+        assert!(try_compile_schema_to_rust_with_options(&schema, &GenOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn test_canonical_bytes() {
+        let output = generate(
+            r#"
+            struct Point { int x; int y; }
+            message Vote { uint64 slot = 2; byte[64] signature = 1; }
+            "#,
+        );
+        // Declaration order is already field-ID order
+        assert!(output.contains(
+            "    pub fn canonical_bytes(&self) -> Vec<u8> {\n        // Fields are declared in field-ID order\n        self.to_bytes()\n    }"
+        ));
+        // Otherwise fields are written one by one, sorted by ID
+        assert!(output
+            .contains("fn write(src: &Vote, out: &mut Vec<u8>) -> wincode::WriteResult<()> {"));
+        let signature = output
+            .find("<Option<[u8; 64]> as wincode::SchemaWrite>::write(writer, &src.signature)?;")
+            .unwrap();
+        let slot = output
+            .find("<Option<u64> as wincode::SchemaWrite>::write(writer, &src.slot)?;")
+            .unwrap();
+        assert!(signature < slot);
+    }
+
+    #[test]
+    fn test_since_note() {
+        let output = generate(
+            r#"
+            message Block {
+                // Current epoch
+                uint64 epoch = 6 @since("2.0");
+                required uint64 slot = 7 @since("2.1");
+            }
+            enum Kind { A = 0; B = 1 @since("1.1"); }
+            "#,
+        );
+        assert!(output.contains(
+            "    /// Current epoch\n    ///\n    /// Since: 2.0\n    pub epoch: Option<u64>,"
+        ));
+        assert!(output.contains(
+            "    /// Required: always present on the wire.\n    ///\n    /// Since: 2.1\n    pub slot: u64,"
+        ));
+        assert!(output.contains("    /// Since: 1.1\n    B = 1,"));
+    }
+
+    #[test]
+    fn test_ord_struct() {
+        let output = generate(
+            r#"
+            enum Kind { A = 0; }
+            enum Other { B = 0; }
+            struct BlockHeader [ord] { uint64 height; byte[32] hash; Kind kind; }
+            "#,
+        );
+        assert!(output.contains(
+            "#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Default, SchemaRead, SchemaWrite)]\n#[repr(C)]\npub struct BlockHeader"
+        ));
+        // Enums used by the struct are ordered too, others are left alone
+        assert!(output.contains(
+            "#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, SchemaRead, SchemaWrite)]\n#[repr(u32)]\npub enum Kind"
+        ));
+        assert!(output.contains(
+            "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, SchemaRead, SchemaWrite)]\n#[repr(u32)]\npub enum Other"
+        ));
+    }
+
+    #[test]
+    fn test_newtypes() {
+        let input = r#"
+            options { newtypes = true; }
+            struct Hash { byte[32] data; }
+            struct Point { int x; int y; }
+        "#;
+        let output = generate(input);
+        assert!(
+            output.contains("#[repr(transparent)]\npub struct Hash {\n    pub data: [u8; 32],\n}")
+        );
+        assert!(output.contains(
+            "impl std::ops::Deref for Hash {\n    type Target = [u8; 32];\n\n    fn deref(&self) -> &[u8; 32] {\n        &self.data\n    }\n}"
+        ));
+        assert!(output.contains(
+            "impl From<[u8; 32]> for Hash {\n    fn from(data: [u8; 32]) -> Self {\n        Self { data }\n    }\n}"
+        ));
+        assert!(output.contains("impl From<Hash> for [u8; 32] {"));
+        // Only single-field structs are newtypes
+        assert!(output.contains("#[repr(C)]\npub struct Point"));
+
+        let default = generate(&input.replace("options { newtypes = true; }", ""));
+        assert!(default.contains("#[repr(C)]\npub struct Hash"));
+        assert!(!default.contains("impl From<[u8; 32]> for Hash"));
+    }
+
+    #[test]
+    fn test_to_array() {
+        let input = r#"
+            options { to_array = true; }
+            struct Point { int x; int y; }
+            struct BlockHeader { uint64 height; byte[32] hash; Point at; }
+            struct Named { string name; }
+            message Block { BlockHeader header = 1; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains(
+            "impl BlockHeader {\n    /// Serialized size of a `BlockHeader`\n    \
+             pub const SIZE: usize = 48;\n\n    \
+             /// Serialize this value into a fixed-size array, without allocating\n    \
+             pub fn to_array(&self) -> [u8; Self::SIZE] {\n        \
+             let mut out = [0u8; Self::SIZE];\n        \
+             let mut writer = out.as_mut_slice();\n        \
+             wincode::serialize_into(&mut writer, self)\n            \
+             .expect(\"serialization should not fail\");\n        out\n    }\n}"
+        ));
+        assert!(output.contains(
+            "impl Point {\n    /// Serialized size of a `Point`\n    pub const SIZE: usize = 8;"
+        ));
+        // Only fixed-size structs get one, and views stay off
+        assert_eq!(output.matches("pub fn to_array").count(), 2);
+        assert!(!output.contains("PointView"));
+
+        let default = generate(&input.replace("options { to_array = true; }", ""));
+        assert!(!default.contains("to_array"));
+    }
+
+    #[test]
+    fn test_checked_setters() {
+        let input = r#"
+            options { checked_setters = true; }
+            struct Pixel { uint8 level; float alpha; }
+            message Sample { required int64 offset = 1; uint8[] levels = 2; uint count = 3; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("pub struct RangeError {"));
+        assert!(output.contains(
+            "    pub fn set_level_checked<T: TryInto<u8>>(&mut self, value: T) -> Result<(), RangeError> {\n        \
+             let value = value.try_into().map_err(|_| RangeError { field: \"level\", type_name: \"uint8\" })?;\n        \
+             self.level = value;"
+        ));
+        assert!(output.contains("self.offset = value;"));
+        assert!(output.contains("self.count = Some(value);"));
+        // Only single integer values get a setter
+        assert!(!output.contains("set_alpha_checked"));
+        assert!(!output.contains("set_levels_checked"));
+
+        let default = generate(&input.replace("options { checked_setters = true; }", ""));
+        assert!(!default.contains("RangeError"));
+    }
+
+    #[test]
+    fn test_derive_serde() {
+        let input = r#"
+            enum Kind { A = 0; }
+            struct Header { byte[32] parent; uint64 height; }
+            message Tx { byte[64] sig = 1; required byte[32] hash = 2; Kind kind = 3; }
+        "#;
         let tokens = tokenize_schema(input).unwrap();
         let schema = parse_schema(&tokens).unwrap();
-        compile_schema_to_rust(&schema)
+        let options = GenOptions {
+            derive_serde: true,
+            ..GenOptions::default()
+        };
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+        let derive =
+            "#[cfg_attr(feature = \"serde\", derive(serde::Serialize, serde::Deserialize))]";
+        assert_eq!(output.matches(derive).count(), 3);
+        assert!(output.contains(
+            "    #[cfg_attr(feature = \"serde\", serde(with = \"serde_hex\"))]\n    \
+             pub parent: [u8; 32],"
+        ));
+        assert!(output.contains(
+            "    #[cfg_attr(feature = \"serde\", serde(with = \"serde_hex::option\"))]\n    \
+             pub sig: Option<[u8; 64]>,"
+        ));
+        assert!(output.contains("serde(with = \"serde_hex\"))]\n    pub hash: [u8; 32],"));
+        assert!(output.contains("    pub height: u64,"));
+        assert_eq!(output.matches("serde(with").count(), 3);
+        assert!(output.contains("#[cfg(feature = \"serde\")]\nmod serde_hex {"));
+
+        // The helper module is only emitted when something uses it
+        let schema = parse_schema(&tokenize_schema("struct Point { int x; }").unwrap()).unwrap();
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+        assert!(output.contains(derive));
+        assert!(!output.contains("serde_hex"));
+
+        // Opt-in only
+        assert!(!generate(input).contains("serde"));
     }
 
     #[test]
-    fn test_generate_simple_struct() {
+    fn test_empty_definitions() {
+        let output = generate("message Ping {}\nstruct Empty {}");
+
+        // The wincode derive rejects structs without fields
+        assert!(
+            output.contains("#[derive(Debug, Clone, PartialEq, Default)]\npub struct Ping {}\n")
+        );
+        assert!(output.contains(
+            "#[derive(Debug, Clone, PartialEq, Default)]\n#[repr(C)]\npub struct Empty {}\n"
+        ));
+        assert!(output.contains(
+            "    fn size_of(_src: &Self) -> wincode::WriteResult<usize> {\n        Ok(0)\n    }"
+        ));
+        assert!(output.contains(
+            "    fn write(_writer: &mut impl Writer, _src: &Self) -> wincode::WriteResult<()> {\n        \
+             Ok(())\n    }"
+        ));
+        assert!(output.contains("        _reader: &mut impl wincode::io::Reader<'de>,"));
+        assert!(output.contains("        dst.write(Self {\n        });"));
+        // Nothing derives, so the derive macros aren't imported
+        assert!(!output.contains("use wincode_derive"));
+        assert!(generate("message Ping {}\nenum Kind { A = 0; }").contains("use wincode_derive"));
+    }
+
+    #[test]
+    fn test_json_value_conversions() {
         let input = r#"
-            struct Point {
-                int x;
-                int y;
+            options { serde = true; }
+            enum Kind { A = 0; }
+            struct Header { uint64 height; }
+            message Tx { Kind kind = 1; }
+        "#;
+        let output = generate(input);
+        let gate = "#[cfg(all(feature = \"serde\", feature = \"serde_json\"))]";
+        assert!(output.contains(&format!("{}\nimpl Header {{", gate)));
+        assert!(output.contains(&format!("{}\nimpl Tx {{", gate)));
+        assert_eq!(output.matches(gate).count(), 2);
+        assert!(output.contains(
+            "    pub fn to_json_value(&self) -> Result<serde_json::Value, serde_json::Error> {\n        \
+             serde_json::to_value(self)\n    }"
+        ));
+        assert!(output.contains(
+            "    pub fn from_json_value(value: &serde_json::Value) -> Result<Self, serde_json::Error> {\n        \
+             <Self as serde::Deserialize>::deserialize(value)\n    }"
+        ));
+
+        // Only with the serde derives to convert through
+        assert!(!generate(&input.replace("options { serde = true; }", "")).contains("serde_json"));
+    }
+
+    #[test]
+    fn test_serde_enum_names() {
+        let input = r#"
+            options { serde = true; serde_enum_names = true; }
+            enum NodeRole { VALIDATOR = 1; ARCHIVER = 2; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains(
+            "    #[cfg_attr(feature = \"serde\", serde(rename = \"VALIDATOR\"))]\n    \
+             #[default]\n    Validator = 1,"
+        ));
+        assert!(output.contains(
+            "    #[cfg_attr(feature = \"serde\", serde(rename = \"ARCHIVER\"))]\n    Archiver = 2,"
+        ));
+        // The discriminants used on the wire are unchanged
+        assert!(output.contains("#[repr(u32)]"));
+
+        // Without serde there is nothing to rename
+        let output = generate(&input.replace("serde = true; ", ""));
+        assert!(!output.contains("serde(rename"));
+    }
+
+    #[test]
+    fn test_explicit_discriminants() {
+        let input = r#"
+            enum Kind { A = 0; B = 1; C = 2; OTHER = C; }
+            enum Role { VALIDATOR = 1; ARCHIVER = 2; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains("    #[default]\n    A = 0,\n    B = 1,\n    C = 2,"));
+        assert!(output.contains("    Validator = 1,"));
+
+        let output = generate(&format!(
+            "options {{ explicit_discriminants = false; }}\n{}",
+            input
+        ));
+        assert!(output.contains("    #[default]\n    A,\n    B,\n    C,\n}"));
+        assert!(output.contains("pub const OTHER: Self = Self::C;"));
+        // Values Rust wouldn't assign itself stay explicit
+        assert!(output.contains("    #[default]\n    Validator = 1,\n    Archiver = 2,"));
+    }
+
+    #[test]
+    fn test_generate_validate() {
+        let input = r#"
+            struct Fee { uint8 rate @validate(min = 1, max = 50); int[] tiers @validate(len_min = 1); }
+            message Transfer {
+                uint64 amount = 1 @validate(min = 0, max = 1_000_000);
+                required int delta = 2 @validate(min = -10);
+                string[] memos = 3 @validate(len_max = 2);
             }
+            struct Point { int x; int y; }
         "#;
         let output = generate(input);
-        assert!(output.contains("pub struct Point"));
-        assert!(output.contains("pub x: i32"));
-        assert!(output.contains("pub y: i32"));
-        assert!(output.contains("SchemaRead, SchemaWrite"));
-        assert!(output.contains("#[repr(C)]"));
+        assert!(output.contains("pub struct ValidationError {"));
+        assert!(output.contains(
+            "    pub fn validate(&self) -> Result<(), ValidationError> {\n        \
+             if self.rate < 1 {\n            \
+             return Err(ValidationError { field: \"rate\", bound: \"min\" });\n        }\n        \
+             if self.rate > 50 {\n            \
+             return Err(ValidationError { field: \"rate\", bound: \"max\" });\n        }\n        \
+             if self.tiers.is_empty() {"
+        ));
+        // Optional fields are only checked when present; `min = 0` can't fail
+        assert!(output.contains("        if self.amount.is_some_and(|v| v > 1000000) {\n"));
+        assert!(!output.contains("bound: \"min\" });\n        }\n        if self.amount"));
+        assert!(output.contains("        if self.delta < -10 {\n"));
+        assert!(output.contains("        if self.memos.as_ref().is_some_and(|v| v.len() > 2) {\n"));
+        // Types without bounds get no validate method
+        assert_eq!(output.matches("pub fn validate(").count(), 2);
+
+        let plain = generate("struct Point { int x; int y; }");
+        assert!(!plain.contains("ValidationError"));
     }
 
     #[test]
-    fn test_generate_fixed_byte_array() {
+    fn test_doc_stubs() {
         let input = r#"
-            struct BlockHeader {
-                byte[32] prevHash;
-                byte[32] merkleRoot;
+            options { doc_stubs = true; views = true; rust_module = "wire"; }
+            package chain;
+            # Roles a node can take
+            enum Role { VALIDATOR = 1; ARCHIVER = 2; OTHER = ARCHIVER; }
+            enum Perm [flags] { READ = 1; WRITE = 2; }
+            struct Point { int x; int y @validate(max = 10); }
+            message Tx { required uint64 nonce = 1; Point at = 2 @since("2"); }
+        "#;
+        let output = generate(input);
+        let lines: Vec<&str> = output.lines().map(str::trim_start).collect();
+        for (i, line) in lines.iter().enumerate() {
+            if !line.starts_with("pub ") {
+                continue;
             }
+            let doc = lines[..i].iter().rev().find(|l| !l.starts_with("#["));
+            assert!(
+                doc.is_some_and(|l| l.starts_with("///")),
+                "undocumented: {}",
+                line
+            );
+        }
+        assert!(output.contains("/// Types generated from a TCS schema\npub mod wire {"));
+        assert!(output.contains("/// Types of the `chain` package\n#[allow("));
+        // Schema comments are kept; only undocumented items get a stub
+        assert!(output.contains("/// Roles a node can take\n#[derive("));
+        assert!(
+            output.contains("    /// The `VALIDATOR` variant.\n    #[default]\n    Validator = 1,")
+        );
+        assert!(output.contains("    /// Alias of `ARCHIVER`.\n    pub const OTHER"));
+        assert!(output.contains("    /// The `READ` flag.\n    pub const READ"));
+        assert!(output.contains("/// The `Point` struct.\n#[derive("));
+        assert!(output.contains("    /// The `x` field.\n    pub x: i32,"));
+        assert!(output.contains("    /// The `x` field.\n    pub fn x(&self) -> i32 {"));
+        assert!(output.contains("/// Required: always present on the wire.\n    pub nonce: u64,"));
+        assert!(output.contains("/// Since: 2\n    pub at: Option<Point>,"));
+
+        let default = generate(&input.replace("doc_stubs = true; ", ""));
+        assert!(!default.contains("/// The `"));
+        assert!(!default.contains("Alias of"));
+    }
+
+    #[test]
+    fn test_hex_debug() {
+        let input = r#"
+            options { hex_debug = true; }
+            struct Point { int x; int y; }
+            struct BlockHeader { uint64 height; byte[32] hash; }
+            message Transaction { byte[] data = 1; Point at = 2; }
         "#;
         let output = generate(input);
-        assert!(output.contains("pub prev_hash: [u8; 32]"));
-        assert!(output.contains("pub merkle_root: [u8; 32]"));
+        assert!(output.contains("struct HexDebug<'a>(&'a [u8]);"));
+        assert!(output.contains("write!(f, \"{:02x}\", byte)?;"));
+        assert!(output.contains("#[derive(Clone, PartialEq, Default, SchemaRead, SchemaWrite)]\n#[repr(C)]\npub struct BlockHeader"));
+        assert!(output.contains(
+            "impl std::fmt::Debug for BlockHeader {\n    \
+             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {\n        \
+             f.debug_struct(\"BlockHeader\")\n            \
+             .field(\"height\", &self.height)\n            \
+             .field(\"hash\", &HexDebug(&self.hash))\n            \
+             .finish()\n    }\n}"
+        ));
+        assert!(
+            output.contains(".field(\"data\", &self.data.as_ref().map(|bytes| HexDebug(bytes)))")
+        );
+        // Types without byte arrays keep the derive
+        assert!(output.contains("#[derive(Debug, Clone, PartialEq, Default, SchemaRead, SchemaWrite)]\n#[repr(C)]\npub struct Point"));
+        assert!(!output.contains("impl std::fmt::Debug for Point"));
+
+        let default = generate(&input.replace("options { hex_debug = true; }", ""));
+        assert!(!default.contains("HexDebug"));
     }
 
     #[test]
-    fn test_generate_message() {
+    fn test_explicit_default() {
         let input = r#"
+            options { explicit_default = true; }
+            struct Point { int x; int y; }
             message Transaction {
-                byte[32] txHash = 1;
-                uint64 nonce = 2;
-                byte[] data = 3;
+                required uint64 nonce = 1;
+                byte[32] hash = 2;
+                Point at = 3;
             }
         "#;
         let output = generate(input);
-        assert!(output.contains("pub struct Transaction"));
-        assert!(output.contains("pub tx_hash: Option<[u8; 32]>"));
-        assert!(output.contains("pub nonce: Option<u64>"));
-        assert!(output.contains("pub data: Option<Vec<u8>>"));
-        // Messages don't have repr(C)
-        assert!(!output.contains("#[repr(C)]") || output.matches("#[repr(C)]").count() == 0);
+        assert!(output.contains(
+            "#[derive(Debug, Clone, PartialEq, SchemaRead, SchemaWrite)]\npub struct Transaction"
+        ));
+        assert!(output.contains(
+            "#[allow(clippy::derivable_impls)]\nimpl Default for Transaction {\n    fn default() -> Self {\n        Self {\n            \
+             nonce: Default::default(),\n            hash: None,\n            at: None,\n        }\n    }\n}"
+        ));
+        // Structs keep deriving it
+        assert!(output.contains("#[derive(Debug, Clone, PartialEq, Default, SchemaRead, SchemaWrite)]\n#[repr(C)]\npub struct Point"));
+
+        // Setting a deprecated field isn't a use worth warning about
+        let output = generate(&input.replace("Point at = 3;", "Point at = 3 [deprecated];"));
+        assert!(output.contains("#[allow(deprecated)]\nimpl Default for Transaction {"));
+
+        let default = generate(&input.replace("explicit_default = true; ", ""));
+        assert!(default.contains("Default, SchemaRead, SchemaWrite)]\npub struct Transaction"));
+        assert!(!default.contains("impl Default"));
     }
 
     #[test]
-    fn test_generate_enum() {
+    fn test_generate_views() {
         let input = r#"
-            enum NodeRole {
-                STORAGE = 1;
-                VALIDATOR = 2;
-                LIGHT = 3;
+            options { views = true; }
+            struct Point { int x; int y; }
+            struct BlockHeader {
+                uint64 height;
+                bool done;
+                byte[32] hash;
+                Point at;
             }
+            struct Batch { Point[] points; }
         "#;
         let output = generate(input);
-        assert!(output.contains("pub enum NodeRole"));
-        assert!(output.contains("#[repr(u32)]"));
-        assert!(output.contains("Storage = 1"));
-        assert!(output.contains("Validator = 2"));
-        assert!(output.contains("#[default]"));
+        assert!(output.contains("pub struct BlockHeaderView<'a>(&'a [u8]);"));
+        assert!(output.contains("    pub const SIZE: usize = 49;"));
+        assert!(output.contains(
+            "    pub fn height(&self) -> u64 {\n        \
+             u64::from_le_bytes(self.0[0..8].try_into().unwrap())\n    }"
+        ));
+        assert!(output.contains("    pub fn done(&self) -> bool {\n        self.0[8] != 0\n    }"));
+        assert!(output.contains(
+            "    pub fn hash(&self) -> &'a [u8; 32] {\n        self.0[9..41].try_into().unwrap()\n    }"
+        ));
+        assert!(output.contains(
+            "    pub fn at(&self) -> PointView<'a> {\n        PointView::new(&self.0[41..49]).unwrap()\n    }"
+        ));
+        // Variable-size structs don't get a view
+        assert!(!output.contains("BatchView"));
+
+        let default = generate(&input.replace("options { views = true; }", ""));
+        assert!(!default.contains("View"));
+    }
+    #[test]
+    fn test_deprecated_definition() {
+        let input = r#"
+            package chain;
+            // Replaced by Receipt
+            message OldReceipt [deprecated] { uint64 id = 1; }
+            enum Kind [deprecated] { A = 0; }
+            struct Point { int x; }
+        "#;
+        let output = generate(input);
+        assert!(output.contains(
+            "/// Replaced by Receipt\n#[deprecated]\n#[derive(Debug, Clone, PartialEq, Default, SchemaRead, SchemaWrite)]\npub struct OldReceipt"
+        ));
+        assert!(output.contains("#[deprecated]\n#[derive(Debug, Clone, Copy"));
+        assert_eq!(output.matches("#[deprecated]").count(), 2);
+        assert!(output.contains("#[allow(dead_code, deprecated)]\npub mod chain"));
+
+        // The module only allows deprecation warnings when it needs to
+        let output = generate("struct Point { int x; }");
+        assert!(!output.contains("deprecated"));
+
+        // Without a package each item allows it, in place of its own allow
+        let output = generate(&input.replace("package chain;", ""));
+        assert!(output.contains("#[allow(dead_code, deprecated)]\nimpl OldReceipt {"));
+        assert!(!output.contains("#[allow(deprecated)]"));
     }
 
     #[test]
-    fn test_generate_with_package() {
+    fn test_sectioned_output() {
         let input = r#"
-            package tapedrive;
+            package chain;
+            enum Kind { A = 0; B = 1; }
+            struct Point { int x; int y; }
+            enum Perm [flags] { READ = 1; WRITE = 2; }
+            message Blob { bytes data = 1; Point at = 2; }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let options = GenOptions {
+            sectioned_output: true,
+            ..GenOptions::default()
+        };
+        let output = compile_schema_to_rust_with_options(&schema, &options);
 
-            struct Blob {
-                byte[] data;
+        let first_impl = output.find("\nimpl").unwrap();
+        let last_struct = output.rfind("pub struct ").unwrap();
+        assert!(last_struct < first_impl);
+        assert!(output.find("pub enum Kind").unwrap() < first_impl);
+
+        let types = output.find("// ---- Types ----").unwrap();
+        let impls = output.find("// ---- Impls ----").unwrap();
+        let consts = output.find("// ---- Constants ----").unwrap();
+        assert!(types < impls && impls < consts);
+        assert!(output.find("pub const FIELD_COUNT").unwrap() > consts);
+        assert!(output.find("pub fn to_bytes").unwrap() < consts);
+
+        // Nothing is lost, just moved; Perm, Point and Blob each get a
+        // separate consts impl block
+        let default = generate(input);
+        for item in ["pub const ", "pub fn "] {
+            assert_eq!(output.matches(item).count(), default.matches(item).count());
+        }
+        assert_eq!(
+            output.matches("\nimpl ").count(),
+            default.matches("\nimpl ").count() + 3
+        );
+    }
+
+    #[test]
+    fn test_schema_options() {
+        let input = r#"
+            package chain;
+            options {
+                no_std = true;
+                rust_module = "protocol";
             }
+            struct Point { int x [key]; string label; }
+            message Blob { Point at = 1; }
         "#;
         let output = generate(input);
-        assert!(output.contains("pub mod tapedrive {"));
-        assert!(output.contains("}"));
+        assert!(output.contains("pub mod protocol {\n\n#[allow(dead_code)]\npub mod chain {"));
+        assert!(output.contains("use alloc::{string::String, vec::Vec};"));
+        assert!(output.contains("impl core::hash::Hash for Point {"));
+        assert!(!output.contains("std::"));
+        assert!(output.trim_end().ends_with("}\n}"));
+
+        // Explicit options replace the schema's
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let output = compile_schema_to_rust_with_options(&schema, &GenOptions::default());
+        assert!(output.contains("impl std::hash::Hash for Point {"));
+        assert!(!output.contains("use alloc"));
+
+        let mut options = GenOptions::from_schema(&schema).unwrap();
+        assert!(options.no_std);
+        assert_eq!(options.rust_module.as_deref(), Some("protocol"));
+        options.no_std = false;
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+        assert!(output.contains("pub mod protocol {"));
+        assert!(!output.contains("use alloc"));
     }
 
     #[test]
-    fn test_rust_keyword_escape() {
+    fn test_invalid_schema_options() {
+        for (input, msg) in [
+            ("options { nostd = true; }", "Unknown option \"nostd\""),
+            (
+                "options { no_std = \"yes\"; }",
+                "The option \"no_std\" expects true or false, not \"yes\"",
+            ),
+            (
+                "options { rust_module = true; }",
+                "The option \"rust_module\" expects a string, not true",
+            ),
+        ] {
+            let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+            let err = GenOptions::from_schema(&schema).unwrap_err();
+            assert_eq!(err.code(), crate::error::codes::INVALID_OPTION);
+            assert!(err.to_string().contains(msg), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_embed_source() {
+        let input = "package chain;\n\n// A point\nstruct Point { int x; }\n";
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let options = GenOptions {
+            embed_source: Some(input.to_string()),
+            ..GenOptions::default()
+        };
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+        let embedded = output
+            .split_once("pub const SCHEMA_SOURCE: &str = r\"")
+            .and_then(|(_, rest)| rest.split_once("\";\n"))
+            .map(|(source, _)| source);
+        assert_eq!(embedded, Some(input));
+        assert!(output.contains(
+            "/// The schema this file was generated from\n#[allow(dead_code)]\npub const SCHEMA_SOURCE: &str = r\"package chain;"
+        ));
+
+        // Quotes followed by `#` need more hashes to stay inside the literal
+        assert_eq!(raw_string_literal("a \"b\" c"), "r#\"a \"b\" c\"#");
+        assert_eq!(
+            raw_string_literal("x = \"y\"# z \"##"),
+            "r###\"x = \"y\"# z \"##\"###"
+        );
+        assert!(!generate(input).contains("SCHEMA_SOURCE"));
+    }
+
+    #[test]
+    fn test_custom_wincode_path() {
         let input = r#"
-            struct Config {
-                string type;
-                int match;
-            }
+            struct Point { int x; int y; }
+            message Blob { bytes data = 1; }
+            enum Kind { A = 0; B = 5; }
+            enum Perm [flags] { READ = 1; }
         "#;
-        let output = generate(input);
-        assert!(output.contains("pub type_: String"));
-        assert!(output.contains("pub match_: i32"));
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let options = GenOptions {
+            wincode_path: "crate::vendor::wincode".to_string(),
+            ..GenOptions::default()
+        };
+        let output = compile_schema_to_rust_with_options(&schema, &options);
+        assert!(output.contains("use crate::vendor::wincode::io::Writer;"));
+        assert!(output.contains("crate::vendor::wincode::serialize_into(&mut writer, self)"));
+        assert!(output.contains("crate::vendor::wincode::deserialize(bytes)"));
+        assert!(output.contains("impl crate::vendor::wincode::SchemaWrite for Blob {"));
+        // The derives expand to `::wincode`, so every type is written out by hand
+        assert!(!output.contains("SchemaRead, SchemaWrite"));
+        for name in ["Point", "Blob", "Kind", "Perm"] {
+            assert!(output.contains(&format!(
+                "impl<'de> crate::vendor::wincode::SchemaRead<'de> for {} {{",
+                name
+            )));
+        }
+        // Enums are tagged with the variant index, like the derive
+        assert!(output.contains("            Self::B => 1,"));
+        assert!(output.contains("            1 => Self::B,"));
+        assert!(
+            output.contains("<u32 as crate::vendor::wincode::SchemaWrite>::write(writer, &src.0)")
+        );
+        assert!(!output.contains("wincode_derive"));
+        assert!(!output.contains(" wincode::"));
+        assert!(!output.contains("(wincode::"));
     }
 }
@@ -5,21 +5,37 @@
 //! - Parsing into an AST
 //! - Schema verification
 //! - Rust code generation with wincode derives
+//! - JSON Schema generation
+//! - Schema reports (statistics and fingerprint)
 
 pub mod error;
 pub mod formatter;
+pub mod gen_json_schema;
 pub mod gen_rust;
+mod json;
 pub mod parser;
+pub mod schema_info;
 pub mod tokenizer;
 pub mod utils;
 pub mod verifier;
 
-pub use error::TcsError;
-pub use formatter::format_schema;
-pub use gen_rust::compile_schema_to_rust;
+use tcs_schema::Schema;
+
+pub use error::{Severity, TcsError, Warning};
+pub use formatter::{diff_lines, format_schema, format_schema_with_options, FormatOptions};
+pub use gen_json_schema::compile_schema_to_json_schema;
+pub use gen_rust::{
+    compile_schema_to_rust, compile_schema_to_rust_with_options,
+    try_compile_schema_to_rust_with_options, GenOptions,
+};
 pub use parser::parse_schema;
+pub use schema_info::{schema_hash, schema_info_json};
 pub use tokenizer::tokenize_schema;
-pub use verifier::verify_schema;
+pub use verifier::{
+    check_format_roundtrip, lint_schema, verify_gen_options, verify_schema,
+    verify_schema_against_baseline, verify_schema_with_limits, verify_schema_with_options,
+    verify_schema_with_warnings, VerifyLimits, VerifyOptions,
+};
 
 /// Compile a TCS schema string to Rust code
 ///
@@ -29,10 +45,45 @@ pub use verifier::verify_schema;
 /// 3. Verification
 /// 4. Code generation
 pub fn compile(source: &str) -> Result<String, TcsError> {
+    compile_detailed(source).map(|(_, code)| code)
+}
+
+/// Compile a TCS schema string to Rust code with the given generation options.
+/// These replace the schema's own `options` block rather than extending it.
+pub fn compile_with_options(source: &str, options: &GenOptions) -> Result<String, TcsError> {
+    compile_detailed_with_options(source, options).map(|(_, code)| code)
+}
+
+/// Compile a TCS schema string, returning the verified schema along with the
+/// generated Rust code
+pub fn compile_detailed(source: &str) -> Result<(Schema, String), TcsError> {
     let tokens = tokenize_schema(source)?;
     let schema = parse_schema(&tokens)?;
-    verify_schema(&schema)?;
-    Ok(compile_schema_to_rust(&schema))
+    let code = generate(&schema, &GenOptions::from_schema(&schema)?)?;
+    Ok((schema, code))
+}
+
+/// Like [`compile_detailed`], with the given generation options
+pub fn compile_detailed_with_options(
+    source: &str,
+    options: &GenOptions,
+) -> Result<(Schema, String), TcsError> {
+    let tokens = tokenize_schema(source)?;
+    let schema = parse_schema(&tokens)?;
+    let code = generate(&schema, options)?;
+    Ok((schema, code))
+}
+
+/// Verify an in-memory schema and generate Rust code for it
+///
+/// This is the entry point for schemas that don't come from a single source
+/// string, e.g. ones built in code or combined with [`Schema::merge`].
+/// Unlike [`compile_schema_to_rust_with_options`], it refuses to generate
+/// code for a schema that doesn't verify.
+pub fn generate(schema: &Schema, options: &GenOptions) -> Result<String, TcsError> {
+    verify_schema(schema)?;
+    verify_gen_options(schema, options)?;
+    try_compile_schema_to_rust_with_options(schema, options)
 }
 
 #[cfg(test)]
@@ -89,6 +140,99 @@ mod tests {
         assert!(code.contains("SchemaRead, SchemaWrite"));
     }
 
+    #[test]
+    fn test_empty_message() {
+        let input = r#"
+            message Ping {}
+            message Heartbeat { Ping ping = 1; uint64 at = 2; }
+        "#;
+
+        let code = compile(input).unwrap();
+        assert!(code.contains("pub struct Ping {}"));
+        assert!(code.contains("impl wincode::SchemaWrite for Ping {"));
+        assert!(code.contains("pub const FIELD_COUNT: usize = 0;"));
+        assert!(code.contains("pub ping: Option<Ping>,"));
+    }
+
+    #[test]
+    fn test_compile_detailed() {
+        let input = r#"
+            enum Status { OK = 0; }
+            struct Point { int x; int y; }
+            message Job { Status status = 1; Point at = 2; }
+        "#;
+        let (schema, code) = compile_detailed(input).unwrap();
+        assert_eq!(schema.definitions.len(), 3);
+        assert_eq!(schema.definitions[2].name, "Job");
+        assert!(code.contains("pub struct Job"));
+        assert!(code.contains("pub at: Option<Point>"));
+        assert_eq!(compile(input).unwrap(), code);
+    }
+
+    #[test]
+    fn test_generate_from_schema() {
+        use tcs_schema::{Definition, DefinitionKind, Field, Presence};
+
+        let field = |name: &str, type_: &str, field_id| Field {
+            name: name.to_string(),
+            line: 0,
+            column: 0,
+            type_: Some(type_.to_string()),
+            is_array: false,
+            array_size: None,
+            array_size_text: None,
+            is_deprecated: false,
+            is_key: false,
+            field_id,
+            field_id_text: None,
+            alias_of: None,
+            comments: Vec::new(),
+            floating_comments: Vec::new(),
+            trailing_comment: None,
+            presence: Presence::Implicit,
+            since: None,
+            validate: None,
+            unknown_annotations: Vec::new(),
+        };
+        let mut schema = Schema::new();
+        schema.package = Some("chain".to_string());
+        schema.definitions.push(Definition {
+            name: "Transfer".to_string(),
+            line: 0,
+            column: 0,
+            end_line: 0,
+            end_column: 0,
+            kind: DefinitionKind::Message,
+            fields: vec![field("type", "uint64", 1), field("memo", "string", 2)],
+            comments: Vec::new(),
+            floating_comments: Vec::new(),
+            trailing_comment: None,
+            end_comments: Vec::new(),
+            package: None,
+            is_flags: false,
+            is_deprecated: false,
+            is_ord: false,
+            parent: None,
+            feature: None,
+        });
+
+        let options = GenOptions {
+            raw_identifiers: true,
+            sectioned_output: true,
+            allowed_lints: Vec::new(),
+            ..GenOptions::default()
+        };
+        let code = generate(&schema, &options).unwrap();
+        assert!(code.contains("pub mod chain {"));
+        assert!(code.contains("pub r#type: Option<u64>,"));
+        assert!(code.contains("// ---- Constants ----"));
+        assert!(!code.contains("#[allow("));
+
+        // Unlike compile_schema_to_rust, the schema is verified first
+        schema.definitions[0].fields[1].type_ = Some("Unknown".to_string());
+        assert!(generate(&schema, &options).is_err());
+    }
+
     #[test]
     fn test_error_on_undefined_type() {
         let input = r#"
@@ -2,36 +2,283 @@
 
 use lazy_static::lazy_static;
 use regex::Regex;
-use tcs_schema::{Definition, DefinitionKind, Field, Schema};
+use tcs_schema::{
+    Annotation, Definition, DefinitionKind, Field, OptionValue, Presence, Schema, SchemaOption,
+    TypeAlias, Validation,
+};
 
 use crate::error::TcsError;
-use crate::tokenizer::Token;
+use crate::tokenizer::{comment_text, Token};
 use crate::utils::{error, quote};
 
 lazy_static! {
     static ref IDENTIFIER: Regex = Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*$").unwrap();
+    static ref TYPE_NAME: Regex =
+        Regex::new(r"^[A-Za-z_][A-Za-z0-9_]*(\.[A-Za-z_][A-Za-z0-9_]*)?$").unwrap();
     static ref EQUALS: Regex = Regex::new(r"^=$").unwrap();
     static ref SEMICOLON: Regex = Regex::new(r"^;$").unwrap();
-    static ref INTEGER: Regex = Regex::new(r"^-?\d+$").unwrap();
+    static ref INTEGER: Regex = Regex::new(r"^-?\d+(_\d+)*$").unwrap();
     static ref LEFT_BRACE: Regex = Regex::new(r"^\{$").unwrap();
     static ref RIGHT_BRACE: Regex = Regex::new(r"^\}$").unwrap();
     static ref ARRAY_TOKEN: Regex = Regex::new(r"^\[\]$").unwrap();
-    static ref FIXED_ARRAY_TOKEN: Regex = Regex::new(r"^\[(\d+)\]$").unwrap();
+    static ref FIXED_ARRAY_TOKEN: Regex = Regex::new(r"^\[(\d+(?:_\d+)*)\]$").unwrap();
     static ref ENUM_KEYWORD: Regex = Regex::new(r"^enum$").unwrap();
     static ref STRUCT_KEYWORD: Regex = Regex::new(r"^struct$").unwrap();
     static ref MESSAGE_KEYWORD: Regex = Regex::new(r"^message$").unwrap();
+    static ref DEFINITION_KEYWORD: Regex = Regex::new(r"^(enum|struct|message)$").unwrap();
     static ref PACKAGE_KEYWORD: Regex = Regex::new(r"^package$").unwrap();
+    static ref OPTIONS_KEYWORD: Regex = Regex::new(r"^options$").unwrap();
+    static ref TYPE_KEYWORD: Regex = Regex::new(r"^type$").unwrap();
+    static ref TRUE_KEYWORD: Regex = Regex::new(r"^true$").unwrap();
+    static ref FALSE_KEYWORD: Regex = Regex::new(r"^false$").unwrap();
+    static ref STRING: Regex = Regex::new(r#"^"[^"\n]*"$"#).unwrap();
+    static ref REQUIRED_KEYWORD: Regex = Regex::new(r"^required$").unwrap();
+    static ref OPTIONAL_KEYWORD: Regex = Regex::new(r"^optional$").unwrap();
+    static ref REPEATED_KEYWORD: Regex = Regex::new(r"^repeated$").unwrap();
     static ref DEPRECATED_TOKEN: Regex = Regex::new(r"^\[deprecated\]$").unwrap();
+    static ref DEPRECATED_KEYWORD: Regex = Regex::new(r"^deprecated$").unwrap();
+    static ref KEY_TOKEN: Regex = Regex::new(r"^\[key\]$").unwrap();
+    static ref FLAGS_TOKEN: Regex = Regex::new(r"^\[flags\]$").unwrap();
+    static ref ORD_TOKEN: Regex = Regex::new(r"^\[ord\]$").unwrap();
+    static ref FEATURE_TOKEN: Regex = Regex::new(r#"^\[feature *= *"([^"\n]*)"\]$"#).unwrap();
+    static ref NAMED_ANNOTATION: Regex =
+        Regex::new(r#"^@([A-Za-z_][A-Za-z0-9_]*)(?:\("([^"\n]*)"\)|\(([^()"\n]*)\))?$"#).unwrap();
     static ref EOF: Regex = Regex::new(r"^$").unwrap();
 }
 
+/// An integer token's digits without its `_` separators (`1_000` -> `1000`)
+fn strip_digit_separators(text: &str) -> String {
+    text.replace('_', "")
+}
+
+/// Parse the argument list of `@validate(min = 0, max = 100)`: `min`/`max`
+/// bounds on an integer and `len_min`/`len_max` on an array's length
+fn parse_validation(args: &str, tok: &Token) -> Result<Validation, TcsError> {
+    let usage = "@validate needs bounds, e.g. @validate(min = 0, max = 100)";
+    if args.trim().is_empty() {
+        return Err(error(usage, tok.line, tok.column));
+    }
+    let mut validation = Validation::default();
+    for arg in args.split(',') {
+        let Some((key, value)) = arg.split_once('=') else {
+            return Err(error(usage, tok.line, tok.column));
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let number = Some(value)
+            .filter(|v| INTEGER.is_match(v))
+            .and_then(|v| strip_digit_separators(v).parse::<i128>().ok())
+            .ok_or_else(|| {
+                error(
+                    &format!("Invalid @validate bound {}", quote(value)),
+                    tok.line,
+                    tok.column,
+                )
+            })?;
+        let length = || {
+            usize::try_from(number).map_err(|_| {
+                error(
+                    &format!("Invalid length {} for {}", quote(value), quote(key)),
+                    tok.line,
+                    tok.column,
+                )
+            })
+        };
+        let text = Some(value.to_string());
+        let duplicate = match key {
+            "min" => {
+                validation.min_text = text;
+                validation.min.replace(number).is_some()
+            }
+            "max" => {
+                validation.max_text = text;
+                validation.max.replace(number).is_some()
+            }
+            "len_min" => {
+                validation.len_min_text = text;
+                validation.len_min.replace(length()?).is_some()
+            }
+            "len_max" => {
+                validation.len_max_text = text;
+                validation.len_max.replace(length()?).is_some()
+            }
+            _ => {
+                return Err(error(
+                    &format!(
+                        "Unknown @validate bound {} (expected min, max, len_min or len_max)",
+                        quote(key)
+                    ),
+                    tok.line,
+                    tok.column,
+                ))
+            }
+        };
+        if duplicate {
+            return Err(error(
+                &format!("Duplicate @validate bound {}", quote(key)),
+                tok.line,
+                tok.column,
+            ));
+        }
+    }
+    Ok(validation)
+}
+
+/// Split off the file header: the `//!` comment lines before anything else in
+/// the file. Returns the header text, without markers, and the other tokens.
+fn split_header(tokens: &[Token]) -> (Option<String>, &[Token]) {
+    let len = tokens
+        .iter()
+        .take_while(|t| t.text.starts_with("//!"))
+        .count();
+    if len == 0 {
+        return (None, tokens);
+    }
+    let lines: Vec<&str> = tokens[..len]
+        .iter()
+        .map(|t| {
+            let text = &t.text["//!".len()..];
+            text.strip_prefix(' ').unwrap_or(text).trim_end()
+        })
+        .collect();
+    (Some(lines.join("\n")), &tokens[len..])
+}
+
+/// The comments around one non-comment token
+#[derive(Debug, Default)]
+struct TokenComments {
+    /// Comment blocks above the token that don't end directly above it
+    floating: Vec<Vec<String>>,
+    /// The block of comment lines ending on the line directly above the token
+    attached: Vec<String>,
+    /// The comment after the token on the same line
+    trailing: Option<String>,
+}
+
+/// Comments of the token stream, handed out to the declarations they belong
+/// to as the parser reaches them. Comments no declaration claims, like ones
+/// inside a field or an `options` block, go to the next declaration as
+/// floating blocks, so none are lost.
+struct Comments {
+    /// One entry per non-comment token, plus one for the end of the stream
+    tokens: Vec<TokenComments>,
+    /// Comments of the tokens before this one have all been handed out
+    next: usize,
+}
+
+impl Comments {
+    /// Floating and attached comments of a declaration starting at token
+    /// `index`. Unclaimed comments before it are added to the floating ones.
+    fn leading(&mut self, index: usize) -> (Vec<Vec<String>>, Vec<String>) {
+        let mut floating = self.unclaimed(index);
+        let tok = &mut self.tokens[index];
+        floating.append(&mut tok.floating);
+        (floating, std::mem::take(&mut tok.attached))
+    }
+
+    /// Every unclaimed comment before token `index`, as floating blocks: the
+    /// comments at the end of a definition's body or of the file
+    fn closing(&mut self, index: usize) -> Vec<Vec<String>> {
+        let (mut floating, attached) = self.leading(index);
+        if !attached.is_empty() {
+            floating.push(attached);
+        }
+        floating
+    }
+
+    /// The comment after token `index` on the same line
+    fn trailing(&mut self, index: usize) -> Option<String> {
+        self.tokens[index].trailing.take()
+    }
+
+    /// Every comment of the tokens from `next` up to `end` not handed out yet
+    fn unclaimed(&mut self, end: usize) -> Vec<Vec<String>> {
+        let mut blocks = Vec::new();
+        for tok in &mut self.tokens[self.next.min(end)..end] {
+            blocks.append(&mut tok.floating);
+            if !tok.attached.is_empty() {
+                blocks.push(std::mem::take(&mut tok.attached));
+            }
+            if let Some(trailing) = tok.trailing.take() {
+                blocks.push(vec![trailing]);
+            }
+        }
+        self.next = self.next.max(end);
+        blocks
+    }
+}
+
+/// A comment's text, without its marker, one leading space or trailing whitespace
+fn comment_line(tok: &Token) -> String {
+    let text = comment_text(&tok.text).unwrap_or_default();
+    text.strip_prefix(' ')
+        .unwrap_or(text)
+        .trim_end()
+        .to_string()
+}
+
+/// Separate comment tokens from the rest of the stream.
+///
+/// Returns the non-comment tokens along with the comments around each of
+/// them. A block of contiguous comment lines is attached to the token on the
+/// line directly below it; blocks followed by a blank line are floating. A
+/// comment after code on the same line trails the last token before it.
+fn split_comments(tokens: &[Token]) -> (Vec<Token>, Comments) {
+    let mut kept: Vec<Token> = Vec::new();
+    let mut comments = Comments {
+        tokens: vec![TokenComments::default()],
+        next: 0,
+    };
+    let mut pending: Vec<&Token> = Vec::new();
+
+    fn block(pending: &mut Vec<&Token>) -> Vec<String> {
+        pending.drain(..).map(comment_line).collect()
+    }
+
+    for tok in tokens {
+        // The entry for the next non-comment token, or the end of the stream
+        let current = comments.tokens.last_mut().expect("always one entry");
+        if comment_text(&tok.text).is_some() {
+            if pending.is_empty() && kept.last().is_some_and(|last| last.line == tok.line) {
+                // Trailing comment after code on the same line
+                let previous = comments.tokens.len() - 2;
+                comments.tokens[previous].trailing = Some(comment_line(tok));
+                continue;
+            }
+            if pending.last().is_some_and(|prev| prev.line + 1 != tok.line) {
+                current.floating.push(block(&mut pending));
+            }
+            pending.push(tok);
+            continue;
+        }
+
+        if pending.last().is_some_and(|prev| prev.line + 1 == tok.line) {
+            current.attached = block(&mut pending);
+        } else if !pending.is_empty() {
+            current.floating.push(block(&mut pending));
+        }
+        kept.push(tok.clone());
+        comments.tokens.push(TokenComments::default());
+    }
+    if !pending.is_empty() {
+        let current = comments.tokens.last_mut().expect("always one entry");
+        current.floating.push(block(&mut pending));
+    }
+
+    (kept, comments)
+}
+
 /// Parse tokens into a Schema AST
 pub fn parse_schema(tokens: &[Token]) -> Result<Schema, TcsError> {
+    let (header, tokens) = split_header(tokens);
+    let (tokens, mut comments) = split_comments(tokens);
+    let tokens = &tokens[..];
     let mut definitions = Vec::new();
     let mut package_text = None;
+    let mut options: Vec<SchemaOption> = Vec::new();
+    let mut aliases: Vec<TypeAlias> = Vec::new();
     let mut index = 0;
 
-    fn current_token<'a>(tokens: &'a [Token], index: usize) -> &'a Token {
+    fn current_token(tokens: &[Token], index: usize) -> &Token {
         tokens.get(index).expect("Unexpected end of tokens")
     }
 
@@ -70,103 +317,316 @@ pub fn parse_schema(tokens: &[Token]) -> Result<Schema, TcsError> {
         )
     }
 
-    // Handle package declaration
-    if eat(tokens, &mut index, &PACKAGE_KEYWORD) {
-        if index >= tokens.len() {
-            return Err(error("Expected identifier after package", 0, 0));
+    /// Parse a type name (possibly qualified: pkg.Type) and its optional array
+    /// suffix, returning the name, whether it's an array and the fixed size,
+    /// both parsed and as written
+    fn parse_type(
+        tokens: &[Token],
+        index: &mut usize,
+    ) -> Result<(String, bool, Option<usize>, Option<String>), TcsError> {
+        let t_tok = current_token(tokens, *index);
+        expect(tokens, index, &TYPE_NAME, "identifier")?;
+
+        // Check for array notation
+        let next_tok = current_token(tokens, *index);
+        if eat(tokens, index, &ARRAY_TOKEN) {
+            // Variable-length array: type[]
+            Ok((t_tok.text.clone(), true, None, None))
+        } else if let Some(caps) = FIXED_ARRAY_TOKEN.captures(&next_tok.text) {
+            // Fixed-size array: type[N]
+            *index += 1;
+            let size_str = caps.get(1).unwrap().as_str();
+            let size = strip_digit_separators(size_str).parse::<usize>().map_err(|_| {
+                error(
+                    &format!("Invalid array size {}", quote(size_str)),
+                    next_tok.line,
+                    next_tok.column,
+                )
+            })?;
+            Ok((t_tok.text.clone(), true, Some(size), Some(size_str.to_string())))
+        } else {
+            Ok((t_tok.text.clone(), false, None, None))
         }
-        let pkg_tok = current_token(tokens, index);
-        expect(tokens, &mut index, &IDENTIFIER, "identifier")?;
-        package_text = Some(pkg_tok.text.clone());
-        expect(tokens, &mut index, &SEMICOLON, "\";\"")?;
     }
 
-    // Parse definitions one by one
-    while index < tokens.len() && !eat(tokens, &mut index, &EOF) {
-        let kind = if eat(tokens, &mut index, &ENUM_KEYWORD) {
+    /// Parse one enum, struct or message, and any definitions nested in it,
+    /// appending them to `definitions`
+    fn parse_definition(
+        tokens: &[Token],
+        comments: &mut Comments,
+        index: &mut usize,
+        package: &Option<String>,
+        parent: Option<&str>,
+        definitions: &mut Vec<Definition>,
+    ) -> Result<(), TcsError> {
+        let (floating_comments, def_comments) = comments.leading(*index);
+        let kind = if eat(tokens, index, &ENUM_KEYWORD) {
             DefinitionKind::Enum
-        } else if eat(tokens, &mut index, &STRUCT_KEYWORD) {
+        } else if eat(tokens, index, &STRUCT_KEYWORD) {
             DefinitionKind::Struct
-        } else if eat(tokens, &mut index, &MESSAGE_KEYWORD) {
+        } else if eat(tokens, index, &MESSAGE_KEYWORD) {
             DefinitionKind::Message
         } else {
-            return Err(unexpected_token(tokens, &mut index));
+            return Err(unexpected_token(tokens, index));
         };
 
         // Definition name
-        let name_tok = current_token(tokens, index);
-        expect(tokens, &mut index, &IDENTIFIER, "identifier")?;
-        expect(tokens, &mut index, &LEFT_BRACE, "\"{\"")?;
+        let name_tok = current_token(tokens, *index);
+        expect(tokens, index, &IDENTIFIER, "identifier")?;
+
+        // Annotations: [flags] (enums only, for bit sets), [ord] (structs
+        // only), [deprecated] and [feature = "..."], in any order
+        let mut is_flags = false;
+        let mut is_deprecated = false;
+        let mut is_ord = false;
+        let mut feature = None;
+        loop {
+            let a_tok = current_token(tokens, *index);
+            if eat(tokens, index, &FLAGS_TOKEN) {
+                if kind != DefinitionKind::Enum {
+                    return Err(error(
+                        "Only enums can be marked [flags]",
+                        a_tok.line,
+                        a_tok.column,
+                    ));
+                }
+                is_flags = true;
+            } else if eat(tokens, index, &ORD_TOKEN) {
+                if kind != DefinitionKind::Struct {
+                    return Err(error("Only structs can be marked [ord]", a_tok.line, a_tok.column));
+                }
+                is_ord = true;
+            } else if eat(tokens, index, &DEPRECATED_TOKEN) {
+                is_deprecated = true;
+            } else if let Some(caps) = FEATURE_TOKEN.captures(&a_tok.text) {
+                if feature.is_some() {
+                    return Err(error("Duplicate [feature]", a_tok.line, a_tok.column));
+                }
+                *index += 1;
+                feature = Some(caps[1].to_string());
+            } else {
+                break;
+            }
+        }
+        expect(tokens, index, &LEFT_BRACE, "\"{\"")?;
+        let mut trailing_comment = comments.trailing(*index - 1);
+
+        // The definition goes before the ones nested in it
+        let position = definitions.len();
 
-        // Collect fields
-        let mut fields = Vec::new();
-        while !eat(tokens, &mut index, &RIGHT_BRACE) {
+        // Collect fields and nested definitions, up to the closing brace
+        let mut fields: Vec<Field> = Vec::new();
+        let (end_tok, end_comments) = loop {
+            let brace_tok = current_token(tokens, *index);
+            if eat(tokens, index, &RIGHT_BRACE) {
+                break (brace_tok, comments.closing(*index - 1));
+            }
+            let nested_tok = current_token(tokens, *index);
+            if DEFINITION_KEYWORD.is_match(&nested_tok.text) {
+                if kind == DefinitionKind::Enum {
+                    return Err(error(
+                        "Enums cannot contain definitions",
+                        nested_tok.line,
+                        nested_tok.column,
+                    ));
+                }
+                let parent = Some(name_tok.text.as_str());
+                parse_definition(tokens, comments, index, package, parent, definitions)?;
+                continue;
+            }
+            let (field_floating_comments, field_comments) = comments.leading(*index);
             let mut type_opt = None;
             let mut is_array = false;
             let mut array_size = None;
+            let mut array_size_text = None;
             let mut is_deprecated = false;
+            let mut is_key = false;
+
+            let mut presence = Presence::Implicit;
 
             if kind != DefinitionKind::Enum {
-                // Read the type token
-                let t_tok = current_token(tokens, index);
-                expect(tokens, &mut index, &IDENTIFIER, "identifier")?;
+                // Optional `deprecated` prefix, the same as a trailing [deprecated],
+                // followed by an optional presence keyword. `required x = 1;` is
+                // still a field of type "required", so only treat these as
+                // keywords when a type follows.
+                let after_type = tokens.get(*index + 2).map(|t| t.text.as_str());
+                if !matches!(after_type, Some("=") | Some(";")) {
+                    let d_tok = current_token(tokens, *index);
+                    if eat(tokens, index, &DEPRECATED_KEYWORD) {
+                        if kind != DefinitionKind::Message {
+                            return Err(error(
+                                "Cannot deprecate this field",
+                                d_tok.line,
+                                d_tok.column,
+                            ));
+                        }
+                        is_deprecated = true;
+                    }
+                }
+                let after_type = tokens.get(*index + 2).map(|t| t.text.as_str());
+                if !matches!(after_type, Some("=") | Some(";")) {
+                    let p_tok = current_token(tokens, *index);
+                    if eat(tokens, index, &REQUIRED_KEYWORD) {
+                        presence = Presence::Required;
+                    } else if eat(tokens, index, &OPTIONAL_KEYWORD) {
+                        presence = Presence::Optional;
+                    }
+                    if presence != Presence::Implicit && kind != DefinitionKind::Message {
+                        return Err(error(
+                            &format!(
+                                "The {} keyword is only allowed in messages",
+                                quote(&p_tok.text)
+                            ),
+                            p_tok.line,
+                            p_tok.column,
+                        ));
+                    }
+                }
 
-                // Check for array notation
-                let next_tok = current_token(tokens, index);
-                if eat(tokens, &mut index, &ARRAY_TOKEN) {
-                    // Variable-length array: type[]
-                    is_array = true;
-                } else if let Some(caps) = FIXED_ARRAY_TOKEN.captures(&next_tok.text) {
-                    // Fixed-size array: type[N]
-                    index += 1;
-                    is_array = true;
-                    let size_str = caps.get(1).unwrap().as_str();
-                    array_size = Some(size_str.parse::<usize>().map_err(|_| {
-                        error(
-                            &format!("Invalid array size {}", quote(size_str)),
-                            next_tok.line,
-                            next_tok.column,
-                        )
-                    })?);
+                // Protobuf-style `repeated T`, the same as `T[]`
+                let mut repeated = None;
+                let after_type = tokens.get(*index + 2).map(|t| t.text.as_str());
+                if !matches!(after_type, Some("=") | Some(";")) {
+                    let r_tok = current_token(tokens, *index);
+                    if eat(tokens, index, &REPEATED_KEYWORD) {
+                        repeated = Some(r_tok);
+                    }
+                }
+
+                let (type_name, array, size, size_text) = parse_type(tokens, index)?;
+                if let (Some(r_tok), true) = (repeated, array) {
+                    return Err(error(
+                        "A \"repeated\" field can't also have an array suffix",
+                        r_tok.line,
+                        r_tok.column,
+                    ));
                 }
-                type_opt = Some(t_tok.text.clone());
+                type_opt = Some(type_name);
+                is_array = array || repeated.is_some();
+                array_size = size;
+                array_size_text = size_text;
             }
 
             // Field name
-            let f_tok = current_token(tokens, index);
-            expect(tokens, &mut index, &IDENTIFIER, "identifier")?;
+            let f_tok = current_token(tokens, *index);
+            expect(tokens, index, &IDENTIFIER, "identifier")?;
 
-            // Value (either explicit or auto-increment for structs)
-            let value = if kind != DefinitionKind::Struct {
-                expect(tokens, &mut index, &EQUALS, "\"=\"")?;
-                let v_tok = current_token(tokens, index);
-                expect(tokens, &mut index, &INTEGER, "integer")?;
-                v_tok.text.parse::<i32>().map_err(|_| {
-                    error(
-                        &format!("Invalid integer {}", quote(&v_tok.text)),
-                        v_tok.line,
-                        v_tok.column,
-                    )
-                })?
+            // Value (explicit, or auto-increment for structs and for enum
+            // variants that leave out `= value`)
+            let mut alias_of = None;
+            let mut field_id_text = None;
+            let value = if kind == DefinitionKind::Enum
+                && !EQUALS.is_match(&current_token(tokens, *index).text)
+            {
+                // One past the previous variant, starting at 0
+                match fields.last() {
+                    Some(prev) => prev.field_id.checked_add(1).ok_or_else(|| {
+                        error("Enum value is too large", f_tok.line, f_tok.column)
+                    })?,
+                    None => 0,
+                }
+            } else if kind != DefinitionKind::Struct {
+                expect(tokens, index, &EQUALS, "\"=\"")?;
+                let v_tok = current_token(tokens, *index);
+                if kind == DefinitionKind::Enum && eat(tokens, index, &IDENTIFIER) {
+                    // Alias of an earlier variant: NAME = OTHER;
+                    let target = fields
+                        .iter()
+                        .find(|f: &&Field| f.name == v_tok.text && f.alias_of.is_none())
+                        .ok_or_else(|| {
+                            error(
+                                &format!("Unknown enum variant {}", quote(&v_tok.text)),
+                                v_tok.line,
+                                v_tok.column,
+                            )
+                        })?;
+                    alias_of = Some(target.name.clone());
+                    target.field_id
+                } else {
+                    expect(tokens, index, &INTEGER, "integer")?;
+                    field_id_text = Some(v_tok.text.clone());
+                    strip_digit_separators(&v_tok.text).parse::<i32>().map_err(|_| {
+                        error(
+                            &format!("Invalid integer {}", quote(&v_tok.text)),
+                            v_tok.line,
+                            v_tok.column,
+                        )
+                    })?
+                }
             } else {
                 // For structs, assign in-order values
                 fields.len() as i32 + 1
             };
 
-            // Deprecated?
-            if eat(tokens, &mut index, &DEPRECATED_TOKEN) {
-                if kind != DefinitionKind::Message {
-                    let deprecated = current_token(tokens, index - 1);
-                    return Err(error(
-                        "Cannot deprecate this field",
-                        deprecated.line,
-                        deprecated.column,
-                    ));
+            // Annotations: [deprecated] (messages only), [key], @since("...") and
+            // @validate(...), in any order. Other @name annotations are kept for
+            // the verifier to report
+            let mut since = None;
+            let mut validate = None;
+            let mut unknown_annotations = Vec::new();
+            loop {
+                let a_tok = current_token(tokens, *index);
+                if eat(tokens, index, &DEPRECATED_TOKEN) {
+                    if kind != DefinitionKind::Message {
+                        return Err(error("Cannot deprecate this field", a_tok.line, a_tok.column));
+                    }
+                    is_deprecated = true;
+                } else if eat(tokens, index, &KEY_TOKEN) {
+                    if kind == DefinitionKind::Enum {
+                        return Err(error(
+                            "Enum variants cannot be key fields",
+                            a_tok.line,
+                            a_tok.column,
+                        ));
+                    }
+                    is_key = true;
+                } else if let Some(caps) = NAMED_ANNOTATION.captures(&a_tok.text) {
+                    *index += 1;
+                    let name = &caps[1];
+                    match (name, caps.get(2)) {
+                        ("since", Some(version)) if since.is_none() => {
+                            since = Some(version.as_str().to_string());
+                        }
+                        ("since", Some(_)) => {
+                            return Err(error("Duplicate @since", a_tok.line, a_tok.column));
+                        }
+                        ("since", None) => {
+                            return Err(error(
+                                "@since needs a version, e.g. @since(\"2.0\")",
+                                a_tok.line,
+                                a_tok.column,
+                            ));
+                        }
+                        ("validate", _) if kind == DefinitionKind::Enum => {
+                            return Err(error(
+                                "Enum variants cannot have @validate",
+                                a_tok.line,
+                                a_tok.column,
+                            ));
+                        }
+                        ("validate", _) if validate.is_some() => {
+                            return Err(error("Duplicate @validate", a_tok.line, a_tok.column));
+                        }
+                        ("validate", _) => {
+                            let args = caps.get(3).map_or("", |m| m.as_str());
+                            validate = Some(parse_validation(args, a_tok)?);
+                        }
+                        _ => {
+                            unknown_annotations.push(Annotation {
+                                name: name.to_string(),
+                                text: a_tok.text.clone(),
+                                line: a_tok.line,
+                                column: a_tok.column,
+                            });
+                        }
+                    }
+                } else {
+                    break;
                 }
-                is_deprecated = true;
             }
 
-            expect(tokens, &mut index, &SEMICOLON, "\";\"")?;
+            expect(tokens, index, &SEMICOLON, "\";\"")?;
+            let field_trailing_comment = comments.trailing(*index - 1);
 
             let final_value = if kind != DefinitionKind::Struct {
                 value
@@ -181,23 +641,175 @@ pub fn parse_schema(tokens: &[Token]) -> Result<Schema, TcsError> {
                 type_: type_opt,
                 is_array,
                 array_size,
+                array_size_text,
                 is_deprecated,
+                is_key,
                 field_id: final_value,
+                field_id_text,
+                alias_of,
+                comments: field_comments,
+                floating_comments: field_floating_comments,
+                trailing_comment: field_trailing_comment,
+                presence,
+                since,
+                validate,
+                unknown_annotations,
             });
+        };
+        if trailing_comment.is_none() {
+            trailing_comment = comments.trailing(*index - 1);
         }
 
-        definitions.push(Definition {
+        definitions.insert(position, Definition {
             name: name_tok.text.clone(),
             line: name_tok.line,
             column: name_tok.column,
+            end_line: end_tok.line,
+            end_column: end_tok.column,
             kind,
             fields,
+            comments: def_comments,
+            floating_comments,
+            trailing_comment,
+            end_comments,
+            package: package.clone(),
+            is_flags,
+            is_ord,
+            is_deprecated,
+            parent: parent.map(str::to_string),
+            feature,
         });
+        Ok(())
     }
 
+    // Package of the definitions that follow; a file may switch packages
+    // with further `package` declarations
+    let mut current_package: Option<String> = None;
+
+    // Comments above a leading `package` or `options` declaration belong to
+    // the file rather than to the first definition
+    let first = tokens.first().map_or("", |t| t.text.as_str());
+    let leading_comments = if PACKAGE_KEYWORD.is_match(first) || OPTIONS_KEYWORD.is_match(first) {
+        comments.closing(0)
+    } else {
+        Vec::new()
+    };
+
+    // Parse definitions one by one
+    while index < tokens.len() && !eat(tokens, &mut index, &EOF) {
+        // Handle package declaration
+        if eat(tokens, &mut index, &PACKAGE_KEYWORD) {
+            if current_package.is_none() && !definitions.is_empty() {
+                let pkg_tok = current_token(tokens, index - 1);
+                return Err(error(
+                    "The first package declaration must come before any definitions",
+                    pkg_tok.line,
+                    pkg_tok.column,
+                ));
+            }
+            if index >= tokens.len() {
+                return Err(error("Expected identifier after package", 0, 0));
+            }
+            let pkg_tok = current_token(tokens, index);
+            expect(tokens, &mut index, &IDENTIFIER, "identifier")?;
+            if package_text.is_none() {
+                package_text = Some(pkg_tok.text.clone());
+            }
+            current_package = Some(pkg_tok.text.clone());
+            expect(tokens, &mut index, &SEMICOLON, "\";\"")?;
+            continue;
+        }
+
+        // Handle options block: options { name = value; ... }
+        if eat(tokens, &mut index, &OPTIONS_KEYWORD) {
+            expect(tokens, &mut index, &LEFT_BRACE, "\"{\"")?;
+            while !eat(tokens, &mut index, &RIGHT_BRACE) {
+                let name_tok = current_token(tokens, index);
+                expect(tokens, &mut index, &IDENTIFIER, "identifier")?;
+                expect(tokens, &mut index, &EQUALS, "\"=\"")?;
+
+                let value_tok = current_token(tokens, index);
+                let value = if eat(tokens, &mut index, &TRUE_KEYWORD) {
+                    OptionValue::Bool(true)
+                } else if eat(tokens, &mut index, &FALSE_KEYWORD) {
+                    OptionValue::Bool(false)
+                } else if eat(tokens, &mut index, &STRING) {
+                    let text = &value_tok.text;
+                    OptionValue::String(text[1..text.len() - 1].to_string())
+                } else {
+                    return Err(error(
+                        &format!(
+                            "Expected true, false or a string but found {}",
+                            quote(&value_tok.text)
+                        ),
+                        value_tok.line,
+                        value_tok.column,
+                    ));
+                };
+                expect(tokens, &mut index, &SEMICOLON, "\";\"")?;
+
+                if options.iter().any(|o| o.name == name_tok.text) {
+                    return Err(error(
+                        &format!("The option {} is set twice", quote(&name_tok.text)),
+                        name_tok.line,
+                        name_tok.column,
+                    ));
+                }
+                options.push(SchemaOption {
+                    name: name_tok.text.clone(),
+                    line: name_tok.line,
+                    column: name_tok.column,
+                    value,
+                });
+            }
+            continue;
+        }
+
+        // Handle type aliases: type Name = type;
+        let alias_start = index;
+        if eat(tokens, &mut index, &TYPE_KEYWORD) {
+            let (floating_comments, alias_comments) = comments.leading(alias_start);
+            let name_tok = current_token(tokens, index);
+            expect(tokens, &mut index, &IDENTIFIER, "identifier")?;
+            expect(tokens, &mut index, &EQUALS, "\"=\"")?;
+            let (type_, is_array, array_size, array_size_text) = parse_type(tokens, &mut index)?;
+            expect(tokens, &mut index, &SEMICOLON, "\";\"")?;
+            let trailing_comment = comments.trailing(index - 1);
+            aliases.push(TypeAlias {
+                name: name_tok.text.clone(),
+                line: name_tok.line,
+                column: name_tok.column,
+                type_,
+                is_array,
+                array_size,
+                array_size_text,
+                comments: alias_comments,
+                floating_comments,
+                trailing_comment,
+            });
+            continue;
+        }
+
+        parse_definition(
+            tokens,
+            &mut comments,
+            &mut index,
+            &current_package,
+            None,
+            &mut definitions,
+        )?;
+    }
+
+    let end_comments = comments.closing(tokens.len());
+
     Ok(Schema {
         package: package_text,
         definitions,
+        options,
+        header,
+        aliases,
+        leading_comments,
+        end_comments,
     })
 }
 
@@ -242,6 +854,35 @@ mod tests {
         assert_eq!(def.fields[2].array_size, Some(32));
     }
 
+    #[test]
+    fn test_parse_array_after_whitespace() {
+        // The brackets are their own token, so spacing before them doesn't matter
+        let parse = |input: &str| parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let spaced = parse("struct S { byte [32] hash; int\t[] xs; }");
+        let tight = parse("struct S { byte[32] hash; int[] xs; }");
+        for (a, b) in spaced.definitions[0].fields.iter().zip(&tight.definitions[0].fields) {
+            assert_eq!(a.type_, b.type_);
+            assert_eq!(a.is_array, b.is_array);
+            assert_eq!(a.array_size, b.array_size);
+        }
+        assert_eq!(spaced.definitions[0].fields[0].array_size, Some(32));
+    }
+
+    #[test]
+    fn test_parse_definition_span() {
+        let input = "struct Point {\n  int x;\n  int y;\n}\nenum Empty { }";
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+
+        let point = &schema.definitions[0];
+        assert_eq!((point.line, point.column), (1, 8));
+        assert_eq!((point.end_line, point.end_column), (4, 1));
+
+        let empty = &schema.definitions[1];
+        assert_eq!((empty.line, empty.column), (5, 6));
+        assert_eq!((empty.end_line, empty.end_column), (5, 14));
+    }
+
     #[test]
     fn test_parse_message_with_variable_array() {
         let input = r#"
@@ -281,4 +922,472 @@ mod tests {
         assert_eq!(def.fields[0].name, "STORAGE");
         assert_eq!(def.fields[0].field_id, 1);
     }
+
+    #[test]
+    fn test_parse_digit_separators() {
+        let input = "enum Big { A = 1_000; B = 2_000_000; }\nstruct S { byte[1_024] data; }";
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        assert_eq!(schema.definitions[0].fields[0].field_id, 1000);
+        assert_eq!(schema.definitions[0].fields[1].field_id, 2_000_000);
+        assert_eq!(schema.definitions[1].fields[0].array_size, Some(1024));
+        // The spelling is kept for the formatter
+        assert_eq!(schema.definitions[0].fields[0].field_id_text.as_deref(), Some("1_000"));
+        assert_eq!(schema.definitions[1].fields[0].array_size_text.as_deref(), Some("1_024"));
+
+        // A leading underscore makes an identifier, not an integer
+        let tokens = tokenize_schema("enum E { A = _1; }").unwrap();
+        let err = parse_schema(&tokens).unwrap_err();
+        assert!(err.to_string().contains("Unknown enum variant \"_1\""));
+        let tokens = tokenize_schema("message M { int a = _1; }").unwrap();
+        assert!(parse_schema(&tokens).is_err());
+    }
+
+    #[test]
+    fn test_parse_enum_auto_values() {
+        let input = "enum E { A; B; C = 10; D; F = B; G; }";
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let values: Vec<(&str, i32)> = schema.definitions[0]
+            .fields
+            .iter()
+            .map(|f| (f.name.as_str(), f.field_id))
+            .collect();
+        assert_eq!(
+            values,
+            vec![("A", 0), ("B", 1), ("C", 10), ("D", 11), ("F", 1), ("G", 2)]
+        );
+
+        let tokens = tokenize_schema("enum E { A = 2147483647; B; }").unwrap();
+        let err = parse_schema(&tokens).unwrap_err();
+        assert!(err.to_string().contains("Enum value is too large"));
+    }
+
+    #[test]
+    fn test_parse_enum_alias() {
+        let input = r#"
+            enum Status {
+                OK = 0;
+                SUCCESS = OK;
+            }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+
+        let def = &schema.definitions[0];
+        assert_eq!(def.fields[1].name, "SUCCESS");
+        assert_eq!(def.fields[1].alias_of, Some("OK".to_string()));
+        assert_eq!(def.fields[1].field_id, 0);
+
+        let input = "enum Status { SUCCESS = OK; }";
+        let tokens = tokenize_schema(input).unwrap();
+        assert!(parse_schema(&tokens).is_err());
+    }
+
+    #[test]
+    fn test_parse_comments_between_fields() {
+        let input = r#"
+            // A point in space
+            struct Point {
+                // Horizontal position
+                int x; // trailing, not attached
+
+                // Floating note separated by a blank line
+
+                // Vertical position
+                // (second line)
+                int y;
+                int z;
+            }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+
+        let def = &schema.definitions[0];
+        assert_eq!(def.comments, vec!["A point in space"]);
+        assert_eq!(def.fields.len(), 3);
+        assert_eq!(def.fields[0].name, "x");
+        assert_eq!(def.fields[0].comments, vec!["Horizontal position"]);
+        assert_eq!(def.fields[1].name, "y");
+        assert_eq!(
+            def.fields[1].comments,
+            vec!["Vertical position", "(second line)"]
+        );
+        assert!(def.fields[2].comments.is_empty());
+    }
+
+    #[test]
+    fn test_parse_hash_comments() {
+        let input = "# A point\nstruct Point {\n  # Horizontal\n  // position\n  int x; # trailing\n}";
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+
+        let def = &schema.definitions[0];
+        assert_eq!(def.comments, vec!["A point"]);
+        assert_eq!(def.fields[0].comments, vec!["Horizontal", "position"]);
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let input = "//! Copyright 2026 Tapedrive\n//!\n//! MIT license\n// A point\nstruct Point { int x; }";
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        assert_eq!(
+            schema.header.as_deref(),
+            Some("Copyright 2026 Tapedrive\n\nMIT license")
+        );
+        assert_eq!(schema.definitions[0].comments, vec!["A point"]);
+
+        // Only the very top of the file is the header
+        let input = "// A point\n//! not a header\nstruct Point { int x; }";
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        assert_eq!(schema.header, None);
+        assert_eq!(schema.definitions[0].comments, vec!["A point", "! not a header"]);
+    }
+
+    #[test]
+    fn test_parse_floating_and_trailing_comments() {
+        let input = r#"
+            // About this file
+
+            // A point
+            struct Point { // 2D
+                int x; // left to right
+
+                # Floating
+
+                int y;
+                // More to come
+            } // done
+            type Hash = byte[32]; // sha256
+
+            // The end
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+
+        let def = &schema.definitions[0];
+        assert_eq!(def.floating_comments, vec![vec!["About this file"]]);
+        assert_eq!(def.comments, vec!["A point"]);
+        assert_eq!(def.trailing_comment.as_deref(), Some("2D"));
+        assert_eq!(def.end_comments, vec![vec!["More to come"]]);
+        assert_eq!(
+            def.fields[0].trailing_comment.as_deref(),
+            Some("left to right")
+        );
+        assert_eq!(def.fields[1].floating_comments, vec![vec!["Floating"]]);
+        assert!(def.fields[1].comments.is_empty());
+
+        // The opening brace already has a comment, so the closing brace's
+        // comment goes to the next declaration
+        let alias = &schema.aliases[0];
+        assert_eq!(alias.floating_comments, vec![vec!["done"]]);
+        assert_eq!(alias.trailing_comment.as_deref(), Some("sha256"));
+        assert_eq!(schema.end_comments, vec![vec!["The end"]]);
+    }
+
+    #[test]
+    fn test_parse_multiple_packages() {
+        let input = r#"
+            package geometry;
+            struct Point { int x; int y; }
+
+            package shapes;
+            struct Circle { geometry.Point center; uint radius; }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+
+        assert_eq!(schema.package, Some("geometry".to_string()));
+        assert_eq!(schema.definitions[0].package, Some("geometry".to_string()));
+        assert_eq!(schema.definitions[1].package, Some("shapes".to_string()));
+        assert_eq!(
+            schema.definitions[1].fields[0].type_,
+            Some("geometry.Point".to_string())
+        );
+
+        let input = "struct A { int x; } package late;";
+        let tokens = tokenize_schema(input).unwrap();
+        assert!(parse_schema(&tokens).is_err());
+    }
+
+    #[test]
+    fn test_parse_presence_keywords() {
+        let input = r#"
+            message Tx {
+                required uint64 nonce = 1;
+                optional byte[32] hash = 2;
+                byte[] data = 3;
+                required other = 4;
+            }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+
+        let fields = &schema.definitions[0].fields;
+        assert_eq!(fields[0].presence, Presence::Required);
+        assert_eq!(fields[0].type_, Some("uint64".to_string()));
+        assert_eq!(fields[1].presence, Presence::Optional);
+        assert_eq!(fields[1].array_size, Some(32));
+        assert_eq!(fields[2].presence, Presence::Implicit);
+        assert_eq!(fields[3].presence, Presence::Implicit);
+        assert_eq!(fields[3].type_, Some("required".to_string()));
+        assert_eq!(fields[3].name, "other");
+
+        let tokens = tokenize_schema("struct S { required int x; }").unwrap();
+        assert!(parse_schema(&tokens).is_err());
+    }
+
+    #[test]
+    fn test_parse_repeated_keyword() {
+        // Padded so field names line up, making the ASTs identical
+        let repeated = "message Block { repeated Transaction txs = 1; }";
+        let suffixed = "message Block {        Transaction[] txs = 1; }";
+        let parse = |input: &str| parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        assert_eq!(parse(repeated), parse(suffixed));
+        let field = &parse(repeated).definitions[0].fields[0];
+        assert_eq!(field.type_, Some("Transaction".to_string()));
+        assert!(field.is_array);
+        assert_eq!(field.array_size, None);
+
+        // After the other keywords, in structs too, and still usable as a type name
+        let input = r#"
+            message Tx { deprecated required repeated uint64 nonces = 1; repeated other = 2; }
+            struct Batch { repeated string items; }
+        "#;
+        let schema = parse(input);
+        let fields = &schema.definitions[0].fields;
+        assert!(fields[0].is_deprecated && fields[0].is_array);
+        assert_eq!(fields[0].presence, Presence::Required);
+        assert_eq!(fields[1].type_, Some("repeated".to_string()));
+        assert!(!fields[1].is_array);
+        assert!(schema.definitions[1].fields[0].is_array);
+
+        let tokens = tokenize_schema("struct S { repeated byte[32] hashes; }").unwrap();
+        let err = parse_schema(&tokens).unwrap_err();
+        assert!(err.to_string().contains("can't also have an array suffix"));
+    }
+
+    #[test]
+    fn test_parse_deprecated_keyword() {
+        let input = r#"
+            message Tx {
+                deprecated uint64 old = 1;
+                uint64 older = 2 [deprecated];
+                deprecated required byte[32] hash = 3;
+                deprecated other = 4;
+            }
+        "#;
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let fields = &schema.definitions[0].fields;
+        assert!(fields[0].is_deprecated);
+        assert_eq!(fields[0].type_, Some("uint64".to_string()));
+        assert!(fields[1].is_deprecated);
+        assert!(fields[2].is_deprecated);
+        assert_eq!(fields[2].presence, Presence::Required);
+        assert_eq!(fields[2].array_size, Some(32));
+        assert!(!fields[3].is_deprecated);
+        assert_eq!(fields[3].type_, Some("deprecated".to_string()));
+
+        let tokens = tokenize_schema("struct S { deprecated int x; }").unwrap();
+        assert!(parse_schema(&tokens).is_err());
+    }
+
+    #[test]
+    fn test_parse_since() {
+        let input = r#"
+            message Block {
+                uint64 height = 1;
+                uint64 epoch = 6 [deprecated] @since("2.0");
+            }
+            enum Kind { A = 0; B = 1 @since("1.1"); }
+        "#;
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        assert_eq!(schema.definitions[0].fields[0].since, None);
+        assert_eq!(schema.definitions[0].fields[1].since.as_deref(), Some("2.0"));
+        assert!(schema.definitions[0].fields[1].is_deprecated);
+        assert_eq!(schema.definitions[1].fields[1].since.as_deref(), Some("1.1"));
+
+        let schema = parse_schema(&tokenize_schema("struct S { int x @added(\"2\") @todo; }").unwrap())
+            .unwrap();
+        let unknown = &schema.definitions[0].fields[0].unknown_annotations;
+        assert_eq!(unknown.len(), 2);
+        assert_eq!((unknown[0].name.as_str(), unknown[0].text.as_str()), ("added", "@added(\"2\")"));
+        assert_eq!((unknown[1].name.as_str(), unknown[1].line, unknown[1].column), ("todo", 1, 30));
+
+        for (input, msg) in [
+            ("struct S { int x @since; }", "@since needs a version"),
+            ("struct S { int x @since(\"1\") @since(\"2\"); }", "Duplicate @since"),
+        ] {
+            let err = parse_schema(&tokenize_schema(input).unwrap()).unwrap_err();
+            assert!(err.to_string().contains(msg), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_parse_validate() {
+        let input = r#"
+            message Transfer {
+                uint64 amount = 1 @validate(min = 1, max = 1_000_000);
+                int[] tiers = 2 [deprecated] @validate(len_max=4) @since("2");
+                int delta = 3 @validate(min = -10);
+            }
+        "#;
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let fields = &schema.definitions[0].fields;
+        assert_eq!(
+            fields[0].validate,
+            Some(Validation {
+                min: Some(1),
+                max: Some(1_000_000),
+                min_text: Some("1".to_string()),
+                max_text: Some("1_000_000".to_string()),
+                ..Validation::default()
+            })
+        );
+        assert_eq!(fields[1].validate.as_ref().unwrap().len_max, Some(4));
+        assert_eq!(fields[1].since.as_deref(), Some("2"));
+        assert_eq!(fields[2].validate.as_ref().unwrap().min, Some(-10));
+
+        for (input, msg) in [
+            ("struct S { int x @validate; }", "@validate needs bounds"),
+            ("struct S { int x @validate(); }", "@validate needs bounds"),
+            ("struct S { int x @validate(min); }", "@validate needs bounds"),
+            ("struct S { int x @validate(above = 1); }", "Unknown @validate bound \"above\""),
+            ("struct S { int x @validate(min = x); }", "Invalid @validate bound \"x\""),
+            ("struct S { int x @validate(min = 1, min = 2); }", "Duplicate @validate bound \"min\""),
+            ("struct S { int[] x @validate(len_min = -1); }", "Invalid length \"-1\""),
+            ("struct S { int x @validate(min = 1) @validate(max = 2); }", "Duplicate @validate"),
+            ("enum E { A = 0 @validate(min = 1); }", "Enum variants cannot have @validate"),
+        ] {
+            let err = parse_schema(&tokenize_schema(input).unwrap()).unwrap_err();
+            assert!(err.to_string().contains(msg), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let input = r#"
+            package chain;
+            options {
+                no_std = true;
+                sort = false;
+                rust_module = "protocol";
+            }
+            struct Point { int x; }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let options: Vec<(&str, &OptionValue)> = schema
+            .options
+            .iter()
+            .map(|o| (o.name.as_str(), &o.value))
+            .collect();
+        assert_eq!(
+            options,
+            [
+                ("no_std", &OptionValue::Bool(true)),
+                ("sort", &OptionValue::Bool(false)),
+                ("rust_module", &OptionValue::String("protocol".to_string())),
+            ]
+        );
+        assert_eq!((schema.options[0].line, schema.options[0].column), (4, 17));
+        assert_eq!(schema.definitions.len(), 1);
+
+        for bad in [
+            "options { no_std = 1; }",
+            "options { no_std = true; no_std = false; }",
+            "options { no_std true; }",
+        ] {
+            let tokens = tokenize_schema(bad).unwrap();
+            assert!(parse_schema(&tokens).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_deprecated_definition() {
+        let input = r#"
+            message Old [deprecated] { uint64 id = 1; }
+            enum Perm [deprecated] [flags] { READ = 1; }
+            struct Point { int x; }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        assert!(schema.definitions[0].is_deprecated);
+        assert!(schema.definitions[1].is_deprecated);
+        assert!(schema.definitions[1].is_flags);
+        assert!(!schema.definitions[2].is_deprecated);
+    }
+
+    #[test]
+    fn test_parse_nested_definitions() {
+        let input = r#"
+            package shapes;
+            message Canvas {
+                // One layer
+                struct Layer {
+                    enum Mode { FLAT = 0; }
+                    Mode mode;
+                }
+                Layer[] layers = 1;
+            }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let names: Vec<&str> = schema.definitions.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["Canvas", "Layer", "Mode"]);
+        assert_eq!(schema.definitions[0].parent, None);
+        assert_eq!(schema.definitions[0].fields.len(), 1);
+        assert_eq!(schema.definitions[1].parent.as_deref(), Some("Canvas"));
+        assert_eq!(schema.definitions[1].comments, vec!["One layer"]);
+        assert_eq!(schema.definitions[2].parent.as_deref(), Some("Layer"));
+        assert_eq!(schema.definitions[2].package.as_deref(), Some("shapes"));
+
+        let tokens = tokenize_schema("enum Kind { struct Inner {} }").unwrap();
+        let err = parse_schema(&tokens).unwrap_err();
+        assert!(err.to_string().contains("Enums cannot contain definitions"));
+    }
+
+    #[test]
+    fn test_parse_type_alias() {
+        let input = r#"
+            package chain;
+            // A block hash
+            type Hash = byte[32];
+            struct Block { Hash prev; }
+            type Ids = other.Id[];
+        "#;
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        assert_eq!(schema.aliases.len(), 2);
+        let hash = &schema.aliases[0];
+        assert_eq!((hash.name.as_str(), hash.type_.as_str()), ("Hash", "byte"));
+        assert!(hash.is_array);
+        assert_eq!(hash.array_size, Some(32));
+        assert_eq!(hash.comments, vec!["A block hash".to_string()]);
+        assert_eq!((hash.line, hash.column), (4, 18));
+        assert_eq!(schema.aliases[1].type_, "other.Id");
+        assert!(schema.aliases[1].is_array);
+        assert_eq!(schema.aliases[1].array_size, None);
+        assert_eq!(schema.definitions[0].fields[0].type_.as_deref(), Some("Hash"));
+
+        for input in ["type Hash byte[32];", "type Hash = byte[32]", "type = byte;"] {
+            assert!(parse_schema(&tokenize_schema(input).unwrap()).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_feature() {
+        let input = r#"
+            message Experimental [feature = "exp"] [deprecated] { uint64 id = 1; }
+            enum Mode [feature="exp-v2"] { A = 0; }
+            struct Point { int x; }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        assert_eq!(schema.definitions[0].feature.as_deref(), Some("exp"));
+        assert!(schema.definitions[0].is_deprecated);
+        assert_eq!(schema.definitions[1].feature.as_deref(), Some("exp-v2"));
+        assert_eq!(schema.definitions[2].feature, None);
+
+        let tokens = tokenize_schema(r#"struct P [feature = "a"] [feature = "b"] {}"#).unwrap();
+        let err = parse_schema(&tokens).unwrap_err();
+        assert!(err.to_string().contains("Duplicate [feature]"));
+    }
 }
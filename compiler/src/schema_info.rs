@@ -0,0 +1,148 @@
+//! Machine-readable schema reports
+//!
+//! Summarizes a schema for build pipelines: its statistics, a fingerprint of
+//! its contents and one entry per definition.
+
+use tcs_schema::{Definition, DefinitionKind, Schema};
+
+use crate::formatter::format_schema;
+use crate::json::Json;
+
+/// Fingerprint of a schema, as 16 hex digits
+///
+/// This is the 64-bit FNV-1a hash of the formatted schema with its comments,
+/// header and digit separators removed, so reformatting or rewording comments
+/// keeps the hash while any change to a definition, field or option changes it.
+pub fn schema_hash(schema: &Schema) -> String {
+    let mut schema = schema.clone();
+    schema.header = None;
+    schema.leading_comments.clear();
+    schema.end_comments.clear();
+    for alias in &mut schema.aliases {
+        alias.comments.clear();
+        alias.floating_comments.clear();
+        alias.trailing_comment = None;
+        alias.array_size_text = None;
+    }
+    for definition in &mut schema.definitions {
+        definition.comments.clear();
+        definition.floating_comments.clear();
+        definition.trailing_comment = None;
+        definition.end_comments.clear();
+        for field in &mut definition.fields {
+            field.comments.clear();
+            field.floating_comments.clear();
+            field.trailing_comment = None;
+            field.array_size_text = None;
+            field.field_id_text = None;
+            if let Some(ref mut validate) = field.validate {
+                validate.min_text = None;
+                validate.max_text = None;
+                validate.len_min_text = None;
+                validate.len_max_text = None;
+            }
+        }
+    }
+
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in format_schema(&schema).bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// Build a JSON report of the schema's package, the compiler version, its
+/// statistics, its [`schema_hash`] and a summary of each definition
+pub fn schema_info_json(schema: &Schema) -> String {
+    let stats = schema.stats();
+    let max_field_ids = stats
+        .max_field_ids
+        .iter()
+        .map(|(name, id)| {
+            let id = id.map_or(Json::Null, |id| Json::Number(id as i64));
+            (name.clone(), id)
+        })
+        .collect();
+    let stats = Json::object(vec![
+        ("enums", Json::Number(stats.enums as i64)),
+        ("structs", Json::Number(stats.structs as i64)),
+        ("messages", Json::Number(stats.messages as i64)),
+        ("fields", Json::Number(stats.fields as i64)),
+        (
+            "deprecated_fields",
+            Json::Number(stats.deprecated_fields as i64),
+        ),
+        ("max_field_ids", Json::Object(max_field_ids)),
+    ]);
+
+    let report = Json::object(vec![
+        ("package", optional_str(schema.package.as_deref())),
+        ("version", Json::str(env!("CARGO_PKG_VERSION"))),
+        ("stats", stats),
+        ("schema_hash", Json::String(schema_hash(schema))),
+        (
+            "definitions",
+            Json::Array(schema.definitions.iter().map(definition_info).collect()),
+        ),
+    ]);
+
+    let mut out = String::new();
+    report.write(0, &mut out);
+    out.push('\n');
+    out
+}
+
+/// The `definitions` entry for one definition
+fn definition_info(definition: &Definition) -> Json {
+    let kind = match definition.kind {
+        DefinitionKind::Enum if definition.is_flags => "flags",
+        DefinitionKind::Enum => "enum",
+        DefinitionKind::Struct => "struct",
+        DefinitionKind::Message => "message",
+    };
+    Json::object(vec![
+        ("name", Json::str(&definition.name)),
+        ("kind", Json::str(kind)),
+        ("package", optional_str(definition.package.as_deref())),
+        ("fields", Json::Number(definition.fields.len() as i64)),
+        ("deprecated", Json::Bool(definition.is_deprecated)),
+    ])
+}
+
+fn optional_str(s: Option<&str>) -> Json {
+    s.map_or(Json::Null, Json::str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_schema, tokenize_schema};
+
+    fn parse(source: &str) -> Schema {
+        parse_schema(&tokenize_schema(source).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_schema_hash() {
+        let schema = parse("struct Point { int x; int y; }");
+        let hash = schema_hash(&schema);
+        assert_eq!(hash.len(), 16);
+
+        // Comments and layout don't change the hash
+        let commented = parse(
+            "// Shapes\n\n// A point\nstruct Point {\n  int x; // across\n  int y;\n  // more to come\n}\n// The end\n",
+        );
+        assert_eq!(schema_hash(&commented), hash);
+
+        // Nor do digit separators
+        let spelled = parse("struct S { byte[1_024] data; }");
+        assert_eq!(
+            schema_hash(&spelled),
+            schema_hash(&parse("struct S { byte[1024] data; }"))
+        );
+
+        let renamed = parse("struct Point { int x; int z; }");
+        assert_ne!(schema_hash(&renamed), hash);
+    }
+}
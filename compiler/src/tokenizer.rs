@@ -8,19 +8,28 @@ use crate::utils::{error, quote};
 
 lazy_static! {
     // Token patterns:
-    // - Integers (possibly negative): -?\d+
+    // - Integers (possibly negative), with single `_` separators between digits: -?\d+(_\d+)*
     // - Punctuation: = ; { }
     // - Empty array brackets: []
-    // - Fixed-size array: [123] (captures the number)
-    // - Deprecated tag: [deprecated]
-    // - Identifiers: [A-Za-z_][A-Za-z0-9_]*
-    // - Comments: //.*
+    // - Fixed-size array: [123] or [1_024] (captures the number)
+    // - Annotations: [deprecated], [key], [flags], [ord], [feature = "..."]
+    // - Named annotations, with an optional string or argument list: @name,
+    //   @name("...") or @name(key = 1, ...)
+    // - String literals (option values): "[^"\n]*"
+    // - Identifiers, optionally package-qualified: [A-Za-z_][A-Za-z0-9_]*(\.[A-Za-z_][A-Za-z0-9_]*)?
+    // - Comments: //.* or #.* (so `#` can't be given another meaning later)
     // - Whitespace: \s+
     pub static ref TOKEN_REGEX: Regex = Regex::new(
-        r"((?:-|\b)\d+\b|[=;{}]|\[\d+\]|\[\]|\[deprecated\]|\b[A-Za-z_][A-Za-z0-9_]*\b|//.*|\s+)"
+        r#"((?:-|\b)\d+(?:_\d+)*\b|[=;{}]|\[\d+(?:_\d+)*\]|\[\]|\[deprecated\]|\[key\]|\[flags\]|\[ord\]|\[feature *= *"[^"\n]*"\]|@[A-Za-z_][A-Za-z0-9_]*(?:\("[^"\n]*"\)|\([^()"\n]*\))?|"[^"\n]*"|\b[A-Za-z_][A-Za-z0-9_]*(?:\.[A-Za-z_][A-Za-z0-9_]*)?\b|//.*|#.*|\s+)"#
     ).unwrap();
 
-    pub static ref WHITESPACE_RX: Regex = Regex::new(r"^(//.*|\s+)$").unwrap();
+    pub static ref WHITESPACE_RX: Regex = Regex::new(r"^(//.*|#.*|\s+)$").unwrap();
+}
+
+/// The text of a comment token after its `//` or `#` marker, or `None` if the
+/// token isn't a comment
+pub fn comment_text(token: &str) -> Option<&str> {
+    token.strip_prefix("//").or_else(|| token.strip_prefix('#'))
 }
 
 /// A token from the TCS schema
@@ -33,6 +42,8 @@ pub struct Token {
 
 /// Tokenize a TCS schema string into tokens
 pub fn tokenize_schema(text: &str) -> Result<Vec<Token>, TcsError> {
+    // Editors on some platforms save UTF-8 with a byte order mark
+    let text = text.strip_prefix('\u{FEFF}').unwrap_or(text);
     let mut tokens = Vec::new();
     let mut line = 1;
     let mut column = 1;
@@ -53,7 +64,9 @@ pub fn tokenize_schema(text: &str) -> Result<Vec<Token>, TcsError> {
             ));
         }
 
-        if !WHITESPACE_RX.is_match(part) && !part.starts_with("//") {
+        // Comments are kept as tokens so the parser can attach them to the
+        // definition or field that follows
+        if !WHITESPACE_RX.is_match(part) || comment_text(part).is_some() {
             tokens.push(Token {
                 text: part.to_string(),
                 line,
@@ -61,15 +74,15 @@ pub fn tokenize_schema(text: &str) -> Result<Vec<Token>, TcsError> {
             });
         }
 
-        // Update line/column
+        // Update line/column; columns count chars, not bytes
         let newline_count = part.matches('\n').count();
         if newline_count > 0 {
             line += newline_count;
-            if let Some(last_line_part) = part.split('\n').last() {
-                column = last_line_part.len() + 1;
+            if let Some(last_line_part) = part.split('\n').next_back() {
+                column = last_line_part.chars().count() + 1;
             }
         } else {
-            column += part.len();
+            column += part.chars().count();
         }
 
         last_end = end;
@@ -151,6 +164,73 @@ mod tests {
         assert_eq!(got, expected);
     }
 
+    #[test]
+    fn test_tokenize_comment() {
+        let input = "// hello\nint x;";
+        let got = tokenize_schema(input).unwrap();
+        assert_eq!(got[0], Token { text: "// hello".into(), line: 1, column: 1 });
+        assert_eq!(got[1], Token { text: "int".into(), line: 2, column: 1 });
+    }
+
+    #[test]
+    fn test_tokenize_hash_comment() {
+        let input = "# hello\nint x; # trailing";
+        let got = tokenize_schema(input).unwrap();
+        assert_eq!(got[0], Token { text: "# hello".into(), line: 1, column: 1 });
+        assert_eq!(got[1], Token { text: "int".into(), line: 2, column: 1 });
+        assert_eq!(got[4], Token { text: "# trailing".into(), line: 2, column: 8 });
+        assert_eq!(comment_text(&got[0].text), Some(" hello"));
+        assert_eq!(comment_text(&got[1].text), None);
+    }
+
+    #[test]
+    fn test_tokenize_mixed_comments() {
+        let input = "// Slash comment\n# Hash comment\nstruct Point { int x; // x\n int y; # y\n}";
+        let got = tokenize_schema(input).unwrap();
+        let comments: Vec<&str> = got
+            .iter()
+            .filter_map(|t| comment_text(&t.text))
+            .collect();
+        assert_eq!(comments, [" Slash comment", " Hash comment", " x", " y"]);
+
+        // `#` inside a string literal is not a comment
+        let got = tokenize_schema("rust_module = \"a#b\";").unwrap();
+        assert_eq!(got[2].text, "\"a#b\"");
+        assert_eq!(got[3].text, ";");
+    }
+
+    #[test]
+    fn test_tokenize_multibyte_columns() {
+        let input = "x = \"größe\";";
+        let got = tokenize_schema(input).unwrap();
+        assert_eq!(got[2], Token { text: "\"größe\"".into(), line: 1, column: 5 });
+        assert_eq!(got[3], Token { text: ";".into(), line: 1, column: 12 });
+
+        // Identifiers are ASCII; the error points at the identifier by char
+        let err = tokenize_schema("int größe;").unwrap_err().to_string();
+        assert!(err.contains("column 5"), "{}", err);
+    }
+
+    #[test]
+    fn test_tokenize_byte_order_mark() {
+        let input = "package chain;\nstruct Foo { int x; }";
+        let with_bom = format!("\u{FEFF}{}", input);
+        assert_eq!(tokenize_schema(&with_bom).unwrap(), tokenize_schema(input).unwrap());
+    }
+
+    #[test]
+    fn test_tokenize_digit_separators() {
+        let tokens = tokenize_schema("A = 1_000; byte[1_024]").unwrap();
+        let texts: Vec<&str> = tokens.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(texts, vec!["A", "=", "1_000", ";", "byte", "[1_024]", ""]);
+
+        // Separators only go between digits
+        for input in ["A = 1__0;", "A = 1_;", "byte[_1]"] {
+            let err = tokenize_schema(input).unwrap_err();
+            assert!(matches!(err, TcsError::ParseError { .. }), "{}", input);
+        }
+    }
+
     #[test]
     fn test_tokenize_unexpected_text() {
         let input = "int x = 10 @";
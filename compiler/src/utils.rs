@@ -1,5 +1,8 @@
 //! Utility functions for TCS compiler
 
+use std::collections::HashMap;
+use tcs_schema::{Definition, DefinitionKind, Field, Schema};
+
 use crate::error::TcsError;
 
 /// Quote a string for error messages (escapes special characters)
@@ -16,6 +19,149 @@ pub fn error(msg: &str, line: usize, column: usize) -> TcsError {
     }
 }
 
+/// Create a verification error with the given error code
+pub fn verification_error(code: &'static str, msg: &str) -> TcsError {
+    TcsError::verification(code, msg)
+}
+
+/// Create a verification error pointing at a line and column of the schema
+pub fn verification_error_at(
+    code: &'static str,
+    msg: &str,
+    line: usize,
+    column: usize,
+) -> TcsError {
+    TcsError::verification_at(code, msg, line, column)
+}
+
+/// Smallest and largest value of a native integer type, or `None` for other
+/// types. `uint128`'s upper end is capped at `i128::MAX`.
+pub fn integer_range(type_name: &str) -> Option<(i128, i128)> {
+    match type_name {
+        "byte" | "uint8" => Some((0, u8::MAX.into())),
+        "int" => Some((i32::MIN.into(), i32::MAX.into())),
+        "uint" => Some((0, u32::MAX.into())),
+        "int64" => Some((i64::MIN.into(), i64::MAX.into())),
+        "uint64" | "timestamp" => Some((0, u64::MAX.into())),
+        "int128" => Some((i128::MIN, i128::MAX)),
+        "uint128" => Some((0, i128::MAX)),
+        _ => None,
+    }
+}
+
+/// Create a code generation error pointing at a definition, or at one of its fields
+pub fn codegen_error(definition: &Definition, field: Option<&Field>, msg: &str) -> TcsError {
+    let (location, line, column) = match field {
+        Some(field) => (
+            format!("{}.{}", definition.name, field.name),
+            field.line,
+            field.column,
+        ),
+        None => (definition.name.clone(), definition.line, definition.column),
+    };
+    TcsError::CodeGenError {
+        msg: msg.to_string(),
+        location,
+        line,
+        column,
+    }
+}
+
+/// Split a possibly package-qualified type name (`pkg.Type`) into its parts
+pub fn split_qualified(ty: &str) -> (Option<&str>, &str) {
+    match ty.split_once('.') {
+        Some((package, name)) => (Some(package), name),
+        None => (None, ty),
+    }
+}
+
+/// Serialized size of every struct whose fields all have a fixed size:
+/// numbers, bools, fixed byte arrays and other such structs, keyed by
+/// [`Definition::qualified_name`].
+///
+/// Sizes are added with checked arithmetic. If a struct's size would
+/// overflow `usize`, that struct is returned as the error.
+pub fn fixed_struct_sizes(schema: &Schema) -> Result<HashMap<String, usize>, &Definition> {
+    let mut sizes = HashMap::new();
+    // A struct can only be sized once the structs it contains are, so repeat
+    // until nothing changes
+    loop {
+        let mut changed = false;
+        for def in &schema.definitions {
+            let name = def.qualified_name();
+            if def.kind != DefinitionKind::Struct || sizes.contains_key(&name) {
+                continue;
+            }
+            let field_sizes: Option<Vec<usize>> = def
+                .fields
+                .iter()
+                .map(|f| fixed_size(f, def, schema, &sizes))
+                .collect();
+            let Some(field_sizes) = field_sizes else {
+                continue;
+            };
+            let size = field_sizes
+                .into_iter()
+                .try_fold(0usize, |total, size| total.checked_add(size))
+                .ok_or(def)?;
+            sizes.insert(name, size);
+            changed = true;
+        }
+        if !changed {
+            return Ok(sizes);
+        }
+    }
+}
+
+/// Serialized size of a field of `def`, if it's the same for every value.
+/// `sizes` holds the sizes of the fixed-size structs known so far.
+pub fn fixed_size(
+    field: &Field,
+    def: &Definition,
+    schema: &Schema,
+    sizes: &HashMap<String, usize>,
+) -> Option<usize> {
+    if field.is_fixed_byte_array() {
+        return field.array_size;
+    }
+    if field.is_array {
+        return None;
+    }
+    match field.type_.as_deref()? {
+        "bool" | "byte" | "uint8" => Some(1),
+        "int" | "uint" | "float" => Some(4),
+        "int64" | "uint64" | "timestamp" => Some(8),
+        "int128" | "uint128" => Some(16),
+        "uint256" => Some(32),
+        other => sizes
+            .get(&schema.resolve_type(other, def)?.qualified_name())
+            .copied(),
+    }
+}
+
+/// Render the source line at `line` with a `^` under `column` (both 1-indexed).
+///
+/// Returns `None` if the line does not exist in `source`.
+pub fn source_snippet(source: &str, line: usize, column: usize) -> Option<String> {
+    // Columns are counted after the byte order mark, if any (see tokenize_schema)
+    let source = source.strip_prefix('\u{FEFF}').unwrap_or(source);
+    let text = source.lines().nth(line.checked_sub(1)?)?;
+    let gutter = line.to_string();
+    let pad = " ".repeat(gutter.len());
+
+    // Keep tabs in the caret prefix so it lines up however the terminal renders them
+    let prefix: String = text
+        .chars()
+        .take(column.saturating_sub(1))
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    Some(format!(
+        "{} |\n{} | {}\n{} | {}^",
+        pad, gutter, text, pad, prefix
+    ))
+}
+
 /// Converts a string to PascalCase
 pub fn to_pascal_case(s: &str) -> String {
     if s.contains('_') {
@@ -67,13 +213,14 @@ pub fn to_snake_case(s: &str) -> String {
     snake
 }
 
-/// Escape Rust keywords by appending an underscore
+/// Escape Rust keywords (including the reserved ones) by appending an underscore
 pub fn escape_rust_keyword(s: &str) -> String {
     const KEYWORDS: &[&str] = &[
         "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn",
         "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
         "return", "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe",
-        "use", "where", "while", "async", "await", "dyn",
+        "use", "where", "while", "async", "await", "dyn", "abstract", "become", "box", "do",
+        "final", "macro", "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
     ];
     if KEYWORDS.contains(&s) {
         format!("{}_", s)
@@ -82,10 +229,35 @@ pub fn escape_rust_keyword(s: &str) -> String {
     }
 }
 
+/// Escape Rust keywords as raw identifiers (`r#type`). Keywords that can't be
+/// raw identifiers (`self`, `Self`, `super`, `crate`) get an underscore instead.
+pub fn raw_rust_keyword(s: &str) -> String {
+    const NOT_RAW: &[&str] = &["crate", "self", "Self", "super"];
+    let escaped = escape_rust_keyword(s);
+    if escaped != s && !NOT_RAW.contains(&s) {
+        format!("r#{}", s)
+    } else {
+        escaped
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_source_snippet() {
+        let source = "struct Foo {\n  int x @\n}\n";
+        let snippet = source_snippet(source, 2, 9).unwrap();
+        assert_eq!(snippet, "  |\n2 |   int x @\n  |         ^");
+
+        let lines: Vec<&str> = snippet.lines().collect();
+        assert_eq!(lines[2].find('^'), lines[1].find('@'));
+
+        assert!(source_snippet(source, 10, 1).is_none());
+        assert!(source_snippet(source, 0, 1).is_none());
+    }
+
     #[test]
     fn test_to_pascal_case() {
         assert_eq!(to_pascal_case("hello_world"), "HelloWorld");
@@ -106,5 +278,14 @@ mod tests {
         assert_eq!(escape_rust_keyword("type"), "type_");
         assert_eq!(escape_rust_keyword("name"), "name");
         assert_eq!(escape_rust_keyword("async"), "async_");
+        assert_eq!(escape_rust_keyword("try"), "try_");
+    }
+
+    #[test]
+    fn test_raw_rust_keyword() {
+        assert_eq!(raw_rust_keyword("type"), "r#type");
+        assert_eq!(raw_rust_keyword("self"), "self_");
+        assert_eq!(raw_rust_keyword("Self"), "Self_");
+        assert_eq!(raw_rust_keyword("name"), "name");
     }
 }
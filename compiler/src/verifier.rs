@@ -1,193 +1,2522 @@
 //! Schema verification for TCS
 
 use std::collections::{HashMap, HashSet};
-use tcs_schema::{Definition, DefinitionKind, Schema};
+use tcs_schema::{
+    Annotation, Definition, DefinitionKind, Field, OptionValue, Presence, Schema, TypeAlias,
+};
 
-use crate::error::TcsError;
-use crate::utils::quote;
+use crate::error::{codes, sort_by_location, TcsError, Warning};
+use crate::formatter::format_schema;
+use crate::gen_rust::GenOptions;
+use crate::parser::parse_schema;
+use crate::tokenizer::tokenize_schema;
+use crate::utils::{
+    escape_rust_keyword, fixed_struct_sizes, integer_range, quote, split_qualified, to_pascal_case,
+    to_snake_case, verification_error, verification_error_at,
+};
 
 /// Reserved type names that cannot be used
-pub const RESERVED_NAMES: &[&str] = &["ByteBuffer", "package"];
+pub const RESERVED_NAMES: &[&str] = &["ByteBuffer"];
+
+/// Keywords of the schema language, which can't name a definition or field.
+/// `import`, `const` and `reserved` are kept for syntax that may come later.
+pub const KEYWORDS: &[&str] = &[
+    "enum", "struct", "message", "package", "options", "import", "const", "reserved",
+];
 
 /// Native/builtin types
 pub const NATIVE_TYPES: &[&str] = &[
-    "bool", "byte", "int", "uint", "float", "string", "int64", "uint64",
+    "bool",
+    "byte",
+    "uint8",
+    "int",
+    "uint",
+    "float",
+    "string",
+    "int64",
+    "uint64",
+    "timestamp",
+    "int128",
+    "uint128",
+    "uint256",
+    "bytes",
 ];
 
+/// Options controlling schema verification
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    /// Type names defined outside this schema (e.g. in another file) that
+    /// fields may reference without triggering an undefined-type error
+    pub external_types: HashSet<String>,
+    /// Reject unrecognized `@name` annotations instead of warning about them
+    pub strict_annotations: bool,
+}
+
+/// Limits on the size of a schema, for compiling schemas from untrusted
+/// sources without exhausting memory or stack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerifyLimits {
+    /// Maximum number of definitions in the schema
+    pub max_definitions: usize,
+    /// Maximum number of fields (or enum variants) in one definition
+    pub max_fields: usize,
+    /// Maximum nesting of structs and messages, counting the outermost one
+    pub max_depth: usize,
+    /// Maximum serialized size in bytes of a fixed-size struct
+    pub max_fixed_size: usize,
+}
+
+impl Default for VerifyLimits {
+    fn default() -> Self {
+        VerifyLimits {
+            max_definitions: 10_000,
+            max_fields: 1_000,
+            max_depth: 64,
+            // Small enough for size arithmetic on 32-bit targets
+            max_fixed_size: u32::MAX as usize,
+        }
+    }
+}
+
+/// Check that a field's type exists, and that types from another package are
+/// referenced as `package.Type` while types from the same package are not
+fn check_type_reference(
+    ty: &str,
+    field: &Field,
+    def: &Definition,
+    schema: &Schema,
+) -> Result<(), TcsError> {
+    if NATIVE_TYPES.contains(&ty) {
+        return Ok(());
+    }
+    let (qualifier, name) = split_qualified(ty);
+    let target = match schema.resolve_type(ty, def) {
+        Some(target) => target,
+        None => {
+            // A plain name can only be a type of this package
+            let elsewhere = schema.definitions.iter().find(|d| d.name == name);
+            return Err(match (qualifier, elsewhere) {
+                (None, Some(target)) => verification_error(
+                    codes::UNQUALIFIED_CROSS_PACKAGE,
+                    &format!(
+                        "The type {} for field {} is in another package; refer to it as {}",
+                        quote(name),
+                        quote(&field.name),
+                        quote(&format!(
+                            "{}.{}",
+                            target.package.as_deref().unwrap_or_default(),
+                            name
+                        ))
+                    ),
+                ),
+                _ => verification_error(
+                    codes::UNDEFINED_TYPE,
+                    &format!(
+                        "The type {} is not defined for field {}",
+                        quote(ty),
+                        quote(&field.name)
+                    ),
+                ),
+            });
+        }
+    };
+
+    if !in_scope(target, def, schema) {
+        return Err(verification_error(
+            codes::UNDEFINED_TYPE,
+            &format!(
+                "The type {} for field {} is nested in {} and can only be used there",
+                quote(name),
+                quote(&field.name),
+                quote(target.parent.as_deref().unwrap_or_default())
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `target` can be referenced from `def`: it isn't nested, or it is
+/// nested in `def` or in a definition that (transitively) contains `def`
+fn in_scope(target: &Definition, def: &Definition, schema: &Schema) -> bool {
+    let Some(ref scope) = target.parent else {
+        return true;
+    };
+    let mut current = Some(def);
+    while let Some(d) = current {
+        if d.name == *scope && d.package == target.package {
+            return true;
+        }
+        current = d.parent.as_ref().and_then(|p| schema.resolve_type(p, d));
+    }
+    false
+}
+
+/// Check type aliases: their names can't clash with other types, each must
+/// resolve to a built-in type or a definition without a cycle, and neither an
+/// alias nor a field may make an array of arrays by going through one
+fn check_aliases(schema: &Schema, options: &VerifyOptions) -> Result<(), TcsError> {
+    let mut names = HashSet::new();
+    for alias in &schema.aliases {
+        let name = alias.name.as_str();
+        if KEYWORDS.contains(&name) {
+            return Err(verification_error(
+                codes::RESERVED_NAME,
+                &format!("The type name {} is a TCS keyword", quote(name)),
+            ));
+        }
+        if RESERVED_NAMES.contains(&name) {
+            return Err(verification_error(
+                codes::RESERVED_NAME,
+                &format!("The type name {} is reserved", quote(name)),
+            ));
+        }
+        if NATIVE_TYPES.contains(&name)
+            || schema.find_definition(name).is_some()
+            || !names.insert(name)
+        {
+            return Err(verification_error(
+                codes::DUPLICATE_TYPE,
+                &format!("The type {} is defined twice", quote(name)),
+            ));
+        }
+        check_type_rust_name(name)?;
+
+        let mut chain = vec![name];
+        let mut is_array = alias.is_array;
+        let mut target = alias.type_.as_str();
+        while let Some(next) = schema.find_alias(target) {
+            if chain.contains(&target) {
+                chain.push(target);
+                return Err(verification_error(
+                    codes::RECURSIVE_ALIAS,
+                    &format!(
+                        "The type alias {} never resolves: {}",
+                        quote(name),
+                        chain.join(" -> ")
+                    ),
+                ));
+            }
+            if is_array && next.is_array {
+                return Err(verification_error(
+                    codes::UNSUPPORTED_ARRAY_TYPE,
+                    &format!(
+                        "The type alias {} is an array of arrays, which is not supported",
+                        quote(name)
+                    ),
+                ));
+            }
+            is_array |= next.is_array;
+            chain.push(target);
+            target = &next.type_;
+        }
+        let defined = NATIVE_TYPES.contains(&target)
+            || schema.find_definition(split_qualified(target).1).is_some()
+            || options.external_types.contains(target);
+        if !defined {
+            return Err(verification_error(
+                codes::UNDEFINED_TYPE,
+                &format!(
+                    "The type {} for alias {} is not defined",
+                    quote(target),
+                    quote(name)
+                ),
+            ));
+        }
+    }
+
+    for (def, field) in schema.iter_fields().filter(|(_, f)| f.is_array) {
+        let element = Field {
+            is_array: false,
+            array_size: None,
+            ..field.clone()
+        };
+        if schema.resolve_field(&element).is_array {
+            return Err(verification_error(
+                codes::UNSUPPORTED_ARRAY_TYPE,
+                &format!(
+                    "The field {} in {} is an array of arrays through the type alias {}, which is not supported",
+                    quote(&field.name),
+                    quote(&def.name),
+                    quote(field.type_.as_deref().unwrap_or_default())
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check that a nested definition's parent exists, is a struct or message in
+/// the same package, and doesn't (transitively) nest inside the definition
+fn check_parent(def: &Definition, schema: &Schema) -> Result<(), TcsError> {
+    let mut current = def;
+    for _ in 0..schema.definitions.len() {
+        let Some(ref name) = current.parent else {
+            return Ok(());
+        };
+        let parent = schema.resolve_type(name, current);
+        if parent.is_none() {
+            if let Some(other) = schema.definitions.iter().find(|d| d.name == *name) {
+                return Err(verification_error(
+                    codes::UNQUALIFIED_CROSS_PACKAGE,
+                    &format!(
+                        "The type {} is nested in {} but declared in another package",
+                        quote(&current.name),
+                        quote(&other.name)
+                    ),
+                ));
+            }
+        }
+        let parent = match parent {
+            Some(parent) if parent.kind != DefinitionKind::Enum => parent,
+            _ => {
+                return Err(verification_error(
+                    codes::UNDEFINED_TYPE,
+                    &format!(
+                        "The struct or message {} that {} is nested in is not defined",
+                        quote(name),
+                        quote(&current.name)
+                    ),
+                ));
+            }
+        };
+        current = parent;
+    }
+    Err(verification_error(
+        codes::RECURSIVE_STRUCT,
+        &format!("Recursive nesting of {} is not allowed", quote(&def.name)),
+    ))
+}
+
+/// Check that a `[key]` field is not deprecated and has a hashable type
+fn check_key_field(field: &Field, def: &Definition, schema: &Schema) -> Result<(), TcsError> {
+    if field.is_deprecated {
+        return Err(verification_error(
+            codes::INVALID_KEY_FIELD,
+            &format!("The key field {} cannot be deprecated", quote(&field.name)),
+        ));
+    }
+
+    let ty = field.type_.as_deref().unwrap_or_default();
+    let hashable = match schema.resolve_type(ty, def) {
+        Some(def) => def.kind == DefinitionKind::Enum || def.fields.iter().any(|f| f.is_key),
+        None => ty != "float",
+    };
+    if !hashable {
+        return Err(verification_error(
+            codes::INVALID_KEY_FIELD,
+            &format!(
+                "The key field {} has type {}, which is not hashable",
+                quote(&field.name),
+                quote(ty)
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Check that a field of an `[ord]` struct has a totally ordered type and
+/// isn't a `[key]` field (equality must agree with the derived ordering)
+fn check_ord_field(field: &Field, def: &Definition, schema: &Schema) -> Result<(), TcsError> {
+    let ty = field.type_.as_deref().unwrap_or_default();
+    let reason = if field.is_key {
+        "is a [key] field, but equality must compare every field".to_string()
+    } else {
+        match schema.resolve_type(ty, def) {
+            Some(d) if d.kind == DefinitionKind::Enum || d.is_ord => return Ok(()),
+            Some(_) => format!("has type {}, which is not marked [ord]", quote(ty)),
+            None if ty == "float" => "is a float, which has no total order".to_string(),
+            None => return Ok(()),
+        }
+    };
+    Err(verification_error(
+        codes::INVALID_ORD,
+        &format!(
+            "{} can't be marked [ord]: field {} {}",
+            quote(&def.name),
+            quote(&field.name),
+            reason
+        ),
+    ))
+}
+
 /// Verify a schema for correctness
 pub fn verify_schema(schema: &Schema) -> Result<(), TcsError> {
-    let mut defined_types: HashSet<String> = NATIVE_TYPES.iter().map(|s| s.to_string()).collect();
-    let mut definitions_map: HashMap<String, &Definition> = HashMap::new();
+    verify_schema_with_options(schema, &VerifyOptions::default())
+}
 
-    // 1) Check duplicate / reserved type names
-    for def in &schema.definitions {
-        if defined_types.contains(&def.name) {
-            return Err(TcsError::VerificationError(format!(
-                "The type {} is defined twice",
-                quote(&def.name)
-            )));
-        }
-        if RESERVED_NAMES.contains(&def.name.as_str()) {
-            return Err(TcsError::VerificationError(format!(
-                "The type name {} is reserved",
-                quote(&def.name)
-            )));
+/// Verify a schema, returning non-fatal warnings if it is otherwise valid
+pub fn verify_schema_with_warnings(
+    schema: &Schema,
+    options: &VerifyOptions,
+) -> Result<Vec<Warning>, TcsError> {
+    verify_schema_with_options(schema, options)?;
+    let unresolved = schema;
+    let resolved = schema.resolve_aliases();
+    let schema = &resolved;
+
+    let mut warnings = Vec::new();
+
+    // Flags enums whose values aren't single bits (zero is the empty set)
+    for def in schema.definitions.iter().filter(|d| d.is_flags) {
+        for field in def.fields.iter().filter(|f| f.alias_of.is_none()) {
+            if field.field_id != 0 && (field.field_id as u32).count_ones() != 1 {
+                warnings.push(Warning {
+                    code: codes::FLAGS_NOT_POWER_OF_TWO,
+                    msg: format!(
+                        "The value {} of flag {} in {} is not a power of two",
+                        field.field_id,
+                        quote(&field.name),
+                        quote(&def.name)
+                    ),
+                    line: field.line,
+                    column: field.column,
+                });
+            }
         }
-        defined_types.insert(def.name.clone());
-        definitions_map.insert(def.name.clone(), def);
     }
 
-    // 2) Check fields inside each non-enum definition
+    // Enums that skip values (flags enums skip values by design)
     for def in &schema.definitions {
-        if let DefinitionKind::Enum = def.kind {
+        if def.kind != DefinitionKind::Enum || def.is_flags {
             continue;
         }
-        if def.fields.is_empty() {
-            continue;
+        let mut values: Vec<&Field> = def.fields.iter().filter(|f| f.alias_of.is_none()).collect();
+        values.sort_by_key(|f| f.field_id);
+        for pair in values.windows(2) {
+            if pair[1].field_id > pair[0].field_id + 1 {
+                warnings.push(Warning {
+                    code: codes::ENUM_VALUE_GAP,
+                    msg: format!(
+                        "The enum {} skips values between {} and {}",
+                        quote(&def.name),
+                        pair[0].field_id,
+                        pair[1].field_id
+                    ),
+                    line: pair[1].line,
+                    column: pair[1].column,
+                });
+            }
         }
+    }
 
-        // Check that each field's type is defined
-        for field in &def.fields {
-            if let Some(ref ty) = field.type_ {
-                if !defined_types.contains(ty) {
-                    return Err(TcsError::VerificationError(format!(
-                        "The type {} is not defined for field {}",
-                        quote(ty),
-                        quote(&field.name)
-                    )));
-                }
+    // Definitions named after their package; the generated module is the
+    // snake_case package name and the type the PascalCase definition name
+    for def in &schema.definitions {
+        let package = def.package.as_deref().or(schema.package.as_deref());
+        if let Some(package) = package {
+            if to_snake_case(&def.name) == to_snake_case(package) {
+                warnings.push(Warning {
+                    code: codes::PACKAGE_NAME_COLLISION,
+                    msg: format!(
+                        "The type {} has the same name as its package {}",
+                        quote(&def.name),
+                        quote(package)
+                    ),
+                    line: def.line,
+                    column: def.column,
+                });
             }
+        }
+    }
 
-            // Check that fixed-size arrays only use byte type
-            if let Some(size) = field.array_size {
-                if field.type_.as_deref() != Some("byte") {
-                    return Err(TcsError::VerificationError(format!(
-                        "Fixed-size arrays are only supported for byte type, not {} in field {}",
-                        quote(field.type_.as_deref().unwrap_or("unknown")),
-                        quote(&field.name)
-                    )));
-                }
-                if size == 0 {
-                    return Err(TcsError::VerificationError(format!(
-                        "Fixed-size array cannot have size 0 in field {}",
-                        quote(&field.name)
-                    )));
-                }
+    // Annotations the parser didn't recognize, likely typos
+    for def in &schema.definitions {
+        for field in &def.fields {
+            for annotation in &field.unknown_annotations {
+                warnings.push(Warning {
+                    code: codes::UNKNOWN_ANNOTATION,
+                    msg: unknown_annotation_message(annotation, field, def),
+                    line: annotation.line,
+                    column: annotation.column,
+                });
             }
         }
+    }
 
-        // Check field_id uniqueness and bounds
-        let mut values = HashSet::new();
+    // Type aliases and nested definitions never referenced by a field or
+    // alias. Top-level definitions are entry points for the code using the
+    // generated types, so they're never reported. Names can repeat across
+    // packages, so definitions are looked up from the fields that use them;
+    // alias targets aren't in a package and match by name.
+    let mut used_definitions: HashSet<String> = schema
+        .iter_fields()
+        .filter_map(|(def, f)| schema.resolve_type(f.type_.as_deref()?, def))
+        .map(Definition::qualified_name)
+        .collect();
+    for alias in &schema.aliases {
+        let name = split_qualified(&alias.type_).1;
+        let targets = schema.definitions.iter().filter(|d| d.name == name);
+        used_definitions.extend(targets.map(Definition::qualified_name));
+    }
+    let used: HashSet<&str> = schema
+        .definitions
+        .iter()
+        .chain(&unresolved.definitions)
+        .flat_map(|def| def.fields.iter())
+        .filter_map(|f| f.type_.as_deref())
+        .chain(schema.aliases.iter().map(|a| a.type_.as_str()))
+        .map(|ty| split_qualified(ty).1)
+        .collect();
+    for alias in schema
+        .aliases
+        .iter()
+        .filter(|a| !used.contains(a.name.as_str()))
+    {
+        warnings.push(Warning {
+            code: codes::UNUSED_TYPE,
+            msg: format!("The type alias {} is never used", quote(&alias.name)),
+            line: alias.line,
+            column: alias.column,
+        });
+    }
+    for def in &schema.definitions {
+        if def.parent.is_some() && !used_definitions.contains(&def.qualified_name()) {
+            warnings.push(Warning {
+                code: codes::UNUSED_TYPE,
+                msg: format!("The type {} is never used", quote(&def.name)),
+                line: def.line,
+                column: def.column,
+            });
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// Verify a schema and run every non-fatal check: the warnings from
+/// [`verify_schema_with_warnings`] followed by style lints for undocumented
+/// definitions and naming conventions
+pub fn lint_schema(schema: &Schema, options: &VerifyOptions) -> Result<Vec<Warning>, TcsError> {
+    let mut warnings = verify_schema_with_warnings(schema, options)?;
+
+    for def in &schema.definitions {
+        if def.comments.is_empty() {
+            warnings.push(Warning {
+                code: codes::UNDOCUMENTED,
+                msg: format!("The type {} has no doc comment", quote(&def.name)),
+                line: def.line,
+                column: def.column,
+            });
+        }
+    }
+
+    for alias in &schema.aliases {
+        if alias.name.contains('_') || !alias.name.starts_with(|c: char| c.is_ascii_uppercase()) {
+            warnings.push(naming_warning(
+                "type alias",
+                &alias.name,
+                "PascalCase",
+                alias.line,
+                alias.column,
+            ));
+        }
+    }
+
+    for def in &schema.definitions {
+        if def.name.contains('_') || !def.name.starts_with(|c: char| c.is_ascii_uppercase()) {
+            warnings.push(naming_warning(
+                "type",
+                &def.name,
+                "PascalCase",
+                def.line,
+                def.column,
+            ));
+        }
         for field in &def.fields {
-            if values.contains(&field.field_id) {
-                return Err(TcsError::VerificationError(format!(
-                    "The id for field {} is used twice",
-                    quote(&field.name)
-                )));
+            let (kind, valid, convention) = if def.kind == DefinitionKind::Enum {
+                let upper = field
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+                ("variant", upper, "UPPER_SNAKE_CASE")
+            } else {
+                let lower = field.name.starts_with(|c: char| c.is_ascii_lowercase());
+                ("field", lower, "camelCase or snake_case")
+            };
+            if !valid {
+                warnings.push(naming_warning(
+                    kind,
+                    &field.name,
+                    convention,
+                    field.line,
+                    field.column,
+                ));
             }
-            if field.field_id <= 0 {
-                return Err(TcsError::VerificationError(format!(
-                    "The id for field {} must be positive",
-                    quote(&field.name)
-                )));
-            }
-            if field.field_id > def.fields.len() as i32 {
-                return Err(TcsError::VerificationError(format!(
-                    "The id for field {} cannot be larger than {}",
-                    quote(&field.name),
-                    def.fields.len()
-                )));
+        }
+    }
+
+    Ok(warnings)
+}
+
+fn unknown_annotation_message(annotation: &Annotation, field: &Field, def: &Definition) -> String {
+    format!(
+        "Unknown annotation {} on {} in {}",
+        quote(&format!("@{}", annotation.name)),
+        quote(&field.name),
+        quote(&def.name)
+    )
+}
+
+fn naming_warning(kind: &str, name: &str, convention: &str, line: usize, column: usize) -> Warning {
+    Warning {
+        code: codes::NAMING_CONVENTION,
+        msg: format!("The {} {} should be {}", kind, quote(name), convention),
+        line,
+        column,
+    }
+}
+
+/// Check that parsing `source`, formatting it and parsing the result again
+/// yields the same schema (ignoring source positions). The error describes the
+/// first difference found.
+pub fn check_format_roundtrip(source: &str) -> Result<(), TcsError> {
+    let original = parse_schema(&tokenize_schema(source)?)?;
+    let formatted = format_schema(&original);
+    let reparsed = parse_schema(&tokenize_schema(&formatted)?)?;
+
+    let divergence = |what: String| {
+        Err(verification_error(
+            codes::FORMAT_ROUNDTRIP,
+            &format!("Formatting changed the schema: {}", what),
+        ))
+    };
+
+    if original.header != reparsed.header {
+        return divergence(format!(
+            "header {:?} became {:?}",
+            original.header, reparsed.header
+        ));
+    }
+    if original.package != reparsed.package {
+        return divergence(format!(
+            "package {:?} became {:?}",
+            original.package, reparsed.package
+        ));
+    }
+    let option_values = |schema: &Schema| -> Vec<(String, OptionValue)> {
+        schema
+            .options
+            .iter()
+            .map(|o| (o.name.clone(), o.value.clone()))
+            .collect()
+    };
+    if option_values(&original) != option_values(&reparsed) {
+        return divergence(format!(
+            "options {:?} became {:?}",
+            option_values(&original),
+            option_values(&reparsed)
+        ));
+    }
+    let aliases = |schema: &Schema| -> Vec<TypeAlias> {
+        schema
+            .aliases
+            .iter()
+            .map(|a| TypeAlias {
+                line: 0,
+                column: 0,
+                ..a.clone()
+            })
+            .collect()
+    };
+    if aliases(&original) != aliases(&reparsed) {
+        return divergence(format!(
+            "type aliases {:?} became {:?}",
+            aliases(&original),
+            aliases(&reparsed)
+        ));
+    }
+    if original.definitions.len() != reparsed.definitions.len() {
+        return divergence(format!(
+            "{} definition(s) became {}",
+            original.definitions.len(),
+            reparsed.definitions.len()
+        ));
+    }
+
+    for (before, after) in original.definitions.iter().zip(&reparsed.definitions) {
+        if before.fields.len() != after.fields.len() {
+            return divergence(format!(
+                "{} has {} field(s) instead of {}",
+                quote(&before.name),
+                after.fields.len(),
+                before.fields.len()
+            ));
+        }
+        for (a, b) in before.fields.iter().zip(&after.fields) {
+            let unknown_annotations = b
+                .unknown_annotations
+                .iter()
+                .zip(&a.unknown_annotations)
+                .map(|(b, a)| Annotation {
+                    line: a.line,
+                    column: a.column,
+                    ..b.clone()
+                })
+                .chain(
+                    b.unknown_annotations
+                        .iter()
+                        .skip(a.unknown_annotations.len())
+                        .cloned(),
+                )
+                .collect();
+            let b = Field {
+                line: a.line,
+                column: a.column,
+                unknown_annotations,
+                ..b.clone()
+            };
+            if *a != b {
+                return divergence(format!(
+                    "field {} of {} changed from {:?} to {:?}",
+                    quote(&a.name),
+                    quote(&before.name),
+                    a,
+                    b
+                ));
             }
-            values.insert(field.field_id);
+        }
+        let after = Definition {
+            line: before.line,
+            column: before.column,
+            end_line: before.end_line,
+            end_column: before.end_column,
+            fields: before.fields.clone(),
+            ..after.clone()
+        };
+        if *before != after {
+            return divergence(format!(
+                "definition {} changed from {:?} to {:?}",
+                quote(&before.name),
+                before,
+                after
+            ));
         }
     }
 
-    // 3) Check that structs do not contain themselves recursively
-    let mut state: HashMap<String, u8> = HashMap::new();
+    Ok(())
+}
 
-    fn check_recursion(
-        name: &str,
-        definitions_map: &HashMap<String, &Definition>,
-        state: &mut HashMap<String, u8>,
-    ) -> Result<(), TcsError> {
-        let definition = match definitions_map.get(name) {
-            Some(def) => def,
-            None => return Ok(()),
+/// Check that code generated from `baseline` still decodes messages written
+/// with `current`.
+///
+/// Messages are encoded positionally, as their fields in declaration order, so
+/// for every message in both schemas the baseline's fields must all still be
+/// there, in the same order, with the same id, type and presence (optional or
+/// `required`). New fields can only go after them, and an id may not move to
+/// a field with another name. Switching a field to or from a type alias of the
+/// same type is fine. Appending fields keeps old readers working, but code
+/// generated from `current` can't decode data written before the fields were
+/// added. Returns every incompatibility found, pointing at the fields of
+/// `current` (or at the message, for removed fields) and sorted by position.
+pub fn verify_schema_against_baseline(
+    current: &Schema,
+    baseline: &Schema,
+) -> Result<(), Vec<TcsError>> {
+    let current = &current.resolve_aliases();
+    let baseline = &baseline.resolve_aliases();
+    let mut errors = Vec::new();
+    let messages = current
+        .definitions
+        .iter()
+        .filter(|d| d.kind == DefinitionKind::Message);
+    for def in messages {
+        let old = baseline
+            .definitions
+            .iter()
+            .find(|d| d.qualified_name() == def.qualified_name());
+        let old = match old {
+            Some(old) if old.kind == DefinitionKind::Message => old,
+            _ => continue,
         };
-        if let DefinitionKind::Struct = definition.kind {
-            if let Some(&s) = state.get(name) {
-                if s == 1 {
-                    return Err(TcsError::VerificationError(format!(
-                        "Recursive nesting of {} is not allowed",
-                        quote(name)
-                    )));
-                } else if s == 2 {
-                    return Ok(());
-                }
-            }
-            state.insert(name.to_string(), 1);
-            for field in &definition.fields {
-                // Arrays are allowed to be recursive (they break the recursion)
-                if !field.is_array {
-                    if let Some(ref ty) = field.type_ {
-                        check_recursion(ty, definitions_map, state)?;
-                    }
-                }
-            }
-            state.insert(name.to_string(), 2);
+        for field in &def.fields {
+            let msg = match old.field(&field.name) {
+                Some(before) if before.field_id != field.field_id => format!(
+                    "Field {} in {} changed its id from {} to {}",
+                    quote(&field.name),
+                    quote(&def.name),
+                    before.field_id,
+                    field.field_id
+                ),
+                Some(before) if type_text(before) != type_text(field) => format!(
+                    "Field {} in {} changed its type from {} to {}",
+                    quote(&field.name),
+                    quote(&def.name),
+                    quote(&type_text(before)),
+                    quote(&type_text(field))
+                ),
+                Some(before) if is_required(before) != is_required(field) => format!(
+                    "Field {} in {} changed from {} to {}",
+                    quote(&field.name),
+                    quote(&def.name),
+                    presence_text(before),
+                    presence_text(field)
+                ),
+                Some(_) => continue,
+                None => match old.fields.iter().find(|f| f.field_id == field.field_id) {
+                    Some(before) => format!(
+                        "Field id {} in {} belonged to {} in the baseline and is reused for {}",
+                        field.field_id,
+                        quote(&def.name),
+                        quote(&before.name),
+                        quote(&field.name)
+                    ),
+                    None => continue,
+                },
+            };
+            errors.push(verification_error_at(
+                codes::INCOMPATIBLE_CHANGE,
+                &msg,
+                field.line,
+                field.column,
+            ));
+        }
+
+        let removed: Vec<&Field> = old
+            .fields
+            .iter()
+            .filter(|before| def.field(&before.name).is_none())
+            .collect();
+        for before in &removed {
+            errors.push(verification_error_at(
+                codes::INCOMPATIBLE_CHANGE,
+                &format!(
+                    "Field {} in {} was removed",
+                    quote(&before.name),
+                    quote(&def.name)
+                ),
+                def.line,
+                def.column,
+            ));
+        }
+        // The baseline's fields must come first, in their old order. Once a
+        // field is gone, everything after it has moved anyway.
+        let moved = old
+            .fields
+            .iter()
+            .zip(&def.fields)
+            .find(|(before, field)| before.name != field.name);
+        if let (true, Some((before, field))) = (removed.is_empty(), moved) {
+            errors.push(verification_error_at(
+                codes::INCOMPATIBLE_CHANGE,
+                &format!(
+                    "Field {} in {} is declared where the baseline has {}; existing fields must keep their order, with new ones after them",
+                    quote(&field.name),
+                    quote(&def.name),
+                    quote(&before.name)
+                ),
+                field.line,
+                field.column,
+            ));
         }
+    }
+
+    // Nested messages come after their parent, so their fields were checked
+    // out of source order
+    sort_by_location(&mut errors);
+    if errors.is_empty() {
         Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Whether a message field is generated without an `Option` wrapper
+fn is_required(field: &Field) -> bool {
+    field.presence == Presence::Required
+}
+
+/// How a message field's presence is described in errors
+fn presence_text(field: &Field) -> &'static str {
+    if is_required(field) {
+        "required"
+    } else {
+        "optional"
+    }
+}
+
+/// A field's type as written in the schema, e.g. `byte[32]`
+fn type_text(field: &Field) -> String {
+    let ty = field.type_.as_deref().unwrap_or_default();
+    match (field.is_array, field.array_size) {
+        (_, Some(size)) => format!("{}[{}]", ty, size),
+        (true, None) => format!("{}[]", ty),
+        (false, None) => ty.to_string(),
+    }
+}
+
+/// Check that a field or variant name survives case conversion in the
+/// generated code: `_` and `__` have no PascalCase form, for example, and
+/// `_1` becomes `1`
+fn check_rust_name(field: &Field, def: &Definition) -> Result<(), TcsError> {
+    // Plain enum variants become PascalCase; fields, flags and aliases are
+    // snake_case (upper-cased for consts, which doesn't change validity)
+    let is_variant = def.kind == DefinitionKind::Enum && !def.is_flags && field.alias_of.is_none();
+    let rust_name = if is_variant {
+        to_pascal_case(&field.name)
+    } else {
+        to_snake_case(&field.name)
+    };
+    if is_valid_rust_name(&rust_name) {
+        return Ok(());
+    }
+
+    Err(verification_error(
+        codes::INVALID_NAME,
+        &format!(
+            "The name {} in {} becomes {} in generated Rust, which is not a valid identifier",
+            quote(&field.name),
+            quote(&def.name),
+            quote(&rust_name)
+        ),
+    ))
+}
+
+/// Check that a definition or type alias name survives conversion to the
+/// PascalCase of Rust type names: `__` becomes an empty name, for example,
+/// and `_1` becomes `1`
+fn check_type_rust_name(name: &str) -> Result<(), TcsError> {
+    let rust_name = to_pascal_case(name);
+    if is_valid_rust_name(&rust_name) {
+        return Ok(());
     }
 
+    Err(verification_error(
+        codes::INVALID_NAME,
+        &format!(
+            "The type name {} becomes {} in generated Rust, which is not a valid identifier",
+            quote(name),
+            quote(&rust_name)
+        ),
+    ))
+}
+
+/// Whether a case-converted name is still an identifier
+fn is_valid_rust_name(rust_name: &str) -> bool {
+    !rust_name.is_empty()
+        && rust_name != "_"
+        && !rust_name.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Check that no two types get the same Rust name: definitions generated into
+/// the same module (same package and parent), or a type alias and any other
+/// type, since aliases share one namespace with every definition
+fn check_type_name_collisions(schema: &Schema) -> Result<(), TcsError> {
+    let collision = |a: &str, b: &str, rust_name: &str| {
+        verification_error(
+            codes::RUST_NAME_COLLISION,
+            &format!(
+                "The types {} and {} both become {} in generated Rust",
+                quote(a),
+                quote(b),
+                quote(rust_name)
+            ),
+        )
+    };
+
+    let mut modules: HashMap<(Option<&str>, Option<&str>, String), &str> = HashMap::new();
+    let mut names: HashMap<String, &str> = HashMap::new();
     for def in &schema.definitions {
-        check_recursion(&def.name, &definitions_map, &mut state)?;
+        let package = def.package.as_deref().or(schema.package.as_deref());
+        let rust_name = to_pascal_case(&def.name);
+        let key = (package, def.parent.as_deref(), rust_name.clone());
+        if let Some(other) = modules.insert(key, &def.name) {
+            return Err(collision(other, &def.name, &rust_name));
+        }
+        names.insert(rust_name, &def.name);
+    }
+    for alias in &schema.aliases {
+        let rust_name = to_pascal_case(&alias.name);
+        if let Some(other) = names.insert(rust_name.clone(), &alias.name) {
+            return Err(collision(other, &alias.name, &rust_name));
+        }
     }
+    Ok(())
+}
 
+/// Check that no two members of a definition get the same Rust name. Plain
+/// enum variants are compared by their PascalCase names; fields, flags and
+/// variant aliases (associated consts) by their snake_case names.
+fn check_rust_name_collisions(def: &Definition) -> Result<(), TcsError> {
+    let mut seen: HashMap<(bool, String), &Field> = HashMap::new();
+    for field in &def.fields {
+        let is_variant =
+            def.kind == DefinitionKind::Enum && !def.is_flags && field.alias_of.is_none();
+        let rust_name = if is_variant {
+            to_pascal_case(&field.name)
+        } else {
+            to_snake_case(&field.name)
+        };
+        // Enum aliases and flags become associated consts next to the
+        // generated `FIELD_COUNT`
+        if def.kind == DefinitionKind::Enum
+            && !is_variant
+            && rust_name.to_uppercase() == "FIELD_COUNT"
+        {
+            return Err(verification_error(
+                codes::RUST_NAME_COLLISION,
+                &format!(
+                    "{} in {} becomes FIELD_COUNT in generated Rust, which is already the generated field count constant",
+                    quote(&field.name),
+                    quote(&def.name)
+                ),
+            ));
+        }
+        if let Some(other) = seen.insert((is_variant, rust_name.clone()), field) {
+            let kind = if def.kind == DefinitionKind::Enum {
+                "variants"
+            } else {
+                "fields"
+            };
+            return Err(verification_error(
+                codes::RUST_NAME_COLLISION,
+                &format!(
+                    "The {} {} and {} in {} both become {} in generated Rust",
+                    kind,
+                    quote(&other.name),
+                    quote(&field.name),
+                    quote(&def.name),
+                    quote(&rust_name)
+                ),
+            ));
+        }
+    }
     Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::parser::parse_schema;
-    use crate::tokenizer::tokenize_schema;
+/// Check that a `@since` version is dot-separated numbers, like `2` or `1.4.2`
+fn check_since(field: &Field, def: &Definition) -> Result<(), TcsError> {
+    let Some(ref since) = field.since else {
+        return Ok(());
+    };
+    let valid = since
+        .split('.')
+        .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()));
+    if valid {
+        return Ok(());
+    }
 
-    fn verify(input: &str) -> Result<(), TcsError> {
-        let tokens = tokenize_schema(input)?;
-        let schema = parse_schema(&tokens)?;
-        verify_schema(&schema)
+    Err(verification_error(
+        codes::INVALID_VERSION,
+        &format!(
+            "The @since version {} of {} in {} is not a dot-separated version like \"2.0\"",
+            quote(since),
+            quote(&field.name),
+            quote(&def.name)
+        ),
+    ))
+}
+
+/// Check that a field's `@validate` bounds can be met: value bounds on integer
+/// fields, within the type's range, and length bounds on variable-length
+/// arrays, with neither lower bound above its upper bound
+fn check_validation(field: &Field, def: &Definition) -> Result<(), TcsError> {
+    let Some(ref validate) = field.validate else {
+        return Ok(());
+    };
+    let type_name = field.type_.as_deref().unwrap_or("");
+    let range = integer_range(type_name).filter(|_| !field.is_array);
+    let has_values = validate.min.is_some() || validate.max.is_some();
+    let has_lengths = validate.len_min.is_some() || validate.len_max.is_some();
+    let out_of_range = |key: &str, bound: Option<i128>| {
+        let (lowest, highest) = range?;
+        let bound = bound?;
+        (bound < lowest || bound > highest)
+            .then(|| format!("{} = {} is out of range for {}", key, bound, type_name))
+    };
+
+    let problem = if has_values && range.is_none() {
+        Some("min and max only apply to integer fields".to_string())
+    } else if has_lengths && (!field.is_array || field.array_size.is_some()) {
+        Some("len_min and len_max only apply to variable-length arrays".to_string())
+    } else if let Some(problem) =
+        out_of_range("min", validate.min).or_else(|| out_of_range("max", validate.max))
+    {
+        Some(problem)
+    } else if let (Some(min), Some(max)) = (validate.min, validate.max) {
+        (min > max).then(|| format!("min = {} is greater than max = {}", min, max))
+    } else if let (Some(min), Some(max)) = (validate.len_min, validate.len_max) {
+        (min > max).then(|| format!("len_min = {} is greater than len_max = {}", min, max))
+    } else {
+        None
+    };
+    let Some(problem) = problem else {
+        return Ok(());
+    };
+
+    Err(verification_error(
+        codes::INVALID_VALIDATION,
+        &format!(
+            "Invalid @validate on {} in {}: {}",
+            quote(&field.name),
+            quote(&def.name),
+            problem
+        ),
+    ))
+}
+
+/// Check that a `[feature = "..."]` gate names a valid Cargo feature
+fn check_feature_name(feature: &str, def: &Definition) -> Result<(), TcsError> {
+    let valid_start = feature
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+    let valid_rest = feature
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '.'));
+    if valid_start && valid_rest {
+        return Ok(());
     }
+    Err(verification_error(
+        codes::INVALID_NAME,
+        &format!(
+            "The feature {} of {} is not a valid Cargo feature name",
+            quote(feature),
+            quote(&def.name)
+        ),
+    ))
+}
 
-    #[test]
-    fn test_valid_schema() {
-        let input = r#"
-            struct Point {
-                int x;
-                int y;
-            }
-        "#;
-        assert!(verify(input).is_ok());
+/// Check that a package name becomes a valid module name in the generated
+/// code. Each `.`-separated segment must be an identifier; dotted names are
+/// then rejected, since packages map to a single module for now.
+fn check_package_name(package: &str) -> Result<(), TcsError> {
+    let segments: Vec<&str> = package.split('.').collect();
+    let invalid = segments.iter().find_map(|segment| {
+        let module = to_snake_case(segment);
+        if segment.is_empty() {
+            Some("has an empty segment".to_string())
+        } else if !segment
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            Some(format!(
+                "has the segment {}, which is not an identifier",
+                quote(segment)
+            ))
+        } else if module.is_empty()
+            || module == "_"
+            || module.starts_with(|c: char| c.is_ascii_digit())
+        {
+            Some(format!(
+                "becomes the module {} in generated Rust, which is not a valid identifier",
+                quote(&module)
+            ))
+        } else if escape_rust_keyword(&module) != module {
+            // Modules can't be escaped like fields: `self`, `crate` and
+            // `super` can't be raw identifiers, and `r#type` would leak into
+            // every path users write
+            Some(format!(
+                "becomes the module {} in generated Rust, which is a Rust keyword",
+                quote(&module)
+            ))
+        } else {
+            None
+        }
+    });
+    let reason = match invalid {
+        Some(reason) => reason,
+        None if segments.len() > 1 => "is dotted, which is not supported".to_string(),
+        None => return Ok(()),
+    };
+    Err(verification_error(
+        codes::INVALID_NAME,
+        &format!("The package {} {}", quote(package), reason),
+    ))
+}
+
+/// Check the parts of a schema that only matter under some generation options
+pub fn verify_gen_options(schema: &Schema, options: &GenOptions) -> Result<(), TcsError> {
+    if options.eq_ignore_deprecated {
+        let empty = schema.definitions.iter().find(|d| {
+            !d.fields.is_empty()
+                && d.fields.iter().all(|f| f.is_deprecated)
+                && !d.fields.iter().any(|f| f.is_key)
+        });
+        if let Some(def) = empty {
+            return Err(verification_error(
+                codes::NO_COMPARABLE_FIELDS,
+                &format!(
+                    "Every field of {} is deprecated, so equality would ignore all of them",
+                    quote(&def.name)
+                ),
+            ));
+        }
     }
+    Ok(())
+}
 
-    #[test]
-    fn test_duplicate_type() {
-        let input = r#"
-            struct Foo { int x; }
-            struct Foo { int y; }
-        "#;
-        let err = verify(input).unwrap_err();
-        assert!(matches!(err, TcsError::VerificationError(_)));
+/// Verify a schema with the given options, after rejecting schemas that
+/// exceed `limits`
+pub fn verify_schema_with_limits(
+    schema: &Schema,
+    options: &VerifyOptions,
+    limits: &VerifyLimits,
+) -> Result<(), TcsError> {
+    let exceeded = |msg: String| verification_error(codes::LIMIT_EXCEEDED, &msg);
+
+    if schema.definitions.len() > limits.max_definitions {
+        return Err(exceeded(format!(
+            "The schema has {} definitions, more than the limit of {}",
+            schema.definitions.len(),
+            limits.max_definitions
+        )));
+    }
+    if let Some(def) = schema
+        .definitions
+        .iter()
+        .find(|d| d.fields.len() > limits.max_fields)
+    {
+        return Err(exceeded(format!(
+            "The type {} has {} fields, more than the limit of {}",
+            quote(&def.name),
+            def.fields.len(),
+            limits.max_fields
+        )));
     }
 
-    #[test]
-    fn test_undefined_type() {
-        let input = r#"
-            struct Foo {
-                Unknown x;
-            }
-        "#;
-        let err = verify(input).unwrap_err();
-        assert!(matches!(err, TcsError::VerificationError(_)));
+    // Nesting and sizes are measured through type aliases
+    let resolved = schema.resolve_aliases();
+    let mut depths = HashMap::new();
+    for def in &resolved.definitions {
+        let mut visiting = HashSet::new();
+        if nesting_depth(
+            def,
+            1,
+            limits.max_depth,
+            &resolved,
+            &mut depths,
+            &mut visiting,
+        )
+        .is_none()
+        {
+            return Err(exceeded(format!(
+                "The type {} nests more than {} levels deep",
+                quote(&def.name),
+                limits.max_depth
+            )));
+        }
+    }
+
+    let sizes = fixed_struct_sizes(&resolved).map_err(size_overflow)?;
+    if let Some(def) = resolved.definitions.iter().find(|d| {
+        sizes
+            .get(&d.qualified_name())
+            .is_some_and(|&size| size > limits.max_fixed_size)
+    }) {
+        return Err(exceeded(format!(
+            "The type {} has a fixed size of {} bytes, more than the limit of {}",
+            quote(&def.name),
+            sizes[&def.qualified_name()],
+            limits.max_fixed_size
+        )));
+    }
+
+    verify_schema_with_options(schema, options)
+}
+
+/// The error for a fixed-size struct whose size overflows `usize`
+fn size_overflow(def: &Definition) -> TcsError {
+    verification_error(
+        codes::LIMIT_EXCEEDED,
+        &format!("The fixed size of {} overflows usize", quote(&def.name)),
+    )
+}
+
+/// The nesting depth of a definition: one more than the deepest struct or
+/// message among its field types. Returns `None` once the depth below `level`
+/// exceeds `max_depth`, so deep chains are never fully walked. Recursive
+/// references (allowed through arrays and messages) add no depth.
+fn nesting_depth(
+    def: &Definition,
+    level: usize,
+    max_depth: usize,
+    schema: &Schema,
+    depths: &mut HashMap<String, usize>,
+    visiting: &mut HashSet<String>,
+) -> Option<usize> {
+    let name = def.qualified_name();
+    if let Some(&depth) = depths.get(&name) {
+        return (level + depth - 1 <= max_depth).then_some(depth);
+    }
+    if level > max_depth {
+        return None;
+    }
+    if !visiting.insert(name.clone()) {
+        return Some(0);
+    }
+
+    let mut deepest = 0;
+    for field in &def.fields {
+        let ty = field.type_.as_deref().unwrap_or_default();
+        if let Some(child) = schema
+            .resolve_type(ty, def)
+            .filter(|d| d.kind != DefinitionKind::Enum)
+        {
+            let depth = nesting_depth(child, level + 1, max_depth, schema, depths, visiting)?;
+            deepest = deepest.max(depth);
+        }
+    }
+
+    visiting.remove(&name);
+    depths.insert(name, deepest + 1);
+    Some(deepest + 1)
+}
+
+/// Verify a schema for correctness with the given options
+pub fn verify_schema_with_options(
+    schema: &Schema,
+    options: &VerifyOptions,
+) -> Result<(), TcsError> {
+    // 0) Check that names are valid identifiers (for schemas not built by the parser),
+    //    and still are once converted to Rust's naming conventions, that type
+    //    aliases resolve (the remaining checks see fields with them resolved),
+    //    and that the options block only sets known options, `@since`
+    //    versions are well-formed and `@validate` bounds can be met
+    schema.validate_names()?;
+    check_aliases(schema, options)?;
+    let resolved = schema.resolve_aliases();
+    let schema = &resolved;
+    verify_gen_options(schema, &GenOptions::from_schema(schema)?)?;
+    let packages = schema.definitions.iter().map(|d| &d.package);
+    for package in std::iter::once(&schema.package).chain(packages).flatten() {
+        check_package_name(package)?;
+    }
+    for def in &schema.definitions {
+        check_type_rust_name(&def.name)?;
+        if let Some(ref feature) = def.feature {
+            check_feature_name(feature, def)?;
+        }
+        for field in &def.fields {
+            if KEYWORDS.contains(&field.name.as_str()) {
+                return Err(verification_error(
+                    codes::RESERVED_NAME,
+                    &format!(
+                        "The field name {} in {} is a TCS keyword",
+                        quote(&field.name),
+                        quote(&def.name)
+                    ),
+                ));
+            }
+            check_rust_name(field, def)?;
+            check_since(field, def)?;
+            check_validation(field, def)?;
+            let unknown = field.unknown_annotations.first();
+            if let Some(annotation) = unknown.filter(|_| options.strict_annotations) {
+                return Err(verification_error(
+                    codes::UNKNOWN_ANNOTATION,
+                    &format!(
+                        "{} at line {}, column {}",
+                        unknown_annotation_message(annotation, field, def),
+                        annotation.line,
+                        annotation.column
+                    ),
+                ));
+            }
+        }
+    }
+
+    // 1) Check duplicate / reserved type names. Packages have a namespace
+    //    each, so only names in the same package clash.
+    let mut defined_types = HashSet::new();
+    for def in &schema.definitions {
+        let name = def.name.as_str();
+        if NATIVE_TYPES.contains(&name) || !defined_types.insert((def.package.as_deref(), name)) {
+            return Err(verification_error(
+                codes::DUPLICATE_TYPE,
+                &format!("The type {} is defined twice", quote(&def.name)),
+            ));
+        }
+        if KEYWORDS.contains(&def.name.as_str()) {
+            return Err(verification_error(
+                codes::RESERVED_NAME,
+                &format!("The type name {} is a TCS keyword", quote(&def.name)),
+            ));
+        }
+        if RESERVED_NAMES.contains(&def.name.as_str()) {
+            return Err(verification_error(
+                codes::RESERVED_NAME,
+                &format!("The type name {} is reserved", quote(&def.name)),
+            ));
+        }
+    }
+    for def in &schema.definitions {
+        check_parent(def, schema)?;
+    }
+
+    // 2) Check enum variants: names must be unique, values must be unique
+    //    unless the variant is an explicit alias, and flags (bits of a `u32`)
+    //    can't be negative
+    for def in &schema.definitions {
+        if def.kind != DefinitionKind::Enum {
+            continue;
+        }
+
+        let mut names = HashSet::new();
+        let mut values = HashSet::new();
+        for field in &def.fields {
+            if !names.insert(field.name.as_str()) {
+                return Err(verification_error(
+                    codes::DUPLICATE_ENUM_VARIANT,
+                    &format!(
+                        "The variant {} is defined twice in {}",
+                        quote(&field.name),
+                        quote(&def.name)
+                    ),
+                ));
+            }
+            if def.is_flags && field.field_id < 0 {
+                return Err(verification_error(
+                    codes::FIELD_ID_OUT_OF_RANGE,
+                    &format!(
+                        "The value {} of flag {} in {} is negative",
+                        field.field_id,
+                        quote(&field.name),
+                        quote(&def.name)
+                    ),
+                ));
+            }
+            if field.alias_of.is_none() && !values.insert(field.field_id) {
+                return Err(verification_error(
+                    codes::DUPLICATE_ENUM_VARIANT,
+                    &format!(
+                        "The value {} for variant {} is used twice (use {} to declare an alias)",
+                        field.field_id,
+                        quote(&field.name),
+                        quote("NAME = OTHER;")
+                    ),
+                ));
+            }
+        }
+    }
+
+    // Distinct names must stay distinct once converted to Rust's conventions
+    check_type_name_collisions(schema)?;
+    for def in &schema.definitions {
+        check_rust_name_collisions(def)?;
+    }
+
+    // 3) Check fields inside each non-enum definition
+    for def in &schema.definitions {
+        if let DefinitionKind::Enum = def.kind {
+            continue;
+        }
+        if def.fields.is_empty() {
+            continue;
+        }
+
+        // Check that each field's type is defined and visible from this package
+        for field in &def.fields {
+            // The parser always sets a type here; codegen relies on it
+            let ty = match field.type_ {
+                Some(ref ty) if !ty.is_empty() => ty,
+                _ => {
+                    return Err(verification_error(
+                        codes::UNDEFINED_TYPE,
+                        &format!(
+                            "Field {} in {} has no type",
+                            quote(&field.name),
+                            quote(&def.name)
+                        ),
+                    ));
+                }
+            };
+            if !options.external_types.contains(ty) {
+                check_type_reference(ty, field, def, schema)?;
+            }
+
+            // Types gated on a feature can only be used by types gated on it too
+            let target = schema.resolve_type(ty, def);
+            if let Some(feature) = target.and_then(|t| t.feature.as_ref()) {
+                if def.feature.as_ref() != Some(feature) {
+                    return Err(verification_error(
+                        codes::FEATURE_GATED_TYPE,
+                        &format!(
+                            "The type {} for field {} is only generated with the feature {}; mark {} [feature = {}] too",
+                            quote(split_qualified(ty).1),
+                            quote(&field.name),
+                            quote(feature),
+                            quote(&def.name),
+                            quote(feature)
+                        ),
+                    ));
+                }
+            }
+
+            // Check that fixed-size arrays only use byte (or uint8) type
+            if let Some(size) = field.array_size {
+                if !field.is_fixed_byte_array() {
+                    return Err(verification_error(
+                        codes::INVALID_FIXED_ARRAY,
+                        &format!(
+                            "Fixed-size arrays are only supported for byte or uint8 type, not {} in field {}",
+                            quote(field.type_.as_deref().unwrap_or("unknown")),
+                            quote(&field.name)
+                        ),
+                    ));
+                }
+                if size == 0 {
+                    return Err(verification_error(
+                        codes::INVALID_FIXED_ARRAY,
+                        &format!(
+                            "Fixed-size array cannot have size 0 in field {}",
+                            quote(&field.name)
+                        ),
+                    ));
+                }
+            }
+
+            // Key fields must be usable in the generated PartialEq/Hash impls
+            if field.is_key {
+                check_key_field(field, def, schema)?;
+            }
+
+            // [ord] structs derive Eq and Ord, so every field needs them too
+            if def.is_ord {
+                check_ord_field(field, def, schema)?;
+            }
+
+            // bytes is already a byte sequence; arrays of it aren't supported
+            if field.is_array && field.type_.as_deref() == Some("bytes") {
+                return Err(verification_error(
+                    codes::UNSUPPORTED_ARRAY_TYPE,
+                    &format!(
+                        "Arrays of {} are not supported in field {}",
+                        quote("bytes"),
+                        quote(&field.name)
+                    ),
+                ));
+            }
+        }
+
+        // Check field_id uniqueness and bounds
+        let mut values = HashSet::new();
+        for field in &def.fields {
+            if values.contains(&field.field_id) {
+                return Err(verification_error(
+                    codes::DUPLICATE_FIELD_ID,
+                    &format!("The id for field {} is used twice", quote(&field.name)),
+                ));
+            }
+            if field.field_id <= 0 {
+                return Err(verification_error(
+                    codes::FIELD_ID_OUT_OF_RANGE,
+                    &format!("The id for field {} must be positive", quote(&field.name)),
+                ));
+            }
+            if field.field_id > def.fields.len() as i32 {
+                return Err(verification_error(
+                    codes::FIELD_ID_OUT_OF_RANGE,
+                    &format!(
+                        "The id for field {} cannot be larger than {}",
+                        quote(&field.name),
+                        def.fields.len()
+                    ),
+                ));
+            }
+            values.insert(field.field_id);
+        }
+    }
+
+    // 4) Check that structs do not contain themselves recursively
+    let mut state: HashMap<String, u8> = HashMap::new();
+
+    fn check_recursion(
+        definition: &Definition,
+        schema: &Schema,
+        state: &mut HashMap<String, u8>,
+    ) -> Result<(), TcsError> {
+        if let DefinitionKind::Struct = definition.kind {
+            let name = definition.qualified_name();
+            if let Some(&s) = state.get(&name) {
+                if s == 1 {
+                    return Err(verification_error(
+                        codes::RECURSIVE_STRUCT,
+                        &format!(
+                            "Recursive nesting of {} is not allowed",
+                            quote(&definition.name)
+                        ),
+                    ));
+                } else if s == 2 {
+                    return Ok(());
+                }
+            }
+            state.insert(name.clone(), 1);
+            for field in &definition.fields {
+                // Arrays are allowed to be recursive (they break the recursion)
+                if !field.is_array {
+                    let target = field
+                        .type_
+                        .as_deref()
+                        .and_then(|ty| schema.resolve_type(ty, definition));
+                    if let Some(target) = target {
+                        check_recursion(target, schema, state)?;
+                    }
+                }
+            }
+            state.insert(name, 2);
+        }
+        Ok(())
+    }
+
+    for def in &schema.definitions {
+        check_recursion(def, schema, &mut state)?;
+    }
+
+    // 5) Check that the sizes of fixed-size structs can be computed
+    fixed_struct_sizes(schema).map_err(size_overflow)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Severity;
+    use crate::parser::parse_schema;
+    use crate::tokenizer::tokenize_schema;
+
+    fn verify(input: &str) -> Result<(), TcsError> {
+        let tokens = tokenize_schema(input)?;
+        let schema = parse_schema(&tokens)?;
+        verify_schema(&schema)
+    }
+
+    #[test]
+    fn test_valid_schema() {
+        let input = r#"
+            struct Point {
+                int x;
+                int y;
+            }
+        "#;
+        assert!(verify(input).is_ok());
+    }
+
+    #[test]
+    fn test_keyword_names() {
+        let err = verify("struct message { int x; }").unwrap_err();
+        assert_eq!(err.code(), codes::RESERVED_NAME);
+        assert_eq!(
+            err.to_string(),
+            "Verification error [TCS0004]: The type name \"message\" is a TCS keyword"
+        );
+        let err = verify("message Tx { uint64 import = 1; }").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Verification error [TCS0004]: The field name \"import\" in \"Tx\" is a TCS keyword"
+        );
+        assert!(verify("enum enum { A = 0; }").is_err());
+        assert!(verify("struct package { int x; }").is_err());
+        assert!(verify("enum Kind { reserved = 0; }").is_err());
+
+        // Keywords are case-sensitive, and only whole names count
+        assert!(verify("struct Message { int structure; int Enum; }").is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_type() {
+        let input = r#"
+            struct Foo { int x; }
+            struct Foo { int y; }
+        "#;
+        let err = verify(input).unwrap_err();
+        assert!(matches!(err, TcsError::VerificationError { .. }));
+    }
+
+    #[test]
+    fn test_undefined_type() {
+        let input = r#"
+            struct Foo {
+                Unknown x;
+            }
+        "#;
+        let err = verify(input).unwrap_err();
+        assert!(matches!(err, TcsError::VerificationError { .. }));
+    }
+
+    #[test]
+    fn test_external_type_allowlist() {
+        let input = r#"
+            struct Foo {
+                External x;
+            }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        assert!(verify_schema(&schema).is_err());
+
+        let options = VerifyOptions {
+            external_types: ["External".to_string()].into_iter().collect(),
+            ..VerifyOptions::default()
+        };
+        assert!(verify_schema_with_options(&schema, &options).is_ok());
+    }
+
+    #[test]
+    fn test_duplicate_enum_value() {
+        let input = r#"
+            enum Status {
+                OK = 0;
+                SUCCESS = 0;
+            }
+        "#;
+        let err = verify(input).unwrap_err();
+        assert!(matches!(err, TcsError::VerificationError { .. }));
+    }
+
+    #[test]
+    fn test_enum_alias_allows_shared_value() {
+        let input = r#"
+            enum Status {
+                OK = 0;
+                SUCCESS = OK;
+                FAILED = 1;
+            }
+        "#;
+        assert!(verify(input).is_ok());
+    }
+
+    #[test]
+    fn test_undefined_type_error_code() {
+        let input = r#"
+            struct Foo {
+                Unknown x;
+            }
+        "#;
+        let err = verify(input).unwrap_err();
+        assert_eq!(err.code(), codes::UNDEFINED_TYPE);
+        assert!(err.to_string().contains("[TCS0001]"));
+    }
+
+    #[test]
+    fn test_bytes_array_rejected() {
+        assert!(verify("struct Ok { bytes data; }").is_ok());
+        let err = verify("struct Bad { bytes[] data; }").unwrap_err();
+        assert_eq!(err.code(), codes::UNSUPPORTED_ARRAY_TYPE);
+    }
+
+    #[test]
+    fn test_cross_package_references() {
+        let input = r#"
+            package geometry;
+            struct Point { int x; int y; }
+            struct Line { Point from; geometry.Point to; }
+
+            package shapes;
+            struct Circle { geometry.Point center; }
+        "#;
+        assert!(verify(input).is_ok());
+
+        let input = r#"
+            package geometry;
+            struct Point { int x; int y; }
+
+            package shapes;
+            struct Circle { Point center; }
+        "#;
+        let err = verify(input).unwrap_err();
+        assert_eq!(err.code(), codes::UNQUALIFIED_CROSS_PACKAGE);
+
+        let input = r#"
+            package geometry;
+            struct Point { int x; int y; }
+
+            package shapes;
+            struct Circle { shapes.Point center; }
+        "#;
+        let err = verify(input).unwrap_err();
+        assert_eq!(err.code(), codes::UNDEFINED_TYPE);
+
+        // Each package has its own names; a plain name is the local type
+        let input = r#"
+            package a;
+            struct Point { int x; }
+
+            package b;
+            struct Point { int y; }
+            struct Line { Point from; a.Point to; b.Point mid; }
+        "#;
+        assert!(verify(input).is_ok());
+
+        let input = r#"
+            package a;
+            struct Point { int x; }
+            enum Point { ORIGIN = 0; }
+        "#;
+        assert_eq!(verify(input).unwrap_err().code(), codes::DUPLICATE_TYPE);
+    }
+
+    #[test]
+    fn test_nested_definition_scope() {
+        let input = r#"
+            message Canvas {
+                struct Layer { Style style; }
+                enum Style { FLAT = 0; }
+                Layer[] layers = 1;
+                Style style = 2;
+            }
+        "#;
+        assert!(verify(input).is_ok());
+
+        // Nested names are only in scope inside their parent
+        let input = r#"
+            message Canvas {
+                struct Layer { string name; }
+                Layer[] layers = 1;
+            }
+            struct Other { Layer layer; }
+        "#;
+        let err = verify(input).unwrap_err();
+        assert_eq!(err.code(), codes::UNDEFINED_TYPE);
+        assert!(err
+            .to_string()
+            .contains("The type \"Layer\" for field \"layer\" is nested in \"Canvas\""));
+
+        // They still share the schema's namespace
+        let input = r#"
+            message Canvas { struct Layer { string name; } }
+            struct Layer { int id; }
+        "#;
+        assert_eq!(verify(input).unwrap_err().code(), codes::DUPLICATE_TYPE);
+    }
+
+    #[test]
+    fn test_type_aliases() {
+        let ok = r#"
+            type Hash = byte[32];
+            type Root = Hash;
+            type Amount = uint64;
+            struct Header { Root root; Amount fee; }
+            message Block { Header header = 1; Hash hash = 2; Amount[] fees = 3; }
+        "#;
+        assert!(verify(ok).is_ok());
+
+        for (input, code) in [
+            (
+                "type A = B; type B = A; struct S { A a; }",
+                codes::RECURSIVE_ALIAS,
+            ),
+            ("type A = A;", codes::RECURSIVE_ALIAS),
+            ("type A = Missing;", codes::UNDEFINED_TYPE),
+            (
+                "type Point = int; struct Point { int x; }",
+                codes::DUPLICATE_TYPE,
+            ),
+            ("type uint64 = int;", codes::DUPLICATE_TYPE),
+            ("type A = int; type A = uint;", codes::DUPLICATE_TYPE),
+            (
+                "type Hash = byte[32]; struct S { Hash[] hashes; }",
+                codes::UNSUPPORTED_ARRAY_TYPE,
+            ),
+            (
+                "type Ids = int[]; type Lists = Ids[];",
+                codes::UNSUPPORTED_ARRAY_TYPE,
+            ),
+            (
+                "type Ids = int[4]; struct S { Ids ids; }",
+                codes::INVALID_FIXED_ARRAY,
+            ),
+        ] {
+            let err = verify(input).unwrap_err();
+            assert_eq!(err.code(), code, "{}: {}", input, err);
+        }
+        let err = verify("type A = B; type B = A;").unwrap_err();
+        assert!(err.to_string().contains("A -> B -> A"), "{}", err);
+
+        // Aliases count as uses of their target, and unused ones are reported
+        let input = "type Hash = byte[32]; type Spare = int; struct Id { Hash hash; } struct Root { int x; }";
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let warnings = verify_schema_with_warnings(&schema, &VerifyOptions::default()).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::UNUSED_TYPE);
+        assert!(warnings[0].msg.contains("\"Spare\""));
+
+        // Switching a field to an alias of the same type is compatible
+        let baseline =
+            parse_schema(&tokenize_schema("message M { byte[32] hash = 1; }").unwrap()).unwrap();
+        let current = parse_schema(
+            &tokenize_schema("type Hash = byte[32]; message M { Hash hash = 1; }").unwrap(),
+        )
+        .unwrap();
+        assert!(verify_schema_against_baseline(&current, &baseline).is_ok());
+    }
+
+    #[test]
+    fn test_feature_gated_types() {
+        let input = r#"
+            message Experimental [feature = "exp"] { Mode mode = 1; }
+            enum Mode [feature = "exp"] { A = 0; }
+        "#;
+        assert!(verify(input).is_ok());
+
+        let input = r#"
+            message Experimental [feature = "exp"] { uint64 id = 1; }
+            message Envelope { Experimental payload = 1; }
+        "#;
+        let err = verify(input).unwrap_err();
+        assert_eq!(err.code(), codes::FEATURE_GATED_TYPE);
+        assert!(err
+            .to_string()
+            .contains("mark \"Envelope\" [feature = \"exp\"] too"));
+
+        // A different feature doesn't help
+        let input = r#"
+            message Experimental [feature = "exp"] { uint64 id = 1; }
+            message Envelope [feature = "other"] { Experimental payload = 1; }
+        "#;
+        assert_eq!(verify(input).unwrap_err().code(), codes::FEATURE_GATED_TYPE);
+
+        for feature in ["", "-exp", "exp feature"] {
+            let input = format!("struct Point [feature = \"{}\"] {{ int x; }}", feature);
+            assert_eq!(verify(&input).unwrap_err().code(), codes::INVALID_NAME);
+        }
+    }
+
+    #[test]
+    fn test_warnings() {
+        let input = r#"
+            enum Status {
+                PENDING = 0;
+                FAILED = 2;
+            }
+
+            struct Root { int x; }
+
+            message Job {
+                struct Orphan { int x; }
+                Status status = 1;
+            }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let warnings = verify_schema_with_warnings(&schema, &VerifyOptions::default()).unwrap();
+
+        // Top-level types like `Root` are entry points; only the nested
+        // `Orphan` is unreachable
+        let codes: Vec<&str> = warnings.iter().map(|w| w.code).collect();
+        assert_eq!(codes, vec![codes::ENUM_VALUE_GAP, codes::UNUSED_TYPE]);
+        assert!(warnings[1].msg.contains("Orphan"));
+    }
+
+    #[test]
+    fn test_format_roundtrip() {
+        let schemas = [
+            "struct Point { int x; int y; }",
+            r#"
+                package chain;
+                // Transaction status
+                enum Status { PENDING = 0; DONE = 1; FINISHED = DONE; }
+                struct Header { byte[32] hash [key]; uint8[4] tag; uint64[] sizes; }
+                message Block {
+                  required Header header = 1;
+                  // Old field
+                  string[] notes = 2 [deprecated];
+                  optional Status status = 3;
+                  bytes payload = 4;
+                }
+                package other;
+                struct Wrapper { chain.Header header; }
+            "#,
+            "message Empty {}",
+        ];
+        for source in schemas {
+            check_format_roundtrip(source).unwrap();
+        }
+
+        assert!(check_format_roundtrip("struct {").is_err());
+    }
+
+    #[test]
+    fn test_key_fields() {
+        let ok = r#"
+            enum Kind { A = 0; }
+            struct Id { byte[32] hash [key]; }
+            message Tx { Id id = 1 [key]; Kind kind = 2 [key]; float fee = 3; }
+        "#;
+        assert!(verify(ok).is_ok());
+
+        let bad = [
+            "struct Reading { float value [key]; }",
+            "struct Point { int x; } struct Tagged { Point p [key]; }",
+            "message Tx { uint64 nonce = 1 [key] [deprecated]; }",
+        ];
+        for input in bad {
+            let err = verify(input).unwrap_err();
+            assert_eq!(err.code(), codes::INVALID_KEY_FIELD, "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_flags_warnings() {
+        let input = r#"
+            enum Perm [flags] { NONE = 0; READ = 1; WRITE = 2; EXEC = 3; ADMIN = 16; }
+            message File { Perm perm = 1; }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let warnings = verify_schema_with_warnings(&schema, &VerifyOptions::default()).unwrap();
+
+        // No gap warning for 2..16, only EXEC = 3 is reported
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::FLAGS_NOT_POWER_OF_TWO);
+        assert!(warnings[0].msg.contains("EXEC"));
+
+        // A negative value has no bit pattern in the generated u32
+        let err = verify("enum Perm [flags] { NONE = 0; ALL = -1; }").unwrap_err();
+        assert_eq!(err.code(), codes::FIELD_ID_OUT_OF_RANGE);
+        assert!(err
+            .to_string()
+            .contains("The value -1 of flag \"ALL\" in \"Perm\" is negative"));
+    }
+
+    #[test]
+    fn test_unknown_annotations() {
+        let input = "message Tx {\n  uint64 nonce = 1 @deprectaed;\n}";
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+
+        let warnings = verify_schema_with_warnings(&schema, &VerifyOptions::default()).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].code, codes::UNKNOWN_ANNOTATION);
+        assert_eq!((warnings[0].line, warnings[0].column), (2, 20));
+        assert!(warnings[0].msg.contains("\"@deprectaed\""));
+
+        let strict = VerifyOptions {
+            strict_annotations: true,
+            ..VerifyOptions::default()
+        };
+        let err = verify_schema_with_options(&schema, &strict).unwrap_err();
+        assert_eq!(err.code(), codes::UNKNOWN_ANNOTATION);
+        assert!(
+            err.to_string()
+                .contains("\"@deprectaed\" on \"nonce\" in \"Tx\" at line 2, column 20"),
+            "{}",
+            err
+        );
+
+        assert!(check_format_roundtrip(input).is_ok());
+    }
+
+    #[test]
+    fn test_package_name_collision() {
+        let input = r#"
+            package chain;
+            message Chain { uint64 height = 1; }
+            package block_header;
+            message BlockHeader { uint64 height = 1; }
+            message Block { BlockHeader header = 1; }
+        "#;
+        let tokens = tokenize_schema(input).unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        let warnings = verify_schema_with_warnings(&schema, &VerifyOptions::default()).unwrap();
+        let collisions: Vec<&str> = warnings
+            .iter()
+            .filter(|w| w.code == codes::PACKAGE_NAME_COLLISION)
+            .map(|w| w.msg.as_str())
+            .collect();
+        assert_eq!(
+            collisions,
+            [
+                "The type \"Chain\" has the same name as its package \"chain\"",
+                "The type \"BlockHeader\" has the same name as its package \"block_header\"",
+            ]
+        );
+
+        // Without a package there's nothing to collide with
+        let tokens = tokenize_schema("message Chain { uint64 height = 1; }").unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        assert!(
+            verify_schema_with_warnings(&schema, &VerifyOptions::default())
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_invalid_package_name() {
+        // The parser only accepts an identifier
+        assert!(parse_schema(&tokenize_schema("package ;").unwrap()).is_err());
+        assert!(parse_schema(&tokenize_schema("package a.b;").unwrap()).is_err());
+
+        // Schemas built in code get the same guarantee from the verifier
+        let tokens = tokenize_schema("package chain;\nstruct Point { int x; }").unwrap();
+        let schema = parse_schema(&tokens).unwrap();
+        assert!(verify_schema(&schema).is_ok());
+        for (package, reason) in [
+            ("", "has an empty segment"),
+            ("chain.", "has an empty segment"),
+            ("a..b", "has an empty segment"),
+            (
+                " chain",
+                "has the segment \" chain\", which is not an identifier",
+            ),
+            ("chain\n", "is not an identifier"),
+            ("_", "becomes the module \"_\""),
+            ("2d", "becomes the module \"2d\""),
+            ("tapedrive.chain", "is dotted, which is not supported"),
+            (
+                "type",
+                "becomes the module \"type\" in generated Rust, which is a Rust keyword",
+            ),
+            ("self", "which is a Rust keyword"),
+            ("Crate", "becomes the module \"crate\""),
+            ("match", "which is a Rust keyword"),
+            ("try", "which is a Rust keyword"),
+        ] {
+            let mut top = schema.clone();
+            top.package = Some(package.to_string());
+            let mut own = schema.clone();
+            own.definitions[0].package = Some(package.to_string());
+            for schema in [top, own] {
+                let err = verify_schema(&schema).unwrap_err();
+                assert_eq!(err.code(), codes::INVALID_NAME, "{:?}", package);
+                assert!(err.to_string().contains(reason), "{}", err);
+            }
+        }
+
+        // Keyword packages are rejected where they're declared, so nothing
+        // can refer to them from another package either
+        let err = verify("package type;\nstruct Point { int x; }").unwrap_err();
+        assert_eq!(err.code(), codes::INVALID_NAME);
+        let input = r#"
+            package geometry;
+            struct Point { int x; int y; }
+
+            package match;
+            struct Circle { geometry.Point center; }
+
+            package shapes;
+            struct Square { match.Circle inner; }
+        "#;
+        let err = verify(input).unwrap_err();
+        assert_eq!(err.code(), codes::INVALID_NAME);
+        assert!(err.to_string().contains("\"match\""), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        let tokens = tokenize_schema("struct Point { int x; }").unwrap();
+        let mut schema = parse_schema(&tokens).unwrap();
+        schema.definitions[0].fields[0].name = "1x".to_string();
+
+        let err = verify_schema(&schema).unwrap_err();
+        assert_eq!(err.code(), codes::INVALID_NAME);
+        assert!(err.to_string().contains("\"1x\""));
+    }
+
+    #[test]
+    fn test_names_invalid_after_case_conversion() {
+        for input in [
+            "enum Kind { _ = 0; }",
+            "enum Kind { __ = 0; }",
+            "enum Kind { _1 = 0; }",
+            "struct Point { int _; }",
+        ] {
+            let err = verify(input).unwrap_err();
+            assert_eq!(err.code(), codes::INVALID_NAME, "{}", input);
+            assert!(err.to_string().contains("in generated Rust"), "{}", input);
+        }
+
+        // `1st` can't be written in a .tcs file, so build it directly
+        let tokens = tokenize_schema("struct Point { int first; }").unwrap();
+        let mut schema = parse_schema(&tokens).unwrap();
+        schema.definitions[0].fields[0].name = "1st".to_string();
+        let err = verify_schema(&schema).unwrap_err();
+        assert_eq!(err.code(), codes::INVALID_NAME);
+
+        // Underscores are fine as long as something is left over
+        assert!(verify("enum Kind { _A = 0; }").is_ok());
+        assert!(verify("struct Point { int __x; int _y_; }").is_ok());
+    }
+
+    #[test]
+    fn test_type_names_invalid_after_case_conversion() {
+        for input in [
+            "struct __ { int x; }",
+            "struct _1 { int x; }",
+            "enum _ { A = 0; }",
+            "type __ = int;",
+        ] {
+            let err = verify(input).unwrap_err();
+            assert_eq!(err.code(), codes::INVALID_NAME, "{}", input);
+            assert!(err.to_string().contains("The type name"), "{}", err);
+        }
+
+        assert!(verify("struct _point { int x; }").is_ok());
+    }
+
+    #[test]
+    fn test_field_without_type() {
+        let tokens = tokenize_schema("struct Point { int x; int[] y; }").unwrap();
+        let mut schema = parse_schema(&tokens).unwrap();
+        schema.definitions[0].fields[1].type_ = None;
+        let err = verify_schema(&schema).unwrap_err();
+        assert_eq!(err.code(), codes::UNDEFINED_TYPE);
+        assert!(err
+            .to_string()
+            .contains("Field \"y\" in \"Point\" has no type"));
+
+        schema.definitions[0].fields[1].type_ = Some(String::new());
+        assert_eq!(
+            verify_schema(&schema).unwrap_err().code(),
+            codes::UNDEFINED_TYPE
+        );
+    }
+
+    #[test]
+    fn test_invalid_option() {
+        let err = verify("options { nostd = true; }").unwrap_err();
+        assert_eq!(err.code(), codes::INVALID_OPTION);
+        assert!(verify("options { no_std = true; }").is_ok());
+    }
+
+    #[test]
+    fn test_eq_ignore_deprecated_needs_field() {
+        let input = r#"
+            options { eq_ignore_deprecated = true; }
+            message Legacy { uint64 id = 1 [deprecated]; }
+        "#;
+        let err = verify(input).unwrap_err();
+        assert_eq!(err.code(), codes::NO_COMPARABLE_FIELDS);
+        // Without the option deprecated fields are still compared
+        assert!(verify("message Legacy { uint64 id = 1 [deprecated]; }").is_ok());
+    }
+
+    #[test]
+    fn test_validate_bounds() {
+        let input = r#"
+            struct Fee { uint8 rate @validate(min = 0, max = 255); int[] tiers @validate(len_max = 4); }
+            message Transfer { int delta = 1 @validate(min = -10, max = -10); }
+        "#;
+        assert!(verify(input).is_ok());
+
+        for (input, msg) in [
+            (
+                "struct S { uint8 x @validate(min = 10, max = 5); }",
+                "min = 10 is greater than max = 5",
+            ),
+            (
+                "struct S { int[] x @validate(len_min = 3, len_max = 2); }",
+                "len_min = 3 is greater than len_max = 2",
+            ),
+            (
+                "struct S { uint8 x @validate(max = 256); }",
+                "max = 256 is out of range for uint8",
+            ),
+            (
+                "struct S { uint64 x @validate(min = -1); }",
+                "min = -1 is out of range for uint64",
+            ),
+            (
+                "struct S { string x @validate(max = 5); }",
+                "min and max only apply to integer fields",
+            ),
+            (
+                "struct S { int[] x @validate(min = 5); }",
+                "min and max only apply to integer fields",
+            ),
+            (
+                "struct S { byte[32] x @validate(len_min = 1); }",
+                "len_min and len_max only apply to variable-length arrays",
+            ),
+        ] {
+            let err = verify(input).unwrap_err();
+            assert_eq!(err.code(), codes::INVALID_VALIDATION, "{}", input);
+            assert!(err.to_string().contains(msg), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_since_version() {
+        assert!(verify("struct S { int x @since(\"1.4.2\"); }").is_ok());
+        for version in ["v2", "2.", "", "2.0-beta"] {
+            let input = format!("struct S {{ int x @since(\"{}\"); }}", version);
+            let err = verify(&input).unwrap_err();
+            assert_eq!(err.code(), codes::INVALID_VERSION, "{}", version);
+        }
+    }
+
+    #[test]
+    fn test_rust_name_collisions() {
+        let err = verify("enum Role { NODE_ROLE = 0; nodeRole = 1; }").unwrap_err();
+        assert_eq!(err.code(), codes::RUST_NAME_COLLISION);
+        assert!(
+            err.to_string()
+                .contains("\"NODE_ROLE\" and \"nodeRole\" in \"Role\" both become \"NodeRole\""),
+            "{}",
+            err
+        );
+
+        for input in [
+            "struct Tx { byte[32] txHash; byte[32] tx_hash; }",
+            "enum Perm [flags] { READ = 1; read = 2; }",
+            "enum Role { NODE = 0; OLD = NODE; old = NODE; }",
+        ] {
+            let err = verify(input).unwrap_err();
+            assert_eq!(err.code(), codes::RUST_NAME_COLLISION, "{}", input);
+        }
+
+        for input in [
+            "enum Role { NODE = 0; FieldCount = NODE; }",
+            "enum Role { NODE = 0; fieldCount = NODE; }",
+            "enum Perm [flags] { READ = 1; field_count = 2; }",
+        ] {
+            let err = verify(input).unwrap_err();
+            assert_eq!(err.code(), codes::RUST_NAME_COLLISION, "{}", input);
+            assert!(err.to_string().contains("FIELD_COUNT"), "{}", err);
+        }
+        // Variants and fields don't share the const's name
+        assert!(verify("enum Role { FIELD_COUNT = 0; }").is_ok());
+        assert!(verify("struct Stats { int field_count; }").is_ok());
+
+        // A variant and an alias const can share a name once converted
+        assert!(verify("enum Mode { A = 0; a = A; }").is_ok());
+        // Exact duplicates are still reported as duplicates
+        let err = verify("enum Role { NODE = 0; NODE = 1; }").unwrap_err();
+        assert_eq!(err.code(), codes::DUPLICATE_ENUM_VARIANT);
+    }
+
+    #[test]
+    fn test_type_name_collisions() {
+        let err = verify("struct point { int x; } struct Point { int x; }").unwrap_err();
+        assert_eq!(err.code(), codes::RUST_NAME_COLLISION);
+        assert!(
+            err.to_string()
+                .contains("\"point\" and \"Point\" both become \"Point\""),
+            "{}",
+            err
+        );
+
+        for input in [
+            "type hash = byte[32]; struct Hash { int x; }",
+            "struct tx_hash { int x; } enum TxHash { A = 0; }",
+            "struct Outer { struct inner { int x; } struct Inner { int y; } }",
+        ] {
+            let err = verify(input).unwrap_err();
+            assert_eq!(err.code(), codes::RUST_NAME_COLLISION, "{}", input);
+        }
+
+        // Nested types live in their parent's module
+        assert!(verify("struct point { int x; } struct Outer { struct Point { int y; } }").is_ok());
+    }
+
+    #[test]
+    fn test_enum_auto_value_collision() {
+        assert!(verify("enum E { A; B; C = 10; D; }").is_ok());
+
+        // C continues from B and lands on A's value
+        let err = verify("enum E { A = 1; B = 0; C; }").unwrap_err();
+        assert_eq!(err.code(), codes::DUPLICATE_ENUM_VARIANT);
+        assert!(err.to_string().contains("The value 1 for variant \"C\""));
+    }
+
+    #[test]
+    fn test_ord_struct() {
+        let input = r#"
+            enum Kind { A = 0; }
+            struct Point [ord] { int x; int y; }
+            struct BlockHeader [ord] { uint64 height; byte[32] hash; Kind kind; Point[] points; }
+        "#;
+        assert!(verify(input).is_ok());
+
+        let err = verify("struct Sample [ord] { uint64 at; float value; }").unwrap_err();
+        assert_eq!(err.code(), codes::INVALID_ORD);
+        assert_eq!(
+            err.to_string(),
+            "Verification error [TCS0017]: \"Sample\" can't be marked [ord]: field \"value\" is a float, which has no total order"
+        );
+
+        let err = verify("struct P { int x; }\nstruct Q [ord] { P p; }").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("has type \"P\", which is not marked [ord]"));
+        let err = verify("struct Q [ord] { int id [key]; }").unwrap_err();
+        assert_eq!(err.code(), codes::INVALID_ORD);
+    }
+
+    #[test]
+    fn test_verify_against_baseline() {
+        let schema = |input: &str| parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let baseline = schema("message Tx { uint64 nonce = 1; byte[32] hash = 2; }");
+
+        // Adding a field is fine
+        let added = schema("message Tx { uint64 nonce = 1; byte[32] hash = 2; string memo = 3; }");
+        assert!(verify_schema_against_baseline(&added, &baseline).is_ok());
+
+        let changed = schema("message Tx { uint64 nonce = 2; byte[32] hash = 1; }");
+        let errors = verify_schema_against_baseline(&changed, &baseline).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].code(), codes::INCOMPATIBLE_CHANGE);
+        assert!(errors[0]
+            .to_string()
+            .contains("Field \"nonce\" in \"Tx\" changed its id from 1 to 2"));
+
+        let retyped = schema("message Tx { uint64 nonce = 1; byte[] hash = 2; }");
+        let errors = verify_schema_against_baseline(&retyped, &baseline).unwrap_err();
+        assert!(errors[0]
+            .to_string()
+            .contains("changed its type from \"byte[32]\" to \"byte[]\""));
+
+        let reused = schema("message Tx { uint64 nonce = 1; string memo = 2; }");
+        let errors = verify_schema_against_baseline(&reused, &baseline).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[1].to_string().contains(
+            "Field id 2 in \"Tx\" belonged to \"hash\" in the baseline and is reused for \"memo\""
+        ));
+
+        // The generated type and its encoding differ with and without `Option`
+        let required = schema("message Tx { required uint64 nonce = 1; byte[32] hash = 2; }");
+        let errors = verify_schema_against_baseline(&required, &baseline).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .to_string()
+            .contains("Field \"nonce\" in \"Tx\" changed from optional to required"));
+        let explicit = schema("message Tx { optional uint64 nonce = 1; byte[32] hash = 2; }");
+        assert!(verify_schema_against_baseline(&explicit, &baseline).is_ok());
+        let errors = verify_schema_against_baseline(&baseline, &required).unwrap_err();
+        assert!(errors[0]
+            .to_string()
+            .contains("changed from required to optional"));
+    }
+
+    #[test]
+    fn test_baseline_field_order() {
+        let schema = |input: &str| parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let baseline = schema("message Tx { uint64 nonce = 1; byte[32] hash = 2; }");
+
+        // Old readers decode fields by position, so none can go missing
+        let removed = schema("message Tx {\n  uint64 nonce = 1;\n}");
+        let errors = verify_schema_against_baseline(&removed, &baseline).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].location(), Some((1, 9)));
+        assert!(errors[0]
+            .to_string()
+            .contains("Field \"hash\" in \"Tx\" was removed"));
+
+        let reordered = schema("message Tx { byte[32] hash = 2; uint64 nonce = 1; }");
+        let errors = verify_schema_against_baseline(&reordered, &baseline).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .to_string()
+            .contains("Field \"hash\" in \"Tx\" is declared where the baseline has \"nonce\""));
+
+        // Nor can a new field go before existing ones
+        let inserted =
+            schema("message Tx { uint64 nonce = 1; string memo = 3; byte[32] hash = 2; }");
+        let errors = verify_schema_against_baseline(&inserted, &baseline).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .to_string()
+            .contains("\"memo\" in \"Tx\" is declared where"));
+    }
+
+    #[test]
+    fn test_baseline_errors_sorted() {
+        let schema = |input: &str| parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let baseline =
+            schema("message Block {\n  message Tx { uint64 nonce = 1; }\n  uint64 height = 1;\n}");
+
+        // Block's field is checked first, but Tx's field comes first in the source
+        let current =
+            schema("message Block {\n  message Tx { uint32 nonce = 1; }\n  uint32 height = 1;\n}");
+        assert_eq!(current.definitions[0].name, "Block");
+        let errors = verify_schema_against_baseline(&current, &baseline).unwrap_err();
+        let locations: Vec<_> = errors.iter().map(|e| e.location()).collect();
+        assert_eq!(locations, [Some((2, 23)), Some((3, 10))]);
+        assert!(errors[0].to_string().starts_with(
+            "Verification error [TCS0018] at line 2, column 23: Field \"nonce\" in \"Tx\""
+        ));
+    }
+
+    #[test]
+    fn test_lint_schema() {
+        let input = r#"
+            // Shades
+            enum Color { RED = 0; Green = 1; }
+            // A canvas
+            message canvas { Color Background = 1; }
+        "#;
+        let schema = parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let warnings = lint_schema(&schema, &VerifyOptions::default()).unwrap();
+        let messages: Vec<String> = warnings.iter().map(|w| w.msg.clone()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "The variant \"Green\" should be UPPER_SNAKE_CASE",
+                "The type \"canvas\" should be PascalCase",
+                "The field \"Background\" should be camelCase or snake_case",
+            ]
+        );
+        assert!(warnings.iter().all(|w| w.severity() == Severity::Style));
+
+        let schema =
+            parse_schema(&tokenize_schema("type Id = int; struct Point { int x; }").unwrap())
+                .unwrap();
+        let warnings = lint_schema(&schema, &VerifyOptions::default()).unwrap();
+        let codes: Vec<&str> = warnings.iter().map(|w| w.code).collect();
+        assert_eq!(codes, vec![codes::UNUSED_TYPE, codes::UNDOCUMENTED]);
+        assert_eq!(warnings[0].severity(), Severity::Warning);
+    }
+
+    #[test]
+    fn test_verify_with_limits() {
+        let schema = |input: &str| parse_schema(&tokenize_schema(input).unwrap()).unwrap();
+        let limits = VerifyLimits {
+            max_definitions: 3,
+            max_fields: 2,
+            max_depth: 2,
+            max_fixed_size: 64,
+        };
+        let verify = |input: &str| {
+            verify_schema_with_limits(&schema(input), &VerifyOptions::default(), &limits)
+        };
+
+        assert!(verify("struct P { int x; int y; }\nmessage M { P p = 1; M[] m = 2; }").is_ok());
+
+        let err = verify("struct P { int x; int y; int z; }").unwrap_err();
+        assert_eq!(err.code(), codes::LIMIT_EXCEEDED);
+        assert_eq!(
+            err.to_string(),
+            "Verification error [TCS0019]: The type \"P\" has 3 fields, more than the limit of 2"
+        );
+
+        let err = verify("struct A { B b; }\nstruct B { C c; }\nstruct C { int x; }").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("The type \"A\" nests more than 2 levels deep"));
+
+        let err =
+            verify("enum A { X = 0; }\nenum B { X = 0; }\nenum C { X = 0; }\nenum D { X = 0; }")
+                .unwrap_err();
+        assert!(err.to_string().contains("4 definitions"));
+
+        let err = verify("struct H { byte[32] a; byte[33] b; }").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("The type \"H\" has a fixed size of 65 bytes, more than the limit of 64"));
+
+        // Within the limits, the schema is still verified as usual
+        assert_eq!(
+            verify("struct P { Q q; }").map_err(|e| e.code()),
+            Err(codes::UNDEFINED_TYPE)
+        );
+    }
+
+    #[test]
+    fn test_fixed_size_overflow() {
+        let input = format!(
+            "struct Big {{ byte[{}] a; }}\nstruct Twice {{ Big first; Big second; }}",
+            usize::MAX / 2 + 1
+        );
+        let err = verify(&input).unwrap_err();
+        assert_eq!(err.code(), codes::LIMIT_EXCEEDED);
+        assert_eq!(
+            err.to_string(),
+            "Verification error [TCS0019]: The fixed size of \"Twice\" overflows usize"
+        );
+
+        // One of them fits
+        let input = input.replace(" Big second;", "");
+        assert!(verify(&input).is_ok());
     }
 
     #[test]
@@ -198,7 +2527,7 @@ mod tests {
             }
         "#;
         let err = verify(input).unwrap_err();
-        assert!(matches!(err, TcsError::VerificationError(_)));
+        assert!(matches!(err, TcsError::VerificationError { .. }));
     }
 
     #[test]
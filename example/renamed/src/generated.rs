@@ -0,0 +1,350 @@
+// This file was generated by the TCS compiler.
+// Do not edit manually.
+
+#[allow(dead_code)]
+pub mod renamed {
+
+    use mywin::io::Writer;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    #[repr(u32)]
+    pub enum Shape {
+        #[default]
+        Flat = 0,
+        Round = 1,
+        Pointed = 4,
+    }
+
+    impl Shape {
+        /// Number of variants in the schema
+        pub const FIELD_COUNT: usize = 3;
+
+        /// Every variant, in schema order
+        pub const fn all() -> &'static [Self] {
+            &[Self::Flat, Self::Round, Self::Pointed]
+        }
+    }
+
+    impl mywin::SchemaWrite for Shape {
+        type Src = Self;
+
+        fn size_of(_src: &Self) -> mywin::WriteResult<usize> {
+            <u32 as mywin::SchemaWrite>::size_of(&0)
+        }
+
+        fn write(writer: &mut impl Writer, src: &Self) -> mywin::WriteResult<()> {
+            let tag: u32 = match src {
+                Self::Flat => 0,
+                Self::Round => 1,
+                Self::Pointed => 2,
+            };
+            <u32 as mywin::SchemaWrite>::write(writer, &tag)
+        }
+    }
+
+    impl<'de> mywin::SchemaRead<'de> for Shape {
+        type Dst = Self;
+
+        fn read(
+            reader: &mut impl mywin::io::Reader<'de>,
+            dst: &mut std::mem::MaybeUninit<Self>,
+        ) -> mywin::ReadResult<()> {
+            let tag = <u32 as mywin::SchemaRead<'de>>::get(reader)?;
+            dst.write(match tag {
+                0 => Self::Flat,
+                1 => Self::Round,
+                2 => Self::Pointed,
+                _ => return Err(mywin::error::invalid_tag_encoding(tag as usize)),
+            });
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    #[repr(transparent)]
+    pub struct Mode(pub u32);
+
+    impl Mode {
+        pub const DRAW: Self = Self(1);
+        pub const ERASE: Self = Self(2);
+
+        /// Number of flags in the schema
+        pub const FIELD_COUNT: usize = 2;
+
+        /// The set with no flags
+        pub const fn empty() -> Self {
+            Self(0)
+        }
+
+        /// The raw bits
+        pub const fn bits(self) -> u32 {
+            self.0
+        }
+
+        /// Whether no flags are set
+        pub const fn is_empty(self) -> bool {
+            self.0 == 0
+        }
+
+        /// Whether every flag in `other` is also set in `self`
+        pub const fn contains(self, other: Self) -> bool {
+            self.0 & other.0 == other.0
+        }
+    }
+
+    impl std::ops::BitOr for Mode {
+        type Output = Self;
+
+        fn bitor(self, rhs: Self) -> Self {
+            Self(self.0 | rhs.0)
+        }
+    }
+
+    impl std::ops::BitOrAssign for Mode {
+        fn bitor_assign(&mut self, rhs: Self) {
+            self.0 |= rhs.0;
+        }
+    }
+
+    impl std::ops::BitAnd for Mode {
+        type Output = Self;
+
+        fn bitand(self, rhs: Self) -> Self {
+            Self(self.0 & rhs.0)
+        }
+    }
+
+    impl std::ops::BitAndAssign for Mode {
+        fn bitand_assign(&mut self, rhs: Self) {
+            self.0 &= rhs.0;
+        }
+    }
+
+    impl mywin::SchemaWrite for Mode {
+        type Src = Self;
+
+        fn size_of(src: &Self) -> mywin::WriteResult<usize> {
+            <u32 as mywin::SchemaWrite>::size_of(&src.0)
+        }
+
+        fn write(writer: &mut impl Writer, src: &Self) -> mywin::WriteResult<()> {
+            <u32 as mywin::SchemaWrite>::write(writer, &src.0)
+        }
+    }
+
+    impl<'de> mywin::SchemaRead<'de> for Mode {
+        type Dst = Self;
+
+        fn read(
+            reader: &mut impl mywin::io::Reader<'de>,
+            dst: &mut std::mem::MaybeUninit<Self>,
+        ) -> mywin::ReadResult<()> {
+            dst.write(Self(<u32 as mywin::SchemaRead<'de>>::get(reader)?));
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Default)]
+    #[repr(C)]
+    pub struct Stroke {
+        pub shape: Shape,
+        pub mode: Mode,
+        pub color: [u8; 4],
+    }
+
+    impl mywin::SchemaWrite for Stroke {
+        type Src = Self;
+
+        fn size_of(src: &Self) -> mywin::WriteResult<usize> {
+            let mut size = 0;
+            size += <Shape as mywin::SchemaWrite>::size_of(&src.shape)?;
+            size += <Mode as mywin::SchemaWrite>::size_of(&src.mode)?;
+            size += <[u8; 4] as mywin::SchemaWrite>::size_of(&src.color)?;
+            Ok(size)
+        }
+
+        fn write(writer: &mut impl Writer, src: &Self) -> mywin::WriteResult<()> {
+            <Shape as mywin::SchemaWrite>::write(writer, &src.shape)?;
+            <Mode as mywin::SchemaWrite>::write(writer, &src.mode)?;
+            <[u8; 4] as mywin::SchemaWrite>::write(writer, &src.color)?;
+            Ok(())
+        }
+    }
+
+    impl<'de> mywin::SchemaRead<'de> for Stroke {
+        type Dst = Self;
+
+        fn read(
+            reader: &mut impl mywin::io::Reader<'de>,
+            dst: &mut std::mem::MaybeUninit<Self>,
+        ) -> mywin::ReadResult<()> {
+            dst.write(Self {
+                shape: <Shape as mywin::SchemaRead<'de>>::get(reader)?,
+                mode: <Mode as mywin::SchemaRead<'de>>::get(reader)?,
+                color: <[u8; 4] as mywin::SchemaRead<'de>>::get(reader)?,
+            });
+            Ok(())
+        }
+    }
+
+    impl Stroke {
+        /// Number of fields in the schema
+        pub const FIELD_COUNT: usize = 3;
+
+        /// Serialize this value to bytes
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let mut out = Vec::new();
+            self.to_bytes_into(&mut out)
+                .expect("serialization should not fail");
+            out
+        }
+
+        /// Serialize this value into a buffer, replacing its contents
+        pub fn to_bytes_into(&self, out: &mut Vec<u8>) -> Result<(), mywin::WriteError> {
+            out.clear();
+            self.write_to(out)
+        }
+
+        /// Serialize this value, appending to `out` without reallocating if it has room
+        pub fn write_to(&self, out: &mut Vec<u8>) -> Result<(), mywin::WriteError> {
+            let size = mywin::serialized_size(self)? as usize;
+            out.reserve(size);
+            let mut writer = unsafe { out.as_trusted_for(size)? };
+            mywin::serialize_into(&mut writer, self)?;
+            writer.finish()?;
+            Ok(())
+        }
+
+        /// Deserialize from bytes
+        pub fn from_bytes(bytes: &[u8]) -> Result<Self, mywin::ReadError> {
+            mywin::deserialize(bytes)
+        }
+    }
+
+    impl TryFrom<&[u8]> for Stroke {
+        type Error = mywin::ReadError;
+
+        fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+            Self::from_bytes(bytes)
+        }
+    }
+
+    impl Stroke {
+        /// Serialize this value with its fields in field-ID order, for hashing
+        /// and signing. Equal values always give identical bytes, and the
+        /// output stays the same across versions while the schema is unchanged.
+        pub fn canonical_bytes(&self) -> Vec<u8> {
+            // Fields are declared in field-ID order
+            self.to_bytes()
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Default)]
+    pub struct Sketch {
+        pub title: Option<String>,
+        pub strokes: Option<Vec<Stroke>>,
+    }
+
+    impl mywin::SchemaWrite for Sketch {
+        type Src = Self;
+
+        fn size_of(src: &Self) -> mywin::WriteResult<usize> {
+            let mut size = 0;
+            size += <Option<String> as mywin::SchemaWrite>::size_of(&src.title)?;
+            size += <Option<Vec<Stroke>> as mywin::SchemaWrite>::size_of(&src.strokes)?;
+            Ok(size)
+        }
+
+        fn write(writer: &mut impl Writer, src: &Self) -> mywin::WriteResult<()> {
+            <Option<String> as mywin::SchemaWrite>::write(writer, &src.title)?;
+            <Option<Vec<Stroke>> as mywin::SchemaWrite>::write(writer, &src.strokes)?;
+            Ok(())
+        }
+    }
+
+    impl<'de> mywin::SchemaRead<'de> for Sketch {
+        type Dst = Self;
+
+        fn read(
+            reader: &mut impl mywin::io::Reader<'de>,
+            dst: &mut std::mem::MaybeUninit<Self>,
+        ) -> mywin::ReadResult<()> {
+            dst.write(Self {
+                title: <Option<String> as mywin::SchemaRead<'de>>::get(reader)?,
+                strokes: <Option<Vec<Stroke>> as mywin::SchemaRead<'de>>::get(reader)?,
+            });
+            Ok(())
+        }
+    }
+
+    impl Sketch {
+        /// Number of fields in the schema
+        pub const FIELD_COUNT: usize = 2;
+
+        /// Serialize this value to bytes
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let mut out = Vec::new();
+            self.to_bytes_into(&mut out)
+                .expect("serialization should not fail");
+            out
+        }
+
+        /// Serialize this value into a buffer, replacing its contents
+        pub fn to_bytes_into(&self, out: &mut Vec<u8>) -> Result<(), mywin::WriteError> {
+            out.clear();
+            self.write_to(out)
+        }
+
+        /// Serialize this value, appending to `out` without reallocating if it has room
+        pub fn write_to(&self, out: &mut Vec<u8>) -> Result<(), mywin::WriteError> {
+            let size = mywin::serialized_size(self)? as usize;
+            out.reserve(size);
+            let mut writer = unsafe { out.as_trusted_for(size)? };
+            mywin::serialize_into(&mut writer, self)?;
+            writer.finish()?;
+            Ok(())
+        }
+
+        /// Deserialize from bytes
+        pub fn from_bytes(bytes: &[u8]) -> Result<Self, mywin::ReadError> {
+            mywin::deserialize(bytes)
+        }
+    }
+
+    impl TryFrom<&[u8]> for Sketch {
+        type Error = mywin::ReadError;
+
+        fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+            Self::from_bytes(bytes)
+        }
+    }
+
+    impl Sketch {
+        /// Number of fields that are set: every optional field that is `Some`,
+        /// plus the required fields, which are always present
+        pub fn present_field_count(&self) -> usize {
+            self.title.is_some() as usize + self.strokes.is_some() as usize
+        }
+    }
+
+    impl Sketch {
+        /// The schema name of the field with ID `id`, if there is one
+        pub const fn field_name(id: u32) -> Option<&'static str> {
+            match id {
+                1 => Some("title"),
+                2 => Some("strokes"),
+                _ => None,
+            }
+        }
+    }
+
+    impl Sketch {
+        /// Serialize this value with its fields in field-ID order, for hashing
+        /// and signing. Equal values always give identical bytes, and the
+        /// output stays the same across versions while the schema is unchanged.
+        pub fn canonical_bytes(&self) -> Vec<u8> {
+            // Fields are declared in field-ID order
+            self.to_bytes()
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Code generated from `renamed.tcs`, whose `wincode_path` option names
+//! wincode by the `mywin` alias it has in `Cargo.toml`
+
+mod generated;
+
+pub use generated::renamed::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let sketch = Sketch {
+            title: Some("Draft".to_string()),
+            strokes: Some(vec![Stroke {
+                shape: Shape::Pointed,
+                mode: Mode::DRAW | Mode::ERASE,
+                color: [1, 2, 3, 4],
+            }]),
+        };
+        assert_eq!(Sketch::from_bytes(&sketch.to_bytes()).unwrap(), sketch);
+    }
+
+    #[test]
+    fn test_derive_encoding() {
+        // Enums are tagged with the variant's index rather than its value,
+        // and flags are their raw bits, as wincode's derives encode them
+        let shape = mywin::serialize(&Shape::Pointed).unwrap();
+        assert_eq!(shape, 2u32.to_le_bytes());
+        assert_eq!(mywin::deserialize::<Shape>(&shape).unwrap(), Shape::Pointed);
+        assert!(mywin::deserialize::<Shape>(&3u32.to_le_bytes()).is_err());
+
+        let mode = mywin::serialize(&(Mode::DRAW | Mode::ERASE)).unwrap();
+        assert_eq!(mode, 3u32.to_le_bytes());
+        assert_eq!(
+            mywin::deserialize::<Mode>(&mode).unwrap(),
+            Mode::DRAW | Mode::ERASE
+        );
+
+        let stroke = Stroke {
+            shape: Shape::Round,
+            mode: Mode::DRAW,
+            color: [9; 4],
+        };
+        assert_eq!(stroke.to_bytes(), [1, 0, 0, 0, 1, 0, 0, 0, 9, 9, 9, 9]);
+    }
+}
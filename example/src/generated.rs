@@ -1,12 +1,79 @@
 // This file was generated by the TCS compiler.
 // Do not edit manually.
 
+#[allow(dead_code)]
 pub mod example {
 
     use wincode::io::Writer;
     use wincode_derive::{SchemaRead, SchemaWrite};
 
+    /// Serde helpers rendering fixed-size byte arrays as hex strings
+    #[cfg(feature = "serde")]
+    mod serde_hex {
+        pub fn serialize<S: serde::Serializer, const N: usize>(
+            bytes: &[u8; N],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&hex::encode(bytes))
+        }
+
+        pub fn deserialize<'de, D: serde::Deserializer<'de>, const N: usize>(
+            deserializer: D,
+        ) -> Result<[u8; N], D::Error> {
+            let mut bytes = [0u8; N];
+            let hex: String = serde::Deserialize::deserialize(deserializer)?;
+            hex::decode_to_slice(hex, &mut bytes).map_err(serde::de::Error::custom)?;
+            Ok(bytes)
+        }
+
+        pub mod option {
+            pub fn serialize<S: serde::Serializer, const N: usize>(
+                bytes: &Option<[u8; N]>,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                match bytes {
+                    Some(bytes) => serializer.serialize_some(&hex::encode(bytes)),
+                    None => serializer.serialize_none(),
+                }
+            }
+
+            pub fn deserialize<'de, D: serde::Deserializer<'de>, const N: usize>(
+                deserializer: D,
+            ) -> Result<Option<[u8; N]>, D::Error> {
+                let hex: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+                hex.map(|hex| {
+                    let mut bytes = [0u8; N];
+                    hex::decode_to_slice(hex, &mut bytes).map_err(serde::de::Error::custom)?;
+                    Ok(bytes)
+                })
+                .transpose()
+            }
+        }
+    }
+
+    /// A value too large or too small for the integer field it was set on
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct RangeError {
+        /// Name of the field in the schema
+        pub field: &'static str,
+        /// The field's type in the schema, e.g. `uint8`
+        pub type_name: &'static str,
+    }
+
+    impl std::fmt::Display for RangeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(
+                f,
+                "value out of range for {} ({})",
+                self.field, self.type_name
+            )
+        }
+    }
+
+    impl std::error::Error for RangeError {}
+
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, SchemaRead, SchemaWrite)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[repr(u32)]
     pub enum Type {
         #[default]
@@ -15,7 +82,18 @@ pub mod example {
         Pointed = 2,
     }
 
+    impl Type {
+        /// Number of variants in the schema
+        pub const FIELD_COUNT: usize = 3;
+
+        /// Every variant, in schema order
+        pub const fn all() -> &'static [Self] {
+            &[Self::Flat, Self::Round, Self::Pointed]
+        }
+    }
+
     #[derive(Debug, Clone, PartialEq, Default, SchemaRead, SchemaWrite)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[repr(C)]
     pub struct Color {
         pub red: u8,
@@ -25,6 +103,9 @@ pub mod example {
     }
 
     impl Color {
+        /// Number of fields in the schema
+        pub const FIELD_COUNT: usize = 4;
+
         /// Serialize this value to bytes
         pub fn to_bytes(&self) -> Vec<u8> {
             let mut out = Vec::new();
@@ -33,9 +114,14 @@ pub mod example {
             out
         }
 
-        /// Serialize this value into a buffer
+        /// Serialize this value into a buffer, replacing its contents
         pub fn to_bytes_into(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {
             out.clear();
+            self.write_to(out)
+        }
+
+        /// Serialize this value, appending to `out` without reallocating if it has room
+        pub fn write_to(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {
             let size = wincode::serialized_size(self)? as usize;
             out.reserve(size);
             let mut writer = unsafe { out.as_trusted_for(size)? };
@@ -50,7 +136,95 @@ pub mod example {
         }
     }
 
+    impl TryFrom<&[u8]> for Color {
+        type Error = wincode::ReadError;
+
+        fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+            Self::from_bytes(bytes)
+        }
+    }
+
+    impl Color {
+        /// Set `red` from any integer, failing if it doesn't fit in a `byte`
+        pub fn set_red_checked<T: TryInto<u8>>(&mut self, value: T) -> Result<(), RangeError> {
+            let value = value.try_into().map_err(|_| RangeError {
+                field: "red",
+                type_name: "byte",
+            })?;
+            self.red = value;
+            Ok(())
+        }
+
+        /// Set `green` from any integer, failing if it doesn't fit in a `byte`
+        pub fn set_green_checked<T: TryInto<u8>>(&mut self, value: T) -> Result<(), RangeError> {
+            let value = value.try_into().map_err(|_| RangeError {
+                field: "green",
+                type_name: "byte",
+            })?;
+            self.green = value;
+            Ok(())
+        }
+
+        /// Set `blue` from any integer, failing if it doesn't fit in a `byte`
+        pub fn set_blue_checked<T: TryInto<u8>>(&mut self, value: T) -> Result<(), RangeError> {
+            let value = value.try_into().map_err(|_| RangeError {
+                field: "blue",
+                type_name: "byte",
+            })?;
+            self.blue = value;
+            Ok(())
+        }
+
+        /// Set `alpha` from any integer, failing if it doesn't fit in a `byte`
+        pub fn set_alpha_checked<T: TryInto<u8>>(&mut self, value: T) -> Result<(), RangeError> {
+            let value = value.try_into().map_err(|_| RangeError {
+                field: "alpha",
+                type_name: "byte",
+            })?;
+            self.alpha = value;
+            Ok(())
+        }
+    }
+
+    impl Color {
+        /// Serialize this value with its fields in field-ID order, for hashing
+        /// and signing. Equal values always give identical bytes, and the
+        /// output stays the same across versions while the schema is unchanged.
+        pub fn canonical_bytes(&self) -> Vec<u8> {
+            // Fields are declared in field-ID order
+            self.to_bytes()
+        }
+    }
+
+    impl Color {
+        /// Serialized size of a `Color`
+        pub const SIZE: usize = 4;
+
+        /// Serialize this value into a fixed-size array, without allocating
+        pub fn to_array(&self) -> [u8; Self::SIZE] {
+            let mut out = [0u8; Self::SIZE];
+            let mut writer = out.as_mut_slice();
+            wincode::serialize_into(&mut writer, self).expect("serialization should not fail");
+            out
+        }
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    impl Color {
+        /// Convert this value to a `serde_json::Value`
+        pub fn to_json_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+            serde_json::to_value(self)
+        }
+
+        /// Read a value from a `serde_json::Value`
+        pub fn from_json_value(value: &serde_json::Value) -> Result<Self, serde_json::Error> {
+            <Self as serde::Deserialize>::deserialize(value)
+        }
+    }
+
+    /// A brush with a shape type and gradient colors
     #[derive(Debug, Clone, PartialEq, Default, SchemaRead, SchemaWrite)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[repr(C)]
     pub struct Brush {
         pub type_: Type,
@@ -58,6 +232,9 @@ pub mod example {
     }
 
     impl Brush {
+        /// Number of fields in the schema
+        pub const FIELD_COUNT: usize = 2;
+
         /// Serialize this value to bytes
         pub fn to_bytes(&self) -> Vec<u8> {
             let mut out = Vec::new();
@@ -66,9 +243,14 @@ pub mod example {
             out
         }
 
-        /// Serialize this value into a buffer
+        /// Serialize this value into a buffer, replacing its contents
         pub fn to_bytes_into(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {
             out.clear();
+            self.write_to(out)
+        }
+
+        /// Serialize this value, appending to `out` without reallocating if it has room
+        pub fn write_to(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {
             let size = wincode::serialized_size(self)? as usize;
             out.reserve(size);
             let mut writer = unsafe { out.as_trusted_for(size)? };
@@ -83,7 +265,40 @@ pub mod example {
         }
     }
 
+    impl TryFrom<&[u8]> for Brush {
+        type Error = wincode::ReadError;
+
+        fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+            Self::from_bytes(bytes)
+        }
+    }
+
+    impl Brush {
+        /// Serialize this value with its fields in field-ID order, for hashing
+        /// and signing. Equal values always give identical bytes, and the
+        /// output stays the same across versions while the schema is unchanged.
+        pub fn canonical_bytes(&self) -> Vec<u8> {
+            // Fields are declared in field-ID order
+            self.to_bytes()
+        }
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    impl Brush {
+        /// Convert this value to a `serde_json::Value`
+        pub fn to_json_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+            serde_json::to_value(self)
+        }
+
+        /// Read a value from a `serde_json::Value`
+        pub fn from_json_value(value: &serde_json::Value) -> Result<Self, serde_json::Error> {
+            <Self as serde::Deserialize>::deserialize(value)
+        }
+    }
+
+    /// A layer containing multiple brushes
     #[derive(Debug, Clone, PartialEq, Default, SchemaRead, SchemaWrite)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     #[repr(C)]
     pub struct Layer {
         pub name: String,
@@ -91,6 +306,9 @@ pub mod example {
     }
 
     impl Layer {
+        /// Number of fields in the schema
+        pub const FIELD_COUNT: usize = 2;
+
         /// Serialize this value to bytes
         pub fn to_bytes(&self) -> Vec<u8> {
             let mut out = Vec::new();
@@ -99,9 +317,14 @@ pub mod example {
             out
         }
 
-        /// Serialize this value into a buffer
+        /// Serialize this value into a buffer, replacing its contents
         pub fn to_bytes_into(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {
             out.clear();
+            self.write_to(out)
+        }
+
+        /// Serialize this value, appending to `out` without reallocating if it has room
+        pub fn write_to(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {
             let size = wincode::serialized_size(self)? as usize;
             out.reserve(size);
             let mut writer = unsafe { out.as_trusted_for(size)? };
@@ -116,7 +339,39 @@ pub mod example {
         }
     }
 
+    impl TryFrom<&[u8]> for Layer {
+        type Error = wincode::ReadError;
+
+        fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+            Self::from_bytes(bytes)
+        }
+    }
+
+    impl Layer {
+        /// Serialize this value with its fields in field-ID order, for hashing
+        /// and signing. Equal values always give identical bytes, and the
+        /// output stays the same across versions while the schema is unchanged.
+        pub fn canonical_bytes(&self) -> Vec<u8> {
+            // Fields are declared in field-ID order
+            self.to_bytes()
+        }
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    impl Layer {
+        /// Convert this value to a `serde_json::Value`
+        pub fn to_json_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+            serde_json::to_value(self)
+        }
+
+        /// Read a value from a `serde_json::Value`
+        pub fn from_json_value(value: &serde_json::Value) -> Result<Self, serde_json::Error> {
+            <Self as serde::Deserialize>::deserialize(value)
+        }
+    }
+
     #[derive(Debug, Clone, PartialEq, Default, SchemaRead, SchemaWrite)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct Canvas {
         pub client_id: Option<u32>,
         pub width: Option<u32>,
@@ -125,6 +380,9 @@ pub mod example {
     }
 
     impl Canvas {
+        /// Number of fields in the schema
+        pub const FIELD_COUNT: usize = 4;
+
         /// Serialize this value to bytes
         pub fn to_bytes(&self) -> Vec<u8> {
             let mut out = Vec::new();
@@ -133,9 +391,14 @@ pub mod example {
             out
         }
 
-        /// Serialize this value into a buffer
+        /// Serialize this value into a buffer, replacing its contents
         pub fn to_bytes_into(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {
             out.clear();
+            self.write_to(out)
+        }
+
+        /// Serialize this value, appending to `out` without reallocating if it has room
+        pub fn write_to(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {
             let size = wincode::serialized_size(self)? as usize;
             out.reserve(size);
             let mut writer = unsafe { out.as_trusted_for(size)? };
@@ -149,4 +412,298 @@ pub mod example {
             wincode::deserialize(bytes)
         }
     }
+
+    impl TryFrom<&[u8]> for Canvas {
+        type Error = wincode::ReadError;
+
+        fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+            Self::from_bytes(bytes)
+        }
+    }
+
+    impl Canvas {
+        /// Number of fields that are set: every optional field that is `Some`,
+        /// plus the required fields, which are always present
+        pub fn present_field_count(&self) -> usize {
+            self.client_id.is_some() as usize
+                + self.width.is_some() as usize
+                + self.height.is_some() as usize
+                + self.layers.is_some() as usize
+        }
+    }
+
+    impl Canvas {
+        /// The schema name of the field with ID `id`, if there is one
+        pub const fn field_name(id: u32) -> Option<&'static str> {
+            match id {
+                1 => Some("clientID"),
+                2 => Some("width"),
+                3 => Some("height"),
+                4 => Some("layers"),
+                _ => None,
+            }
+        }
+    }
+
+    impl Canvas {
+        /// Set `clientID` from any integer, failing if it doesn't fit in a `uint`
+        pub fn set_client_id_checked<T: TryInto<u32>>(
+            &mut self,
+            value: T,
+        ) -> Result<(), RangeError> {
+            let value = value.try_into().map_err(|_| RangeError {
+                field: "clientID",
+                type_name: "uint",
+            })?;
+            self.client_id = Some(value);
+            Ok(())
+        }
+
+        /// Set `width` from any integer, failing if it doesn't fit in a `uint`
+        pub fn set_width_checked<T: TryInto<u32>>(&mut self, value: T) -> Result<(), RangeError> {
+            let value = value.try_into().map_err(|_| RangeError {
+                field: "width",
+                type_name: "uint",
+            })?;
+            self.width = Some(value);
+            Ok(())
+        }
+
+        /// Set `height` from any integer, failing if it doesn't fit in a `uint`
+        pub fn set_height_checked<T: TryInto<u32>>(&mut self, value: T) -> Result<(), RangeError> {
+            let value = value.try_into().map_err(|_| RangeError {
+                field: "height",
+                type_name: "uint",
+            })?;
+            self.height = Some(value);
+            Ok(())
+        }
+    }
+
+    impl Canvas {
+        /// Serialize this value with its fields in field-ID order, for hashing
+        /// and signing. Equal values always give identical bytes, and the
+        /// output stays the same across versions while the schema is unchanged.
+        pub fn canonical_bytes(&self) -> Vec<u8> {
+            // Fields are declared in field-ID order
+            self.to_bytes()
+        }
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    impl Canvas {
+        /// Convert this value to a `serde_json::Value`
+        pub fn to_json_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+            serde_json::to_value(self)
+        }
+
+        /// Read a value from a `serde_json::Value`
+        pub fn from_json_value(value: &serde_json::Value) -> Result<Self, serde_json::Error> {
+            <Self as serde::Deserialize>::deserialize(value)
+        }
+    }
+
+    /// Signature over an exported canvas
+    #[derive(Debug, Clone, PartialEq, SchemaRead, SchemaWrite)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    #[repr(C)]
+    pub struct Signature {
+        #[cfg_attr(feature = "serde", serde(with = "serde_hex"))]
+        pub bytes: [u8; 64],
+    }
+
+    impl Default for Signature {
+        fn default() -> Self {
+            Self {
+                bytes: [Default::default(); 64],
+            }
+        }
+    }
+
+    impl Signature {
+        /// Number of fields in the schema
+        pub const FIELD_COUNT: usize = 1;
+
+        /// Serialize this value to bytes
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let mut out = Vec::new();
+            self.to_bytes_into(&mut out)
+                .expect("serialization should not fail");
+            out
+        }
+
+        /// Serialize this value into a buffer, replacing its contents
+        pub fn to_bytes_into(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {
+            out.clear();
+            self.write_to(out)
+        }
+
+        /// Serialize this value, appending to `out` without reallocating if it has room
+        pub fn write_to(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {
+            let size = wincode::serialized_size(self)? as usize;
+            out.reserve(size);
+            let mut writer = unsafe { out.as_trusted_for(size)? };
+            wincode::serialize_into(&mut writer, self)?;
+            writer.finish()?;
+            Ok(())
+        }
+
+        /// Deserialize from bytes
+        pub fn from_bytes(bytes: &[u8]) -> Result<Self, wincode::ReadError> {
+            wincode::deserialize(bytes)
+        }
+    }
+
+    impl TryFrom<&[u8]> for Signature {
+        type Error = wincode::ReadError;
+
+        fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+            Self::from_bytes(bytes)
+        }
+    }
+
+    impl Signature {
+        /// Serialize this value with its fields in field-ID order, for hashing
+        /// and signing. Equal values always give identical bytes, and the
+        /// output stays the same across versions while the schema is unchanged.
+        pub fn canonical_bytes(&self) -> Vec<u8> {
+            // Fields are declared in field-ID order
+            self.to_bytes()
+        }
+    }
+
+    impl Signature {
+        /// Serialized size of a `Signature`
+        pub const SIZE: usize = 64;
+
+        /// Serialize this value into a fixed-size array, without allocating
+        pub fn to_array(&self) -> [u8; Self::SIZE] {
+            let mut out = [0u8; Self::SIZE];
+            let mut writer = out.as_mut_slice();
+            wincode::serialize_into(&mut writer, self).expect("serialization should not fail");
+            out
+        }
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    impl Signature {
+        /// Convert this value to a `serde_json::Value`
+        pub fn to_json_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+            serde_json::to_value(self)
+        }
+
+        /// Read a value from a `serde_json::Value`
+        pub fn from_json_value(value: &serde_json::Value) -> Result<Self, serde_json::Error> {
+            <Self as serde::Deserialize>::deserialize(value)
+        }
+    }
+
+    /// Clears the canvas; a signal without any data
+    #[derive(Debug, Clone, PartialEq, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Clear {}
+
+    impl wincode::SchemaWrite for Clear {
+        type Src = Self;
+
+        fn size_of(_src: &Self) -> wincode::WriteResult<usize> {
+            Ok(0)
+        }
+
+        fn write(_writer: &mut impl Writer, _src: &Self) -> wincode::WriteResult<()> {
+            Ok(())
+        }
+    }
+
+    impl<'de> wincode::SchemaRead<'de> for Clear {
+        type Dst = Self;
+
+        fn read(
+            _reader: &mut impl wincode::io::Reader<'de>,
+            dst: &mut std::mem::MaybeUninit<Self>,
+        ) -> wincode::ReadResult<()> {
+            dst.write(Self {});
+            Ok(())
+        }
+    }
+
+    impl Clear {
+        /// Number of fields in the schema
+        pub const FIELD_COUNT: usize = 0;
+
+        /// Serialize this value to bytes
+        pub fn to_bytes(&self) -> Vec<u8> {
+            let mut out = Vec::new();
+            self.to_bytes_into(&mut out)
+                .expect("serialization should not fail");
+            out
+        }
+
+        /// Serialize this value into a buffer, replacing its contents
+        pub fn to_bytes_into(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {
+            out.clear();
+            self.write_to(out)
+        }
+
+        /// Serialize this value, appending to `out` without reallocating if it has room
+        pub fn write_to(&self, out: &mut Vec<u8>) -> Result<(), wincode::WriteError> {
+            let size = wincode::serialized_size(self)? as usize;
+            out.reserve(size);
+            let mut writer = unsafe { out.as_trusted_for(size)? };
+            wincode::serialize_into(&mut writer, self)?;
+            writer.finish()?;
+            Ok(())
+        }
+
+        /// Deserialize from bytes
+        pub fn from_bytes(bytes: &[u8]) -> Result<Self, wincode::ReadError> {
+            wincode::deserialize(bytes)
+        }
+    }
+
+    impl TryFrom<&[u8]> for Clear {
+        type Error = wincode::ReadError;
+
+        fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+            Self::from_bytes(bytes)
+        }
+    }
+
+    impl Clear {
+        /// Number of fields that are set: every optional field that is `Some`,
+        /// plus the required fields, which are always present
+        pub fn present_field_count(&self) -> usize {
+            0
+        }
+    }
+
+    impl Clear {
+        /// The schema name of the field with ID `id`, if there is one
+        pub const fn field_name(_id: u32) -> Option<&'static str> {
+            None
+        }
+    }
+
+    impl Clear {
+        /// Serialize this value with its fields in field-ID order, for hashing
+        /// and signing. Equal values always give identical bytes, and the
+        /// output stays the same across versions while the schema is unchanged.
+        pub fn canonical_bytes(&self) -> Vec<u8> {
+            // Fields are declared in field-ID order
+            self.to_bytes()
+        }
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    impl Clear {
+        /// Convert this value to a `serde_json::Value`
+        pub fn to_json_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+            serde_json::to_value(self)
+        }
+
+        /// Read a value from a `serde_json::Value`
+        pub fn from_json_value(value: &serde_json::Value) -> Result<Self, serde_json::Error> {
+            <Self as serde::Deserialize>::deserialize(value)
+        }
+    }
 }
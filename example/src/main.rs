@@ -7,8 +7,10 @@
 //!                 └── Color[] (struct with RGBA bytes)
 
 mod generated;
+mod signals;
 
-use generated::example::{Brush, Canvas, Color, Layer, Type};
+use generated::example::{Brush, Canvas, Clear, Color, Layer, Type};
+use signals::Heartbeat;
 
 fn main() {
     // Create some colors
@@ -71,4 +73,153 @@ fn main() {
     assert_eq!(canvas, decoded);
     println!("\n✓ Round-trip successful!");
     println!("  Nested structure: Canvas -> Layer[] -> Brush[] -> Color[]");
+
+    // Signals without data serialize to nothing at all
+    let clear = Clear {};
+    println!("\nClear signal: {} bytes", clear.to_bytes().len());
+
+    // Generated into a hand-written file, between marker comments
+    let heartbeat = Heartbeat::at(1_700_000_000_000);
+    println!("Heartbeat: {} bytes", heartbeat.to_bytes().len());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_to_matches_to_bytes() {
+        let canvas = Canvas {
+            client_id: Some(7),
+            layers: Some(vec![Layer {
+                name: "Sketch".to_string(),
+                brushes: vec![Brush { type_: Type::Round, colors: vec![Color::default(); 2] }],
+            }]),
+            ..Canvas::default()
+        };
+        let expected = canvas.to_bytes();
+
+        // Appends after existing contents, within the reserved capacity
+        let mut buf = Vec::with_capacity(expected.len() + 3);
+        buf.extend_from_slice(&[1, 2, 3]);
+        let capacity = buf.capacity();
+        canvas.write_to(&mut buf).unwrap();
+        assert_eq!(&buf[..3], &[1, 2, 3]);
+        assert_eq!(&buf[3..], expected.as_slice());
+        assert_eq!(buf.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_present_field_count() {
+        let mut canvas = Canvas::default();
+        assert_eq!(canvas.present_field_count(), 0);
+
+        canvas.width = Some(640);
+        canvas.layers = Some(Vec::new());
+        assert_eq!(canvas.present_field_count(), 2);
+
+        let decoded = Canvas::from_bytes(&canvas.to_bytes()).unwrap();
+        assert_eq!(decoded.present_field_count(), 2);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn test_json_value_round_trip() {
+        let canvas = Canvas {
+            client_id: Some(7),
+            width: Some(640),
+            layers: Some(vec![Layer {
+                name: "Sketch".to_string(),
+                brushes: vec![Brush { type_: Type::Pointed, colors: vec![Color::default()] }],
+            }]),
+            ..Canvas::default()
+        };
+
+        let value = canvas.to_json_value().unwrap();
+        assert_eq!(value["width"], 640);
+        assert_eq!(value["height"], serde_json::Value::Null);
+        assert_eq!(Canvas::from_json_value(&value).unwrap(), canvas);
+    }
+
+    #[test]
+    fn test_large_array_default() {
+        use generated::example::Signature;
+
+        // byte[64] is over the 32 elements Default can be derived for
+        assert_eq!(Signature::default().bytes, [0u8; 64]);
+
+        let signature = Signature { bytes: [7; 64] };
+        assert_eq!(Signature::from_bytes(&signature.to_bytes()).unwrap(), signature);
+    }
+
+    #[test]
+    fn test_spliced_types() {
+        let heartbeat = Heartbeat::at(42);
+        assert_eq!(Heartbeat::from_bytes(&heartbeat.to_bytes()).unwrap(), heartbeat);
+        assert_eq!(Heartbeat::field_name(1), Some("sentAt"));
+    }
+
+    #[test]
+    fn test_empty_message() {
+        let bytes = Clear {}.to_bytes();
+        assert!(bytes.is_empty());
+        assert_eq!(Clear::from_bytes(&bytes).unwrap(), Clear {});
+        assert_eq!(Clear::FIELD_COUNT, 0);
+    }
+
+    #[test]
+    fn test_field_name() {
+        assert_eq!(Canvas::field_name(1), Some("clientID"));
+        assert_eq!(Canvas::field_name(4), Some("layers"));
+        assert_eq!(Canvas::field_name(0), None);
+        assert_eq!(Canvas::field_name(5), None);
+    }
+
+    #[test]
+    fn test_canonical_bytes() {
+        let built = Canvas {
+            client_id: Some(7),
+            width: Some(640),
+            height: None,
+            layers: Some(Vec::new()),
+        };
+        let updated = Canvas {
+            layers: Some(Vec::new()),
+            width: Some(640),
+            client_id: Some(7),
+            ..Default::default()
+        };
+
+        assert_eq!(built, updated);
+        assert_eq!(built.canonical_bytes(), updated.canonical_bytes());
+
+        let decoded = Canvas::from_bytes(&built.canonical_bytes()).unwrap();
+        assert_eq!(decoded, built);
+    }
+
+    #[test]
+    fn test_to_array() {
+        let color = Color { red: 1, green: 2, blue: 3, alpha: 4 };
+        let bytes: [u8; Color::SIZE] = color.to_array();
+        assert_eq!(bytes.len(), 4);
+        assert_eq!(&bytes[..], &color.to_bytes()[..]);
+        assert_eq!(Color::from_bytes(&bytes).unwrap(), color);
+    }
+
+    #[test]
+    fn test_checked_setters() {
+        let mut color = Color::default();
+        let err = color.set_red_checked(256u64).unwrap_err();
+        assert_eq!(err.field, "red");
+        assert_eq!(err.type_name, "byte");
+        assert_eq!(color.red, 0);
+
+        color.set_red_checked(255u64).unwrap();
+        assert_eq!(color.red, 255);
+
+        let mut canvas = Canvas::default();
+        assert!(canvas.set_width_checked(-1i64).is_err());
+        canvas.set_width_checked(1920i64).unwrap();
+        assert_eq!(canvas.width, Some(1920));
+    }
 }
@@ -3,10 +3,82 @@
 /// Represents a complete TCS schema parsed from a .tcs file.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Schema {
-    /// Optional package name for the generated code module
+    /// Optional package name for the generated code module (the first
+    /// `package` declaration; see `Definition::package` for multi-package files)
     pub package: Option<String>,
     /// All type definitions in the schema
     pub definitions: Vec<Definition>,
+    /// Entries of the schema's `options { ... }` block, in source order
+    pub options: Vec<SchemaOption>,
+    /// The file header: `//!` comment lines at the very top of the file (e.g.
+    /// a license), without their markers, one line per line
+    pub header: Option<String>,
+    /// `type Name = ...;` declarations, in source order. Aliases are shared by
+    /// every package in the file.
+    pub aliases: Vec<TypeAlias>,
+    /// Comment blocks before the file's first `package` or `options`
+    /// declaration (after the header)
+    pub leading_comments: Vec<Vec<String>>,
+    /// Comment blocks after the last declaration, at the end of the file
+    pub end_comments: Vec<Vec<String>>,
+}
+
+/// A `type Name = target;` declaration, e.g. `type Hash = byte[32];`. Fields
+/// of type `Hash` are treated as `byte[32]` (see [`Schema::resolve_aliases`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeAlias {
+    /// Alias name
+    pub name: String,
+    /// Source line number (1-indexed)
+    pub line: usize,
+    /// Source column number (1-indexed)
+    pub column: usize,
+    /// The aliased type name (possibly another alias, or qualified: `pkg.Type`)
+    pub type_: String,
+    /// Whether the alias is an array (e.g. `type Hashes = Hash[];`)
+    pub is_array: bool,
+    /// Fixed array size, as for [`Field::array_size`]
+    pub array_size: Option<usize>,
+    /// The array size as written, as for [`Field::array_size_text`]
+    pub array_size_text: Option<String>,
+    /// Comment lines directly above the declaration (without the `//` marker)
+    pub comments: Vec<String>,
+    /// Comment blocks before the declaration that aren't directly above it,
+    /// in source order (see [`Definition::floating_comments`])
+    pub floating_comments: Vec<Vec<String>>,
+    /// Comment after the declaration on the same line
+    pub trailing_comment: Option<String>,
+}
+
+/// One `name = value;` entry of a schema's `options` block, e.g. `no_std = true;`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaOption {
+    /// Option name
+    pub name: String,
+    /// Source line number (1-indexed)
+    pub line: usize,
+    /// Source column number (1-indexed)
+    pub column: usize,
+    /// Option value
+    pub value: OptionValue,
+}
+
+/// The value of a schema option
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionValue {
+    /// `true` or `false`
+    Bool(bool),
+    /// A double-quoted string
+    String(String),
+}
+
+impl std::fmt::Display for OptionValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OptionValue::Bool(value) => write!(f, "{}", value),
+            OptionValue::String(value) => write!(f, "\"{}\"", value),
+        }
+    }
 }
 
 /// The kind of a type definition
@@ -20,6 +92,54 @@ pub enum DefinitionKind {
     Message = 2,
 }
 
+/// Presence keyword on a message field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Presence {
+    /// No keyword; message fields are optional
+    #[default]
+    Implicit,
+    /// Explicitly marked `optional`
+    Optional,
+    /// Marked `required`; generated without an `Option` wrapper
+    Required,
+}
+
+/// Bounds from a field's `@validate(...)` annotation, checked by the generated
+/// `validate` method
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Validation {
+    /// Smallest allowed value of an integer field (`min`)
+    pub min: Option<i128>,
+    /// Largest allowed value of an integer field (`max`)
+    pub max: Option<i128>,
+    /// Fewest elements allowed in a variable-length array (`len_min`)
+    pub len_min: Option<usize>,
+    /// Most elements allowed in a variable-length array (`len_max`)
+    pub len_max: Option<usize>,
+    /// `min` as written, e.g. `1_000`
+    pub min_text: Option<String>,
+    /// `max` as written
+    pub max_text: Option<String>,
+    /// `len_min` as written
+    pub len_min_text: Option<String>,
+    /// `len_max` as written
+    pub len_max_text: Option<String>,
+}
+
+/// An `@name` annotation the parser doesn't recognize, kept so the verifier
+/// can report it (e.g. a misspelled `@deprectaed`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    /// Annotation name, without the `@`
+    pub name: String,
+    /// The annotation as written, e.g. `@added("2.0")`
+    pub text: String,
+    /// Source line number (1-indexed)
+    pub line: usize,
+    /// Source column number (1-indexed)
+    pub column: usize,
+}
+
 /// A field within a definition (enum variant, struct field, or message field)
 #[derive(Debug, Clone, PartialEq)]
 pub struct Field {
@@ -35,10 +155,33 @@ pub struct Field {
     pub is_array: bool,
     /// Fixed array size for types like `byte[32]` (None for scalars or variable arrays)
     pub array_size: Option<usize>,
+    /// The array size as written, e.g. `1_024` for `byte[1_024]`
+    pub array_size_text: Option<String>,
     /// Whether this field is marked as deprecated
     pub is_deprecated: bool,
+    /// Whether this field is marked `[key]` (equality and hashing use only key fields)
+    pub is_key: bool,
     /// Field index/value (auto-assigned for structs, explicit for enums/messages)
     pub field_id: i32,
+    /// The value as written, e.g. `1_000` (None when it isn't written out)
+    pub field_id_text: Option<String>,
+    /// For enum variants declared as `NAME = OTHER;`, the variant this one aliases
+    pub alias_of: Option<String>,
+    /// Comment lines directly above this field (without the `//` marker)
+    pub comments: Vec<String>,
+    /// Comment blocks before this field that aren't directly above it, in
+    /// source order (see [`Definition::floating_comments`])
+    pub floating_comments: Vec<Vec<String>>,
+    /// Comment after the field on the same line
+    pub trailing_comment: Option<String>,
+    /// `optional`/`required` keyword (messages only)
+    pub presence: Presence,
+    /// Version the field was added in, from `@since("2.0")`
+    pub since: Option<String>,
+    /// Bounds from `@validate(min = 0, max = 100)`
+    pub validate: Option<Validation>,
+    /// Unrecognized `@name` annotations, in source order
+    pub unknown_annotations: Vec<Annotation>,
 }
 
 /// A type definition (enum, struct, or message)
@@ -50,10 +193,40 @@ pub struct Definition {
     pub line: usize,
     /// Source column number (1-indexed)
     pub column: usize,
+    /// Source line of the closing `}` (1-indexed)
+    pub end_line: usize,
+    /// Source column of the closing `}` (1-indexed)
+    pub end_column: usize,
     /// Kind of definition
     pub kind: DefinitionKind,
     /// Fields/variants within this definition
     pub fields: Vec<Field>,
+    /// Comment lines directly above this definition (without the `//` marker)
+    pub comments: Vec<String>,
+    /// Comment blocks before this definition that aren't directly above it,
+    /// in source order: ones separated from it by a blank line, and ones
+    /// between the tokens of whatever came before (e.g. an `options` block)
+    pub floating_comments: Vec<Vec<String>>,
+    /// Comment after the opening `{` on the same line, or else after the
+    /// closing `}`
+    pub trailing_comment: Option<String>,
+    /// Comment blocks after the last field, before the closing `}`
+    pub end_comments: Vec<Vec<String>>,
+    /// Package this definition belongs to (the most recent `package` declaration)
+    pub package: Option<String>,
+    /// Whether this enum is marked `[flags]` (a bit set rather than a single value)
+    pub is_flags: bool,
+    /// Whether the whole definition is marked `[deprecated]`
+    pub is_deprecated: bool,
+    /// Whether this struct is marked `[ord]` (ordered by its fields in declaration order)
+    pub is_ord: bool,
+    /// The struct or message this definition is nested in, e.g. `Canvas` for
+    /// `message Canvas { struct Layer { ... } }`. Nested definitions can only
+    /// be referenced from inside their parent.
+    pub parent: Option<String>,
+    /// Cargo feature the generated code is gated on (`[feature = "exp"]`),
+    /// emitted as `#[cfg(feature = "exp")]` on each of its items
+    pub feature: Option<String>,
 }
 
 impl Schema {
@@ -62,19 +235,815 @@ impl Schema {
         Schema {
             package: None,
             definitions: Vec::new(),
+            options: Vec::new(),
+            header: None,
+            aliases: Vec::new(),
+            leading_comments: Vec::new(),
+            end_comments: Vec::new(),
+        }
+    }
+
+    /// Find a definition by (unqualified) name
+    pub fn find_definition(&self, name: &str) -> Option<&Definition> {
+        self.definitions.iter().find(|d| d.name == name)
+    }
+
+    /// The definition that a field of `from` refers to as `ty`: `pkg.Name` is
+    /// `Name` in the package `pkg`, and a plain `Name` is looked up in
+    /// `from`'s own package. Definitions in different packages may share a
+    /// name. Returns `None` for built-in and undefined types.
+    pub fn resolve_type(&self, ty: &str, from: &Definition) -> Option<&Definition> {
+        let (package, name) = match ty.split_once('.') {
+            Some((package, name)) => (Some(package), name),
+            None => (from.package.as_deref(), ty),
+        };
+        self.definitions
+            .iter()
+            .find(|d| d.name == name && d.package.as_deref() == package)
+    }
+
+    /// Find a type alias by name
+    pub fn find_alias(&self, name: &str) -> Option<&TypeAlias> {
+        self.aliases.iter().find(|a| a.name == name)
+    }
+
+    /// `field` with its type alias (if any) replaced by the aliased type,
+    /// following chains of aliases. The array parts combine, so a `Hash[]`
+    /// field with `type Hash = byte[32];` becomes an array of `byte[32]`,
+    /// which verification rejects. Cyclic aliases are left partly resolved.
+    pub fn resolve_field(&self, field: &Field) -> Field {
+        let mut field = field.clone();
+        for _ in 0..self.aliases.len() {
+            let alias = match field.type_.as_deref().and_then(|ty| self.find_alias(ty)) {
+                Some(alias) => alias,
+                None => break,
+            };
+            field.type_ = Some(alias.type_.clone());
+            field.is_array |= alias.is_array;
+            if field.array_size.is_none() {
+                field.array_size = alias.array_size;
+                field.array_size_text = alias.array_size_text.clone();
+            }
+        }
+        field
+    }
+
+    /// A copy of the schema with every field's type alias replaced by the
+    /// aliased type (see [`Schema::resolve_field`]). Code generation works on
+    /// the resolved schema; the aliases themselves are kept.
+    pub fn resolve_aliases(&self) -> Schema {
+        let mut resolved = self.clone();
+        if self.aliases.is_empty() {
+            return resolved;
+        }
+        for def in &mut resolved.definitions {
+            for field in &mut def.fields {
+                *field = self.resolve_field(field);
+            }
+        }
+        resolved
+    }
+
+    /// Every field (and enum variant) in the schema along with the definition
+    /// it belongs to, in declaration order
+    pub fn iter_fields(&self) -> impl Iterator<Item = (&Definition, &Field)> {
+        self.definitions
+            .iter()
+            .flat_map(|def| def.fields.iter().map(move |field| (def, field)))
+    }
+
+    /// Check that every definition and field name is a valid identifier: ASCII
+    /// letters, digits and underscores, not starting with a digit, and at most
+    /// [`MAX_NAME_LEN`] bytes long. The parser only produces such names, but
+    /// schemas built or merged in code may not.
+    pub fn validate_names(&self) -> Result<(), InvalidName> {
+        for alias in &self.aliases {
+            validate_name(&alias.name)?;
+        }
+        for def in &self.definitions {
+            validate_name(&def.name)?;
+            for field in &def.fields {
+                validate_name(&field.name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Count definitions by kind, fields and deprecated fields, and find the
+    /// highest field ID of each message
+    pub fn stats(&self) -> SchemaStats {
+        let mut stats = SchemaStats::default();
+        for def in &self.definitions {
+            match def.kind {
+                DefinitionKind::Enum => stats.enums += 1,
+                DefinitionKind::Struct => stats.structs += 1,
+                DefinitionKind::Message => {
+                    stats.messages += 1;
+                    let max_id = def.fields.iter().map(|f| f.field_id).max();
+                    stats.max_field_ids.push((def.name.clone(), max_id));
+                }
+            }
+            stats.fields += def.fields.len();
+            stats.deprecated_fields += def.fields.iter().filter(|f| f.is_deprecated).count();
         }
+        stats
+    }
+
+    /// Append the definitions of `other` (e.g. another file of the same schema).
+    ///
+    /// The first package declaration seen is kept as `package`, and likewise
+    /// the first header; definitions keep their own package. Options are
+    /// appended, so `other`'s take precedence, and so are type aliases. If a
+    /// definition name is already taken in its package, nothing is merged.
+    pub fn merge(&mut self, other: Schema) -> Result<(), DuplicateDefinition> {
+        if let Some(def) = other.definitions.iter().find(|d| {
+            self.definitions
+                .iter()
+                .any(|existing| existing.qualified_name() == d.qualified_name())
+        }) {
+            return Err(DuplicateDefinition {
+                name: def.qualified_name(),
+            });
+        }
+
+        if self.package.is_none() {
+            self.package = other.package;
+        }
+        if self.header.is_none() {
+            self.header = other.header;
+        }
+        self.definitions.extend(other.definitions);
+        self.options.extend(other.options);
+        self.aliases.extend(other.aliases);
+        Ok(())
+    }
+}
+
+/// Summary counts for a schema, returned by [`Schema::stats`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchemaStats {
+    /// Number of enums (including `[flags]` enums)
+    pub enums: usize,
+    /// Number of structs
+    pub structs: usize,
+    /// Number of messages
+    pub messages: usize,
+    /// Fields across all definitions, enum variants included
+    pub fields: usize,
+    /// Fields marked `[deprecated]`
+    pub deprecated_fields: usize,
+    /// Highest field ID of each message, in definition order (`None` for a
+    /// message without fields)
+    pub max_field_ids: Vec<(String, Option<i32>)>,
+}
+
+impl Definition {
+    /// Find a field (or enum variant) by name
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// The name prefixed with the package, e.g. `geometry.Point`, which
+    /// identifies the definition within a schema
+    pub fn qualified_name(&self) -> String {
+        match self.package {
+            Some(ref package) => format!("{}.{}", package, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Longest allowed definition or field name, in bytes
+pub const MAX_NAME_LEN: usize = 64;
+
+/// Error returned by [`Schema::validate_names`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidName {
+    /// The offending name
+    pub name: String,
+    /// Which rule it breaks
+    pub reason: String,
+}
+
+impl std::fmt::Display for InvalidName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The name \"{}\" is invalid: {}", self.name, self.reason)
+    }
+}
+
+impl std::error::Error for InvalidName {}
+
+/// Check a single name against the rules of [`Schema::validate_names`]
+fn validate_name(name: &str) -> Result<(), InvalidName> {
+    let reason = if name.is_empty() {
+        "names cannot be empty".to_string()
+    } else if name.len() > MAX_NAME_LEN {
+        format!("names can be at most {} characters long", MAX_NAME_LEN)
+    } else if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        "names can only contain ASCII letters, digits and underscores".to_string()
+    } else if name.starts_with(|c: char| c.is_ascii_digit()) {
+        "names cannot start with a digit".to_string()
+    } else {
+        return Ok(());
+    };
+    Err(InvalidName {
+        name: name.to_string(),
+        reason,
+    })
+}
+
+/// Error returned by [`Schema::merge`] when both schemas define the same name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateDefinition {
+    /// The name defined by both schemas, qualified with its package if it
+    /// has one
+    pub name: String,
+}
+
+impl std::fmt::Display for DuplicateDefinition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The type \"{}\" is defined twice", self.name)
     }
 }
 
+impl std::error::Error for DuplicateDefinition {}
+
 impl Default for Schema {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// How a field is laid out on the wire, independent of any target language
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireType {
+    /// Fixed-width little-endian number
+    Scalar { bits: u8, signed: bool, float: bool },
+    /// Single byte, 0 or 1
+    Bool,
+    /// Length-prefixed UTF-8 string
+    String,
+    /// Enum, encoded as its `u32` discriminant
+    Enum { name: String },
+    /// User-defined struct or message
+    Nested { name: String },
+    /// Fixed number of elements with no length prefix (e.g., `byte[32]`)
+    FixedArray { elem: Box<WireType>, len: usize },
+    /// Length-prefixed sequence of elements (e.g., `int[]`)
+    VarArray { elem: Box<WireType> },
+}
+
+/// Error returned when a field's type can't be resolved against a schema
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedType {
+    /// Name of the field being resolved
+    pub field: String,
+    /// The type that couldn't be found (None for enum variants, which have no type)
+    pub type_name: Option<String>,
+}
+
+impl std::fmt::Display for UnresolvedType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.type_name {
+            Some(ref ty) => write!(
+                f,
+                "The type \"{}\" is not defined for field \"{}\"",
+                ty, self.field
+            ),
+            None => write!(f, "The field \"{}\" has no type", self.field),
+        }
+    }
+}
+
+impl std::error::Error for UnresolvedType {}
+
 impl Field {
-    /// Check if this field is a fixed-size byte array (e.g., `byte[32]`)
+    /// Check if this field is a fixed-size byte array (e.g., `byte[32]` or `uint8[32]`)
     pub fn is_fixed_byte_array(&self) -> bool {
-        self.array_size.is_some() && self.type_.as_deref() == Some("byte")
+        self.array_size.is_some() && matches!(self.type_.as_deref(), Some("byte" | "uint8"))
+    }
+
+    /// Check if this field is a single fixed-width number or bool (e.g.,
+    /// `uint64` or `bool`, but not `uint64[]` or `uint256`, which is a byte array)
+    pub fn is_scalar(&self) -> bool {
+        !self.is_array
+            && matches!(
+                self.type_.as_deref(),
+                Some(
+                    "bool"
+                        | "byte"
+                        | "uint8"
+                        | "int"
+                        | "uint"
+                        | "float"
+                        | "int64"
+                        | "uint64"
+                        | "timestamp"
+                        | "int128"
+                        | "uint128"
+                )
+            )
+    }
+
+    /// Check if this is an enum variant, which has a value but no type
+    pub fn is_enum_variant(&self) -> bool {
+        self.type_.is_none()
+    }
+
+    /// Check if this field belongs to a message, given the kind of its
+    /// definition: it has an explicit ID and is optional unless `required`
+    pub fn is_message_field(&self, kind: DefinitionKind) -> bool {
+        kind == DefinitionKind::Message && !self.is_enum_variant()
+    }
+
+    /// Classify how this field of `from` is serialized, resolving type aliases
+    /// and user-defined types against `schema` as [`Schema::resolve_type`]
+    /// does: a plain name is looked up in `from`'s package, `pkg.Type` in `pkg`
+    pub fn wire_type(
+        &self,
+        schema: &Schema,
+        from: &Definition,
+    ) -> Result<WireType, UnresolvedType> {
+        let field = schema.resolve_field(self);
+        let unresolved = || UnresolvedType {
+            field: field.name.clone(),
+            type_name: field.type_.clone(),
+        };
+        let type_name = field.type_.as_deref().ok_or_else(unresolved)?;
+
+        let scalar = |bits, signed, float| WireType::Scalar {
+            bits,
+            signed,
+            float,
+        };
+        let elem = match type_name {
+            "bool" => WireType::Bool,
+            "byte" | "uint8" => scalar(8, false, false),
+            "int" => scalar(32, true, false),
+            "uint" => scalar(32, false, false),
+            "float" => scalar(32, true, true),
+            "int64" => scalar(64, true, false),
+            "uint64" | "timestamp" => scalar(64, false, false),
+            "int128" => scalar(128, true, false),
+            "uint128" => scalar(128, false, false),
+            // Represented as 32 little-endian bytes
+            "uint256" => WireType::FixedArray {
+                elem: Box::new(scalar(8, false, false)),
+                len: 32,
+            },
+            "string" => WireType::String,
+            // bytes shares the byte[] encoding
+            "bytes" => WireType::VarArray {
+                elem: Box::new(scalar(8, false, false)),
+            },
+            other => {
+                let def = schema.resolve_type(other, from).ok_or_else(unresolved)?;
+                match def.kind {
+                    DefinitionKind::Enum => WireType::Enum {
+                        name: def.name.clone(),
+                    },
+                    DefinitionKind::Struct | DefinitionKind::Message => WireType::Nested {
+                        name: def.name.clone(),
+                    },
+                }
+            }
+        };
+
+        Ok(match (field.is_array, field.array_size) {
+            (true, Some(len)) => WireType::FixedArray {
+                elem: Box::new(elem),
+                len,
+            },
+            (true, None) => WireType::VarArray {
+                elem: Box::new(elem),
+            },
+            (false, _) => elem,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(type_: &str, is_array: bool, array_size: Option<usize>) -> Field {
+        Field {
+            name: "f".to_string(),
+            line: 1,
+            column: 1,
+            type_: Some(type_.to_string()),
+            is_array,
+            array_size,
+            array_size_text: None,
+            is_deprecated: false,
+            is_key: false,
+            field_id: 1,
+            field_id_text: None,
+            alias_of: None,
+            comments: Vec::new(),
+            floating_comments: Vec::new(),
+            trailing_comment: None,
+            presence: Presence::Implicit,
+            since: None,
+            validate: None,
+            unknown_annotations: Vec::new(),
+        }
+    }
+
+    fn schema() -> Schema {
+        let definition = |name: &str, kind| Definition {
+            name: name.to_string(),
+            line: 1,
+            column: 1,
+            end_line: 1,
+            end_column: 1,
+            kind,
+            fields: Vec::new(),
+            comments: Vec::new(),
+            floating_comments: Vec::new(),
+            trailing_comment: None,
+            end_comments: Vec::new(),
+            package: Some("pkg".to_string()),
+            is_flags: false,
+            is_deprecated: false,
+            is_ord: false,
+            parent: None,
+            feature: None,
+        };
+        Schema {
+            package: Some("pkg".to_string()),
+            definitions: vec![
+                definition("Color", DefinitionKind::Enum),
+                definition("Point", DefinitionKind::Struct),
+            ],
+            options: Vec::new(),
+            header: None,
+            aliases: Vec::new(),
+            leading_comments: Vec::new(),
+            end_comments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_field_predicates() {
+        let variant = Field {
+            type_: None,
+            ..field("int", false, None)
+        };
+
+        assert!(field("uint64", false, None).is_scalar());
+        assert!(field("bool", false, None).is_scalar());
+        assert!(field("uint128", false, None).is_scalar());
+        assert!(!field("uint64", true, None).is_scalar());
+        assert!(!field("byte", true, Some(32)).is_scalar());
+        assert!(!field("uint256", false, None).is_scalar());
+        assert!(!field("string", false, None).is_scalar());
+        assert!(!field("Point", false, None).is_scalar());
+        assert!(!variant.is_scalar());
+
+        assert!(variant.is_enum_variant());
+        assert!(!field("int", false, None).is_enum_variant());
+        assert!(!field("Color", false, None).is_enum_variant());
+
+        let f = field("int", false, None);
+        assert!(f.is_message_field(DefinitionKind::Message));
+        assert!(!f.is_message_field(DefinitionKind::Struct));
+        assert!(!variant.is_message_field(DefinitionKind::Enum));
+        assert!(!variant.is_message_field(DefinitionKind::Message));
+    }
+
+    #[test]
+    fn test_wire_type_scalars() {
+        let schema = schema();
+        let point = &schema.definitions[1];
+        assert_eq!(
+            field("uint64", false, None).wire_type(&schema, point),
+            Ok(WireType::Scalar {
+                bits: 64,
+                signed: false,
+                float: false
+            })
+        );
+        assert_eq!(
+            field("timestamp", false, None).wire_type(&schema, point),
+            field("uint64", false, None).wire_type(&schema, point)
+        );
+        assert_eq!(
+            field("uint128", false, None).wire_type(&schema, point),
+            Ok(WireType::Scalar {
+                bits: 128,
+                signed: false,
+                float: false
+            })
+        );
+        assert_eq!(
+            field("uint256", false, None).wire_type(&schema, point),
+            field("byte", true, Some(32)).wire_type(&schema, point)
+        );
+        assert_eq!(
+            field("float", false, None).wire_type(&schema, point),
+            Ok(WireType::Scalar {
+                bits: 32,
+                signed: true,
+                float: true
+            })
+        );
+        assert_eq!(
+            field("bool", false, None).wire_type(&schema, point),
+            Ok(WireType::Bool)
+        );
+        assert_eq!(
+            field("string", false, None).wire_type(&schema, point),
+            Ok(WireType::String)
+        );
+    }
+
+    #[test]
+    fn test_wire_type_arrays() {
+        let schema = schema();
+        let point = &schema.definitions[1];
+        let byte = Box::new(WireType::Scalar {
+            bits: 8,
+            signed: false,
+            float: false,
+        });
+        assert_eq!(
+            field("byte", true, Some(32)).wire_type(&schema, point),
+            Ok(WireType::FixedArray {
+                elem: byte.clone(),
+                len: 32
+            })
+        );
+        assert_eq!(
+            field("byte", true, None).wire_type(&schema, point),
+            Ok(WireType::VarArray { elem: byte.clone() })
+        );
+        assert_eq!(
+            field("bytes", false, None).wire_type(&schema, point),
+            Ok(WireType::VarArray { elem: byte })
+        );
+    }
+
+    #[test]
+    fn test_wire_type_user_defined() {
+        let mut schema = schema();
+        let mut other = schema.definitions[0].clone();
+        other.name = "Point".to_string();
+        other.package = Some("other".to_string());
+        schema.definitions.push(other);
+        let point = &schema.definitions[1];
+        assert_eq!(
+            field("Color", false, None).wire_type(&schema, point),
+            Ok(WireType::Enum {
+                name: "Color".to_string()
+            })
+        );
+        assert_eq!(
+            field("pkg.Point", true, None).wire_type(&schema, point),
+            Ok(WireType::VarArray {
+                elem: Box::new(WireType::Nested {
+                    name: "Point".to_string()
+                })
+            })
+        );
+        assert!(field("other.Color", false, None)
+            .wire_type(&schema, point)
+            .is_err());
+        assert!(field("Missing", false, None)
+            .wire_type(&schema, point)
+            .is_err());
+
+        // A plain name means the type in the field's own package, even when
+        // another package has a type of that name
+        let nested = Ok(WireType::Nested {
+            name: "Point".to_string(),
+        });
+        assert_eq!(
+            field("Point", false, None).wire_type(&schema, point),
+            nested
+        );
+        let in_other = &schema.definitions[2];
+        let as_enum = Ok(WireType::Enum {
+            name: "Point".to_string(),
+        });
+        assert_eq!(
+            field("Point", false, None).wire_type(&schema, in_other),
+            as_enum
+        );
+        assert_eq!(
+            field("other.Point", false, None).wire_type(&schema, point),
+            as_enum
+        );
+        assert_eq!(
+            field("pkg.Point", false, None).wire_type(&schema, in_other),
+            nested
+        );
+    }
+
+    #[test]
+    fn test_resolve_aliases() {
+        let alias = |name: &str, type_: &str, is_array, array_size| TypeAlias {
+            name: name.to_string(),
+            line: 1,
+            column: 1,
+            type_: type_.to_string(),
+            is_array,
+            array_size,
+            array_size_text: None,
+            comments: Vec::new(),
+            floating_comments: Vec::new(),
+            trailing_comment: None,
+        };
+        let mut schema = schema();
+        schema.aliases = vec![
+            alias("Hash", "byte", true, Some(32)),
+            alias("Root", "Hash", false, None),
+            alias("Points", "pkg.Point", true, None),
+            alias("A", "B", false, None),
+            alias("B", "A", false, None),
+        ];
+        let point = &schema.definitions[1];
+
+        let root = schema.resolve_field(&field("Root", false, None));
+        assert_eq!(root.type_.as_deref(), Some("byte"));
+        assert!(root.is_array);
+        assert_eq!(root.array_size, Some(32));
+        assert_eq!(
+            field("Points", false, None).wire_type(&schema, point),
+            Ok(WireType::VarArray {
+                elem: Box::new(WireType::Nested {
+                    name: "Point".to_string()
+                })
+            })
+        );
+        assert!(field("A", false, None).wire_type(&schema, point).is_err());
+
+        schema.definitions[1]
+            .fields
+            .push(field("Hash", false, None));
+        let resolved = schema.resolve_aliases();
+        assert_eq!(resolved.definitions[1].fields[0], root);
+        assert_eq!(resolved.aliases, schema.aliases);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut merged = Schema::new();
+        merged.merge(schema()).unwrap();
+        assert_eq!(merged.package.as_deref(), Some("pkg"));
+
+        let mut other = schema();
+        other.package = Some("other".to_string());
+        other.definitions.truncate(1);
+        assert_eq!(
+            merged.clone().merge(other),
+            Err(DuplicateDefinition {
+                name: "pkg.Color".to_string()
+            })
+        );
+
+        let mut extra = Schema::new();
+        extra.definitions.push(Definition {
+            name: "Line".to_string(),
+            ..schema().definitions[1].clone()
+        });
+        merged.merge(extra).unwrap();
+        assert_eq!(merged.definitions.len(), 3);
+        assert_eq!(merged.package.as_deref(), Some("pkg"));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut schema = schema();
+        schema.definitions[0].fields.push(field("int", false, None));
+        schema.definitions[1].fields.push(field("int", false, None));
+        schema.definitions[1].fields.push(field("int", false, None));
+
+        let point = schema.definitions[1].clone();
+        let message = |name: &str, ids: &[(i32, bool)]| Definition {
+            name: name.to_string(),
+            kind: DefinitionKind::Message,
+            fields: ids
+                .iter()
+                .map(|&(field_id, is_deprecated)| Field {
+                    field_id,
+                    is_deprecated,
+                    ..field("uint64", false, None)
+                })
+                .collect(),
+            ..point.clone()
+        };
+        schema
+            .definitions
+            .push(message("Tx", &[(1, false), (7, true), (3, true)]));
+        schema.definitions.push(message("Ping", &[]));
+
+        assert_eq!(
+            schema.stats(),
+            SchemaStats {
+                enums: 1,
+                structs: 1,
+                messages: 2,
+                fields: 6,
+                deprecated_fields: 2,
+                max_field_ids: vec![("Tx".to_string(), Some(7)), ("Ping".to_string(), None)],
+            }
+        );
+        assert_eq!(Schema::new().stats(), SchemaStats::default());
+    }
+
+    #[test]
+    fn test_iter_fields() {
+        let mut schema = schema();
+        assert_eq!(schema.iter_fields().count(), 0);
+
+        schema.definitions[0].fields.push(field("int", false, None));
+        schema.definitions[1].fields.push(field("int", false, None));
+        schema.definitions[1]
+            .fields
+            .push(field("byte", true, Some(32)));
+        let pairs: Vec<(&str, &str)> = schema
+            .iter_fields()
+            .map(|(def, field)| (def.name.as_str(), field.type_.as_deref().unwrap()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [("Color", "int"), ("Point", "int"), ("Point", "byte")]
+        );
+        assert_eq!(
+            schema
+                .iter_fields()
+                .filter(|(_, f)| f.is_fixed_byte_array())
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_validate_names() {
+        let mut schema = schema();
+        schema.definitions[1].fields.push(field("int", false, None));
+        assert_eq!(schema.validate_names(), Ok(()));
+
+        for (name, reason) in [
+            ("", "names cannot be empty"),
+            ("2d", "names cannot start with a digit"),
+            (
+                "größe",
+                "names can only contain ASCII letters, digits and underscores",
+            ),
+            (
+                "has space",
+                "names can only contain ASCII letters, digits and underscores",
+            ),
+        ] {
+            let mut bad = schema.clone();
+            bad.definitions[1].fields[0].name = name.to_string();
+            assert_eq!(
+                bad.validate_names().unwrap_err().reason,
+                reason,
+                "{:?}",
+                name
+            );
+        }
+
+        let mut long = schema.clone();
+        long.definitions[0].name = "a".repeat(MAX_NAME_LEN + 1);
+        assert_eq!(
+            long.validate_names().unwrap_err().reason,
+            format!("names can be at most {} characters long", MAX_NAME_LEN)
+        );
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut schema = schema();
+        schema.definitions[1].fields.push(Field {
+            name: "x".to_string(),
+            ..field("int", false, None)
+        });
+
+        let point = schema.find_definition("Point").unwrap();
+        assert_eq!(point.kind, DefinitionKind::Struct);
+        assert_eq!(point.field("x").unwrap().type_.as_deref(), Some("int"));
+        assert!(point.field("y").is_none());
+        assert!(schema.find_definition("Missing").is_none());
+    }
+
+    #[test]
+    fn test_resolve_type() {
+        let mut schema = schema();
+        let mut other = schema.definitions[1].clone();
+        other.package = Some("other".to_string());
+        schema.definitions.push(other);
+        let (color, point) = (&schema.definitions[0], &schema.definitions[1]);
+
+        assert_eq!(schema.resolve_type("Point", color), Some(point));
+        assert_eq!(schema.resolve_type("pkg.Point", color), Some(point));
+        let other = schema.resolve_type("other.Point", color).unwrap();
+        assert_eq!(other.qualified_name(), "other.Point");
+        assert_eq!(schema.resolve_type("Point", other), Some(other));
+        assert!(schema.resolve_type("other.Color", color).is_none());
+        assert!(schema.resolve_type("int", color).is_none());
     }
 }